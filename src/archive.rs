@@ -0,0 +1,343 @@
+use crate::{
+    manifest::CapabilityManifest,
+    trace::{SignedTrace, TraceError, pubkey_pem, sha256_hex},
+};
+use base64::{Engine, engine::general_purpose};
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, VerifyingKey};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt::Write as _, fs, path::Path};
+use thiserror::Error;
+
+/// Signed trace in a run bundle
+pub const ARCHIVE_TRACE_FILE: &str = "trace.json";
+/// Manifest the run was pinned to
+pub const ARCHIVE_MANIFEST_FILE: &str = "manifest.json";
+/// Signer public key (PEM)
+pub const ARCHIVE_PUBKEY_FILE: &str = "pubkey.pem";
+/// Optional report (e.g. SARIF) about the run
+pub const ARCHIVE_REPORT_FILE: &str = "report.json";
+/// `sha256sum`-style listing of every other file
+pub const ARCHIVE_SUMS_FILE: &str = "SHA256SUMS";
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIR: u32 = 0x0605_4b50;
+/// 1980-01-01, the earliest DOS date, so archives are byte-for-byte reproducible
+const ZIP_DOS_DATE: u16 = 0x21;
+
+/// Errors from writing or reading run bundles.
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("IO error in run bundle: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON (de)serialization failed: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Run bundle is missing `{0}`")]
+    MissingFile(String),
+
+    #[error("Run bundle file `{0}` is not listed in {ARCHIVE_SUMS_FILE}")]
+    UnlistedFile(String),
+
+    #[error("Hash of `{file}` does not match {ARCHIVE_SUMS_FILE}")]
+    HashMismatch { file: String },
+
+    #[error("Trace pins manifest {pinned}, bundle holds {actual}")]
+    ManifestMismatch { pinned: String, actual: String },
+
+    #[error("Malformed zip: {0}")]
+    MalformedZip(String),
+
+    #[error(transparent)]
+    Trace(#[from] TraceError),
+}
+
+/// Everything retained about one run, see [`write_run_bundle`].
+#[derive(Debug, Clone)]
+pub struct RunBundle {
+    pub trace: SignedTrace,
+    pub manifest: CapabilityManifest,
+    pub pubkey: VerifyingKey,
+    pub report: Option<Value>,
+}
+
+/// Write a signed trace with its manifest, signer key and an optional report
+/// as a single artifact for retention, plus a [`ARCHIVE_SUMS_FILE`] listing.
+///
+/// `path` ending in `.zip` produces an uncompressed zip archive; any other
+/// path is created as a directory. The trace must verify with `pubkey` and
+/// pin `manifest`, so inconsistent runs are never archived.
+///
+/// # Errors
+///
+/// [`ArchiveError`] if the trace does not verify, does not pin `manifest`,
+/// or the bundle cannot be written.
+pub fn write_run_bundle<P: AsRef<Path>>(
+    path: P,
+    trace: &SignedTrace,
+    manifest: &CapabilityManifest,
+    pubkey: &VerifyingKey,
+    report: Option<&Value>,
+) -> Result<(), ArchiveError> {
+    trace.verify(pubkey)?;
+    check_pinned(trace, manifest)?;
+
+    let mut files = vec![
+        (ARCHIVE_TRACE_FILE, serde_json::to_vec_pretty(trace)?),
+        (ARCHIVE_MANIFEST_FILE, serde_json::to_vec_pretty(manifest)?),
+        (
+            ARCHIVE_PUBKEY_FILE,
+            pubkey_pem(pubkey.as_bytes()).into_bytes(),
+        ),
+    ];
+    if let Some(report) = report {
+        files.push((ARCHIVE_REPORT_FILE, serde_json::to_vec_pretty(report)?));
+    }
+    let sums = files.iter().fold(String::new(), |mut sums, (name, bytes)| {
+        let _ = writeln!(sums, "{}  {name}", sha256_hex(bytes));
+        sums
+    });
+    files.push((ARCHIVE_SUMS_FILE, sums.into_bytes()));
+
+    let path = path.as_ref();
+    if is_zip(path) {
+        fs::write(path, zip_stored(&files))?;
+    } else {
+        fs::create_dir_all(path)?;
+        for (name, bytes) in &files {
+            fs::write(path.join(name), bytes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a bundle written by [`write_run_bundle`], checking every file
+/// against [`ARCHIVE_SUMS_FILE`], the trace signature against the bundled
+/// key, and the manifest against the hash the trace pins.
+///
+/// # Errors
+///
+/// [`ArchiveError`] if a file is missing, unlisted or altered, or the trace
+/// does not verify.
+pub fn read_run_bundle<P: AsRef<Path>>(path: P) -> Result<RunBundle, ArchiveError> {
+    let path = path.as_ref();
+    let mut files = if is_zip(path) {
+        unzip_stored(&fs::read(path)?)?
+    } else {
+        read_dir_files(path)?
+    };
+
+    let sums = files
+        .remove(ARCHIVE_SUMS_FILE)
+        .ok_or_else(|| ArchiveError::MissingFile(ARCHIVE_SUMS_FILE.into()))?;
+    let sums = String::from_utf8_lossy(&sums)
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(hash, name)| (name.to_owned(), hash.to_owned()))
+        .collect::<BTreeMap<_, _>>();
+    if let Some(name) = files.keys().find(|name| !sums.contains_key(*name)) {
+        return Err(ArchiveError::UnlistedFile(name.clone()));
+    }
+    for (name, hash) in &sums {
+        let bytes = files
+            .get(name)
+            .ok_or_else(|| ArchiveError::MissingFile(name.clone()))?;
+        if sha256_hex(bytes) != *hash {
+            return Err(ArchiveError::HashMismatch { file: name.clone() });
+        }
+    }
+
+    let mut take = |name: &str| {
+        files
+            .remove(name)
+            .ok_or_else(|| ArchiveError::MissingFile(name.into()))
+    };
+    let trace = serde_json::from_slice::<SignedTrace>(&take(ARCHIVE_TRACE_FILE)?)?;
+    let manifest = serde_json::from_slice::<CapabilityManifest>(&take(ARCHIVE_MANIFEST_FILE)?)?;
+    let pubkey = decode_pem(&take(ARCHIVE_PUBKEY_FILE)?)?;
+    let report = take(ARCHIVE_REPORT_FILE)
+        .ok()
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()?;
+
+    trace.verify(&pubkey)?;
+    check_pinned(&trace, &manifest)?;
+    Ok(RunBundle {
+        trace,
+        manifest,
+        pubkey,
+        report,
+    })
+}
+
+fn check_pinned(trace: &SignedTrace, manifest: &CapabilityManifest) -> Result<(), ArchiveError> {
+    let actual = manifest.content_hash();
+    if actual != trace.manifest_hash {
+        return Err(ArchiveError::ManifestMismatch {
+            pinned: trace.manifest_hash.clone(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "zip")
+}
+
+fn read_dir_files(dir: &Path) -> Result<BTreeMap<String, Vec<u8>>, ArchiveError> {
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            files.insert(name, fs::read(entry.path())?);
+        }
+    }
+    Ok(files)
+}
+
+/// Decode the key written by [`pubkey_pem`]: the last 32 bytes of the DER.
+fn decode_pem(pem: &[u8]) -> Result<VerifyingKey, TraceError> {
+    let body = String::from_utf8_lossy(pem)
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+    let der = general_purpose::STANDARD.decode(body.trim())?;
+    let key = der
+        .len()
+        .checked_sub(PUBLIC_KEY_LENGTH)
+        .and_then(|start| <[u8; PUBLIC_KEY_LENGTH]>::try_from(&der[start..]).ok())
+        .ok_or(TraceError::InvalidPublicKey)?;
+    VerifyingKey::from_bytes(&key).map_err(|_| TraceError::InvalidPublicKey)
+}
+
+/// Zip `files` without compression ("stored"), readable by any unzip tool.
+fn zip_stored(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, bytes) in files {
+        let offset = zip_u32(out.len());
+        let crc = crc32(bytes);
+        let size = zip_u32(bytes.len());
+        let name_len = u16::try_from(name.len()).unwrap_or(u16::MAX);
+
+        out.extend(ZIP_LOCAL_HEADER.to_le_bytes());
+        push_entry_fields(&mut out, crc, size, name_len);
+        out.extend(0_u16.to_le_bytes()); // extra field length
+        out.extend(name.as_bytes());
+        out.extend(bytes);
+
+        central.extend(ZIP_CENTRAL_HEADER.to_le_bytes());
+        central.extend(20_u16.to_le_bytes()); // version made by
+        push_entry_fields(&mut central, crc, size, name_len);
+        central.extend([0_u8; 12]); // extra, comment, disk, internal/external attrs
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    let central_offset = zip_u32(out.len());
+    let central_len = zip_u32(central.len());
+    let count = u16::try_from(files.len()).unwrap_or(u16::MAX);
+    out.extend(central);
+    out.extend(ZIP_END_OF_CENTRAL_DIR.to_le_bytes());
+    out.extend([0_u8; 4]); // disk numbers
+    out.extend(count.to_le_bytes());
+    out.extend(count.to_le_bytes());
+    out.extend(central_len.to_le_bytes());
+    out.extend(central_offset.to_le_bytes());
+    out.extend(0_u16.to_le_bytes()); // comment length
+    out
+}
+
+/// Fields shared by local and central headers, from "version needed" to the
+/// file name length.
+fn push_entry_fields(out: &mut Vec<u8>, crc: u32, size: u32, name_len: u16) {
+    out.extend(20_u16.to_le_bytes()); // version needed
+    out.extend(0_u16.to_le_bytes()); // flags
+    out.extend(0_u16.to_le_bytes()); // method: stored
+    out.extend(0_u16.to_le_bytes()); // time
+    out.extend(ZIP_DOS_DATE.to_le_bytes());
+    out.extend(crc.to_le_bytes());
+    out.extend(size.to_le_bytes()); // compressed
+    out.extend(size.to_le_bytes()); // uncompressed
+    out.extend(name_len.to_le_bytes());
+}
+
+fn zip_u32(len: usize) -> u32 {
+    u32::try_from(len).unwrap_or(u32::MAX)
+}
+
+/// Read the files of a stored (uncompressed) zip via its central directory.
+fn unzip_stored(zip: &[u8]) -> Result<BTreeMap<String, Vec<u8>>, ArchiveError> {
+    let malformed = |reason: &str| ArchiveError::MalformedZip(reason.into());
+    let u16_at = |at: usize| {
+        zip.get(at..at + 2)
+            .map(|b| usize::from(u16::from_le_bytes([b[0], b[1]])))
+            .ok_or_else(|| malformed("truncated"))
+    };
+    let u32_at = |at: usize| {
+        zip.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| malformed("truncated"))
+    };
+    let usize_at = |at: usize| u32_at(at).map(|v| usize::try_from(v).unwrap_or(usize::MAX));
+
+    let eocd = zip
+        .len()
+        .checked_sub(22)
+        .ok_or_else(|| malformed("too short"))?;
+    if u32_at(eocd)? != ZIP_END_OF_CENTRAL_DIR {
+        return Err(malformed("no end of central directory"));
+    }
+    let count = u16_at(eocd + 10)?;
+    let mut at = usize_at(eocd + 16)?;
+    let mut files = BTreeMap::new();
+    for _ in 0..count {
+        if u32_at(at)? != ZIP_CENTRAL_HEADER {
+            return Err(malformed("bad central directory entry"));
+        }
+        if u16_at(at + 10)? != 0 {
+            return Err(malformed("compressed entries are not supported"));
+        }
+        let crc = u32_at(at + 16)?;
+        let size = usize_at(at + 20)?;
+        let name_len = u16_at(at + 28)?;
+        let skip = u16_at(at + 30)? + u16_at(at + 32)?;
+        let local = usize_at(at + 42)?;
+        let name = zip
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| malformed("truncated"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_len + skip;
+
+        if u32_at(local)? != ZIP_LOCAL_HEADER {
+            return Err(malformed("bad local header"));
+        }
+        let start = local + 30 + u16_at(local + 26)? + u16_at(local + 28)?;
+        let data = zip
+            .get(start..start.saturating_add(size))
+            .ok_or_else(|| malformed("truncated"))?;
+        if crc32(data) != crc {
+            return Err(ArchiveError::HashMismatch { file: name });
+        }
+        files.insert(name, data.to_vec());
+    }
+    Ok(files)
+}
+
+/// CRC-32 (IEEE) as required by zip entries.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,190 @@
+use crate::trace::{
+    EventType, SignedTrace, TraceError, TraceEvent, pubkey_fingerprint, verify_key_rotations,
+};
+use ed25519_dalek::VerifyingKey;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Trusted trace signers by [`pubkey_fingerprint`], for [`verify_batch`].
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: HashMap<String, VerifyingKey>,
+}
+
+impl Keyring {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_key(mut self, key: VerifyingKey) -> Self {
+        self.insert(key);
+        self
+    }
+
+    /// Trust `key`, returning its fingerprint.
+    pub fn insert(&mut self, key: VerifyingKey) -> String {
+        let fingerprint = pubkey_fingerprint(key.as_bytes());
+        self.keys.insert(fingerprint.clone(), key);
+        fingerprint
+    }
+
+    /// Trusted key with `fingerprint`
+    #[must_use]
+    pub fn get(&self, fingerprint: &str) -> Option<&VerifyingKey> {
+        self.keys.get(fingerprint)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl FromIterator<VerifyingKey> for Keyring {
+    fn from_iter<I: IntoIterator<Item = VerifyingKey>>(keys: I) -> Self {
+        let mut keyring = Self::new();
+        for key in keys {
+            keyring.insert(key);
+        }
+        keyring
+    }
+}
+
+/// A trace that passed [`verify_signed_trace_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedTrace {
+    /// Run id of the signed events
+    pub run_id: String,
+    /// Fingerprint of the trusted signer (the initial key if it was rotated)
+    pub fingerprint: String,
+    pub events: usize,
+}
+
+/// Outcome for one file of [`verify_batch`].
+#[derive(Debug)]
+pub struct BatchVerification {
+    pub path: PathBuf,
+    pub result: Result<VerifiedTrace, TraceError>,
+}
+
+/// Passed to the [`verify_batch`] callback after each trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchProgress {
+    /// Traces verified so far, including failures
+    pub done: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+/// Verify a [`SignedTrace`] JSON file against `keyring`.
+///
+/// The key it was first signed with must be in `keyring`, then
+/// [`SignedTrace::verify_with_rotations`] checks the hash chain, each
+/// `key.rotate` and the signature, which covers the envelope's `run_id`
+/// (see [`crate::trace_message`]). That `run_id` must also agree with the
+/// events'.
+///
+/// # Errors
+///
+/// [`TraceError::UntrustedSigner`] if no key in `keyring` starts the chain
+/// of custody, [`TraceError::InvalidSignature`] for a renamed run,
+/// [`TraceError::IntegrityViolation`] for a run whose events disagree with
+/// it or are empty, or the [`TraceError`] of the first failing check.
+pub fn verify_signed_trace_file<P: AsRef<Path>>(
+    path: P,
+    keyring: &Keyring,
+) -> Result<VerifiedTrace, TraceError> {
+    let signed = serde_json::from_str::<SignedTrace>(&fs::read_to_string(path)?)?;
+    let events = serde_json::from_str::<Vec<TraceEvent>>(&signed.trace_json)?;
+    let embedded = pubkey_fingerprint(signed.embedded_pubkey()?.as_bytes());
+    let initial = if events
+        .iter()
+        .any(|ev| ev.event_type == EventType::KeyRotate)
+    {
+        // Only the initial key can have signed the first rotation.
+        keyring
+            .keys
+            .values()
+            .find(|key| verify_key_rotations(&events, key).is_ok())
+    } else {
+        keyring.get(&embedded)
+    }
+    .ok_or(TraceError::UntrustedSigner {
+        fingerprint: embedded,
+    })?;
+    signed.verify_with_rotations(initial)?;
+
+    let run_id = events.first().map(|ev| ev.run_id.clone()).ok_or_else(|| {
+        TraceError::IntegrityViolation {
+            seq: 0,
+            reason: "no signed events to take the run id from".into(),
+        }
+    })?;
+    if run_id != signed.run_id {
+        return Err(TraceError::IntegrityViolation {
+            seq: events[0].seq,
+            reason: format!(
+                "envelope run_id `{}` differs from the events'",
+                signed.run_id
+            ),
+        });
+    }
+    Ok(VerifiedTrace {
+        run_id,
+        fingerprint: pubkey_fingerprint(initial.as_bytes()),
+        events: events.len(),
+    })
+}
+
+/// [`verify_signed_trace_file`] for every path, e.g. a night's archived runs.
+///
+/// Files are verified on the rayon thread pool with the `parallel` feature,
+/// sequentially otherwise; results are in input order either way. `progress`
+/// is called after each file, from whichever thread verified it.
+pub fn verify_batch<I, P>(
+    paths: I,
+    keyring: &Keyring,
+    progress: impl Fn(BatchProgress) + Sync,
+) -> Vec<BatchVerification>
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let paths = paths.into_iter().map(Into::into).collect::<Vec<PathBuf>>();
+    let total = paths.len();
+    let done = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let verify = |path: PathBuf| {
+        let result = verify_signed_trace_file(&path, keyring);
+        let failed = failed.fetch_add(usize::from(result.is_err()), Ordering::Relaxed)
+            + usize::from(result.is_err());
+        progress(BatchProgress {
+            done: done.fetch_add(1, Ordering::Relaxed) + 1,
+            failed,
+            total,
+        });
+        BatchVerification { path, result }
+    };
+
+    #[cfg(feature = "parallel")]
+    let results = paths.into_par_iter().map(verify).collect();
+    #[cfg(not(feature = "parallel"))]
+    let results = paths.into_iter().map(verify).collect();
+    results
+}
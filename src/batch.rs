@@ -0,0 +1,203 @@
+use crate::{
+    host::HostState,
+    manifest::CapabilityManifest,
+    plugin::{ModuleCache, PluginInstance},
+    session::RunSession,
+    trace::{RandomnessMode, SignedTrace, TraceError, TraceEvent, event_hash},
+    wasm::WasmError,
+};
+use ed25519_dalek::SigningKey;
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+/// Runs one plugin over many inputs on a pool of worker threads.
+///
+/// Every input gets its own [`HostState`] and store, seeded with
+/// `base_seed + index`, so per-input traces do not depend on scheduling.
+/// Workers share one [`RunSession`]; vector clocks therefore record the actual
+/// interleaving across workers.
+///
+/// The per-input traces are then aggregated, in input order, into one trace
+/// of run `captra-batch-{base_seed}` and signed like
+/// [`HostState::sign_current_trace`] signs a single run.
+#[derive(Debug)]
+pub struct BatchRunner {
+    manifest: CapabilityManifest,
+    wasm: Vec<u8>,
+    keypair: SigningKey,
+    export: String,
+    base_seed: u64,
+    workers: usize,
+    cache: ModuleCache,
+    session: RunSession,
+}
+
+/// Outcome of [`BatchRunner::run_all`].
+#[derive(Debug)]
+pub struct BatchReport {
+    /// Export return value per input, in input order
+    pub results: Vec<Result<i32, WasmError>>,
+    /// Trace per input, in input order; each is its own hash chain under the
+    /// input's run id, empty if the plugin failed to instantiate
+    pub traces: Vec<Vec<TraceEvent>>,
+    /// All of `traces` renumbered into one hash chain of run
+    /// `captra-batch-{base_seed}` and signed with the runner keypair
+    pub signed: SignedTrace,
+}
+
+impl BatchRunner {
+    /// Create a runner calling the `run(ptr, len) -> i32` export, see
+    /// [`PluginInstance::call_with_input`].
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Engine`] if the module cache engine cannot be created.
+    pub fn new(
+        manifest: CapabilityManifest,
+        wasm: impl Into<Vec<u8>>,
+        keypair: SigningKey,
+    ) -> Result<Self, WasmError> {
+        Ok(Self {
+            manifest,
+            wasm: wasm.into(),
+            keypair,
+            export: "run".into(),
+            base_seed: 0,
+            workers: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            cache: ModuleCache::new()?,
+            session: RunSession::new(),
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn with_export(mut self, export: impl Into<String>) -> Self {
+        self.export = export.into();
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_seed(mut self, base_seed: u64) -> Self {
+        self.base_seed = base_seed;
+        self
+    }
+
+    /// Number of worker threads (at least one)
+    #[inline]
+    #[must_use]
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Get `session`
+    #[inline]
+    #[must_use]
+    pub const fn session(&self) -> &RunSession {
+        &self.session
+    }
+
+    /// Execute the plugin once per input, collect the per-input traces and
+    /// sign their aggregate.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] if the aggregated trace cannot be signed.
+    pub fn run_all<I: AsRef<[u8]> + Sync>(&self, inputs: &[I]) -> Result<BatchReport, TraceError> {
+        let next = AtomicUsize::new(0);
+        let outputs = Mutex::new(Vec::with_capacity(inputs.len()));
+
+        thread::scope(|scope| {
+            for worker in 0..self.workers.min(inputs.len()) {
+                let (next, outputs) = (&next, &outputs);
+                scope.spawn(move || {
+                    let host_id = format!("worker-{worker}");
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(idx) else {
+                            break;
+                        };
+                        let output = self.run_one(idx, input.as_ref(), &host_id);
+                        outputs
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push((idx, output));
+                    }
+                });
+            }
+        });
+
+        let mut outputs = outputs.into_inner().unwrap_or_else(PoisonError::into_inner);
+        outputs.sort_by_key(|(idx, _)| *idx);
+        let (results, traces): (Vec<_>, Vec<_>) =
+            outputs.into_iter().map(|(_, output)| output).unzip();
+        let signed = self.sign(&traces)?;
+        Ok(BatchReport {
+            results,
+            traces,
+            signed,
+        })
+    }
+
+    fn run_one(
+        &self,
+        idx: usize,
+        input: &[u8],
+        host_id: &str,
+    ) -> (Result<i32, WasmError>, Vec<TraceEvent>) {
+        let seed = self
+            .base_seed
+            .wrapping_add(u64::try_from(idx).unwrap_or(u64::MAX));
+        let host = HostState::new(self.manifest.clone(), seed, self.keypair.clone())
+            .with_session(&self.session, host_id);
+        match PluginInstance::with_cache(&self.cache, host, &self.wasm) {
+            Ok(mut plugin) => {
+                let result = plugin.call_with_input(&self.export, input);
+                (result, plugin.shutdown().trace().to_vec())
+            }
+            Err(err) => (Err(err), Vec::new()),
+        }
+    }
+
+    /// Sign the [`aggregate`] of `traces` through a [`HostState`] resumed
+    /// from it, so the header matches that of a single run.
+    fn sign(&self, traces: &[Vec<TraceEvent>]) -> Result<SignedTrace, TraceError> {
+        let randomness = RandomnessMode::Deterministic(self.base_seed);
+        let run_id = format!("captra-batch-{}", self.base_seed);
+        HostState::new(self.manifest.clone(), self.base_seed, self.keypair.clone())
+            .with_randomness(randomness)
+            .with_resumed_trace(aggregate(traces, &run_id, randomness))?
+            .sign_current_trace()
+    }
+}
+
+/// `traces` concatenated into one hash chain of run `run_id`.
+///
+/// Like [`crate::compact`], events are renumbered from seq 1 and draw
+/// `ts_seed`s for the new seqs from `randomness`; everything else, including
+/// vector clocks, is kept.
+fn aggregate(
+    traces: &[Vec<TraceEvent>],
+    run_id: &str,
+    randomness: RandomnessMode,
+) -> Vec<TraceEvent> {
+    let mut trace = Vec::<TraceEvent>::new();
+    for event in traces.iter().flatten() {
+        let seq = trace.last().map_or(1, |prev| prev.last_seq() + 1);
+        trace.push(TraceEvent {
+            seq,
+            ts_seed: randomness.ts_seed(seq),
+            run_id: run_id.to_owned(),
+            prev_hash: trace.last().map(event_hash).unwrap_or_default(),
+            ..event.clone()
+        });
+    }
+    trace
+}
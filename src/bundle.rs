@@ -0,0 +1,173 @@
+use crate::manifest::{CapabilityManifest, LoadOptions, ManifestError, TrustRoots};
+use ed25519_dalek::VerifyingKey;
+use thiserror::Error;
+
+/// Name of the wasm custom section holding an embedded manifest.
+pub const MANIFEST_SECTION: &str = "captra-manifest";
+
+const WASM_HEADER: &[u8; 8] = b"\0asm\x01\0\0\0";
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// Errors from reading or writing self-describing plugin bundles.
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Not a binary wasm module")]
+    NotWasm,
+
+    #[error("Malformed wasm section at offset {0}")]
+    Malformed(usize),
+
+    #[error("No `{MANIFEST_SECTION}` custom section")]
+    MissingManifest,
+
+    #[error("More than one `{MANIFEST_SECTION}` custom section")]
+    DuplicateManifest,
+
+    #[error("Invalid embedded manifest: {0}")]
+    Manifest(#[from] ManifestError),
+}
+
+/// A plugin module together with its verified capability manifest.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    manifest: CapabilityManifest,
+    wasm: Vec<u8>,
+    signer: VerifyingKey,
+}
+
+impl Bundle {
+    /// Read the manifest from the `captra-manifest` custom section of `wasm`,
+    /// validate it and verify its signature chain against `roots`, so a
+    /// single `.wasm` file can be distributed without a side-car manifest.
+    ///
+    /// # Errors
+    ///
+    /// [`BundleError`] if the module is malformed, has no (or several)
+    /// manifest sections, or the manifest is invalid or not signed by a
+    /// trusted chain.
+    pub fn from_wasm_with_embedded_manifest(
+        wasm: impl Into<Vec<u8>>,
+        roots: &TrustRoots,
+    ) -> Result<Self, BundleError> {
+        let wasm = wasm.into();
+        let mut sections = custom_sections(&wasm, MANIFEST_SECTION)?.into_iter();
+        let section = sections.next().ok_or(BundleError::MissingManifest)?;
+        if sections.next().is_some() {
+            return Err(BundleError::DuplicateManifest);
+        }
+        let json = std::str::from_utf8(section).map_err(|_| BundleError::Malformed(0))?;
+        let manifest = CapabilityManifest::from_json_with(json, LoadOptions::strict())?;
+        let signer = manifest.validate_chain(roots)?;
+        Ok(Self {
+            manifest,
+            wasm,
+            signer,
+        })
+    }
+
+    /// Get `manifest`
+    #[inline]
+    #[must_use]
+    pub const fn manifest(&self) -> &CapabilityManifest {
+        &self.manifest
+    }
+
+    /// Module bytes, including the manifest section
+    #[inline]
+    #[must_use]
+    pub fn wasm(&self) -> &[u8] {
+        &self.wasm
+    }
+
+    /// Key that signed the manifest, as verified by the trust chain
+    #[inline]
+    #[must_use]
+    pub const fn signer(&self) -> &VerifyingKey {
+        &self.signer
+    }
+
+    /// Split into manifest and module bytes.
+    #[must_use]
+    pub fn into_parts(self) -> (CapabilityManifest, Vec<u8>) {
+        (self.manifest, self.wasm)
+    }
+}
+
+/// Append `manifest` (signed beforehand, see [`CapabilityManifest::sign`])
+/// to `wasm` as a `captra-manifest` custom section.
+///
+/// # Errors
+///
+/// [`BundleError`] if `wasm` is not a binary module, already embeds a
+/// manifest, or the manifest cannot be serialized.
+pub fn embed_manifest(wasm: &[u8], manifest: &CapabilityManifest) -> Result<Vec<u8>, BundleError> {
+    if !custom_sections(wasm, MANIFEST_SECTION)?.is_empty() {
+        return Err(BundleError::DuplicateManifest);
+    }
+    let json = serde_json::to_vec(manifest).map_err(ManifestError::from)?;
+    let mut payload = Vec::with_capacity(MANIFEST_SECTION.len() + json.len() + 5);
+    write_leb128(&mut payload, MANIFEST_SECTION.len());
+    payload.extend_from_slice(MANIFEST_SECTION.as_bytes());
+    payload.extend_from_slice(&json);
+
+    let mut out = wasm.to_vec();
+    out.push(CUSTOM_SECTION_ID);
+    write_leb128(&mut out, payload.len());
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// Payloads of every custom section of `wasm` called `name`, in order.
+///
+/// # Errors
+///
+/// [`BundleError::NotWasm`] or [`BundleError::Malformed`].
+pub fn custom_sections<'a>(wasm: &'a [u8], name: &str) -> Result<Vec<&'a [u8]>, BundleError> {
+    let mut rest = wasm.strip_prefix(WASM_HEADER).ok_or(BundleError::NotWasm)?;
+    let mut found = Vec::new();
+    while let Some((&id, after_id)) = rest.split_first() {
+        let offset = wasm.len() - rest.len();
+        let malformed = || BundleError::Malformed(offset);
+        let (size, after_size) = read_leb128(after_id).ok_or_else(malformed)?;
+        if size > after_size.len() {
+            return Err(malformed());
+        }
+        let (section, after_section) = after_size.split_at(size);
+        if id == CUSTOM_SECTION_ID {
+            let (name_len, after_len) = read_leb128(section).ok_or_else(malformed)?;
+            if name_len > after_len.len() {
+                return Err(malformed());
+            }
+            let (section_name, payload) = after_len.split_at(name_len);
+            if section_name == name.as_bytes() {
+                found.push(payload);
+            }
+        }
+        rest = after_section;
+    }
+    Ok(found)
+}
+
+/// Unsigned LEB128 `u32`, as used for wasm sizes.
+fn read_leb128(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let mut value = 0_u32;
+    for (idx, &byte) in bytes.iter().enumerate().take(5) {
+        value |= u32::from(byte & 0x7f).checked_shl(7 * u32::try_from(idx).ok()?)?;
+        if byte & 0x80 == 0 {
+            return Some((usize::try_from(value).ok()?, &bytes[idx + 1..]));
+        }
+    }
+    None
+}
+
+fn write_leb128(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = u8::try_from(value & 0x7f).unwrap_or_default();
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
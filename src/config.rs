@@ -0,0 +1,262 @@
+use crate::{
+    host::{DenialMode, HostState},
+    manifest::TrustRoots,
+    paths::CaptraDirs,
+    trace::{TraceError, TracePolicy, save_trace_csv},
+};
+use base64::{Engine, engine::general_purpose};
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{File, read_to_string},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
+
+/// File name of the host configuration, see [`CaptraDirs::config_file`].
+pub const CONFIG_FILE: &str = "captra.toml";
+
+/// Errors from host configuration loading.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("IO error reading config: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("TOML deserialization failed: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("Invalid signing key {path}: {reason}")]
+    InvalidKey { path: PathBuf, reason: String },
+
+    #[error("Invalid public key for trusted issuer {name}")]
+    InvalidIssuer { name: String },
+
+    #[error("No trace directory configured and no default location known")]
+    NoTraceDir,
+
+    #[error(transparent)]
+    Trace(#[from] TraceError),
+}
+
+/// Deployment settings for hosts, read from `captra.toml` so they need not
+/// be wired up in code:
+///
+/// ```toml
+/// denial_mode = "deny"
+/// dedup_denials = true
+/// signing_key = "keys/host.key"
+///
+/// [trace]
+/// dir = "/var/lib/captra/traces"
+/// format = "jsonl"
+///
+/// [trace_policy]
+/// default = { sampled = { every = 10 } }
+///
+/// [latency_caps_ms]
+/// read_file = 50
+///
+/// [trusted_issuers]
+/// dev-team = "<base64 public key>"
+/// ```
+///
+/// Relative paths are resolved against the config file's directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostConfig {
+    /// Enforcement mode, see [`HostState::with_denial_mode`].
+    #[serde(default)]
+    pub denial_mode: DenialMode,
+    /// Fold repeated identical denials, see [`HostState::with_denial_dedup`].
+    #[serde(default)]
+    pub dedup_denials: bool,
+    #[serde(default)]
+    pub trace: TraceSinkConfig,
+    /// Logging verbosity and sampling, see [`HostState::with_trace_policy`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_policy: Option<TracePolicy>,
+    /// Host call latency caps in milliseconds by call, see
+    /// [`HostState::with_latency_cap`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub latency_caps_ms: BTreeMap<String, u64>,
+    /// File holding the base64 ed25519 secret key traces are signed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<PathBuf>,
+    /// Base64 public keys of trusted root issuers by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trusted_issuers: BTreeMap<String, String>,
+}
+
+/// Where [`HostConfig::save_trace`] writes finished traces.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TraceSinkConfig {
+    /// Output directory ([`CaptraDirs::traces_dir`] if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub format: TraceFormat,
+}
+
+/// File format of saved traces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceFormat {
+    /// Pretty JSON behind a [`crate::TraceFileHeader`], see [`crate::save_trace`].
+    #[default]
+    Json,
+    /// Signed JSON Lines, see [`HostState::write_signed_jsonl`].
+    Jsonl,
+    /// CSV for analytics, see [`save_trace_csv`].
+    Csv,
+}
+
+impl TraceFormat {
+    /// File extension without the dot
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+impl HostConfig {
+    /// Load a config file.
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError`] (IO or TOML, including unknown keys).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let mut config = Self::from_toml(&read_to_string(path)?)?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        config.resolve_paths(base);
+        Ok(config)
+    }
+
+    /// Load [`CaptraDirs::config_file`], or the defaults if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError`] if the file exists but cannot be loaded.
+    pub fn load_default() -> Result<Self, ConfigError> {
+        match CaptraDirs::new().map(|dirs| dirs.config_file()) {
+            Some(path) if path.exists() => Self::load(path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Parse a config from TOML; paths are kept as written.
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError::Parse`] if the TOML is invalid or has unknown keys.
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [self.signing_key.as_mut(), self.trace.dir.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// Apply the enforcement mode, denial dedup, latency caps and trace
+    /// policy to `host`.
+    #[must_use]
+    pub fn configure(&self, host: HostState) -> HostState {
+        let host = self.latency_caps_ms.iter().fold(
+            host.with_denial_mode(self.denial_mode)
+                .with_denial_dedup(self.dedup_denials),
+            |host, (import, cap_ms)| host.with_latency_cap(import, Duration::from_millis(*cap_ms)),
+        );
+        match &self.trace_policy {
+            Some(policy) => host.with_trace_policy(policy.clone()),
+            None => host,
+        }
+    }
+
+    /// Read the configured signing key (`None` if unset).
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError::InvalidKey`] if the file cannot be read or does not
+    /// hold a base64 32-byte secret key.
+    pub fn signing_key(&self) -> Result<Option<SigningKey>, ConfigError> {
+        let Some(path) = &self.signing_key else {
+            return Ok(None);
+        };
+        let invalid = |reason: String| ConfigError::InvalidKey {
+            path: path.clone(),
+            reason,
+        };
+        let encoded = read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+        let bytes = general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|err| invalid(err.to_string()))?;
+        let bytes = <[u8; SECRET_KEY_LENGTH]>::try_from(bytes)
+            .map_err(|bytes| invalid(format!("expected 32 bytes, got {}", bytes.len())))?;
+        Ok(Some(SigningKey::from_bytes(&bytes)))
+    }
+
+    /// Decode `trusted_issuers` for [`crate::CapabilityManifest::validate_chain`].
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError::InvalidIssuer`] for the first key that does not decode.
+    pub fn trust_roots(&self) -> Result<TrustRoots, ConfigError> {
+        self.trusted_issuers
+            .iter()
+            .map(|(name, encoded)| {
+                general_purpose::STANDARD
+                    .decode(encoded)
+                    .ok()
+                    .and_then(|bytes| <[u8; PUBLIC_KEY_LENGTH]>::try_from(bytes).ok())
+                    .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+                    .map(|key| (name.clone(), key))
+                    .ok_or_else(|| ConfigError::InvalidIssuer { name: name.clone() })
+            })
+            .collect()
+    }
+
+    /// Directory traces are saved to.
+    #[must_use]
+    pub fn trace_dir(&self) -> Option<PathBuf> {
+        self.trace
+            .dir
+            .clone()
+            .or_else(|| CaptraDirs::new().map(|dirs| dirs.traces_dir()))
+    }
+
+    /// Save the trace of `host` to `<trace dir>/<run_id>.<ext>` in the
+    /// configured format, returning the path written.
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError::NoTraceDir`] without a directory, or [`ConfigError`]
+    /// (IO or trace) if writing fails.
+    pub fn save_trace(&self, host: &mut HostState) -> Result<PathBuf, ConfigError> {
+        let dir = self.trace_dir().ok_or(ConfigError::NoTraceDir)?;
+        let format = self.trace.format;
+        let path = dir.join(format!("{}.{}", host.run_id(), format.extension()));
+        match format {
+            TraceFormat::Json => host.save_current_trace(&path)?,
+            TraceFormat::Jsonl => {
+                host.write_signed_jsonl(BufWriter::new(File::create(&path)?))?;
+            }
+            TraceFormat::Csv => save_trace_csv(host.trace(), &path)?,
+        }
+        Ok(path)
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(all(feature = "landlock", target_os = "linux"))]
+use crate::landlock::LandlockError;
+#[cfg(all(
+    feature = "native",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+use crate::native::NativeError;
+use crate::{
+    archive::ArchiveError, bundle::BundleError, config::ConfigError, host::CapError,
+    manifest::ManifestError, matcher::MatcherError, registry::RegistryError,
+    secure_open::SecureOpenError, trace::TraceError, wasm::WasmError,
+};
+use thiserror::Error;
+
+/// Any error returned by captra's public API.
+///
+/// Every module error converts into it, so embedders can use one `Result`
+/// type with `?` and still match on the failing subsystem.
+#[derive(Debug, Error)]
+pub enum CaptraError {
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+
+    #[error(transparent)]
+    Trace(#[from] TraceError),
+
+    #[error(transparent)]
+    Cap(#[from] CapError),
+
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+
+    #[error(transparent)]
+    Wasm(#[from] WasmError),
+
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+
+    #[error(transparent)]
+    SecureOpen(#[from] SecureOpenError),
+
+    #[error(transparent)]
+    Matcher(#[from] MatcherError),
+
+    #[cfg(all(feature = "landlock", target_os = "linux"))]
+    #[error(transparent)]
+    Landlock(#[from] LandlockError),
+
+    #[cfg(all(
+        feature = "native",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[error(transparent)]
+    Native(#[from] NativeError),
+}
+
+/// `Result` with [`CaptraError`]
+pub type Result<T, E = CaptraError> = std::result::Result<T, E>;
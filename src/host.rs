@@ -1,32 +1,277 @@
+#[cfg(feature = "test-util")]
+use crate::testing::{Fault, FaultInjector};
 use crate::{
-    manifest::{CapabilityManifest, PRIME_MULTIPLIER},
+    error::CaptraError,
+    identity::ProcessIdentity,
+    jsonl::JsonlWriter,
+    manifest::{CapabilityManifest, ManifestError, Posture, TimeCapability},
+    matcher::{CompiledSpecs, Matcher, MatcherError, MatcherKind, bounding_glob},
+    metrics::{DecisionStats, DecisionTiming},
+    plugin::PluginInstance,
+    policy::{ApprovalRequest, PolicyHook},
+    secure_open::{SecureOpenError, grant_root, secure_open, secure_read_dir},
+    session::RunSession,
+    spawn::{ChildRun, MAX_SPAWN_DEPTH, PluginCatalog, SPAWN_EXPORT},
     trace::{
-        CapEventSubtype, EventType, SignedTrace, TraceError, TraceEvent, finalize_trace,
-        log_trace_event, save_trace,
+        CapEventSubtype, CompactionPolicy, DEFAULT_MAX_INPUT_LEN, EventType, RandomnessMode,
+        RunLabels, SeqCounter, SeverityRules, SignedTrace, TraceCheckpoint, TraceError, TraceEvent,
+        TraceFileHeader, TraceHasher, TracePolicy, checkpoint_message, compact, event_hash,
+        finalize_trace, key_rotation_message, log_trace_event, pubkey_fingerprint, pubkey_hex,
+        pubkey_openssh, pubkey_openssh_fingerprint, pubkey_pem, save_trace_with_header, sha256_hex,
+        summary_run_id, trace_message, truncate_input, verify_chain,
     },
+    watchdog::Watchdog,
+    webhook::{WebhookSink, WebhookStats},
 };
+use base64::{Engine, engine::general_purpose};
 use ed25519_dalek::{PUBLIC_KEY_LENGTH, SigningKey, ed25519::signature::SignerMut};
-use glob::Pattern;
-use rand::{Rng, SeedableRng, rngs::StdRng};
-use sha2::{Digest, Sha256};
-use std::{path::Path, str::from_utf8};
+use glob::PatternError;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Debug, Display},
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use thiserror::Error;
 use tracing::Level;
-use wasmtime::{Caller, Extern, Linker, Trap};
 
 #[derive(Debug)]
 pub struct HostState {
+    session: Option<(String, RunSession)>,
+    policy_hook: Option<Box<dyn PolicyHook>>,
+    custom_enforcers: CustomEnforcers,
+    /// Read grants of the shadow manifest, see [`HostState::with_shadow_manifest`]
+    shadow_read: Option<CompiledSpecs>,
+    denial_mode: DenialMode,
+    hash_reads: bool,
+    dedup_denials: bool,
+    embed_manifest: bool,
+    #[cfg(feature = "test-util")]
+    faults: Option<FaultInjector>,
+    decision_cache: Option<DecisionCache>,
+    read_handles: ReadHandles,
+    blobs: Blobs,
+    config_provider: Option<Box<dyn ConfigProvider>>,
+    latency_caps: HashMap<String, Duration>,
+    /// Helper threads still running capped I/O, see [`MAX_CAPPED_WORKERS`]
+    capped_workers: Arc<AtomicUsize>,
+    max_input_len: usize,
+    trace_policy: Option<TracePolicy>,
+    severity_rules: SeverityRules,
+    watchdog: Option<Watchdog>,
+    webhook: Option<WebhookSink>,
+    budget: Option<(Budget, Instant)>,
+    budget_calls: u64,
+    thresholds: Thresholds,
+    catalog: PluginCatalog,
+    spawn_depth: u32,
+    parent_run_id: Option<String>,
+    labels: RunLabels,
+    profile: Option<String>,
+    child_runs: Vec<ChildRun>,
     manifest: CapabilityManifest,
+    fs_matchers: FsMatchers,
     trace: Vec<TraceEvent>,
-    seed: u64,
+    trace_hasher: TraceHasher,
+    /// Events covered by a checkpoint, which denial dedup must not fold into
+    sealed_len: usize,
+    timings: Vec<DecisionTiming>,
+    last_denial: Option<String>,
+    randomness: RandomnessMode,
     keypair: SigningKey,
     pubkey: [u8; PUBLIC_KEY_LENGTH],
     run_id: String,
     manifest_hash: String,
+    seq: SeqCounter,
+    policy_epoch: u64,
+    chain_head: String,
+}
+
+/// Embedder decision for a custom capability request: receives the manifest
+/// `params` for the kind and the request, returns whether to allow it.
+pub type CustomEnforcer = Box<dyn FnMut(&serde_json::Value, &str) -> bool + Send>;
+
+/// Source of the typed values guests read with [`HostState::get_config`]
+/// (an embedder's settings, a config service, ...).
+pub trait ConfigProvider: Debug + Send {
+    /// Value of `key`, `None` if unset.
+    fn get(&self, key: &str) -> Option<serde_json::Value>;
+}
+
+impl ConfigProvider for BTreeMap<String, serde_json::Value> {
+    fn get(&self, key: &str) -> Option<serde_json::Value> {
+        Self::get(self, key).cloned()
+    }
+}
+
+/// Registered [`CustomEnforcer`]s by kind.
+#[derive(Default)]
+struct CustomEnforcers(HashMap<String, CustomEnforcer>);
+
+impl Debug for CustomEnforcers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Embedder callback for [`HostState::on_threshold`].
+type ThresholdCallback = Box<dyn FnMut(&BudgetThreshold) + Send>;
+
+/// A [`Budget`] resource whose usage crossed a registered threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetThreshold {
+    /// `calls` or `wall_ms`, as in [`CapError::BudgetExhausted`]
+    pub resource: &'static str,
+    /// Registered threshold, in percent of `limit`
+    pub percent: u8,
+    pub used: u64,
+    pub limit: u64,
+}
+
+struct Threshold {
+    resource: &'static str,
+    percent: u8,
+    fired: bool,
+    callback: ThresholdCallback,
+}
+
+/// Registered [`ThresholdCallback`]s, each firing at most once per run.
+#[derive(Default)]
+struct Thresholds(Vec<Threshold>);
+
+impl Debug for Thresholds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|t| (t.resource, t.percent, t.fired)))
+            .finish()
+    }
+}
+
+/// FS read paths whose pattern and extension checks passed, valid only for
+/// the policy epoch they were computed under.
+#[derive(Debug, Default)]
+struct DecisionCache {
+    epoch: u64,
+    allowed: HashSet<String>,
+}
+
+/// FS grant matchers of the manifest in force, compiled when it is loaded.
+#[derive(Debug, Default)]
+struct FsMatchers {
+    read: CompiledSpecs,
+    list: CompiledSpecs,
+}
+
+impl FsMatchers {
+    fn new(manifest: &CapabilityManifest) -> Self {
+        let fs = manifest.capabilities.fs.as_ref();
+        let compile =
+            |specs: Option<&Vec<String>>| CompiledSpecs::new(specs.map_or(&[], Vec::as_slice));
+        Self {
+            read: compile(fs.and_then(|fs| fs.read.as_ref())),
+            list: compile(fs.and_then(|fs| fs.list.as_ref())),
+        }
+    }
+}
+
+/// Files opened by [`HostState::open_read`], by handle.
+#[derive(Debug, Default)]
+struct ReadHandles {
+    next: u32,
+    open: HashMap<u32, ReadHandle>,
+}
+
+#[derive(Debug)]
+struct ReadHandle {
+    path: String,
+    file: File,
+    /// Bytes handed to the guest so far
+    bytes: u64,
+}
+
+/// Helper threads that may run capped I/O at once (see
+/// [`HostState::with_latency_cap`]), bounding the threads stuck on hung storage.
+pub const MAX_CAPPED_WORKERS: usize = 4;
+
+/// Slot of a helper thread counted against [`MAX_CAPPED_WORKERS`], freed on drop.
+struct CappedWorker(Arc<AtomicUsize>);
+
+impl CappedWorker {
+    fn claim(workers: &Arc<AtomicUsize>) -> Option<Self> {
+        workers
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                (running < MAX_CAPPED_WORKERS).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(workers)))
+    }
+}
+
+impl Drop for CappedWorker {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Buffers registered with [`HostState::with_blob`], and those opened by
+/// [`HostState::get_blob`] (the handle is the index).
+#[derive(Debug, Default)]
+struct Blobs {
+    registered: HashMap<String, Arc<[u8]>>,
+    open: Vec<Arc<[u8]>>,
+}
+
+/// How policy failures surface from [`HostState::execute_plugin`].
+///
+/// Both modes trace identically; only the returned value differs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DenialMode {
+    /// Typed [`CapError`]s for Rust callers.
+    #[default]
+    Error,
+    /// Uniform `Ok(false)` for guests that only understand allow/deny.
+    /// Invalid input ([`CapError::InvalidPath`]) still errors.
+    Deny,
+}
+
+/// Per-run limits on capability decisions; once either is used up, every
+/// further call is denied with [`CapError::BudgetExhausted`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    /// Wall-clock milliseconds from [`HostState::with_budget`] (unlimited if `None`)
+    pub wall_ms: Option<u64>,
+    /// Number of host calls (unlimited if `None`)
+    pub calls: Option<u64>,
+}
+
+/// Host-side state captured by [`HostState::snapshot`] for later rollback.
+#[derive(Debug, Clone)]
+pub struct HostSnapshot {
+    trace_len: usize,
+    /// Repeats folded into the last event so far, see [`HostState::with_denial_dedup`]
+    last_repeats: u64,
+    chain_head: String,
+    /// Signing key in force, see [`HostState::rotate_key`]
+    keypair: SigningKey,
+    /// Policy in force, see [`HostState::reload_manifest`]
+    manifest: CapabilityManifest,
+    manifest_hash: String,
+    policy_epoch: u64,
 }
 
 /// Errors from capability enforcement.
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Error)]
 pub enum CapError {
     #[error("No FS capability declared")]
     NoFsCapability,
@@ -34,20 +279,99 @@ pub enum CapError {
     #[error("No read patterns defined")]
     NoReadPatterns,
 
-    #[error("Path does not match any glob pattern")]
-    GlobMismatch,
+    #[error("Path `{path}` does not match any glob pattern of {patterns_tried:?}")]
+    GlobMismatch {
+        path: String,
+        patterns_tried: Vec<String>,
+    },
+
+    #[error("Invalid {capability} glob pattern #{idx} `{pattern}`: {source}")]
+    RuntimeInvalidGlob {
+        /// Capability kind the pattern belongs to (e.g. `fs.read`)
+        capability: &'static str,
+        idx: usize,
+        pattern: String,
+        source: PatternError,
+    },
+
+    #[error("Invalid {capability} matcher #{idx} `{pattern}`: {reason}")]
+    RuntimeInvalidMatcher {
+        /// Capability kind the pattern belongs to (e.g. `fs.read`)
+        capability: &'static str,
+        idx: usize,
+        pattern: String,
+        reason: String,
+    },
 
     #[error("Invalid path provided (empty or invalid UTF-8)")]
     InvalidPath,
+
+    #[error("Access to `{path}` was not approved")]
+    ApprovalDenied { path: String },
+
+    #[error("Path `{path}` violates FS constraint: {reason}")]
+    ConstraintViolation { path: String, reason: String },
+
+    #[error("No custom capability `{kind}` declared")]
+    NoCustomCapability { kind: String },
+
+    #[error("Custom capability `{kind}` denied `{request}`")]
+    CustomDenied { kind: String, request: String },
+
+    #[error("Run budget exhausted: no {resource} left")]
+    BudgetExhausted { resource: &'static str },
+
+    #[error("Plugin may not run as {identity}")]
+    RunAsMismatch { identity: String },
+
+    #[error("Host lacks required {requirement}")]
+    UnmetRequirement { requirement: String },
+
+    #[error("No time capability declared")]
+    NoTimeCapability,
+
+    #[error("No blob capability declared")]
+    NoBlobCapability,
+
+    #[error("Blob `{name}` is not allowed")]
+    BlobDenied { name: String },
+
+    #[error("No config capability declared")]
+    NoConfigCapability,
+
+    #[error("Config key `{key}` is not allowed")]
+    ConfigDenied { key: String },
+
+    #[error("Host call `{import}` did not answer within {cap_ms}ms")]
+    Busy { import: String, cap_ms: u64 },
+
+    #[error("IO error reading file: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 /// Host-visible status codes returned from host functions.
+///
+/// These codes are part of the guest ABI and never change. Status-returning
+/// imports (`read_file`, `spawn_plugin`, `status_*`) return the status code;
+/// imports returning a length, handle or time return the status negated (see
+/// [`HostStatus::length_code`]) so non-negative results stay values, and trap
+/// instead of returning `Error`:
+///
+/// | Status    | Status code | Length code | [`CapError`] variants                                    |
+/// |-----------|-------------|-------------|----------------------------------------------------------|
+/// | `Allowed` | `0`         | `>= 0`      | none (granted)                                           |
+/// | `Denied`  | `1`         | `-1`        | every variant where [`CapError::is_policy_denial`] holds |
+/// | `Error`   | `-1`        | trap        | `InvalidPath`, `Io`                                      |
+/// | `Busy`    | `2`         | `-2`        | `Busy`                                                   |
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HostStatus {
     Allowed = 0,
     Denied = 1,
     Error = -1,
+    /// A latency cap elapsed, see [`HostState::with_latency_cap`]
+    Busy = 2,
 }
 
 impl HostState {
@@ -59,258 +383,2348 @@ impl HostState {
     pub fn new(manifest: CapabilityManifest, seed: u64, keypair: SigningKey) -> Self {
         let pubkey = keypair.verifying_key().to_bytes();
         let run_id = format!("captra-run-{seed}");
-        let manifest_json = serde_json::to_string(&manifest).expect("Manifest serializes"); // Safe: validated earlier
-        let mut hashser = Sha256::default();
-        hashser.update(manifest_json.as_bytes());
-        let manifest_hash = format!("{:x}", hashser.finalize());
+        let manifest_hash = manifest.content_hash();
 
         Self {
+            session: None,
+            policy_hook: None,
+            custom_enforcers: CustomEnforcers::default(),
+            shadow_read: None,
+            denial_mode: DenialMode::default(),
+            hash_reads: false,
+            dedup_denials: false,
+            embed_manifest: false,
+            #[cfg(feature = "test-util")]
+            faults: None,
+            decision_cache: None,
+            read_handles: ReadHandles::default(),
+            blobs: Blobs::default(),
+            config_provider: None,
+            latency_caps: HashMap::new(),
+            capped_workers: Arc::default(),
+            max_input_len: DEFAULT_MAX_INPUT_LEN,
+            trace_policy: None,
+            severity_rules: SeverityRules::default(),
+            watchdog: None,
+            webhook: None,
+            budget: None,
+            budget_calls: 0,
+            thresholds: Thresholds::default(),
+            catalog: PluginCatalog::default(),
+            spawn_depth: 0,
+            parent_run_id: None,
+            labels: RunLabels::new(),
+            profile: None,
+            child_runs: Vec::new(),
+            fs_matchers: FsMatchers::new(&manifest),
             manifest,
             trace: Vec::new(),
-            seed,
+            trace_hasher: TraceHasher::default(),
+            sealed_len: 0,
+            timings: Vec::new(),
+            last_denial: None,
+            randomness: RandomnessMode::Deterministic(seed),
             keypair,
             pubkey,
             run_id,
             manifest_hash,
+            seq: SeqCounter::default(),
+            policy_epoch: 0,
+            chain_head: String::new(),
         }
     }
 
-    /// Get `pubkey`
-    #[must_use]
-    pub const fn pubkey(&self) -> &[u8; PUBLIC_KEY_LENGTH] {
-        &self.pubkey
+    /// [`HostState::new`], refusing manifests whose `requires` this host does
+    /// not meet (see [`HostState::check_requirements`]). Only built-in
+    /// features count here, as no custom enforcers are registered yet.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::UnmetRequirement`] naming what is missing.
+    pub fn try_new(
+        manifest: CapabilityManifest,
+        seed: u64,
+        keypair: SigningKey,
+    ) -> Result<Self, CapError> {
+        let mut host = Self::new(manifest, seed, keypair);
+        host.check_requirements()?;
+        Ok(host)
     }
 
-    /// Get `run_id`
-    #[inline]
-    #[must_use]
-    pub fn run_id(&self) -> &str {
-        &self.run_id
+    /// Run under the manifest's capability profile `name` (see
+    /// [`CapabilityManifest::resolve_profile`]), recorded in trace headers.
+    /// The trace pins the hash of the resolved manifest, and
+    /// [`HostState::reload_manifest`] keeps applying the profile.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::UnknownProfile`] if the manifest does not declare it.
+    pub fn with_profile(mut self, name: &str) -> Result<Self, ManifestError> {
+        self.manifest = self.manifest.resolve_profile(name)?;
+        self.fs_matchers = FsMatchers::new(&self.manifest);
+        self.manifest_hash = self.manifest.content_hash();
+        self.profile = Some(name.to_owned());
+        Ok(self)
     }
 
-    /// Get `trace`
+    /// Active capability profile, see [`HostState::with_profile`]
     #[inline]
     #[must_use]
-    pub fn trace(&self) -> &[TraceEvent] {
-        &self.trace
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
     }
 
-    /// Simulate "plugin execution": check if path is allowed via FS read cap.
-    /// Logs to trace on success/error (outcome=false for errors).
+    /// Continue a previously saved (partial) run instead of starting a new one,
+    /// see [`HostState::with_resumed_trace`].
     ///
     /// # Errors
     ///
-    /// [`CapError`] if enforcement fails (e.g., no caps or mismatch).
-    pub fn execute_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<bool, CapError> {
-        let path_str = path.as_ref().to_string_lossy();
-        if path_str.is_empty() {
-            return Err(CapError::InvalidPath);
-        }
+    /// [`TraceError::IntegrityViolation`] if the trace is broken or belongs to another run.
+    pub fn resume(
+        manifest: CapabilityManifest,
+        seed: u64,
+        keypair: SigningKey,
+        existing_trace: Vec<TraceEvent>,
+    ) -> Result<Self, TraceError> {
+        Self::new(manifest, seed, keypair).with_resumed_trace(existing_trace)
+    }
 
-        if self.manifest.capabilities.fs.is_none() {
-            self.log_cap_error(CapEventSubtype::NoFsCapability, "missing fs cap", &path_str);
-            return Err(CapError::NoFsCapability);
+    /// Continue `existing_trace` on this host, after configuring it like the
+    /// original run (e.g. [`HostState::with_profile`]).
+    ///
+    /// Takes the run id from the trace and rehydrates seq numbering, policy
+    /// epoch and the hash chain from it. With deterministic randomness the
+    /// trace must have been seeded like this host.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::IntegrityViolation`] if the trace is broken, mixes runs or
+    /// was seeded differently.
+    pub fn with_resumed_trace(
+        mut self,
+        existing_trace: Vec<TraceEvent>,
+    ) -> Result<Self, TraceError> {
+        let chain_head = verify_chain(&existing_trace)?;
+        if let Some(first) = existing_trace.first() {
+            if let Some(event) = existing_trace.iter().find(|ev| ev.run_id != first.run_id) {
+                return Err(TraceError::IntegrityViolation {
+                    seq: event.seq,
+                    reason: format!(
+                        "run_id `{}` does not match `{}`",
+                        event.run_id, first.run_id
+                    ),
+                });
+            }
+            if matches!(self.randomness, RandomnessMode::Deterministic(_))
+                && first.ts_seed != self.randomness.ts_seed(first.seq)
+            {
+                return Err(TraceError::IntegrityViolation {
+                    seq: first.seq,
+                    reason: "ts_seed was not derived from this host's seed".into(),
+                });
+            }
+            self.run_id.clone_from(&first.run_id);
         }
+        self.policy_epoch = existing_trace.last().map_or(0, |ev| ev.policy_epoch);
+        self.seq
+            .reset(existing_trace.last().map_or(0, TraceEvent::last_seq));
+        self.trace_hasher = TraceHasher::from_trace(&existing_trace);
+        self.trace = existing_trace;
+        self.chain_head = chain_head;
+        Ok(self)
+    }
 
-        let read_patterns_ops = self
-            .manifest
-            .capabilities
-            .fs
-            .as_ref()
-            .and_then(|fs| fs.read.clone());
+    /// Join a multi-host [`RunSession`] as `host_id`.
+    /// Subsequent events carry a vector-clock snapshot of the session.
+    #[must_use]
+    pub fn with_session(mut self, session: &RunSession, host_id: impl Into<String>) -> Self {
+        self.session = Some((host_id.into(), session.clone()));
+        self
+    }
 
-        let read_patterns = match read_patterns_ops {
-            Some(v) if !v.is_empty() => v,
-            _ => {
-                self.log_cap_error(
-                    CapEventSubtype::NoReadPatterns,
-                    "empty read patterns",
-                    &path_str,
-                );
-                return Err(CapError::NoReadPatterns);
-            }
-        };
+    /// Let the guest start the plugins of `catalog` as child runs through
+    /// [`HostState::spawn_plugin`].
+    #[must_use]
+    pub fn with_plugin_catalog(mut self, catalog: PluginCatalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
 
-        let seq = u64::try_from(self.trace.len()).map_or(1, |len| len + 1);
+    /// Ask `hook` to approve every call the manifest allows; the decision is
+    /// recorded as a `cap.approval` event before the call itself.
+    #[must_use]
+    pub fn with_policy_hook(mut self, hook: impl PolicyHook + 'static) -> Self {
+        self.policy_hook = Some(Box::new(hook));
+        self
+    }
 
-        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_mul(PRIME_MULTIPLIER + seq));
-        let ts_seed = rng.r#gen();
+    /// Decide requests for the `kind` custom capability with `enforcer`,
+    /// replacing any previous one, see [`HostState::check_custom`].
+    pub fn register_custom_enforcer(
+        &mut self,
+        kind: impl Into<String>,
+        enforcer: impl FnMut(&serde_json::Value, &str) -> bool + Send + 'static,
+    ) {
+        self.custom_enforcers
+            .0
+            .insert(kind.into(), Box::new(enforcer));
+    }
 
-        let is_allowed = read_patterns.iter().any(|pattern| {
-            Pattern::new(pattern).map_or_else(
-                |_| {
-                    self.log_cap_error(CapEventSubtype::InvalidGlob, pattern, &path_str);
-                    false
-                },
-                |p| p.matches(&path_str),
-            )
-        });
+    /// Evaluate every decision against a candidate manifest as well, without
+    /// enforcing it; disagreements are recorded as `shadow.mismatch` events.
+    /// The candidate is validated and resolved to the active profile like
+    /// [`HostState::reload_manifest`] would.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError`] if `candidate` is invalid or lacks the active profile.
+    pub fn with_shadow_manifest(
+        mut self,
+        candidate: CapabilityManifest,
+    ) -> Result<Self, ManifestError> {
+        candidate.validate()?;
+        let candidate = match &self.profile {
+            Some(name) => candidate.resolve_profile(name)?,
+            None => candidate,
+        };
+        let read = candidate.capabilities.fs.and_then(|fs| fs.read);
+        self.shadow_read = Some(CompiledSpecs::new(&read.unwrap_or_default()));
+        Ok(self)
+    }
 
-        if !is_allowed {
-            self.log_cap_error(
-                CapEventSubtype::GlobMismatch,
-                "no matching pattern",
-                &path_str,
-            );
-            return Err(CapError::GlobMismatch);
-        }
+    /// Choose how policy failures are returned, see [`DenialMode`].
+    #[must_use]
+    pub const fn with_denial_mode(mut self, mode: DenialMode) -> Self {
+        self.denial_mode = mode;
+        self
+    }
 
-        log_trace_event(
-            seq,
-            EventType::CapCall,
-            &path_str,
-            true,
-            ts_seed,
-            &self.manifest.plugin,
-        );
+    /// Choose where `ts_seed` values come from (default: deterministic from
+    /// the constructor seed). The run id stays derived from the constructor seed.
+    #[must_use]
+    pub const fn with_randomness(mut self, mode: RandomnessMode) -> Self {
+        self.randomness = mode;
+        self
+    }
 
-        self.trace.push(TraceEvent {
-            run_id: self.run_id.clone(),
-            seq,
-            event_type: EventType::CapCall,
-            input: path_str.into(),
-            outcome: is_allowed,
-            ts_seed,
-        });
+    /// Replace the seed-derived run id with a time-ordered UUID v7, so runs
+    /// sort chronologically and never collide across hosts. The seed stays
+    /// recorded in the signed trace header via [`RandomnessMode`].
+    ///
+    /// No effect once events are recorded, a run never changes id mid-trace.
+    #[cfg(feature = "ulid")]
+    #[must_use]
+    pub fn with_uuid_run_id(mut self) -> Self {
+        if self.trace.is_empty() {
+            self.run_id = uuid::Uuid::now_v7().to_string();
+        }
+        self
+    }
 
-        Ok(true)
+    /// Record the SHA256 of every file returned by [`HostState::read_file`]
+    /// as a `fs.read` event, so auditors can prove what data reached the guest.
+    #[must_use]
+    pub const fn with_read_hashing(mut self, enabled: bool) -> Self {
+        self.hash_reads = enabled;
+        self
     }
 
-    /// Signs the current trace JSON with the host keypair.
-    /// Computes SHA256 hash of trace for integrity.
+    /// Collapse a denial identical to the one just recorded (same type,
+    /// input, details, severity and epoch) into it, bumping its
+    /// [`TraceEvent::repeat_count`] rather than appending a new event.
     ///
-    /// # Errors
-    ///
-    /// [`TraceError`] (serialization).
-    pub fn sign_current_trace(&mut self) -> Result<SignedTrace, TraceError> {
-        let trace_json = finalize_trace(&self.trace);
-        let mut hasher = Sha256::default();
-        hasher.update(trace_json.as_bytes());
-        let trace_hash = format!("{:x}", hasher.finalize());
+    /// Folded repeats still consume seqs and are neither logged nor sent to
+    /// the webhook again; the chain head is rehashed over the updated event.
+    /// Events covered by [`HostState::sign_checkpoint`] are never folded into.
+    #[must_use]
+    pub const fn with_denial_dedup(mut self, enabled: bool) -> Self {
+        self.dedup_denials = enabled;
+        self
+    }
+
+    /// Embed the full manifest in signed traces, not just its hash, so they
+    /// stay verifiable after the manifest file is gone.
+    #[must_use]
+    pub const fn with_embedded_manifest(mut self, enabled: bool) -> Self {
+        self.embed_manifest = enabled;
+        self
+    }
 
-        let signature = self.keypair.sign(trace_hash.as_bytes()).to_bytes().to_vec();
+    /// Forward denials and critical events to `sink` as they are recorded.
+    #[must_use]
+    pub fn with_webhook(mut self, sink: WebhookSink) -> Self {
+        self.webhook = Some(sink);
+        self
+    }
 
-        Ok(SignedTrace::new(
-            self.run_id.clone(),
-            self.manifest_hash.clone(),
-            trace_json,
-            signature,
-        ))
+    /// Detach the webhook sink, flushing its pending alerts.
+    pub fn close_webhook(&mut self) -> Option<WebhookStats> {
+        self.webhook.take().map(WebhookSink::close)
     }
 
-    /// Serialize trace to pretty JSON string
-    #[inline]
+    /// Provide a read-only buffer guests may open by `name` with
+    /// [`HostState::get_blob`], if the manifest's `blob` capability allows it.
     #[must_use]
-    pub fn get_trace_json(&self) -> String {
-        finalize_trace(&self.trace)
+    pub fn with_blob(mut self, name: impl Into<String>, bytes: impl Into<Arc<[u8]>>) -> Self {
+        self.blobs.registered.insert(name.into(), bytes.into());
+        self
     }
 
-    /// Save the current trace to a file as pretty JSON.
+    /// Serve [`HostState::get_config`] from `provider`, replacing any previous one.
+    #[must_use]
+    pub fn with_config_provider(mut self, provider: impl ConfigProvider + 'static) -> Self {
+        self.config_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Give the storage work of host call `import` at most `cap` after its
+    /// capability check, so a slow disk or network mount never stalls the
+    /// embedder's thread. Calls are named after their guest imports:
     ///
-    /// # Errors
+    /// - `read_file`: the `read_file` import (see [`HostState::check_read`])
+    ///   and [`HostState::read_file`], behind `read_alloc`
+    /// - `read_range`, `list_dir`, `read_open`, `read_chunk` and `get_blob`
     ///
-    /// If file write fails (e.g., I/O error) or JSON serialization fails.
-    pub fn save_current_trace<P: AsRef<Path>>(&self, path: P) -> Result<(), TraceError> {
-        save_trace(&self.trace, path)
+    /// Capped calls run the I/O on one of at most [`MAX_CAPPED_WORKERS`]
+    /// helper threads; once `cap` elapses, or if every helper is still stuck
+    /// on earlier calls, they record a `host.busy` event and fail with
+    /// [`CapError::Busy`] (guests see [`HostStatus::Busy`]). A timed-out
+    /// `read_chunk` closes its handle. Child runs inherit the caps and share
+    /// the helpers. Timing depends on the machine, so traces with caps are not
+    /// replay-deterministic.
+    #[must_use]
+    pub fn with_latency_cap(mut self, import: impl Into<String>, cap: Duration) -> Self {
+        self.latency_caps.insert(import.into(), cap);
+        self
     }
 
-    fn log_cap_error(&mut self, event_subtype: CapEventSubtype, reason: &str, path_str: &str) {
-        let seq = u64::try_from(self.trace.len()).map_or(1, |len| len + 1);
-        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_mul(PRIME_MULTIPLIER + seq));
-        let ts_seed = rng.r#gen();
-
-        let event_type = EventType::from(event_subtype);
+    /// Latency cap of host call `import`, see [`HostState::with_latency_cap`]
+    #[must_use]
+    pub fn latency_cap(&self, import: &str) -> Option<Duration> {
+        self.latency_caps.get(import).copied()
+    }
 
-        log_trace_event(
-            seq,
-            event_type,
-            path_str,
-            false,
-            ts_seed,
-            &self.manifest.plugin,
-        );
+    /// Force denials, delays or corrupted reads on the deterministic schedule
+    /// of `injector`. Each injected fault is traced as `fault.injected`.
+    #[cfg(feature = "test-util")]
+    #[must_use]
+    pub fn with_fault_injector(mut self, injector: FaultInjector) -> Self {
+        self.faults = Some(injector);
+        self
+    }
 
-        self.trace.push(TraceEvent {
-            run_id: self.run_id.clone(),
-            seq,
-            event_type,
-            input: format!("{event_subtype}: {reason}"),
-            outcome: false,
-            ts_seed,
+    /// Cache FS read grants by path so repeated reads skip glob matching.
+    /// The cache is tied to the policy epoch: [`HostState::reload_manifest`]
+    /// flushes it, so a revoked grant takes effect on the very next call.
+    /// The policy hook is still consulted and every call is still traced.
+    #[must_use]
+    pub fn with_decision_cache(mut self) -> Self {
+        self.decision_cache = Some(DecisionCache {
+            epoch: self.policy_epoch,
+            allowed: HashSet::new(),
         });
+        self
     }
-}
 
-pub fn init_tracing() {
-    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
-}
+    /// Limit the capability calls of this run, starting the wall clock now.
+    /// Rolling back with [`HostState::restore`] does not refund the budget.
+    #[must_use]
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some((budget, Instant::now()));
+        self
+    }
 
-/// Register host functions for Wasmtime on the provided linker.
-///
-/// Exposes:
-///  - `host::read_file(ptr: i32, len: i32) -> Result<i32, Trap>`
-///  - `host::status_allowed() -> i32`
-///  - `host::status_denied() -> i32`
-///  - `host::status_error() -> i32`
-///
-/// # Errors
-///
-/// `read_file` returns `Ok(HostStatus::Allowed/Denied)` for normal outcomes,
-/// and Err(Trap) for exceptional errors (OOB, invalid pointer, invalid UTF-8).
-pub fn add_wasm_linker_funcs(linker: &mut Linker<HostState>) -> anyhow::Result<()> {
-    linker.func_wrap(
-        "host",
-        "read_file",
-        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> anyhow::Result<i32> {
-            let memory = caller
-                .get_export("memory")
-                .and_then(Extern::into_memory)
-                .ok_or(Trap::MemoryOutOfBounds)?;
-
-            let start = usize::try_from(ptr).map_err(|_| Trap::BadConversionToInteger)?;
-            let read_len = usize::try_from(len).map_err(|_| Trap::BadConversionToInteger)?;
-
-            let data = memory.data(&caller);
-
-            if start
-                .checked_add(read_len)
-                .is_none_or(|end| end > data.len())
-            {
-                return Err(Trap::MemoryOutOfBounds.into());
+    /// Call `callback` once when usage of the budget `resource` (`calls` or
+    /// `wall_ms`) reaches `percent` of its limit, so embedders can warn
+    /// before a plugin runs out mid-task. Checked on every charged call;
+    /// resources without a limit never fire.
+    pub fn on_threshold(
+        &mut self,
+        resource: &'static str,
+        percent: u8,
+        callback: impl FnMut(&BudgetThreshold) + Send + 'static,
+    ) {
+        self.thresholds.0.push(Threshold {
+            resource,
+            percent,
+            fired: false,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// What is left of the [`Budget`], if one is set
+    #[must_use]
+    pub fn remaining_budget(&self) -> Option<Budget> {
+        self.budget.map(|(budget, started)| {
+            let elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+            Budget {
+                wall_ms: budget
+                    .wall_ms
+                    .map(|wall_ms| wall_ms.saturating_sub(elapsed_ms)),
+                calls: budget
+                    .calls
+                    .map(|calls| calls.saturating_sub(self.budget_calls)),
             }
+        })
+    }
 
-            let bytes = &data[start..start + read_len];
-            let path_str = from_utf8(bytes)
-                .map_err(|_| Trap::BadConversionToInteger)?
-                .to_string();
-
-            match caller.data_mut().execute_plugin(path_str) {
-                Ok(true) => Ok(HostStatus::Allowed.into()),
-                Ok(false) => Ok(HostStatus::Denied.into()),
-                Err(err) => match err {
-                    CapError::GlobMismatch => Ok(HostStatus::Denied.into()),
-                    CapError::NoFsCapability | CapError::NoReadPatterns => {
-                        Ok(HostStatus::Denied.into())
-                    }
-                    CapError::InvalidPath => Err(Trap::MemoryOutOfBounds.into()),
-                },
+    /// Truncate event inputs longer than `max_len` bytes (default
+    /// [`DEFAULT_MAX_INPUT_LEN`]), see [`truncate_input`].
+    #[must_use]
+    pub const fn with_max_input_len(mut self, max_len: usize) -> Self {
+        self.max_input_len = max_len;
+        self
+    }
+
+    /// Log events according to `policy` instead of the manifest's
+    /// `trace_policy`. Only the `tracing` output changes; the trace keeps
+    /// every event.
+    #[must_use]
+    pub fn with_trace_policy(mut self, policy: TracePolicy) -> Self {
+        self.trace_policy = Some(policy);
+        self
+    }
+
+    /// Stamp events with severities from `rules` rather than only
+    /// [`crate::Severity::derived`].
+    #[must_use]
+    pub fn with_severity_rules(mut self, rules: SeverityRules) -> Self {
+        self.severity_rules = rules;
+        self
+    }
+
+    /// Capture the current trace position and counters, e.g. before a
+    /// speculative guest call whose result may be discarded.
+    #[must_use]
+    pub fn snapshot(&self) -> HostSnapshot {
+        HostSnapshot {
+            trace_len: self.trace.len(),
+            last_repeats: self.trace.last().map_or(0, |ev| ev.repeat_count),
+            chain_head: self.chain_head.clone(),
+            keypair: self.keypair.clone(),
+            manifest: self.manifest.clone(),
+            manifest_hash: self.manifest_hash.clone(),
+            policy_epoch: self.policy_epoch,
+        }
+    }
+
+    /// Roll host-side state back to `snapshot`, dropping every event recorded
+    /// since and undoing later key rotations and manifest reloads.
+    ///
+    /// Events stamped by a shared [`RunSession`] keep their clock ticks; the
+    /// session itself is not rolled back.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::IntegrityViolation`] if `snapshot` was not taken from this
+    /// run's current history (e.g. after an earlier restore).
+    pub fn restore(&mut self, snapshot: &HostSnapshot) -> Result<(), TraceError> {
+        let last = snapshot
+            .trace_len
+            .checked_sub(1)
+            .and_then(|idx| self.trace.get(idx))
+            .map(|ev| TraceEvent {
+                repeat_count: snapshot.last_repeats,
+                ..ev.clone()
+            });
+        let head = last.as_ref().map(event_hash).unwrap_or_default();
+        if head != snapshot.chain_head {
+            return Err(TraceError::IntegrityViolation {
+                seq: u64::try_from(snapshot.trace_len).unwrap_or(u64::MAX),
+                reason: "snapshot does not belong to the current trace".into(),
+            });
+        }
+        self.trace.truncate(snapshot.trace_len);
+        self.sealed_len = self.sealed_len.min(snapshot.trace_len);
+        if let (Some(event), Some(last)) = (self.trace.last_mut(), last) {
+            *event = last;
+        }
+        self.trace_hasher = TraceHasher::from_trace(&self.trace);
+        let max_seq = self.trace.last().map_or(0, TraceEvent::last_seq);
+        self.seq.reset(max_seq);
+        self.timings.retain(|timing| timing.seq <= max_seq);
+        self.chain_head = head;
+        self.keypair.clone_from(&snapshot.keypair);
+        self.pubkey = self.keypair.verifying_key().to_bytes();
+        if self.policy_epoch != snapshot.policy_epoch {
+            self.manifest.clone_from(&snapshot.manifest);
+            self.fs_matchers = FsMatchers::new(&self.manifest);
+            self.manifest_hash.clone_from(&snapshot.manifest_hash);
+            self.policy_epoch = snapshot.policy_epoch;
+            if let Some(cache) = self.decision_cache.as_mut() {
+                cache.allowed.clear();
+                cache.epoch = self.policy_epoch;
             }
-        },
-    )?;
-    linker.func_wrap("host", "status_allowed", || -> i32 {
-        HostStatus::Allowed.into()
-    })?;
-    linker.func_wrap("host", "status_denied", || -> i32 {
-        HostStatus::Denied.into()
-    })?;
-    linker.func_wrap("host", "status_error", || -> i32 {
-        HostStatus::Error.into()
-    })?;
-    Ok(())
-}
+        }
+        Ok(())
+    }
 
-impl From<HostStatus> for i32 {
-    fn from(value: HostStatus) -> Self {
-        value as Self
+    /// Get `pubkey`
+    #[must_use]
+    pub const fn pubkey(&self) -> &[u8; PUBLIC_KEY_LENGTH] {
+        &self.pubkey
+    }
+
+    /// Public key as lowercase hex, see [`pubkey_hex`]
+    #[must_use]
+    pub fn pubkey_hex(&self) -> String {
+        pubkey_hex(&self.pubkey)
+    }
+
+    /// Public key as PEM, see [`pubkey_pem`]
+    #[must_use]
+    pub fn pubkey_pem(&self) -> String {
+        pubkey_pem(&self.pubkey)
+    }
+
+    /// Public key as an `ssh-ed25519` line, see [`pubkey_openssh`]
+    #[must_use]
+    pub fn pubkey_openssh(&self) -> String {
+        pubkey_openssh(&self.pubkey)
+    }
+
+    /// Fingerprint accepted by [`SignedTrace::verify_with_pinned_fingerprint`],
+    /// see [`pubkey_fingerprint`]
+    #[must_use]
+    pub fn pubkey_fingerprint(&self) -> String {
+        pubkey_fingerprint(&self.pubkey)
+    }
+
+    /// Fingerprint as shown by `ssh-keygen -l`, see [`pubkey_openssh_fingerprint`]
+    #[must_use]
+    pub fn pubkey_openssh_fingerprint(&self) -> String {
+        pubkey_openssh_fingerprint(&self.pubkey)
+    }
+
+    /// Get `manifest_hash`
+    #[inline]
+    #[must_use]
+    pub fn manifest_hash(&self) -> &str {
+        &self.manifest_hash
+    }
+
+    /// Get `policy_epoch`: number of [`HostState::reload_manifest`] calls so far
+    #[inline]
+    #[must_use]
+    pub const fn policy_epoch(&self) -> u64 {
+        self.policy_epoch
+    }
+
+    /// Swap in a new manifest without restarting the run. Bumps the policy
+    /// epoch stamped on every later event and records a `policy.reload`
+    /// event with the new manifest hash.
+    ///
+    /// The new manifest's `run_as` and `requires` are checked as before
+    /// instantiation. Read handles opened under the old policy are closed
+    /// (traced like [`HostState::close_read`]); the guest has to reopen them.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError`] if `manifest` is invalid or lacks the active profile,
+    /// or [`CapError::RunAsMismatch`] / [`CapError::UnmetRequirement`]; the
+    /// current one stays in force.
+    pub fn reload_manifest(&mut self, manifest: CapabilityManifest) -> Result<(), CaptraError> {
+        manifest.validate()?;
+        let manifest = match &self.profile {
+            Some(name) => manifest.resolve_profile(name)?,
+            None => manifest,
+        };
+        let previous = std::mem::replace(&mut self.manifest, manifest);
+        if let Err(err) = self.check_run_as().and_then(|()| self.check_requirements()) {
+            self.manifest = previous;
+            return Err(err.into());
+        }
+        let mut handles = self.read_handles.open.keys().copied().collect::<Vec<_>>();
+        handles.sort_unstable();
+        for handle in handles {
+            self.close_read(handle)?;
+        }
+        self.fs_matchers = FsMatchers::new(&self.manifest);
+        self.manifest_hash = self.manifest.content_hash();
+        self.policy_epoch += 1;
+        if let Some(cache) = self.decision_cache.as_mut() {
+            cache.allowed.clear();
+            cache.epoch = self.policy_epoch;
+        }
+        let input = format!(
+            "manifest_sha256={} epoch={}",
+            self.manifest_hash, self.policy_epoch
+        );
+        self.record_event(EventType::PolicyReload, input.clone(), true, &input);
+        Ok(())
+    }
+
+    /// Switch to signing with `new_key` mid-run. Records a `key.rotate` event
+    /// carrying the new public key, signed by the old key over its trace
+    /// position, so [`SignedTrace::verify_with_rotations`] can follow the
+    /// chain of custody from the original key.
+    pub fn rotate_key(&mut self, new_key: SigningKey) {
+        let new_pubkey = new_key.verifying_key().to_bytes();
+        let encoded = general_purpose::STANDARD.encode(new_pubkey);
+        let seq = self.seq.peek();
+        let message = key_rotation_message(&self.run_id, seq, &self.chain_head, &encoded);
+        let signature = general_purpose::STANDARD.encode(self.keypair.sign(&message).to_bytes());
+
+        let input = format!("new_fingerprint={}", pubkey_fingerprint(&new_pubkey));
+        self.record_event_with(
+            EventType::KeyRotate,
+            input.clone(),
+            true,
+            &input,
+            Some(serde_json::json!({ "new_pubkey": encoded, "signature": signature })),
+        );
+        self.keypair = new_key;
+        self.pubkey = new_pubkey;
+    }
+
+    /// Get `run_id`
+    #[inline]
+    #[must_use]
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Run that spawned this one, for child runs
+    #[inline]
+    #[must_use]
+    pub fn parent_run_id(&self) -> Option<&str> {
+        self.parent_run_id.as_deref()
+    }
+
+    /// Label the run with business context (ticket id, CI job, customer id),
+    /// recorded in the trace file header and [`SignedTrace::labels`] and
+    /// inherited by spawned child runs. Setting a key again replaces its value.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::LabelsSealed`] once the first event is recorded, so every
+    /// event of a run shares its labels.
+    pub fn set_label(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), TraceError> {
+        let key = key.into();
+        if !self.trace.is_empty() {
+            return Err(TraceError::LabelsSealed { key });
+        }
+        self.labels.insert(key, value.into());
+        Ok(())
+    }
+
+    /// Labels set with [`HostState::set_label`]
+    #[inline]
+    #[must_use]
+    pub const fn labels(&self) -> &RunLabels {
+        &self.labels
+    }
+
+    /// Child runs spawned so far, depth first
+    #[inline]
+    #[must_use]
+    pub fn child_runs(&self) -> &[ChildRun] {
+        &self.child_runs
+    }
+
+    /// Get `trace`
+    #[inline]
+    #[must_use]
+    pub fn trace(&self) -> &[TraceEvent] {
+        &self.trace
+    }
+
+    /// Get `timings`: evaluation time of every capability decision so far
+    #[inline]
+    #[must_use]
+    pub fn decision_timings(&self) -> &[DecisionTiming] {
+        &self.timings
+    }
+
+    /// `"<reason_code>: <message>"` of the most recent policy denial, see
+    /// [`CapError::reason_code`]
+    #[inline]
+    #[must_use]
+    pub fn last_denial(&self) -> Option<&str> {
+        self.last_denial.as_deref()
+    }
+
+    /// Aggregate of [`HostState::decision_timings`]
+    #[must_use]
+    pub fn decision_stats(&self) -> DecisionStats {
+        DecisionStats::from_timings(&self.timings)
+    }
+
+    /// Simulate "plugin execution": check if path is allowed via FS read cap.
+    /// Logs to trace on success/error (outcome=false for errors).
+    ///
+    /// # Errors
+    ///
+    /// [`CapError`] if enforcement fails (e.g., no caps or mismatch). With
+    /// [`DenialMode::Deny`] policy failures return `Ok(false)` instead.
+    pub fn execute_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<bool, CapError> {
+        let path_str = path.as_ref().to_string_lossy();
+        if path_str.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+
+        let result = self.timed_decision(&path_str, |host| host.enforce_read(&path_str));
+        self.compare_shadow(&path_str);
+        match (self.denial_mode, result) {
+            (DenialMode::Deny, Err(err)) if err.is_policy_denial() => Ok(false),
+            (_, result) => result,
+        }
+    }
+
+    /// [`HostState::execute_plugin`] as the guest `read_file` import answers
+    /// it: with a `read_file` latency cap (see
+    /// [`HostState::with_latency_cap`]) a granted path is also opened within
+    /// the cap, so stalled storage answers [`CapError::Busy`] rather than a
+    /// grant the guest's next read would hang on.
+    ///
+    /// # Errors
+    ///
+    /// As [`HostState::execute_plugin`], plus [`CapError::Busy`] and
+    /// [`CapError::Io`] from the open.
+    pub fn check_read<P: AsRef<Path>>(&mut self, path: P) -> Result<bool, CapError> {
+        let path = path.as_ref();
+        let allowed = self.execute_plugin(path)?;
+        if allowed && self.latency_cap("read_file").is_some() {
+            let path_str = path.to_string_lossy().into_owned();
+            let (owned, grant) = (path.to_path_buf(), self.read_grant(&path_str));
+            self.capped_io("read_file", &path_str, move || {
+                secure_open(&owned, &grant).map(drop)
+            })?;
+        }
+        Ok(allowed)
+    }
+
+    /// Gate an embedder-defined operation (e.g. `kind = "clipboard"`,
+    /// `request = "paste"`): the manifest must declare `kind` and its
+    /// registered enforcer must allow `request`. Traced like an FS call.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::NoCustomCapability`] if `kind` is undeclared (see [`Posture`]),
+    /// [`CapError::CustomDenied`] if the enforcer refuses or none is registered.
+    /// With [`DenialMode::Deny`] these return `Ok(false)` instead.
+    pub fn check_custom(&mut self, kind: &str, request: &str) -> Result<bool, CapError> {
+        let result = self.timed_decision(request, |host| host.enforce_custom(kind, request));
+        match (self.denial_mode, result) {
+            (DenialMode::Deny, Err(err)) if err.is_policy_denial() => Ok(false),
+            (_, result) => result,
+        }
+    }
+
+    /// Wall-clock milliseconds since the Unix epoch, rounded down to the
+    /// manifest's `time.granularity_ms`. Each read is traced as a `cap.call`
+    /// carrying the granularity but not the value, so traces stay replayable.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::NoTimeCapability`] if time is undeclared (see [`Posture`]).
+    pub fn now_millis(&mut self) -> Result<u64, CapError> {
+        let granularity = self.time_granularity()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+            });
+        Ok(now - now % granularity)
+    }
+
+    fn time_granularity(&mut self) -> Result<u64, CapError> {
+        let input = "time: now_millis";
+        let Some(time) = self.manifest.capabilities.time else {
+            if self.manifest.posture == Posture::Audit {
+                let granularity = TimeCapability::default().granularity_ms;
+                let audit = format!(
+                    "{input}: time undeclared, allowed by audit posture granularity_ms={granularity}"
+                );
+                self.record_event(EventType::CapAudit, audit, true, input);
+                return Ok(granularity);
+            }
+            self.log_cap_error(CapEventSubtype::NoTimeCapability, "time undeclared", input);
+            return Err(CapError::NoTimeCapability);
+        };
+        let granularity = time.granularity_ms.max(1);
+        let call = format!("{input} granularity_ms={granularity}");
+        self.record_event(EventType::CapCall, call, true, input);
+        Ok(granularity)
+    }
+
+    fn enforce_custom(&mut self, kind: &str, request: &str) -> Result<bool, CapError> {
+        let input = format!("{kind}: {request}");
+        let Some(cap) = self
+            .manifest
+            .capabilities
+            .custom
+            .iter()
+            .find(|cap| cap.kind == kind)
+        else {
+            if self.manifest.posture == Posture::Audit {
+                let audit = format!("{input}: custom undeclared, allowed by audit posture");
+                self.record_event(EventType::CapAudit, audit, true, &input);
+                return Ok(true);
+            }
+            let reason = format!("{kind} undeclared");
+            self.log_cap_error(CapEventSubtype::NoCustomCapability, &reason, &input);
+            return Err(CapError::NoCustomCapability { kind: kind.into() });
+        };
+
+        let params = cap.params.clone();
+        let allowed = self
+            .custom_enforcers
+            .0
+            .get_mut(kind)
+            .is_some_and(|enforcer| enforcer(&params, request));
+        if !allowed {
+            let reason = format!("{kind} enforcer refused");
+            self.log_cap_error(CapEventSubtype::CustomDenied, &reason, &input);
+            return Err(CapError::CustomDenied {
+                kind: kind.into(),
+                request: request.into(),
+            });
+        }
+
+        self.record_event(EventType::CapCall, input.clone(), true, &input);
+        Ok(true)
+    }
+
+    fn enforce_read(&mut self, path_str: &str) -> Result<bool, CapError> {
+        if self.manifest.capabilities.fs.is_none() {
+            self.undeclared_fs(path_str)?;
+            return Ok(true);
+        }
+        if !self.cached_allow(path_str) {
+            self.match_read_patterns(path_str)?;
+            if let Some(cache) = self.decision_cache.as_mut() {
+                cache.allowed.insert(path_str.to_owned());
+            }
+        }
+
+        if let Some(hook) = self.policy_hook.as_mut() {
+            let approval = hook.decide(&ApprovalRequest {
+                plugin: self.manifest.plugin.clone(),
+                path: path_str.to_owned(),
+            });
+            self.record_event(
+                EventType::CapApproval,
+                format!("{path_str}: {approval}"),
+                approval.is_allowed(),
+                path_str,
+            );
+            if !approval.is_allowed() {
+                return Err(CapError::ApprovalDenied {
+                    path: path_str.into(),
+                });
+            }
+        }
+
+        self.record_event(EventType::CapCall, path_str.to_owned(), true, path_str);
+
+        Ok(true)
+    }
+
+    /// Whether `path_str` was granted under the current policy epoch.
+    fn cached_allow(&self, path_str: &str) -> bool {
+        self.decision_cache.as_ref().is_some_and(|cache| {
+            cache.epoch == self.policy_epoch && cache.allowed.contains(path_str)
+        })
+    }
+
+    fn match_read_patterns(&mut self, path_str: &str) -> Result<(), CapError> {
+        let read_patterns_ops = self
+            .manifest
+            .capabilities
+            .fs
+            .as_ref()
+            .and_then(|fs| fs.read.clone());
+
+        let read_patterns = match read_patterns_ops {
+            Some(v) if !v.is_empty() => v,
+            _ => {
+                self.log_cap_error(
+                    CapEventSubtype::NoReadPatterns,
+                    "empty read patterns",
+                    path_str,
+                );
+                return Err(CapError::NoReadPatterns);
+            }
+        };
+
+        let matched = self.fs_matchers.read.position(path_str);
+        let invalid = self
+            .fs_matchers
+            .read
+            .iter()
+            .enumerate()
+            .take(matched.unwrap_or(usize::MAX))
+            .filter_map(|(idx, matcher)| matcher.as_ref().err().map(|err| (idx, err.clone())))
+            .collect::<Vec<_>>();
+        let mut invalid_glob = None;
+        for (idx, source) in invalid {
+            let pattern = read_patterns[idx].clone();
+            let details = serde_json::json!({
+                "capability": "fs.read",
+                "index": idx,
+                "pattern": pattern,
+                "error": source.to_string(),
+            });
+            self.record_event_with(
+                EventType::from(CapEventSubtype::InvalidGlob),
+                format!("{}: {pattern}", CapEventSubtype::InvalidGlob),
+                false,
+                path_str,
+                Some(details),
+            );
+            invalid_glob.get_or_insert((idx, pattern, source));
+        }
+
+        if matched.is_none() {
+            if let Some((idx, pattern, source)) = invalid_glob {
+                return Err(match source {
+                    MatcherError::Glob(source) => CapError::RuntimeInvalidGlob {
+                        capability: "fs.read",
+                        idx,
+                        pattern,
+                        source,
+                    },
+                    source => CapError::RuntimeInvalidMatcher {
+                        capability: "fs.read",
+                        idx,
+                        pattern,
+                        reason: source.to_string(),
+                    },
+                });
+            }
+            self.log_cap_error(
+                CapEventSubtype::GlobMismatch,
+                "no matching pattern",
+                path_str,
+            );
+            return Err(CapError::GlobMismatch {
+                path: path_str.into(),
+                patterns_tried: read_patterns,
+            });
+        }
+
+        let fs = self.manifest.capabilities.fs.as_ref();
+        if !fs.is_some_and(|fs| fs.allows_extension(path_str)) {
+            let reason = "extension not allowed";
+            self.log_cap_error(CapEventSubtype::ConstraintViolation, reason, path_str);
+            return Err(CapError::ConstraintViolation {
+                path: path_str.into(),
+                reason: reason.into(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Enforce the FS read capability for `path`, then read it from disk.
+    /// Files larger than `max_file_bytes` are rejected with a
+    /// `fs.constraint_violation` event after the `cap.call` grant.
+    /// Lines matching the manifest's `content_filters` are dropped from the
+    /// returned contents.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError`] if enforcement fails (regardless of [`DenialMode`]), the
+    /// file is too large or cannot be read.
+    pub fn read_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, CapError> {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy();
+        if path_str.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+
+        let result = self.timed_decision(&path_str, |host| host.enforce_read(&path_str));
+        self.compare_shadow(&path_str);
+        result?;
+
+        let max_file_bytes = self
+            .manifest
+            .capabilities
+            .fs
+            .as_ref()
+            .and_then(|fs| fs.max_file_bytes);
+        let (owned, grant) = (path.to_path_buf(), self.read_grant(&path_str));
+        // Read one byte past the limit so files growing after `open` are caught too.
+        let limit = max_file_bytes.map_or(u64::MAX, |max| max.saturating_add(1));
+        let contents = self.capped_io("read_file", &path_str, move || {
+            let mut contents = Vec::new();
+            secure_open(&owned, &grant)?
+                .take(limit)
+                .read_to_end(&mut contents)?;
+            Ok(contents)
+        })?;
+        if let Some(max) =
+            max_file_bytes.filter(|max| u64::try_from(contents.len()).unwrap_or(u64::MAX) > *max)
+        {
+            let reason = format!("file exceeds max_file_bytes={max}");
+            self.log_cap_error(CapEventSubtype::ConstraintViolation, &reason, &path_str);
+            return Err(CapError::ConstraintViolation {
+                path: path_str.into(),
+                reason,
+            });
+        }
+        #[cfg_attr(not(feature = "test-util"), allow(unused_mut))]
+        let mut contents = self.filter_content(&path_str, contents)?;
+
+        if self.hash_reads {
+            let input = format!(
+                "{path_str}: sha256={} bytes={}",
+                sha256_hex(&contents),
+                contents.len()
+            );
+            self.record_event(EventType::FsRead, input, true, &path_str);
+        }
+        #[cfg(feature = "test-util")]
+        self.inject_corruption(&mut contents);
+        Ok(contents)
+    }
+
+    /// Enforce the FS read capability for `path`, then read `len` bytes from
+    /// `offset` (fewer at end of file). The requested range is traced as an
+    /// `fs.read` event, or in the `fs.constraint_violation` event rejecting
+    /// ranges longer than `max_range_bytes` or `max_file_bytes`.
+    /// Content filters apply to the returned bytes.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError`] if enforcement fails (regardless of [`DenialMode`]), the
+    /// range is too long or the file cannot be read.
+    pub fn read_file_range<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, CapError> {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy();
+        if path_str.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+
+        let result = self.timed_decision(&path_str, |host| host.enforce_read(&path_str));
+        self.compare_shadow(&path_str);
+        result?;
+
+        let fs = self.manifest.capabilities.fs.as_ref();
+        let limits = [
+            ("max_range_bytes", fs.and_then(|fs| fs.max_range_bytes)),
+            ("max_file_bytes", fs.and_then(|fs| fs.max_file_bytes)),
+        ];
+        if let Some((name, max)) = limits
+            .into_iter()
+            .find_map(|(name, max)| max.filter(|max| len > *max).map(|max| (name, max)))
+        {
+            let reason = format!("range={offset}+{len} exceeds {name}={max}");
+            self.log_cap_error(CapEventSubtype::ConstraintViolation, &reason, &path_str);
+            return Err(CapError::ConstraintViolation {
+                path: path_str.into(),
+                reason,
+            });
+        }
+        let range = format!("{path_str}: range={offset}+{len}");
+        self.record_event(EventType::FsRead, range, true, &path_str);
+
+        let (owned, grant) = (path.to_path_buf(), self.read_grant(&path_str));
+        let contents = self.capped_io("read_range", &path_str, move || {
+            let mut file = secure_open(&owned, &grant)?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut contents = Vec::new();
+            file.take(len).read_to_end(&mut contents)?;
+            Ok(contents)
+        })?;
+        #[cfg_attr(not(feature = "test-util"), allow(unused_mut))]
+        let mut contents = self.filter_content(&path_str, contents)?;
+        #[cfg(feature = "test-util")]
+        self.inject_corruption(&mut contents);
+        Ok(contents)
+    }
+
+    /// Enforce the FS read capability for `path` and open it for streaming
+    /// with [`HostState::read_chunk`], returning the handle. Traced as an
+    /// `fs.read` event. Paths with applicable content filters are refused, as
+    /// filters need the whole file.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError`] if enforcement fails (regardless of [`DenialMode`]) or
+    /// the file cannot be opened.
+    pub fn open_read<P: AsRef<Path>>(&mut self, path: P) -> Result<u32, CapError> {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy();
+        if path_str.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+
+        let result = self.timed_decision(&path_str, |host| host.enforce_read(&path_str));
+        self.compare_shadow(&path_str);
+        result?;
+
+        let filtered = self
+            .manifest
+            .capabilities
+            .fs
+            .iter()
+            .flat_map(|fs| &fs.content_filters)
+            .any(|filter| filter.applies_to(&path_str));
+        if filtered {
+            let reason = "content filters need whole-file reads";
+            self.log_cap_error(CapEventSubtype::ConstraintViolation, reason, &path_str);
+            return Err(CapError::ConstraintViolation {
+                path: path_str.into(),
+                reason: reason.into(),
+            });
+        }
+
+        let (owned, grant) = (path.to_path_buf(), self.read_grant(&path_str));
+        let file = self.capped_io("read_open", &path_str, move || secure_open(&owned, &grant))?;
+        let handle = self.read_handles.next;
+        self.read_handles.next += 1;
+        self.read_handles.open.insert(
+            handle,
+            ReadHandle {
+                path: path_str.clone().into_owned(),
+                file,
+                bytes: 0,
+            },
+        );
+        let input = format!("{path_str}: open handle={handle}");
+        self.record_event(EventType::FsRead, input, true, &path_str);
+        Ok(handle)
+    }
+
+    /// Read up to `max_len` bytes from a handle of [`HostState::open_read`]
+    /// (empty at end of file). Exceeding `max_file_bytes` across chunks is a
+    /// `fs.constraint_violation` and closes the handle.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::ConstraintViolation`] past `max_file_bytes`, or
+    /// [`CapError::Io`] for unknown handles and read failures.
+    pub fn read_chunk(&mut self, handle: u32, max_len: u64) -> Result<Vec<u8>, CapError> {
+        let max_file_bytes = self
+            .manifest
+            .capabilities
+            .fs
+            .as_ref()
+            .and_then(|fs| fs.max_file_bytes);
+        let open = self
+            .read_handles
+            .open
+            .get(&handle)
+            .ok_or_else(|| unknown_handle("read", handle))?;
+        let (path, mut file) = (open.path.clone(), open.file.try_clone()?);
+        let result = self.capped_io("read_chunk", &path, move || {
+            let mut chunk = Vec::new();
+            (&mut file).take(max_len).read_to_end(&mut chunk)?;
+            Ok(chunk)
+        });
+        #[cfg_attr(not(feature = "test-util"), allow(unused_mut))]
+        let mut chunk = match result {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                if matches!(err, CapError::Busy { .. }) {
+                    // The helper may still advance the shared file offset.
+                    self.read_handles.open.remove(&handle);
+                }
+                return Err(err);
+            }
+        };
+        let open = self
+            .read_handles
+            .open
+            .get_mut(&handle)
+            .ok_or_else(|| unknown_handle("read", handle))?;
+        open.bytes += u64::try_from(chunk.len()).unwrap_or(u64::MAX);
+
+        if let Some(max) = max_file_bytes.filter(|max| open.bytes > *max) {
+            let path = open.path.clone();
+            self.read_handles.open.remove(&handle);
+            let reason = format!("handle={handle} exceeds max_file_bytes={max}");
+            self.log_cap_error(CapEventSubtype::ConstraintViolation, &reason, &path);
+            return Err(CapError::ConstraintViolation { path, reason });
+        }
+        #[cfg(feature = "test-util")]
+        self.inject_corruption(&mut chunk);
+        Ok(chunk)
+    }
+
+    /// Close a handle of [`HostState::open_read`], tracing the bytes read
+    /// through it as an `fs.read` event. Returns that byte count.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::Io`] if the handle is unknown.
+    pub fn close_read(&mut self, handle: u32) -> Result<u64, CapError> {
+        let open = self
+            .read_handles
+            .open
+            .remove(&handle)
+            .ok_or_else(|| unknown_handle("read", handle))?;
+        let input = format!("{}: close handle={handle} bytes={}", open.path, open.bytes);
+        self.record_event(EventType::FsRead, input, true, &open.path);
+        Ok(open.bytes)
+    }
+
+    /// Enforce the blob capability for `name` and open the buffer registered
+    /// under it with [`HostState::with_blob`], returning a handle for
+    /// [`HostState::blob`]. Traced as a `cap.call` carrying the handle and size.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::NoBlobCapability`] if blobs are undeclared (see [`Posture`]),
+    /// [`CapError::BlobDenied`] if `name` is not allowed (regardless of
+    /// [`DenialMode`]), or [`CapError::Io`] if no such blob is registered.
+    pub fn get_blob(&mut self, name: &str) -> Result<u32, CapError> {
+        if name.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+        self.timed_decision(name, |host| host.enforce_blob(name))?;
+        let registered = self.blobs.registered.get(name).cloned();
+        let missing = format!("no blob named `{name}`");
+        let bytes = self.capped_io("get_blob", name, move || {
+            registered
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, missing).into())
+        })?;
+        let handle = u32::try_from(self.blobs.open.len()).unwrap_or(u32::MAX);
+        let input = format!("blob {name}: handle={handle} bytes={}", bytes.len());
+        self.blobs.open.push(bytes);
+        self.record_event(EventType::CapCall, input, true, name);
+        Ok(handle)
+    }
+
+    /// Contents of a handle of [`HostState::get_blob`].
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::Io`] if the handle is unknown.
+    pub fn blob(&self, handle: u32) -> Result<&[u8], CapError> {
+        usize::try_from(handle)
+            .ok()
+            .and_then(|idx| self.blobs.open.get(idx))
+            .map(AsRef::as_ref)
+            .ok_or_else(|| unknown_handle("blob", handle))
+    }
+
+    fn enforce_blob(&mut self, name: &str) -> Result<(), CapError> {
+        let input = format!("blob {name}");
+        let Some(blob) = &self.manifest.capabilities.blob else {
+            if self.manifest.posture == Posture::Audit {
+                let audit = format!("{input}: blob undeclared, allowed by audit posture");
+                self.record_event(EventType::CapAudit, audit, true, name);
+                return Ok(());
+            }
+            self.log_cap_error(CapEventSubtype::NoBlobCapability, "blob undeclared", &input);
+            return Err(CapError::NoBlobCapability);
+        };
+        if !blob.allows(name) {
+            self.log_cap_error(CapEventSubtype::BlobDenied, "name not allowed", &input);
+            return Err(CapError::BlobDenied { name: name.into() });
+        }
+        Ok(())
+    }
+
+    /// Enforce the config capability for `key` and look it up in the
+    /// [`ConfigProvider`] (`None` if unset or no provider is attached).
+    /// Traced as a `cap.call` with the value's size; values themselves are
+    /// kept out of the trace.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::NoConfigCapability`] if config is undeclared (see
+    /// [`Posture`]), [`CapError::ConfigDenied`] if `key` is not allowed
+    /// (regardless of [`DenialMode`]), or [`CapError::InvalidPath`] for an
+    /// empty key.
+    pub fn get_config(&mut self, key: &str) -> Result<Option<serde_json::Value>, CapError> {
+        if key.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+        self.timed_decision(key, |host| host.enforce_config(key))?;
+        let value = self
+            .config_provider
+            .as_ref()
+            .and_then(|provider| provider.get(key));
+        let input = value.as_ref().map_or_else(
+            || format!("config {key}: unset"),
+            |value| format!("config {key}: bytes={}", value.to_string().len()),
+        );
+        self.record_event(EventType::CapCall, input, true, key);
+        Ok(value)
+    }
+
+    fn enforce_config(&mut self, key: &str) -> Result<(), CapError> {
+        let input = format!("config {key}");
+        let Some(config) = &self.manifest.capabilities.config else {
+            if self.manifest.posture == Posture::Audit {
+                let audit = format!("{input}: config undeclared, allowed by audit posture");
+                self.record_event(EventType::CapAudit, audit, true, key);
+                return Ok(());
+            }
+            self.log_cap_error(
+                CapEventSubtype::NoConfigCapability,
+                "config undeclared",
+                &input,
+            );
+            return Err(CapError::NoConfigCapability);
+        };
+        if !config.allows(key) {
+            self.log_cap_error(CapEventSubtype::ConfigDenied, "key not allowed", &input);
+            return Err(CapError::ConfigDenied { key: key.into() });
+        }
+        Ok(())
+    }
+
+    /// [`CapError`] for a failed [`secure_open`] of `path_str`; escapes are
+    /// traced as `fs.constraint_violation`.
+    fn open_error(&mut self, err: SecureOpenError, path_str: &str) -> CapError {
+        if let SecureOpenError::Io(err) = err {
+            return err.into();
+        }
+        let reason = err.to_string();
+        self.log_cap_error(CapEventSubtype::ConstraintViolation, &reason, path_str);
+        CapError::ConstraintViolation {
+            path: path_str.into(),
+            reason,
+        }
+    }
+
+    /// Run the storage work `io` of host call `import` on `path_str`, on a
+    /// helper thread if it has a latency cap (see
+    /// [`HostState::with_latency_cap`]).
+    fn capped_io<T: Send + 'static>(
+        &mut self,
+        import: &str,
+        path_str: &str,
+        io: impl FnOnce() -> Result<T, SecureOpenError> + Send + 'static,
+    ) -> Result<T, CapError> {
+        let Some(cap) = self.latency_cap(import) else {
+            return io().map_err(|err| self.open_error(err, path_str));
+        };
+        let cap_ms = u64::try_from(cap.as_millis()).unwrap_or(u64::MAX);
+        let Some(worker) = CappedWorker::claim(&self.capped_workers) else {
+            let input = format!("{import} {path_str}: cap_ms={cap_ms} no free worker");
+            self.record_event(EventType::HostBusy, input, false, path_str);
+            return Err(CapError::Busy {
+                import: import.into(),
+                cap_ms,
+            });
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _worker = worker;
+            let _ = sender.send(io());
+        });
+        match receiver.recv_timeout(cap) {
+            Ok(result) => result.map_err(|err| self.open_error(err, path_str)),
+            Err(RecvTimeoutError::Timeout) => {
+                let input = format!("{import} {path_str}: cap_ms={cap_ms}");
+                self.record_event(EventType::HostBusy, input, false, path_str);
+                Err(CapError::Busy {
+                    import: import.into(),
+                    cap_ms,
+                })
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(std::io::Error::other(format!("{import} helper thread panicked")).into())
+            }
+        }
+    }
+
+    /// Directory the first `fs.read` pattern matching `path_str` grants, see
+    /// [`grant_root`] (empty without one, e.g. under an audit posture).
+    fn read_grant(&self, path_str: &str) -> PathBuf {
+        let fs = self.manifest.capabilities.fs.as_ref();
+        let patterns = fs.and_then(|fs| fs.read.as_deref()).unwrap_or_default();
+        spec_grant(&self.fs_matchers.read, patterns, path_str)
+    }
+
+    /// Directory the first `fs.list` pattern matching `path_str` grants, as
+    /// [`HostState::read_grant`] does for reads.
+    fn list_grant(&self, path_str: &str) -> PathBuf {
+        let fs = self.manifest.capabilities.fs.as_ref();
+        let patterns = fs.and_then(|fs| fs.list.as_deref()).unwrap_or_default();
+        spec_grant(&self.fs_matchers.list, patterns, path_str)
+    }
+
+    /// Drop lines matching the applicable `content_filters`, recording a
+    /// `fs.content_filtered` event if any were removed.
+    fn filter_content(&mut self, path_str: &str, contents: Vec<u8>) -> Result<Vec<u8>, CapError> {
+        let filters = self
+            .manifest
+            .capabilities
+            .fs
+            .iter()
+            .flat_map(|fs| fs.content_filters.iter().enumerate())
+            .filter(|(_, filter)| filter.applies_to(path_str))
+            .map(
+                |(idx, filter)| match Matcher::parse(&MatcherKind::Regex.spec(&filter.regex)) {
+                    Ok(regex) => Ok((idx, regex)),
+                    Err(err) => Err(err.to_string()),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>();
+        let filters = match filters {
+            Ok(filters) if filters.is_empty() => return Ok(contents),
+            Ok(filters) => filters,
+            Err(err) => {
+                let reason = format!("invalid content filter: {err}");
+                self.log_cap_error(CapEventSubtype::ConstraintViolation, &reason, path_str);
+                return Err(CapError::ConstraintViolation {
+                    path: path_str.into(),
+                    reason,
+                });
+            }
+        };
+
+        let mut removed = vec![0_usize; filters.len()];
+        let mut kept = Vec::with_capacity(contents.len());
+        for line in contents.split_inclusive(|byte| *byte == b'\n') {
+            let text = String::from_utf8_lossy(line);
+            match filters.iter().position(|(_, regex)| regex.matches(&text)) {
+                Some(pos) => removed[pos] += 1,
+                None => kept.extend_from_slice(line),
+            }
+        }
+        let total = removed.iter().sum::<usize>();
+        if total > 0 {
+            let details = filters
+                .iter()
+                .zip(&removed)
+                .filter(|(_, lines)| **lines > 0)
+                .map(|((idx, _), lines)| serde_json::json!({ "index": idx, "lines": lines }))
+                .collect::<Vec<_>>();
+            self.record_event_with(
+                EventType::FsContentFiltered,
+                format!("{path_str}: removed {total} lines"),
+                false,
+                path_str,
+                Some(serde_json::json!({ "filters": details })),
+            );
+        }
+        Ok(kept)
+    }
+
+    /// Enforce the FS list capability for directory `path` and return its
+    /// sorted entry names. Traced as `fs.list`, separately from file reads.
+    /// The directory is opened beneath its list grant, see [`secure_read_dir`].
+    ///
+    /// # Errors
+    ///
+    /// [`CapError`] if no list pattern matches (regardless of [`DenialMode`])
+    /// or the directory cannot be read, [`CapError::ConstraintViolation`] if
+    /// `path` climbs out of its grant or crosses a symlink.
+    pub fn list_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<String>, CapError> {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy();
+        if path_str.is_empty() {
+            return Err(CapError::InvalidPath);
+        }
+        self.timed_decision(&path_str, |host| host.enforce_list(&path_str))?;
+
+        let (owned, grant) = (path.to_path_buf(), self.list_grant(&path_str));
+        let entries = self.capped_io("list_dir", &path_str, move || {
+            secure_read_dir(&owned, &grant)
+        })?;
+        self.record_event(
+            EventType::FsList,
+            format!("{path_str}: {} entries", entries.len()),
+            true,
+            &path_str,
+        );
+        Ok(entries)
+    }
+
+    fn enforce_list(&mut self, path_str: &str) -> Result<(), CapError> {
+        let declared = self.manifest.capabilities.fs.as_ref();
+        let list_patterns = declared.and_then(|fs| fs.list.clone()).unwrap_or_default();
+        if declared.is_none() {
+            return self.undeclared_fs(path_str);
+        }
+        if self.fs_matchers.list.position(path_str).is_none() {
+            self.record_event(
+                EventType::FsList,
+                format!("{path_str}: no matching list pattern"),
+                false,
+                path_str,
+            );
+            return Err(CapError::GlobMismatch {
+                path: path_str.into(),
+                patterns_tried: list_patterns,
+            });
+        }
+        Ok(())
+    }
+
+    /// Run a capability decision on `subject`, charging it to the [`Budget`],
+    /// timing it into [`HostState::decision_timings`] and remembering a denial
+    /// for [`HostState::last_denial`].
+    fn timed_decision<T>(
+        &mut self,
+        subject: &str,
+        decide: impl FnOnce(&mut Self) -> Result<T, CapError>,
+    ) -> Result<T, CapError> {
+        let start = Instant::now();
+        #[cfg_attr(not(feature = "test-util"), allow(unused_mut))]
+        let mut result = self.charge_budget(subject);
+        #[cfg(feature = "test-util")]
+        {
+            result = result.and_then(|()| self.inject_fault(subject));
+        }
+        let result = result.and_then(|()| decide(self));
+        self.timings.push(DecisionTiming {
+            seq: self.seq.last(),
+            duration: start.elapsed(),
+        });
+        if let Err(err) = &result
+            && err.is_policy_denial()
+        {
+            self.last_denial = Some(format!("{}: {err}", err.reason_code()));
+        }
+        result
+    }
+
+    /// Apply the injector's fault for this call: trace it, then deny, stall,
+    /// or mark the next returned data for corruption.
+    #[cfg(feature = "test-util")]
+    fn inject_fault(&mut self, subject: &str) -> Result<(), CapError> {
+        let Some(fault) = self.faults.as_mut().and_then(FaultInjector::next_fault) else {
+            return Ok(());
+        };
+        let input = match fault {
+            Fault::Delay(delay) => format!("{subject}: delay ms={}", delay.as_millis()),
+            _ => format!("{subject}: {}", fault.as_str()),
+        };
+        let denied = fault == Fault::Deny;
+        self.record_event(EventType::FaultInjected, input, !denied, subject);
+        match fault {
+            Fault::Deny => Err(CapError::ConstraintViolation {
+                path: subject.into(),
+                reason: "injected fault".into(),
+            }),
+            Fault::Delay(delay) => {
+                std::thread::sleep(delay);
+                Ok(())
+            }
+            Fault::Corrupt => Ok(()),
+        }
+    }
+
+    /// Corrupt `data` if the injector has a pending [`Fault::Corrupt`].
+    #[cfg(feature = "test-util")]
+    fn inject_corruption(&mut self, data: &mut [u8]) {
+        if let Some(faults) = &mut self.faults {
+            faults.corrupt(data);
+        }
+    }
+
+    /// Count one call against the budget, denying it once calls or wall time
+    /// are used up.
+    fn charge_budget(&mut self, subject: &str) -> Result<(), CapError> {
+        let Some((budget, started)) = self.budget else {
+            return Ok(());
+        };
+        let elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        let exhausted = if budget.calls.is_some_and(|calls| self.budget_calls >= calls) {
+            Some("calls")
+        } else if budget.wall_ms.is_some_and(|wall_ms| elapsed_ms >= wall_ms) {
+            Some("wall_ms")
+        } else {
+            None
+        };
+        if let Some(resource) = exhausted {
+            let reason = format!("{resource} budget used up");
+            self.log_cap_error(CapEventSubtype::BudgetExhausted, &reason, subject);
+            return Err(CapError::BudgetExhausted { resource });
+        }
+        self.budget_calls += 1;
+        self.check_thresholds(budget, elapsed_ms);
+        Ok(())
+    }
+
+    /// Fire the [`ThresholdCallback`]s whose threshold was reached.
+    fn check_thresholds(&mut self, budget: Budget, elapsed_ms: u64) {
+        for threshold in self.thresholds.0.iter_mut().filter(|t| !t.fired) {
+            let (used, limit) = match threshold.resource {
+                "calls" => (self.budget_calls, budget.calls),
+                "wall_ms" => (elapsed_ms, budget.wall_ms),
+                _ => continue,
+            };
+            let Some(limit) = limit else {
+                continue;
+            };
+            if u128::from(used) * 100 >= u128::from(limit) * u128::from(threshold.percent) {
+                threshold.fired = true;
+                (threshold.callback)(&BudgetThreshold {
+                    resource: threshold.resource,
+                    percent: threshold.percent,
+                    used,
+                    limit,
+                });
+            }
+        }
+    }
+
+    /// Apply the manifest [`Posture`] to an FS operation without an `fs` section.
+    fn undeclared_fs(&mut self, path_str: &str) -> Result<(), CapError> {
+        match self.manifest.posture {
+            Posture::DenyAll => {
+                self.log_cap_error(CapEventSubtype::NoFsCapability, "missing fs cap", path_str);
+                Err(CapError::NoFsCapability)
+            }
+            Posture::Audit => {
+                let input = format!("{path_str}: fs undeclared, allowed by audit posture");
+                self.record_event(EventType::CapAudit, input, true, path_str);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record a `shadow.mismatch` event when the candidate manifest would
+    /// decide `path_str` differently from the enforced one.
+    fn compare_shadow(&mut self, path_str: &str) {
+        let Some(shadow) = &self.shadow_read else {
+            return;
+        };
+        let enforced = self.fs_matchers.read.position(path_str).is_some();
+        let candidate = shadow.position(path_str).is_some();
+        if enforced == candidate {
+            return;
+        }
+        let verdict = |allowed: bool| if allowed { "allow" } else { "deny" };
+        self.record_event(
+            EventType::ShadowMismatch,
+            format!(
+                "{path_str}: enforced={} shadow={}",
+                verdict(enforced),
+                verdict(candidate)
+            ),
+            false,
+            path_str,
+        );
+    }
+
+    /// Check the manifest's `run_as` constraints against the identity of this
+    /// process. [`crate::PluginInstance`] calls this before instantiating, so a
+    /// privileged host does not run a plugin meant for an unprivileged context.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::RunAsMismatch`] (recorded as `cap.error`) if the process
+    /// identity is not permitted.
+    pub fn check_run_as(&mut self) -> Result<(), CapError> {
+        let Some(run_as) = &self.manifest.run_as else {
+            return Ok(());
+        };
+        let identity = ProcessIdentity::current();
+        if run_as.permits(&identity) {
+            return Ok(());
+        }
+        let identity = identity.to_string();
+        self.log_cap_error(CapEventSubtype::RunAsMismatch, &identity, &identity);
+        Err(CapError::RunAsMismatch { identity })
+    }
+
+    /// Check the manifest's `requires` section against this host: the captra
+    /// version must match and every feature must pass
+    /// [`HostState::has_feature`]. [`crate::PluginInstance`] calls this before
+    /// instantiating, after embedder enforcers are registered.
+    ///
+    /// # Errors
+    ///
+    /// [`CapError::UnmetRequirement`] (recorded as `cap.error`) naming the
+    /// version requirement or the missing features.
+    pub fn check_requirements(&mut self) -> Result<(), CapError> {
+        let Some(requires) = &self.manifest.requires else {
+            return Ok(());
+        };
+        let version = Version::parse(env!("CARGO_PKG_VERSION")).ok();
+        let requirement = match requires.captra_req() {
+            Ok(Some(req)) if !version.is_some_and(|version| req.matches(&version)) => Some(
+                format!("captra {req} (running {})", env!("CARGO_PKG_VERSION")),
+            ),
+            Ok(_) => None,
+            Err(err) => Some(err.to_string()),
+        }
+        .or_else(|| {
+            let missing = requires
+                .features
+                .iter()
+                .filter(|feature| !self.has_feature(feature))
+                .map(|feature| format!("`{feature}`"))
+                .collect::<Vec<_>>();
+            (!missing.is_empty()).then(|| format!("features {}", missing.join(", ")))
+        });
+        let Some(requirement) = requirement else {
+            return Ok(());
+        };
+        self.log_cap_error(
+            CapEventSubtype::UnmetRequirement,
+            &requirement,
+            &requirement,
+        );
+        Err(CapError::UnmetRequirement { requirement })
+    }
+
+    /// Whether the host provides `feature`: one of [`host_features`] or a
+    /// custom capability kind with a registered enforcer.
+    #[must_use]
+    pub fn has_feature(&self, feature: &str) -> bool {
+        host_features().contains(&feature) || self.custom_enforcers.0.contains_key(feature)
+    }
+
+    /// Record a `plugin.init` event, called by [`crate::PluginInstance`] once
+    /// instantiating the module with the given SHA256 succeeded, or with the
+    /// `error` (as a denial) if it failed.
+    pub fn record_plugin_init(&mut self, module_hash: &str, error: Option<&str>) {
+        self.record_init(format!("module_sha256={module_hash}"), error);
+    }
+
+    /// Like [`HostState::record_plugin_init`], for a module loaded from a
+    /// precompiled artifact with the given SHA256.
+    pub fn record_plugin_init_precompiled(&mut self, artifact_hash: &str, error: Option<&str>) {
+        self.record_init(format!("precompiled_sha256={artifact_hash}"), error);
+    }
+
+    fn record_init(&mut self, module: String, error: Option<&str>) {
+        let input = match error {
+            Some(error) => format!("{module} error={error}"),
+            None => module,
+        };
+        self.record_event(
+            EventType::PluginInit,
+            input.clone(),
+            error.is_none(),
+            &input,
+        );
+    }
+
+    /// Record a `plugin.shutdown` event with the guest's resource usage.
+    pub fn record_plugin_shutdown(&mut self, memory_high_water: usize, fuel_consumed: u64) {
+        let input = format!("memory_high_water={memory_high_water} fuel_consumed={fuel_consumed}");
+        self.record_event(EventType::PluginShutdown, input.clone(), true, &input);
+    }
+
+    /// Record a `plugin.call` event for a [`crate::PluginInstance::call_json`]
+    /// round trip, with the SHA256 of the JSON sent and received.
+    pub fn record_plugin_call(&mut self, export: &str, input_hash: &str, output_hash: &str) {
+        let input = format!("{export} input_sha256={input_hash} output_sha256={output_hash}");
+        self.record_event(EventType::PluginCall, input.clone(), true, &input);
+    }
+
+    /// Run the catalog plugin `name` to completion in a child run with its
+    /// own manifest, seeded from this run and linked to it through
+    /// `parent_run_id`; the child joins the [`RunSession`], if any.
+    ///
+    /// A `plugin.spawn` event records the child run id and its result (or
+    /// error). Unknown plugins and spawns deeper than [`MAX_SPAWN_DEPTH`]
+    /// are denied. The child's trace, and those of its own children, are
+    /// kept in [`HostState::child_runs`].
+    pub fn spawn_plugin(&mut self, name: &str) -> HostStatus {
+        let entry = self
+            .catalog
+            .get(name)
+            .filter(|_| self.spawn_depth < MAX_SPAWN_DEPTH)
+            .map(|(manifest, wasm)| (manifest.clone(), wasm.to_vec()));
+        let Some((manifest, wasm)) = entry else {
+            let input = format!("{name}: denied");
+            self.record_event(EventType::PluginSpawn, input, false, name);
+            return HostStatus::Denied;
+        };
+
+        let seq = self.seq.peek();
+        let mut child = Self::new(manifest, self.randomness.ts_seed(seq), self.keypair.clone())
+            .with_plugin_catalog(self.catalog.clone());
+        child.spawn_depth = self.spawn_depth + 1;
+        child.parent_run_id = Some(self.run_id.clone());
+        child.labels.clone_from(&self.labels);
+        child.latency_caps.clone_from(&self.latency_caps);
+        child.capped_workers = Arc::clone(&self.capped_workers);
+        if let Some((host_id, session)) = &self.session {
+            child = child.with_session(session, format!("{host_id}/{name}"));
+        }
+        let run_id = child.run_id.clone();
+        let (result, mut grandchildren, trace) = match PluginInstance::new(child, wasm) {
+            Ok(mut instance) => {
+                let result = instance.call(SPAWN_EXPORT);
+                let child = instance.shutdown();
+                (result, child.child_runs, child.trace)
+            }
+            Err(err) => (Err(err), Vec::new(), Vec::new()),
+        };
+
+        let details = match &result {
+            Ok(value) => serde_json::json!({ "child_run_id": run_id, "result": value }),
+            Err(err) => serde_json::json!({ "child_run_id": run_id, "error": err.to_string() }),
+        };
+        let input = format!("{name}: {run_id}");
+        self.record_event_with(
+            EventType::PluginSpawn,
+            input,
+            result.is_ok(),
+            name,
+            Some(details),
+        );
+        self.child_runs.push(ChildRun {
+            plugin: name.to_owned(),
+            run_id,
+            parent_run_id: self.run_id.clone(),
+            result: result.as_ref().ok().copied(),
+            trace,
+        });
+        self.child_runs.append(&mut grandchildren);
+        if result.is_ok() {
+            HostStatus::Allowed
+        } else {
+            HostStatus::Error
+        }
+    }
+
+    /// Record a `guest.stalled` event for every stall the attached
+    /// [`Watchdog`] saw since the last call. Stall timing depends on the
+    /// machine, so traces with a watchdog are not replay-deterministic.
+    pub fn record_watchdog_stalls(&mut self) {
+        let stalls = self
+            .watchdog
+            .as_ref()
+            .map(Watchdog::take_stalls)
+            .unwrap_or_default();
+        for stall in stalls {
+            let input = format!(
+                "idle_ms={} interrupted={}",
+                stall.idle_ms, stall.interrupted
+            );
+            self.push_event(EventType::GuestStalled, input.clone(), false, &input, None);
+        }
+    }
+
+    /// Attach (or detach) a liveness [`Watchdog`], see
+    /// [`crate::PluginInstance::with_watchdog`]. Host calls count as progress.
+    pub fn set_watchdog(&mut self, watchdog: Option<Watchdog>) {
+        self.watchdog = watchdog;
+    }
+
+    /// Get `watchdog`
+    #[inline]
+    #[must_use]
+    pub const fn watchdog(&self) -> Option<&Watchdog> {
+        self.watchdog.as_ref()
+    }
+
+    /// Record a `native.seccomp_violation` event for a native plugin child
+    /// killed by its seccomp filter.
+    pub fn record_seccomp_violation(&mut self, pid: i32) {
+        let input = format!("pid={pid} signal=SIGSYS");
+        self.record_event(EventType::SeccompViolation, input.clone(), false, &input);
+    }
+
+    /// Record a `native.cpu_quota_exceeded` event for a native plugin child
+    /// killed for using up its CPU time limit.
+    pub fn record_cpu_quota_exceeded(&mut self, pid: i32, limit_secs: u64) {
+        let input = format!("pid={pid} cpu_secs={limit_secs}");
+        self.record_event(EventType::CpuQuotaExceeded, input.clone(), false, &input);
+    }
+
+    /// Record a `native.memory_quota_exceeded` event for a native plugin
+    /// child that aborted under its memory limit.
+    pub fn record_memory_quota_exceeded(&mut self, pid: i32, limit_bytes: u64) {
+        let input = format!("pid={pid} memory_bytes={limit_bytes}");
+        self.record_event(EventType::MemoryQuotaExceeded, input.clone(), false, &input);
+    }
+
+    /// Interleave an embedder's own audit event (e.g. a user approving a job)
+    /// into the hash-chained trace. `source` becomes the event input and
+    /// `payload` its details.
+    pub fn record_external_event(&mut self, source: &str, payload: serde_json::Value) {
+        self.record_event_with(
+            EventType::External,
+            source.to_owned(),
+            true,
+            source,
+            Some(payload),
+        );
+    }
+
+    /// Redacted JSON description of the plugin's own grants (plugin, version
+    /// and capabilities; issuer, signatures and audit metadata are omitted).
+    #[must_use]
+    pub fn capabilities_json(&self) -> String {
+        serde_json::json!({
+            "plugin": self.manifest.plugin,
+            "version": self.manifest.version,
+            "posture": self.manifest.posture,
+            "capabilities": self.manifest.capabilities,
+        })
+        .to_string()
+    }
+
+    /// Signs the current trace JSON with the host keypair, bound to the run
+    /// and manifest hash (see [`trace_message`]).
+    /// The signed SHA256 is the running [`TraceHasher`] digest of the trace,
+    /// so only the JSON embedded in the [`SignedTrace`] is serialized.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] (serialization).
+    pub fn sign_current_trace(&mut self) -> Result<SignedTrace, TraceError> {
+        let trace_json = finalize_trace(&self.trace);
+        let trace_hash = self.trace_hasher.digest();
+        let message = trace_message(&self.run_id, &self.manifest_hash, &trace_hash);
+
+        let signature = self.keypair.sign(message.as_bytes()).to_bytes().to_vec();
+
+        let signed = SignedTrace::new(
+            self.run_id.clone(),
+            self.manifest_hash.clone(),
+            trace_json,
+            signature,
+        )
+        .with_metadata(self.manifest.audit_metadata())
+        .with_pubkey(&self.pubkey)
+        .with_randomness(self.randomness)
+        .with_time_granularity(self.time_granularity_header())
+        .with_labels(self.labels.clone())
+        .with_profile(self.profile.clone());
+        Ok(self.embed_manifest(signed))
+    }
+
+    /// Sign the running digest of the trace so far without serializing it,
+    /// e.g. periodically during long runs; see [`TraceCheckpoint::verify`].
+    ///
+    /// Covered events are sealed: a later identical denial is appended rather
+    /// than folded into them (see [`HostState::with_denial_dedup`]), so the
+    /// checkpoint stays valid.
+    #[must_use]
+    pub fn sign_checkpoint(&mut self) -> TraceCheckpoint {
+        let trace_hash = self.trace_hasher.digest();
+        let len = self.trace.len();
+        let message = checkpoint_message(&self.run_id, len, &trace_hash);
+        let signature = self.keypair.sign(message.as_bytes()).to_bytes();
+        self.sealed_len = len;
+        TraceCheckpoint {
+            run_id: self.run_id.clone(),
+            len,
+            seq: self.trace.last().map_or(0, TraceEvent::last_seq),
+            trace_hash,
+            signature: general_purpose::STANDARD.encode(signature),
+        }
+    }
+
+    /// Signs a [`compact`]ed summary of the current trace, under run id
+    /// `{run_id}.summary` (see [`summary_run_id`]) so it cannot be mistaken for
+    /// the full trace.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] (serialization).
+    pub fn sign_summary_trace(
+        &mut self,
+        policy: CompactionPolicy,
+    ) -> Result<SignedTrace, TraceError> {
+        let trace_json = finalize_trace(&compact(&self.trace, policy, self.randomness));
+        let trace_hash = sha256_hex(trace_json.as_bytes());
+        let run_id = summary_run_id(&self.run_id);
+        let message = trace_message(&run_id, &self.manifest_hash, &trace_hash);
+
+        let signature = self.keypair.sign(message.as_bytes()).to_bytes().to_vec();
+
+        let signed = SignedTrace::new(run_id, self.manifest_hash.clone(), trace_json, signature)
+            .with_metadata(self.manifest.audit_metadata())
+            .with_pubkey(&self.pubkey)
+            .with_randomness(self.randomness)
+            .with_time_granularity(self.time_granularity_header())
+            .with_labels(self.labels.clone())
+            .with_profile(self.profile.clone());
+        Ok(self.embed_manifest(signed))
+    }
+
+    fn embed_manifest(&self, signed: SignedTrace) -> SignedTrace {
+        if self.embed_manifest {
+            signed.with_manifest(self.manifest.clone())
+        } else {
+            signed
+        }
+    }
+
+    fn time_granularity_header(&self) -> Option<u64> {
+        self.manifest
+            .capabilities
+            .time
+            .map(|time| time.granularity_ms)
+    }
+
+    /// Serialize trace to pretty JSON string
+    #[inline]
+    #[must_use]
+    pub fn get_trace_json(&self) -> String {
+        finalize_trace(&self.trace)
+    }
+
+    /// Save the current trace to a file as pretty JSON.
+    ///
+    /// # Errors
+    ///
+    /// If file write fails (e.g., I/O error) or JSON serialization fails.
+    pub fn save_current_trace<P: AsRef<Path>>(&self, path: P) -> Result<(), TraceError> {
+        let header = TraceFileHeader::new(self.run_id.clone(), self.manifest_hash.clone())
+            .with_labels(self.labels.clone())
+            .with_profile(self.profile.clone());
+        save_trace_with_header(&self.trace, &header, path)
+    }
+
+    /// Write the current trace as signed JSON Lines, see [`JsonlWriter`].
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] (JSON or IO).
+    pub fn write_signed_jsonl<W: Write>(&mut self, out: W) -> Result<W, TraceError> {
+        let mut writer = JsonlWriter::new(out);
+        for event in &self.trace {
+            writer.push(event)?;
+        }
+        writer.finish(&mut self.keypair)
+    }
+
+    fn log_cap_error(&mut self, event_subtype: CapEventSubtype, reason: &str, path_str: &str) {
+        self.record_event(
+            EventType::from(event_subtype),
+            format!("{event_subtype}: {reason}"),
+            false,
+            path_str,
+        );
+    }
+
+    /// Append an event to the trace (next seq, seeded `ts_seed`, chain link)
+    /// and log it; `logged_input` is what goes to the `tracing` output.
+    fn record_event(
+        &mut self,
+        event_type: EventType,
+        input: String,
+        outcome: bool,
+        logged_input: &str,
+    ) {
+        self.record_event_with(event_type, input, outcome, logged_input, None);
+    }
+
+    /// [`HostState::record_event`] with a structured `details` payload.
+    fn record_event_with(
+        &mut self,
+        event_type: EventType,
+        input: String,
+        outcome: bool,
+        logged_input: &str,
+        details: Option<serde_json::Value>,
+    ) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.beat();
+        }
+        self.record_watchdog_stalls();
+        self.push_event(event_type, input, outcome, logged_input, details);
+    }
+
+    fn push_event(
+        &mut self,
+        event_type: EventType,
+        input: String,
+        outcome: bool,
+        logged_input: &str,
+        details: Option<serde_json::Value>,
+    ) {
+        let seq = self.seq.next();
+        let ts_seed = self.randomness.ts_seed(seq);
+        let input = truncate_input(input, self.max_input_len);
+        let logged_input = truncate_input(logged_input.to_owned(), self.max_input_len);
+
+        let vclock = self
+            .session
+            .as_ref()
+            .map(|(host_id, session)| session.tick(host_id));
+        let severity = self
+            .severity_rules
+            .severity_for(&event_type, outcome, &logged_input);
+
+        let event = TraceEvent {
+            run_id: self.run_id.clone(),
+            seq,
+            event_type,
+            input,
+            outcome,
+            ts_seed,
+            vclock,
+            policy_epoch: self.policy_epoch,
+            severity,
+            details,
+            repeat_count: 0,
+            prev_hash: self.chain_head.clone(),
+        };
+        if self.dedup_denials
+            && self.trace.len() > self.sealed_len
+            && let Some(last) = self.trace.last_mut().filter(|last| is_repeat(last, &event))
+        {
+            last.repeat_count += 1;
+            self.chain_head = event_hash(last);
+            self.trace_hasher.replace_last(last);
+            return;
+        }
+        let default_policy = TracePolicy::default();
+        let policy = self
+            .trace_policy
+            .as_ref()
+            .or(self.manifest.trace_policy.as_ref())
+            .unwrap_or(&default_policy);
+        log_trace_event(policy, &event, &logged_input, &self.manifest.plugin);
+        if let Some(webhook) = &self.webhook {
+            webhook.notify(&event);
+        }
+        self.chain_head = event_hash(&event);
+        self.trace_hasher.push(&event);
+        self.trace.push(event);
+    }
+}
+
+/// Directory granted by the first of `specs` matching `path_str`, see
+/// [`grant_root`] (empty without one).
+fn spec_grant(specs: &CompiledSpecs, patterns: &[String], path_str: &str) -> PathBuf {
+    specs
+        .position(path_str)
+        .and_then(|idx| patterns.get(idx))
+        .map(|pattern| grant_root(&bounding_glob(pattern)))
+        .unwrap_or_default()
+}
+
+/// Whether `event` is a denial identical to `last` apart from its position.
+fn is_repeat(last: &TraceEvent, event: &TraceEvent) -> bool {
+    !event.outcome
+        && !last.outcome
+        && last.event_type == event.event_type
+        && last.input == event.input
+        && last.details == event.details
+        && last.severity == event.severity
+        && last.policy_epoch == event.policy_epoch
+}
+
+/// Features of this build for manifests' `requires.features`: the built-in
+/// capability kinds and host subsystems, plus enabled platform sandboxes.
+#[must_use]
+pub fn host_features() -> Vec<&'static str> {
+    let mut features = vec!["fs", "time", "blob", "config", "custom", "spawn", "webhook"];
+    if cfg!(all(feature = "landlock", target_os = "linux")) {
+        features.push("landlock");
+    }
+    if cfg!(all(
+        feature = "native",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )) {
+        features.push("native");
+    }
+    features
+}
+
+/// Install the default `fmt` subscriber (no-op if one is already set).
+pub fn init_tracing() {
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(Level::INFO)
+        .try_init();
+}
+
+impl CapError {
+    /// Whether the error is a policy decision (as opposed to invalid input).
+    #[inline]
+    #[must_use]
+    pub const fn is_policy_denial(&self) -> bool {
+        !matches!(self, Self::InvalidPath | Self::Busy { .. } | Self::Io(_))
+    }
+
+    /// Stable machine-readable code for the error kind.
+    #[must_use]
+    pub const fn reason_code(&self) -> &'static str {
+        match self {
+            Self::NoFsCapability => "no_fs_capability",
+            Self::NoReadPatterns => "no_read_patterns",
+            Self::GlobMismatch { .. } => "glob_mismatch",
+            Self::RuntimeInvalidGlob { .. } => "invalid_glob",
+            Self::RuntimeInvalidMatcher { .. } => "invalid_matcher",
+            Self::InvalidPath => "invalid_path",
+            Self::ApprovalDenied { .. } => "approval_denied",
+            Self::ConstraintViolation { .. } => "constraint_violation",
+            Self::NoCustomCapability { .. } => "no_custom_capability",
+            Self::CustomDenied { .. } => "custom_denied",
+            Self::BudgetExhausted { .. } => "budget_exhausted",
+            Self::RunAsMismatch { .. } => "run_as_mismatch",
+            Self::UnmetRequirement { .. } => "unmet_requirement",
+            Self::NoTimeCapability => "no_time_capability",
+            Self::NoBlobCapability => "no_blob_capability",
+            Self::BlobDenied { .. } => "blob_denied",
+            Self::NoConfigCapability => "no_config_capability",
+            Self::ConfigDenied { .. } => "config_denied",
+            Self::Busy { .. } => "busy",
+            Self::Io(_) => "io",
+        }
+    }
+}
+
+impl PartialEq for CapError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::GlobMismatch {
+                    path: lhs_path,
+                    patterns_tried: lhs_patterns,
+                },
+                Self::GlobMismatch {
+                    path: rhs_path,
+                    patterns_tried: rhs_patterns,
+                },
+            ) => lhs_path == rhs_path && lhs_patterns == rhs_patterns,
+            (
+                Self::RuntimeInvalidGlob {
+                    capability: lhs_capability,
+                    idx: lhs_idx,
+                    pattern: lhs_pattern,
+                    source: lhs_source,
+                },
+                Self::RuntimeInvalidGlob {
+                    capability: rhs_capability,
+                    idx: rhs_idx,
+                    pattern: rhs_pattern,
+                    source: rhs_source,
+                },
+            ) => {
+                lhs_capability == rhs_capability
+                    && lhs_idx == rhs_idx
+                    && lhs_pattern == rhs_pattern
+                    && lhs_source.pos == rhs_source.pos
+                    && lhs_source.msg == rhs_source.msg
+            }
+            (
+                Self::RuntimeInvalidMatcher {
+                    capability: lhs_capability,
+                    idx: lhs_idx,
+                    pattern: lhs_pattern,
+                    reason: lhs_reason,
+                },
+                Self::RuntimeInvalidMatcher {
+                    capability: rhs_capability,
+                    idx: rhs_idx,
+                    pattern: rhs_pattern,
+                    reason: rhs_reason,
+                },
+            ) => {
+                lhs_capability == rhs_capability
+                    && lhs_idx == rhs_idx
+                    && lhs_pattern == rhs_pattern
+                    && lhs_reason == rhs_reason
+            }
+            (
+                Self::ConstraintViolation {
+                    path: lhs_path,
+                    reason: lhs_reason,
+                },
+                Self::ConstraintViolation {
+                    path: rhs_path,
+                    reason: rhs_reason,
+                },
+            ) => lhs_path == rhs_path && lhs_reason == rhs_reason,
+            (Self::ApprovalDenied { path: lhs }, Self::ApprovalDenied { path: rhs })
+            | (Self::NoCustomCapability { kind: lhs }, Self::NoCustomCapability { kind: rhs })
+            | (Self::RunAsMismatch { identity: lhs }, Self::RunAsMismatch { identity: rhs })
+            | (
+                Self::UnmetRequirement { requirement: lhs },
+                Self::UnmetRequirement { requirement: rhs },
+            )
+            | (Self::BlobDenied { name: lhs }, Self::BlobDenied { name: rhs })
+            | (Self::ConfigDenied { key: lhs }, Self::ConfigDenied { key: rhs }) => lhs == rhs,
+            (
+                Self::CustomDenied {
+                    kind: lhs_kind,
+                    request: lhs_request,
+                },
+                Self::CustomDenied {
+                    kind: rhs_kind,
+                    request: rhs_request,
+                },
+            ) => lhs_kind == rhs_kind && lhs_request == rhs_request,
+            (Self::BudgetExhausted { resource: lhs }, Self::BudgetExhausted { resource: rhs }) => {
+                lhs == rhs
+            }
+            (
+                Self::Busy {
+                    import: lhs_import,
+                    cap_ms: lhs_cap,
+                },
+                Self::Busy {
+                    import: rhs_import,
+                    cap_ms: rhs_cap,
+                },
+            ) => lhs_import == rhs_import && lhs_cap == rhs_cap,
+            (Self::Io(lhs), Self::Io(rhs)) => lhs.kind() == rhs.kind(),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for CapError {}
+
+impl HostStatus {
+    /// Code imports returning a length, handle or time report this status
+    /// with: the negated status code, see [`HostStatus`].
+    #[must_use]
+    pub const fn length_code(self) -> i32 {
+        -(self as i32)
+    }
+}
+
+impl From<HostStatus> for i32 {
+    fn from(value: HostStatus) -> Self {
+        value as Self
+    }
+}
+
+impl TryFrom<i32> for HostStatus {
+    type Error = i32;
+
+    /// Decode a status returned to a guest; the unknown code is handed back.
+    fn try_from(value: i32) -> Result<Self, i32> {
+        match value {
+            0 => Ok(Self::Allowed),
+            1 => Ok(Self::Denied),
+            -1 => Ok(Self::Error),
+            2 => Ok(Self::Busy),
+            other => Err(other),
+        }
+    }
+}
+
+fn unknown_handle(kind: &str, handle: u32) -> CapError {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("unknown {kind} handle {handle}"),
+    )
+    .into()
+}
+
+impl From<&CapError> for HostStatus {
+    fn from(value: &CapError) -> Self {
+        if value.is_policy_denial() {
+            Self::Denied
+        } else if matches!(value, CapError::Busy { .. }) {
+            Self::Busy
+        } else {
+            Self::Error
+        }
+    }
+}
+
+impl Display for HostStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Allowed => "allowed",
+            Self::Denied => "denied",
+            Self::Error => "error",
+            Self::Busy => "busy",
+        })
     }
 }
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// Process identities a plugin may be run under, checked by
+/// [`crate::HostState::check_run_as`]. An empty list leaves that dimension
+/// unconstrained; users and groups match by name or numeric id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunAs {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    /// Matched against the primary and supplementary groups.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Windows SIDs (e.g. `S-1-5-32-545`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sids: Vec<String>,
+}
+
+impl RunAs {
+    /// Whether `identity` satisfies every non-empty constraint. A dimension
+    /// the platform cannot report (SIDs on Unix, users on Windows) fails closed.
+    #[must_use]
+    pub fn permits(&self, identity: &ProcessIdentity) -> bool {
+        let users = identity
+            .uid
+            .iter()
+            .map(u32::to_string)
+            .chain(identity.user.clone());
+        let groups = identity
+            .gids
+            .iter()
+            .map(u32::to_string)
+            .chain(identity.groups.iter().cloned());
+        matches_any(&self.users, users)
+            && matches_any(&self.groups, groups)
+            && matches_any(&self.sids, identity.sid.iter().cloned())
+    }
+}
+
+fn matches_any(allowed: &[String], mut actual: impl Iterator<Item = String>) -> bool {
+    allowed.is_empty() || actual.any(|name| allowed.contains(&name))
+}
+
+/// Identity of the running process, as far as the platform reports it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessIdentity {
+    /// Effective user id
+    pub uid: Option<u32>,
+    pub user: Option<String>,
+    /// Effective group id first, then supplementary groups
+    pub gids: Vec<u32>,
+    pub groups: Vec<String>,
+    pub sid: Option<String>,
+}
+
+impl ProcessIdentity {
+    /// Effective user and groups of this process (resolving names where the
+    /// system databases have them).
+    #[cfg(unix)]
+    #[must_use]
+    pub fn current() -> Self {
+        // SAFETY: these calls have no preconditions and cannot fail.
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        let mut gids = vec![gid];
+        gids.extend(supplementary_groups().into_iter().filter(|g| *g != gid));
+        Self {
+            uid: Some(uid),
+            user: user_name(uid),
+            groups: gids.iter().filter_map(|gid| group_name(*gid)).collect(),
+            gids,
+            sid: None,
+        }
+    }
+
+    /// SID lookup needs the Win32 security API, which captra does not link;
+    /// the identity is reported empty so SID constraints fail closed.
+    #[cfg(not(unix))]
+    #[must_use]
+    pub fn current() -> Self {
+        Self::default()
+    }
+}
+
+impl Display for ProcessIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.uid, &self.user) {
+            (Some(uid), Some(user)) => write!(f, "uid={uid}({user})")?,
+            (Some(uid), None) => write!(f, "uid={uid}")?,
+            (None, _) => f.write_str("uid=?")?,
+        }
+        match self.gids.first() {
+            Some(gid) => write!(f, " gid={gid}")?,
+            None => f.write_str(" gid=?")?,
+        }
+        if let Some(sid) = &self.sid {
+            write!(f, " sid={sid}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn supplementary_groups() -> Vec<libc::gid_t> {
+    // SAFETY: a zero size with a null buffer only queries the group count.
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    let Ok(len) = usize::try_from(count) else {
+        return Vec::new();
+    };
+    let mut groups = vec![0; len];
+    // SAFETY: `groups` has room for `count` entries.
+    let written = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+    groups.truncate(usize::try_from(written).unwrap_or(0));
+    groups
+}
+
+#[cfg(unix)]
+fn user_name(uid: libc::uid_t) -> Option<String> {
+    let mut buf = vec![0; 4096];
+    // SAFETY: all-zero is a valid `passwd` (null pointers, zero ids).
+    let mut pwd = unsafe { std::mem::zeroed::<libc::passwd>() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: every pointer is valid and `buf.len()` is the buffer size.
+    let rc = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &raw mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &raw mut result,
+        )
+    };
+    // SAFETY: on success `pw_name` points at a NUL-terminated string in `buf`.
+    (rc == 0 && !result.is_null())
+        .then(|| unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) })
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(unix)]
+fn group_name(gid: libc::gid_t) -> Option<String> {
+    let mut buf = vec![0; 4096];
+    // SAFETY: all-zero is a valid `group` (null pointers, zero id).
+    let mut grp = unsafe { std::mem::zeroed::<libc::group>() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: every pointer is valid and `buf.len()` is the buffer size.
+    let rc = unsafe {
+        libc::getgrgid_r(
+            gid,
+            &raw mut grp,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &raw mut result,
+        )
+    };
+    // SAFETY: on success `gr_name` points at a NUL-terminated string in `buf`.
+    (rc == 0 && !result.is_null())
+        .then(|| unsafe { std::ffi::CStr::from_ptr(grp.gr_name) })
+        .map(|name| name.to_string_lossy().into_owned())
+}
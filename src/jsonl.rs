@@ -0,0 +1,125 @@
+use crate::trace::{ChainVerifier, TraceError, TraceEvent};
+use base64::{Engine, engine::general_purpose};
+use ed25519_dalek::{Signature, SigningKey, Verifier, VerifyingKey, ed25519::signature::SignerMut};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, Write};
+
+/// Last line of a signed JSONL trace.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonlTrailer {
+    /// Base64 signature over the hex SHA256 of every preceding line
+    signature: String,
+}
+
+/// Streaming writer of signed JSON Lines traces: one event per line, then a
+/// trailer line with the signature, so [`verify_jsonl`] never has to hold
+/// the whole trace in memory.
+#[derive(Debug)]
+pub struct JsonlWriter<W: Write> {
+    out: W,
+    hasher: Sha256,
+    chain: ChainVerifier,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    #[must_use]
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            hasher: Sha256::new(),
+            chain: ChainVerifier::default(),
+        }
+    }
+
+    /// Append one event line, after checking it extends the events written
+    /// so far (see [`ChainVerifier`]).
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] (JSON or IO), or [`TraceError::IntegrityViolation`] if
+    /// the event breaks the chain; nothing is written then.
+    pub fn push(&mut self, event: &TraceEvent) -> Result<(), TraceError> {
+        self.chain.push(event)?;
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        self.hasher.update(&line);
+        self.out.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Sign the lines written so far with `keypair` and write the trailer.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] (JSON or IO).
+    pub fn finish(mut self, keypair: &mut SigningKey) -> Result<W, TraceError> {
+        let digest = format!("{:x}", self.hasher.finalize());
+        let trailer = JsonlTrailer {
+            signature: general_purpose::STANDARD.encode(keypair.sign(digest.as_bytes()).to_bytes()),
+        };
+        serde_json::to_writer(&mut self.out, &trailer)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Outcome of [`verify_jsonl`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonlVerified {
+    pub run_id: Option<String>,
+    pub events: u64,
+    /// Hash of the last event
+    pub head: String,
+}
+
+/// Verify a trace written by [`JsonlWriter`] one line at a time: the hash
+/// chain per event, then the trailer signature with `pubkey`.
+///
+/// # Errors
+///
+/// [`TraceError::IntegrityViolation`] at the first broken link (or a missing
+/// trailer), [`TraceError::InvalidSignature`] if the signature does not
+/// match, or [`TraceError`] (JSON or IO) for malformed input.
+pub fn verify_jsonl<R: BufRead>(
+    mut reader: R,
+    pubkey: &VerifyingKey,
+) -> Result<JsonlVerified, TraceError> {
+    let mut hasher = Sha256::new();
+    let mut chain = ChainVerifier::default();
+    // One line of lookahead: the last line is the trailer, not an event.
+    let mut pending = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if !pending.is_empty() {
+            chain.push(&serde_json::from_slice::<TraceEvent>(&pending)?)?;
+            hasher.update(&pending);
+        }
+        std::mem::swap(&mut pending, &mut line);
+    }
+    if pending.is_empty() {
+        return Err(TraceError::IntegrityViolation {
+            seq: chain.len(),
+            reason: "missing signature trailer".into(),
+        });
+    }
+
+    let trailer = serde_json::from_slice::<JsonlTrailer>(&pending)?;
+    let sig_bytes = general_purpose::STANDARD.decode(&trailer.signature)?;
+    let signature = Signature::from_slice(&sig_bytes).map_err(|_| TraceError::InvalidSignature)?;
+    let digest = format!("{:x}", hasher.finalize());
+    pubkey
+        .verify(digest.as_bytes(), &signature)
+        .map_err(|_| TraceError::InvalidSignature)?;
+    Ok(JsonlVerified {
+        run_id: chain.run_id().map(ToOwned::to_owned),
+        events: chain.len(),
+        head: chain.head().to_owned(),
+    })
+}
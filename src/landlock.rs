@@ -0,0 +1,207 @@
+use crate::{manifest::CapabilityManifest, matcher::bounding_glob};
+use std::{
+    fs::OpenOptions,
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+    ptr,
+};
+use thiserror::Error;
+
+/// Every filesystem access right of Landlock ABI v1, all handled (denied
+/// unless granted by a rule).
+const HANDLED_ACCESS_FS: u64 = (1 << 13) - 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Errors from applying Landlock rules.
+#[derive(Debug, Error)]
+pub enum LandlockError {
+    #[error("Landlock is not supported or disabled in this kernel")]
+    Unsupported,
+
+    #[error("Cannot open Landlock rule path {path}: {source}")]
+    Path { path: PathBuf, source: io::Error },
+
+    #[error("Landlock syscall failed: {0}")]
+    Syscall(#[source] io::Error),
+}
+
+/// Kernel-enforced read allowlist derived from a manifest's FS globs.
+///
+/// Landlock works on file hierarchies, so every glob is widened to its literal
+/// directory prefix (`./workspace/*.toml` grants `./workspace`). Glob matching
+/// stays with [`crate::HostState`]; this is only a backstop against host bugs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LandlockRules {
+    read_paths: Vec<PathBuf>,
+}
+
+impl LandlockRules {
+    /// Read rules for the literal prefix of every `fs.read` pattern.
+    #[must_use]
+    pub fn from_manifest(manifest: &CapabilityManifest) -> Self {
+        let read_paths = manifest
+            .capabilities
+            .fs
+            .as_ref()
+            .and_then(|fs| fs.read.as_ref())
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .map(|p| glob_base(&bounding_glob(p)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut rules = Self { read_paths };
+        rules.read_paths.sort();
+        rules.read_paths.dedup();
+        rules
+    }
+
+    /// Also allow reading beneath `path`, e.g. the host's own wasm modules.
+    #[must_use]
+    pub fn allow_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_paths.push(path.into());
+        self
+    }
+
+    /// Get `read_paths`
+    #[inline]
+    #[must_use]
+    pub fn read_paths(&self) -> &[PathBuf] {
+        &self.read_paths
+    }
+
+    /// Landlock ABI version of the running kernel (`None` if unavailable).
+    #[must_use]
+    pub fn abi_version() -> Option<u32> {
+        // SAFETY: a null attr with size 0 and the version flag only queries the ABI.
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                ptr::null::<RulesetAttr>(),
+                0_usize,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        u32::try_from(abi).ok().filter(|abi| *abi > 0)
+    }
+
+    /// Restrict the calling thread (and threads or processes it spawns
+    /// afterwards) to reading beneath `read_paths`; every other filesystem
+    /// access, including writes, is denied. This cannot be undone.
+    ///
+    /// # Errors
+    ///
+    /// [`LandlockError::Unsupported`] if the kernel lacks Landlock, otherwise
+    /// if a rule path cannot be opened or a syscall fails.
+    pub fn restrict_self(&self) -> Result<(), LandlockError> {
+        if Self::abi_version().is_none() {
+            return Err(LandlockError::Unsupported);
+        }
+
+        let attr = RulesetAttr {
+            handled_access_fs: HANDLED_ACCESS_FS,
+        };
+        // SAFETY: `attr` is a valid ruleset attribute of the given size.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &raw const attr,
+                size_of::<RulesetAttr>(),
+                0_u32,
+            )
+        };
+        let ruleset = owned_fd(fd)?;
+
+        for path in &self.read_paths {
+            add_read_rule(&ruleset, path)?;
+        }
+
+        // SAFETY: plain prctl/syscall invocations on a ruleset fd we own.
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(LandlockError::Syscall(io::Error::last_os_error()));
+            }
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0_u32) != 0 {
+                return Err(LandlockError::Syscall(io::Error::last_os_error()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn add_read_rule(ruleset: &OwnedFd, path: &Path) -> Result<(), LandlockError> {
+    let rule_err = |source| LandlockError::Path {
+        path: path.to_path_buf(),
+        source,
+    };
+    let parent = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+        .open(path)
+        .map_err(rule_err)?;
+    let is_dir = parent.metadata().map_err(rule_err)?.is_dir();
+
+    let rule = PathBeneathAttr {
+        allowed_access: if is_dir {
+            ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR
+        } else {
+            ACCESS_FS_READ_FILE
+        },
+        parent_fd: parent.as_raw_fd(),
+    };
+    // SAFETY: `rule` is a valid path-beneath attribute and both fds are open.
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &raw const rule,
+            0_u32,
+        )
+    };
+    if res != 0 {
+        return Err(LandlockError::Syscall(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+fn owned_fd(fd: libc::c_long) -> Result<OwnedFd, LandlockError> {
+    let fd = i32::try_from(fd)
+        .ok()
+        .filter(|fd| *fd >= 0)
+        .ok_or_else(|| LandlockError::Syscall(io::Error::last_os_error()))?;
+    // SAFETY: the kernel just returned this descriptor and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Literal leading components of `pattern`, up to the first glob metacharacter.
+fn glob_base(pattern: &str) -> PathBuf {
+    let base = Path::new(pattern)
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect::<PathBuf>();
+    if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    }
+}
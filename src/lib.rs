@@ -1,7 +1,103 @@
+mod archive;
+mod audit;
+mod batch;
+mod bundle;
+mod config;
+mod error;
 mod host;
+mod identity;
+mod jsonl;
+#[cfg(all(feature = "landlock", target_os = "linux"))]
+mod landlock;
 mod manifest;
+mod matcher;
+mod metrics;
+#[cfg(all(
+    feature = "native",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod native;
+mod paths;
+mod plugin;
+mod policy;
+mod registry;
+mod replay;
+mod sarif;
+mod secure_open;
+mod session;
+mod spawn;
+#[cfg(feature = "test-util")]
+pub mod testing;
 mod trace;
+mod wasm;
+mod watchdog;
+mod webhook;
 
-pub use host::{CapError, HostState, HostStatus, add_wasm_linker_funcs, init_tracing};
-pub use manifest::{Capability, CapabilityManifest, ManifestError, load_manifest};
-pub use trace::{CapEventSubtype, EventType, SignedTrace, TraceError, TraceEvent, load_trace};
+pub use archive::{
+    ARCHIVE_MANIFEST_FILE, ARCHIVE_PUBKEY_FILE, ARCHIVE_REPORT_FILE, ARCHIVE_SUMS_FILE,
+    ARCHIVE_TRACE_FILE, ArchiveError, RunBundle, read_run_bundle, write_run_bundle,
+};
+pub use audit::{
+    BatchProgress, BatchVerification, Keyring, VerifiedTrace, verify_batch,
+    verify_signed_trace_file,
+};
+pub use batch::{BatchReport, BatchRunner};
+pub use bundle::{Bundle, BundleError, MANIFEST_SECTION, custom_sections, embed_manifest};
+pub use config::{CONFIG_FILE, ConfigError, HostConfig, TraceFormat, TraceSinkConfig};
+pub use error::{CaptraError, Result};
+pub use host::{
+    Budget, BudgetThreshold, CapError, ConfigProvider, CustomEnforcer, DenialMode, HostSnapshot,
+    HostState, HostStatus, MAX_CAPPED_WORKERS, host_features, init_tracing,
+};
+pub use identity::{ProcessIdentity, RunAs};
+pub use jsonl::{JsonlVerified, JsonlWriter, verify_jsonl};
+#[cfg(all(feature = "landlock", target_os = "linux"))]
+pub use landlock::{LandlockError, LandlockRules};
+pub use manifest::{
+    AuditMetadata, BlobCapability, CAPABILITY_FILES_KEY, Capability, CapabilityManifest,
+    ConfigCapability, ContentFilter, CustomCapability, Decision, IssuerCert, LoadOptions,
+    ManifestError, Posture, Requirements, SimCall, TrustRoots, ValidationProblem, ValidationReport,
+    load_manifest, simulate, validate_report,
+};
+pub use matcher::{Matcher, MatcherError, MatcherKind, bounding_glob};
+pub use metrics::{DecisionStats, DecisionTiming};
+#[cfg(all(
+    feature = "native",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub use native::{NativeError, ResourceLimits, SETUP_FAILED_EXIT, SeccompProfile};
+pub use paths::CaptraDirs;
+pub use plugin::{DEFAULT_FUEL, ModuleCache, PluginInstance, WasmConfig};
+pub use policy::{
+    Approval, ApprovalRequest, DEFAULT_PROMPT_TIMEOUT, PolicyHook, PromptMessage, PromptPolicy,
+};
+pub use registry::{Registry, RegistryError};
+pub use replay::{DEFAULT_DIVERGENCE_CONTEXT, DivergenceReport, ReplayHost, first_divergence};
+pub use sarif::{SARIF_VERSION, to_sarif};
+pub use secure_open::{SecureOpenError, grant_root, secure_open, secure_read_dir};
+pub use semver;
+pub use session::RunSession;
+pub use spawn::{ChildRun, MAX_SPAWN_DEPTH, PluginCatalog, SPAWN_EXPORT};
+pub use trace::{
+    CapEventSubtype, ChainVerifier, CompactionPolicy, DEFAULT_MAX_INPUT_LEN, DETACHED_META_FILE,
+    DETACHED_SIG_FILE, DETACHED_TRACE_FILE, EventType, RandomnessMode, RunLabels, Severity,
+    SeverityRule, SeverityRules, SignedTrace, TRACE_FORMAT_VERSION, TRACE_MAGIC, TRACE_TARGET,
+    TraceCheckpoint, TraceError, TraceEvent, TraceFileHeader, TraceHasher, TraceLoadOptions,
+    TracePolicy, TraceRule, VectorClock, Verbosity, causal_order, checkpoint_message, compact,
+    event_hash, export_csv, load_trace, load_trace_header, load_trace_with, pubkey_fingerprint,
+    pubkey_hex, pubkey_openssh, pubkey_openssh_fingerprint, pubkey_pem, render_table,
+    render_table_with, save_trace, save_trace_csv, save_trace_with_header, summary_run_id,
+    trace_message, truncate_input, verify_chain, verify_key_rotations, verify_ts_seeds,
+};
+pub use wasm::{
+    DEFAULT_MEMORY_EXPORT, GUEST_ALLOCATORS, HostNamespace, NONDETERMINISTIC_IMPORTS, WasmError,
+    add_wasm_linker_funcs, add_wasm_linker_funcs_for, add_wasm_linker_funcs_in,
+    guest_memory_export, is_memory64, memory_export_is_64,
+};
+pub use watchdog::{Stall, Watchdog, WatchdogConfig};
+pub use webhook::{
+    HttpTransport, WEBHOOK_SIGNATURE_HEADER, WebhookConfig, WebhookSink, WebhookStats,
+    WebhookTransport, is_alert, sign_webhook_payload,
+};
@@ -1,15 +1,205 @@
+use crate::{
+    identity::RunAs,
+    matcher::{Matcher, MatcherError, MatcherKind, any_matches, bounding_glob},
+    trace::{TracePolicy, TraceRule, sha256_hex},
+};
+use base64::{Engine, engine::general_purpose};
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use glob::Pattern;
-use serde::{Deserialize, Serialize};
-use std::{fs::read_to_string, path::Path};
+use semver::{Version, VersionReq};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, Visitor},
+    forward_to_deserialize_any,
+};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::read_to_string,
+    path::Path,
+};
 use thiserror::Error;
 
+/// Manifest key listing capability files merged into `capabilities` by
+/// [`CapabilityManifest::load`].
+pub const CAPABILITY_FILES_KEY: &str = "capability_files";
+
 /// Prime for seq hashing to derive per-event RNG state
 pub const PRIME_MULTIPLIER: u64 = 314_159;
 
+/// FS grant entries (`read`, `write`, `list`) are [`Matcher`] specs: globs by
+/// default, or written as `{"type": "prefix" | "regex", "pattern": ...}`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FsCapability {
-    pub read: Option<Vec<String>>,  // Glob patter for read
+    #[serde(default, deserialize_with = "deserialize_matchers")]
+    pub read: Option<Vec<String>>, // Glob patter for read
+    #[serde(default, deserialize_with = "deserialize_matchers")]
     pub write: Option<Vec<String>>, // Stub for now
+    /// Patterns of directories whose entries may be listed. Listing is
+    /// granted separately from reading files inside the directory.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_matchers",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub list: Option<Vec<String>>,
+    /// Largest file the host will read or write, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_bytes: Option<u64>,
+    /// Largest byte range a single ranged read may request, see
+    /// [`crate::HostState::read_file_range`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_range_bytes: Option<u64>,
+    /// Allowed file extensions without the dot (e.g. `["toml", "md"]`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<String>>,
+    /// Lines dropped from file contents before they reach the guest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_filters: Vec<ContentFilter>,
+}
+
+/// A typed FS grant entry, stored as [`MatcherKind::spec`].
+#[derive(Debug, Deserialize)]
+struct TypedMatcher {
+    #[serde(rename = "type")]
+    kind: MatcherKind,
+    pattern: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MatcherEntry {
+    Spec(String),
+    Typed(TypedMatcher),
+}
+
+fn deserialize_matchers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    let entries = Option::<Vec<MatcherEntry>>::deserialize(deserializer)?;
+    Ok(entries.map(|entries| {
+        entries
+            .into_iter()
+            .map(|entry| match entry {
+                MatcherEntry::Spec(spec) => spec,
+                MatcherEntry::Typed(typed) => typed.kind.spec(&typed.pattern),
+            })
+            .collect()
+    }))
+}
+
+/// Refuse to return lines matching `regex` from reads of `paths` (globs; all
+/// readable paths if empty), e.g. `(?i)api_key|secret`. Needs the
+/// `regex-matcher` feature, like regex [`Matcher`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentFilter {
+    pub regex: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+impl ContentFilter {
+    /// Whether the filter covers reads of `path`
+    #[must_use]
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|pattern| Pattern::new(pattern).is_ok_and(|p| p.matches(path)))
+    }
+}
+
+impl FsCapability {
+    /// Whether `path` has one of the allowed `extensions` (always true if unset).
+    #[must_use]
+    pub fn allows_extension(&self, path: &str) -> bool {
+        self.extensions.as_ref().is_none_or(|extensions| {
+            Path::new(path)
+                .extension()
+                .is_some_and(|ext| extensions.iter().any(|allowed| ext == allowed.as_str()))
+        })
+    }
+}
+
+/// Access to the wall clock through `host::now_millis`, rounded down to
+/// `granularity_ms` so guests cannot use it as a precise timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeCapability {
+    pub granularity_ms: u64,
+}
+
+impl Default for TimeCapability {
+    /// One-second granularity, used for undeclared time under [`Posture::Audit`].
+    fn default() -> Self {
+        Self {
+            granularity_ms: 1000,
+        }
+    }
+}
+
+/// Host-provided byte buffers (config blobs, asset packs) the guest may open
+/// by name through `host::get_blob`, without any FS access.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobCapability {
+    /// Exact blob names the guest may open
+    pub allow: Vec<String>,
+}
+
+impl BlobCapability {
+    /// Whether `name` may be opened
+    #[must_use]
+    pub fn allows(&self, name: &str) -> bool {
+        self.allow.iter().any(|allowed| allowed == name)
+    }
+}
+
+/// Typed configuration values the guest may pull by key through
+/// `host::get_config`, served by the host's [`crate::ConfigProvider`], so
+/// plugins need no access to config files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigCapability {
+    /// Exact keys the guest may read
+    pub keys: Vec<String>,
+}
+
+impl ConfigCapability {
+    /// Whether `key` may be read
+    #[must_use]
+    pub fn allows(&self, key: &str) -> bool {
+        self.keys.iter().any(|allowed| allowed == key)
+    }
+}
+
+/// Host machinery a plugin relies on, checked by
+/// [`crate::HostState::check_requirements`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Requirements {
+    /// Semver requirement on the captra version (e.g. `>=0.3`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captra: Option<String>,
+    /// Host features that must be available, see [`crate::HostState::has_feature`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+}
+
+impl Requirements {
+    /// Parsed `captra` version requirement.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::InvalidRequirement`] if it is not a semver requirement.
+    pub fn captra_req(&self) -> Result<Option<VersionReq>, ManifestError> {
+        self.captra
+            .as_deref()
+            .map(|req| {
+                VersionReq::parse(req).map_err(|err| ManifestError::InvalidRequirement {
+                    req: req.to_owned(),
+                    err: err.to_string(),
+                })
+            })
+            .transpose()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,9 +208,48 @@ pub enum Capability {
     // TODO: add Net, Cpu, etc
 }
 
+/// How the host treats operations of capability kinds the manifest does not
+/// mention at all (e.g. a file read when there is no `fs` section).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Posture {
+    /// Deny and record a `cap.error`.
+    #[default]
+    DenyAll,
+    /// Allow but record a `cap.audit` event, for profiling what a plugin uses.
+    Audit,
+}
+
+impl Posture {
+    #[inline]
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Capabilities {
     pub fs: Option<FsCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<BlobCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<ConfigCapability>,
+    /// Embedder-defined capabilities (clipboard, UI dialogs, ...), enforced by
+    /// enforcers registered on the host.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom: Vec<CustomCapability>,
+}
+
+/// A capability kind captra does not know, granted with opaque `params` that
+/// are passed to the host's enforcer for that `kind`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomCapability {
+    pub kind: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,8 +257,83 @@ pub struct CapabilityManifest {
     pub plugin: String,
     pub version: String,
     pub capabilities: Capabilities,
+    /// Named capability sets per deployment environment, selected with
+    /// [`CapabilityManifest::resolve_profile`]. Sections a profile declares
+    /// replace those of `capabilities`; the rest are inherited.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Capabilities>,
+    /// Treatment of undeclared capability kinds.
+    #[serde(default, skip_serializing_if = "Posture::is_default")]
+    pub posture: Posture,
+    /// Process identities the host must be running as, see [`crate::HostState::check_run_as`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as: Option<RunAs>,
+    /// captra version and host features the plugin needs, see [`crate::HostState::check_requirements`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires: Option<Requirements>,
+    /// Logging verbosity per event type, overridable by [`crate::HostState::with_trace_policy`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_policy: Option<TracePolicy>,
     pub issued_by: String,
-    // TODO: add signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub data_classes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    /// Require `description`, `data_classes` and `contact` to be present and non-empty.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_metadata: bool,
+    /// Certificates delegating signing from a trusted root down to `issued_by`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delegation: Vec<IssuerCert>,
+    /// Base64 signature by `issued_by` over the manifest without this field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// How [`CapabilityManifest::load_with`] parses manifests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Reject unknown fields (e.g. a typo'd `"raed"` that would otherwise
+    /// silently grant nothing) instead of ignoring them.
+    pub deny_unknown_fields: bool,
+}
+
+impl LoadOptions {
+    /// Options rejecting unknown fields.
+    #[inline]
+    #[must_use]
+    pub const fn strict() -> Self {
+        Self {
+            deny_unknown_fields: true,
+        }
+    }
+}
+
+/// Trusted root issuers by name.
+pub type TrustRoots = HashMap<String, VerifyingKey>;
+
+/// One link of a delegation chain: `issuer` vouches for `subject`'s key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IssuerCert {
+    pub subject: String,
+    /// Base64 public key of `subject`
+    pub subject_pubkey: String,
+    pub issuer: String,
+    /// Base64 signature by `issuer` over `subject` and `subject_pubkey`
+    pub signature: String,
+}
+
+/// Compliance metadata copied from the manifest into the [`crate::SignedTrace`] header.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub data_classes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
 }
 
 /// Errors from manifest loading/validation.
@@ -47,9 +351,56 @@ pub enum ManifestError {
     #[error("Invalid version: must be non-empty")]
     InvalidVersion,
 
+    #[error("Invalid semver version `{version}`: {err}")]
+    InvalidSemver { version: String, err: String },
+
+    #[error("Version {version} does not satisfy requirement `{req}`")]
+    VersionRequirement { version: String, req: String },
+
+    #[error("Invalid captra version requirement `{req}`: {err}")]
+    InvalidRequirement { req: String, err: String },
+
     #[error("Invalid issuer: must be non-empty")]
     InvalidIssuer,
 
+    #[error("Manifest signature missing or invalid")]
+    InvalidSignature,
+
+    #[error("Issuer {0} is not a trusted root")]
+    UntrustedIssuer(String),
+
+    #[error("Broken delegation chain at link {idx}: {reason}")]
+    BrokenDelegation { idx: usize, reason: String },
+
+    #[error("Invalid metadata: {0} must be non-empty when strict_metadata is set")]
+    InvalidMetadata(&'static str),
+
+    #[error("Invalid custom capability at index {0}: kind must be non-empty")]
+    InvalidCustomKind(usize),
+
+    #[error("Invalid time granularity: granularity_ms must be at least 1")]
+    InvalidTimeGranularity,
+
+    #[error("Invalid content filter at index {idx}: {err}")]
+    InvalidContentFilter { idx: usize, err: String },
+
+    #[error("Capability file `{file}`: {reason}")]
+    CapabilityFile { file: String, reason: String },
+
+    #[error("Unknown manifest field `{0}`")]
+    UnknownField(String),
+
+    #[error("Unknown capability profile `{0}`")]
+    UnknownProfile(String),
+
+    #[error("Invalid {kind} matcher at index {idx}: {pattern} - {err}")]
+    InvalidMatcher {
+        kind: &'static str,
+        idx: usize,
+        pattern: String,
+        err: String,
+    },
+
     #[error("Invalid glob pattern at index {idx}: {pattern} - {err}")]
     InvalidGlob {
         idx: usize,
@@ -58,49 +409,600 @@ pub enum ManifestError {
     },
 }
 
+impl ManifestError {
+    /// Stable machine-readable code for the error kind.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            Self::Deserialize(_) => "deserialize",
+            Self::InvalidPlugin => "invalid_plugin",
+            Self::InvalidVersion => "invalid_version",
+            Self::InvalidSemver { .. } => "invalid_semver",
+            Self::VersionRequirement { .. } => "version_requirement",
+            Self::InvalidRequirement { .. } => "invalid_requirement",
+            Self::InvalidIssuer => "invalid_issuer",
+            Self::InvalidSignature => "invalid_signature",
+            Self::UntrustedIssuer(_) => "untrusted_issuer",
+            Self::BrokenDelegation { .. } => "broken_delegation",
+            Self::InvalidMetadata(_) => "invalid_metadata",
+            Self::InvalidCustomKind(_) => "invalid_custom_kind",
+            Self::InvalidTimeGranularity => "invalid_time_granularity",
+            Self::InvalidContentFilter { .. } => "invalid_content_filter",
+            Self::CapabilityFile { .. } => "capability_file",
+            Self::UnknownField(_) => "unknown_field",
+            Self::UnknownProfile(_) => "unknown_profile",
+            Self::InvalidGlob { .. } => "invalid_glob",
+            Self::InvalidMatcher { .. } => "invalid_matcher",
+        }
+    }
+}
+
+/// Every problem found in a manifest file by [`validate_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub file: String,
+    pub problems: Vec<ValidationProblem>,
+}
+
+/// One entry of a [`ValidationReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationProblem {
+    /// [`ManifestError::code`]
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<&ManifestError> for ValidationProblem {
+    fn from(err: &ManifestError) -> Self {
+        Self {
+            code: err.code(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl ValidationReport {
+    #[inline]
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Pretty JSON for CI output.
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Report serializes") // Safe: plain data
+    }
+
+    fn push(&mut self, err: &ManifestError) {
+        self.problems.push(err.into());
+    }
+}
+
 impl CapabilityManifest {
     /// Validates the manifest: non-empty fields and compilable glob patterns.
     ///
     /// # Errors
     ///
-    /// [`ManifestError`] if invalid.
+    /// [`ManifestError`] if invalid (the first problem of [`CapabilityManifest::validate_all`]).
     pub fn validate(&self) -> Result<(), ManifestError> {
+        self.validate_all().into_iter().next().map_or(Ok(()), Err)
+    }
+
+    /// Every validation problem of the manifest instead of only the first,
+    /// in the order [`CapabilityManifest::validate`] checks them.
+    #[must_use]
+    pub fn validate_all(&self) -> Vec<ManifestError> {
+        let mut problems = Vec::new();
         if self.plugin.is_empty() {
-            return Err(ManifestError::InvalidPlugin);
+            problems.push(ManifestError::InvalidPlugin);
         }
         if self.version.is_empty() {
-            return Err(ManifestError::InvalidVersion);
+            problems.push(ManifestError::InvalidVersion);
+        } else if let Err(err) = self.version_semver() {
+            problems.push(err);
+        }
+        if let Some(Err(err)) = self.requires.as_ref().map(Requirements::captra_req) {
+            problems.push(err);
+        }
+        if self.strict_metadata {
+            problems.extend(self.metadata_problems());
         }
         if self.issued_by.is_empty() {
-            return Err(ManifestError::InvalidIssuer);
+            problems.push(ManifestError::InvalidIssuer);
         }
-        if let Some(fs_cap) = &self.capabilities.fs
-            && let Some(read_patterns) = &fs_cap.read
-        {
-            for (idx, pattern) in read_patterns.iter().enumerate() {
-                Pattern::new(pattern).map_err(|err| ManifestError::InvalidGlob {
-                    idx,
-                    pattern: pattern.clone(),
-                    err: err.to_string(),
-                })?;
-            }
+        capability_problems(&self.capabilities, &mut problems);
+        for capabilities in self.profiles.values() {
+            capability_problems(capabilities, &mut problems);
         }
+        problems
+    }
+
+    /// Names of the declared [`CapabilityManifest::profiles`]
+    pub fn profile_names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// The manifest in force under profile `name`: its sections overlaid on
+    /// `capabilities` (`custom` is replaced when the profile declares any),
+    /// without the other profiles, so its hash pins exactly what was granted.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::UnknownProfile`] if no such profile is declared.
+    pub fn resolve_profile(&self, name: &str) -> Result<Self, ManifestError> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| ManifestError::UnknownProfile(name.to_owned()))?;
+        let base = &self.capabilities;
+        let capabilities = Capabilities {
+            fs: profile.fs.clone().or_else(|| base.fs.clone()),
+            time: profile.time.or(base.time),
+            blob: profile.blob.clone().or_else(|| base.blob.clone()),
+            config: profile.config.clone().or_else(|| base.config.clone()),
+            custom: if profile.custom.is_empty() {
+                base.custom.clone()
+            } else {
+                profile.custom.clone()
+            },
+        };
+        Ok(Self {
+            capabilities,
+            profiles: BTreeMap::new(),
+            ..self.clone()
+        })
+    }
+
+    /// SHA256 of the serialized manifest, as recorded in trace headers.
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    #[must_use]
+    pub fn content_hash(&self) -> String {
+        let manifest_json = serde_json::to_string(self).expect("Manifest serializes"); // Safe: plain data
+        sha256_hex(manifest_json.as_bytes())
+    }
+
+    /// Sign the manifest as `issued_by`, replacing any previous signature.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::Deserialize`] if the manifest cannot be serialized.
+    pub fn sign(&mut self, key: &SigningKey) -> Result<(), ManifestError> {
+        let signature = key.sign(&self.signing_bytes()?);
+        self.signature = Some(general_purpose::STANDARD.encode(signature.to_bytes()));
         Ok(())
     }
 
+    /// Verify the delegation chain from one of `roots` down to `issued_by`,
+    /// then the manifest signature with the resulting key.
+    ///
+    /// Without delegation certificates `issued_by` must itself be a root.
+    /// Returns the verified signer key.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError`] if a link or the manifest signature does not verify.
+    pub fn validate_chain(&self, roots: &TrustRoots) -> Result<VerifyingKey, ManifestError> {
+        let first_issuer = self
+            .delegation
+            .first()
+            .map_or(&self.issued_by, |cert| &cert.issuer);
+        let mut signer_name = first_issuer.as_str();
+        let mut signer_key = *roots
+            .get(signer_name)
+            .ok_or_else(|| ManifestError::UntrustedIssuer(signer_name.into()))?;
+
+        for (idx, cert) in self.delegation.iter().enumerate() {
+            let broken = |reason: &str| ManifestError::BrokenDelegation {
+                idx,
+                reason: reason.into(),
+            };
+            if cert.issuer != signer_name {
+                return Err(broken("issuer does not match previous subject"));
+            }
+            signer_key = cert
+                .verify(&signer_key)
+                .map_err(|()| broken("invalid certificate signature or key"))?;
+            signer_name = &cert.subject;
+        }
+
+        if signer_name != self.issued_by {
+            return Err(ManifestError::BrokenDelegation {
+                idx: self.delegation.len(),
+                reason: "chain does not end at issued_by".into(),
+            });
+        }
+
+        let signature = self
+            .signature
+            .as_deref()
+            .and_then(decode_signature)
+            .ok_or(ManifestError::InvalidSignature)?;
+        signer_key
+            .verify(&self.signing_bytes()?, &signature)
+            .map_err(|_| ManifestError::InvalidSignature)?;
+        Ok(signer_key)
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>, ManifestError> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+
+    /// Whether the FS read grants allow `path` (invalid patterns never match).
+    /// Pure evaluation: nothing is traced.
+    #[must_use]
+    pub fn allows_read(&self, path: &str) -> bool {
+        self.capabilities
+            .fs
+            .as_ref()
+            .and_then(|fs| fs.read.as_ref())
+            .is_some_and(|patterns| any_matches(patterns, path))
+    }
+
+    /// Audit metadata for the trace header (`None` if the manifest declares none).
+    #[must_use]
+    pub fn audit_metadata(&self) -> Option<AuditMetadata> {
+        if self.description.is_none() && self.data_classes.is_empty() && self.contact.is_none() {
+            return None;
+        }
+        Some(AuditMetadata {
+            description: self.description.clone(),
+            data_classes: self.data_classes.clone(),
+            contact: self.contact.clone(),
+        })
+    }
+
+    /// Human-readable summary of what the manifest grants, for install-time
+    /// approval prompts. Manifests carry no expiry, so it is always `never`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            format!("plugin: {} {}", self.plugin, self.version),
+            format!("issued by: {}", self.issued_by),
+            "expires: never".to_owned(),
+        ];
+        if let Some(description) = &self.description {
+            lines.push(format!("description: {description}"));
+        }
+        let count = |patterns: &Option<Vec<String>>| patterns.as_ref().map_or(0, Vec::len);
+        match &self.capabilities.fs {
+            Some(fs) => lines.push(format!(
+                "fs: {} read, {} write, {} list, {} content filters",
+                count(&fs.read),
+                count(&fs.write),
+                count(&fs.list),
+                fs.content_filters.len()
+            )),
+            None => lines.push("fs: none".to_owned()),
+        }
+        match self.capabilities.time {
+            Some(time) => lines.push(format!("time: {}ms granularity", time.granularity_ms)),
+            None => lines.push("time: none".to_owned()),
+        }
+        if let Some(blob) = &self.capabilities.blob {
+            lines.push(format!(
+                "blob: {} ({})",
+                blob.allow.len(),
+                blob.allow.join(", ")
+            ));
+        }
+        if let Some(config) = &self.capabilities.config {
+            lines.push(format!(
+                "config: {} ({})",
+                config.keys.len(),
+                config.keys.join(", ")
+            ));
+        }
+        let kinds = self
+            .capabilities
+            .custom
+            .iter()
+            .map(|cap| cap.kind.as_str())
+            .collect::<Vec<_>>();
+        if kinds.is_empty() {
+            lines.push("custom: 0".to_owned());
+        } else {
+            lines.push(format!("custom: {} ({})", kinds.len(), kinds.join(", ")));
+        }
+        if !self.profiles.is_empty() {
+            let names = self.profile_names().collect::<Vec<_>>();
+            lines.push(format!("profiles: {}", names.join(", ")));
+        }
+        if let Some(glob) = self.widest_glob() {
+            lines.push(format!("widest glob: {glob}"));
+        }
+        if self.posture == Posture::Audit {
+            lines.push("posture: audit (undeclared access allowed and logged)".to_owned());
+        }
+        lines.join("\n")
+    }
+
+    /// The FS grant pattern matching the most paths, judged by the shortest
+    /// literal prefix before the first wildcard (`**` wins ties).
+    #[must_use]
+    pub fn widest_glob(&self) -> Option<&str> {
+        let fs = self.capabilities.fs.as_ref()?;
+        [&fs.read, &fs.write, &fs.list]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .min_by_key(|pattern| {
+                let glob = bounding_glob(pattern);
+                let prefix = glob.find(['*', '?', '[']).unwrap_or(glob.len());
+                (prefix, !glob.contains("**"))
+            })
+            .map(String::as_str)
+    }
+
+    fn metadata_problems(&self) -> impl Iterator<Item = ManifestError> {
+        let is_blank = |field: &Option<String>| field.as_deref().is_none_or(str::is_empty);
+        [
+            ("description", is_blank(&self.description)),
+            (
+                "data_classes",
+                self.data_classes.is_empty() || self.data_classes.iter().any(String::is_empty),
+            ),
+            ("contact", is_blank(&self.contact)),
+        ]
+        .into_iter()
+        .filter(|(_, invalid)| *invalid)
+        .map(|(field, _)| ManifestError::InvalidMetadata(field))
+    }
+
+    /// Parse `version` as semver, padding missing minor/patch components
+    /// (`"0.1"` -> `0.1.0`, `"2"` -> `2.0.0`).
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::InvalidSemver`] if the version is not (lenient) semver.
+    pub fn version_semver(&self) -> Result<Version, ManifestError> {
+        let padded = match self.version.split('.').count() {
+            1 => format!("{}.0.0", self.version),
+            2 => format!("{}.0", self.version),
+            _ => self.version.clone(),
+        };
+        Version::parse(&padded).map_err(|err| self.invalid_semver(&err))
+    }
+
+    /// Parse `version` as strict semver (`MAJOR.MINOR.PATCH[-PRE][+BUILD]`).
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::InvalidSemver`] if the version is not strict semver.
+    pub fn version_semver_strict(&self) -> Result<Version, ManifestError> {
+        Version::parse(&self.version).map_err(|err| self.invalid_semver(&err))
+    }
+
+    /// Check the manifest version against a consumer policy (e.g. `>=1.2`).
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError::VersionRequirement`] if the version does not match `req`.
+    pub fn require_version(&self, req: &VersionReq) -> Result<(), ManifestError> {
+        let version = self.version_semver()?;
+        if req.matches(&version) {
+            return Ok(());
+        }
+        Err(ManifestError::VersionRequirement {
+            version: version.to_string(),
+            req: req.to_string(),
+        })
+    }
+
+    fn invalid_semver(&self, err: &semver::Error) -> ManifestError {
+        ManifestError::InvalidSemver {
+            version: self.version.clone(),
+            err: err.to_string(),
+        }
+    }
+
     /// Loads a capability manifest from a JSON file and validates it.
     ///
+    /// Files listed under `capability_files` (relative to the manifest's
+    /// directory, each holding a `capabilities` object) are merged first, so
+    /// different reviewers can own e.g. `fs.json` and `time.json`. A class may
+    /// be declared only once; `custom` entries are concatenated. The merged
+    /// manifest no longer lists the files, and its signature covers the
+    /// merged capabilities.
+    ///
     /// # Errors
     ///
     /// [`ManifestError`] (IO, JSON, or validation failures).
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ManifestError> {
-        let json_str = read_to_string(path)?;
-        let manifest = serde_json::from_str::<Self>(&json_str)?;
+        Self::load_with(path, LoadOptions::default())
+    }
+
+    /// Like [`CapabilityManifest::load`], parsing according to `options`.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError`] (IO, JSON, unknown fields or validation failures).
+    pub fn load_with<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<Self, ManifestError> {
+        let path = path.as_ref();
+        let mut value = serde_json::from_str::<Value>(&read_to_string(path)?)?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        merge_capability_files(&mut value, base, options)?;
+        Self::from_value_with(value, options)
+    }
+
+    /// Parse and validate a manifest from a JSON string.
+    ///
+    /// # Errors
+    ///
+    /// [`ManifestError`] (JSON, unknown fields or validation failures).
+    pub fn from_json_with(json: &str, options: LoadOptions) -> Result<Self, ManifestError> {
+        let value = serde_json::from_str::<Value>(json)?;
+        if let Some(files) = value.get(CAPABILITY_FILES_KEY) {
+            return Err(ManifestError::CapabilityFile {
+                file: files.to_string(),
+                reason: "only resolved when loading the manifest from a file".into(),
+            });
+        }
+        Self::from_value_with(value, options)
+    }
+
+    fn from_value_with(value: Value, options: LoadOptions) -> Result<Self, ManifestError> {
+        if options.deny_unknown_fields {
+            check_known_fields(&value)?;
+        }
+        let manifest = serde_json::from_value::<Self>(value)?;
         manifest.validate()?;
         Ok(manifest)
     }
 }
 
+impl IssuerCert {
+    /// Issue a certificate for `subject` signed by `issuer`.
+    #[must_use]
+    pub fn issue(
+        issuer: impl Into<String>,
+        issuer_key: &SigningKey,
+        subject: impl Into<String>,
+        subject_pubkey: &VerifyingKey,
+    ) -> Self {
+        let subject = subject.into();
+        let subject_pubkey = general_purpose::STANDARD.encode(subject_pubkey.as_bytes());
+        let signature = issuer_key.sign(&Self::signing_bytes(&subject, &subject_pubkey));
+        Self {
+            subject,
+            subject_pubkey,
+            issuer: issuer.into(),
+            signature: general_purpose::STANDARD.encode(signature.to_bytes()),
+        }
+    }
+
+    /// Check the certificate against the issuer key, returning the subject key.
+    fn verify(&self, issuer_key: &VerifyingKey) -> Result<VerifyingKey, ()> {
+        let signature = decode_signature(&self.signature).ok_or(())?;
+        issuer_key
+            .verify(
+                &Self::signing_bytes(&self.subject, &self.subject_pubkey),
+                &signature,
+            )
+            .map_err(|_| ())?;
+        let key_bytes = general_purpose::STANDARD
+            .decode(&self.subject_pubkey)
+            .map_err(|_| ())?;
+        let key_bytes = <[u8; PUBLIC_KEY_LENGTH]>::try_from(key_bytes).map_err(|_| ())?;
+        VerifyingKey::from_bytes(&key_bytes).map_err(|_| ())
+    }
+
+    fn signing_bytes(subject: &str, subject_pubkey: &str) -> Vec<u8> {
+        format!("captra-issuer-cert\n{subject}\n{subject_pubkey}").into_bytes()
+    }
+}
+
+fn decode_signature(encoded: &str) -> Option<Signature> {
+    let bytes = general_purpose::STANDARD.decode(encoded).ok()?;
+    Signature::from_slice(&bytes).ok()
+}
+
+/// A hypothetical operation evaluated by [`simulate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimCall {
+    /// File read of `path`, as [`crate::HostState::read_file`].
+    Read(String),
+    /// Directory listing of `path`, as [`crate::HostState::list_dir`].
+    List(String),
+    /// Wall clock read, as [`crate::HostState::now_millis`].
+    Time,
+    /// Host blob lookup by name, as [`crate::HostState::get_blob`].
+    Blob(String),
+    /// Host config lookup by key, as [`crate::HostState::get_config`].
+    Config(String),
+    /// Embedder-defined operation of `kind`, as [`crate::HostState::check_custom`].
+    Custom(String),
+}
+
+/// Outcome of a [`SimCall`] under a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    /// Undeclared but allowed by [`Posture::Audit`].
+    Audit,
+    /// Denied with the [`crate::CapError::reason_code`] the host would report.
+    Deny(&'static str),
+}
+
+impl Decision {
+    #[inline]
+    #[must_use]
+    pub const fn is_allowed(self) -> bool {
+        matches!(self, Self::Allow | Self::Audit)
+    }
+}
+
+/// Evaluate `calls` against the manifest alone, without a host or trace, so
+/// tests can assert what a manifest would allow or deny.
+///
+/// Only manifest rules are applied: custom calls are allowed once their kind
+/// is declared (host enforcers, policy hooks and budgets are not consulted),
+/// and nothing touches the file system.
+#[must_use]
+pub fn simulate(manifest: &CapabilityManifest, calls: &[SimCall]) -> Vec<Decision> {
+    calls
+        .iter()
+        .map(|call| simulate_call(manifest, call))
+        .collect()
+}
+
+fn simulate_call(manifest: &CapabilityManifest, call: &SimCall) -> Decision {
+    let capabilities = &manifest.capabilities;
+    let undeclared = |reason| match manifest.posture {
+        Posture::DenyAll => Decision::Deny(reason),
+        Posture::Audit => Decision::Audit,
+    };
+    match call {
+        SimCall::Read(path) | SimCall::List(path) if path.is_empty() => {
+            Decision::Deny("invalid_path")
+        }
+        SimCall::Read(path) => {
+            let Some(fs) = &capabilities.fs else {
+                return undeclared("no_fs_capability");
+            };
+            match fs.read.as_deref() {
+                None | Some([]) => Decision::Deny("no_read_patterns"),
+                Some(patterns) if !any_matches(patterns, path) => Decision::Deny("glob_mismatch"),
+                Some(_) if !fs.allows_extension(path) => Decision::Deny("constraint_violation"),
+                Some(_) => Decision::Allow,
+            }
+        }
+        SimCall::List(path) => match &capabilities.fs {
+            None => undeclared("no_fs_capability"),
+            Some(fs) if any_matches(fs.list.as_deref().unwrap_or_default(), path) => {
+                Decision::Allow
+            }
+            Some(_) => Decision::Deny("glob_mismatch"),
+        },
+        SimCall::Time if capabilities.time.is_some() => Decision::Allow,
+        SimCall::Time => undeclared("no_time_capability"),
+        SimCall::Blob(name) => match &capabilities.blob {
+            None => undeclared("no_blob_capability"),
+            Some(blob) if blob.allows(name) => Decision::Allow,
+            Some(_) => Decision::Deny("blob_denied"),
+        },
+        SimCall::Config(key) => match &capabilities.config {
+            None => undeclared("no_config_capability"),
+            Some(config) if config.allows(key) => Decision::Allow,
+            Some(_) => Decision::Deny("config_denied"),
+        },
+        SimCall::Custom(kind) if capabilities.custom.iter().any(|cap| &cap.kind == kind) => {
+            Decision::Allow
+        }
+        SimCall::Custom(_) => undeclared("no_custom_capability"),
+    }
+}
+
 /// A think wrapper around `CapabilityManifest::load()`
 ///
 /// # Errors
@@ -109,3 +1011,272 @@ impl CapabilityManifest {
 pub fn load_manifest<P: AsRef<Path>>(path: P) -> Result<CapabilityManifest, ManifestError> {
     CapabilityManifest::load(path)
 }
+
+/// Check a manifest file like [`CapabilityManifest::load_with`], but report
+/// every problem instead of failing on the first, so authors can fix them in
+/// one pass.
+///
+/// Unreadable JSON, capability files and type errors still end the check, as nothing after them can be inspected.
+#[must_use]
+pub fn validate_report<P: AsRef<Path>>(path: P, options: LoadOptions) -> ValidationReport {
+    let path = path.as_ref();
+    let mut report = ValidationReport {
+        file: path.display().to_string(),
+        problems: Vec::new(),
+    };
+    let parsed = read_to_string(path)
+        .map_err(ManifestError::from)
+        .and_then(|json| Ok(serde_json::from_str::<Value>(&json)?));
+    let mut value = match parsed {
+        Ok(value) => value,
+        Err(err) => {
+            report.push(&err);
+            return report;
+        }
+    };
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    if let Err(err) = merge_capability_files(&mut value, base, options) {
+        report.push(&err);
+        return report;
+    }
+    if options.deny_unknown_fields {
+        for field in unknown_fields(&value) {
+            report.push(&ManifestError::UnknownField(field));
+        }
+    }
+    match serde_json::from_value::<CapabilityManifest>(value) {
+        Ok(manifest) => {
+            for err in manifest.validate_all() {
+                report.push(&err);
+            }
+        }
+        Err(err) => report.push(&err.into()),
+    }
+    report
+}
+
+/// Merge the `capability_files` of `manifest` (resolved against `base`)
+/// into its `capabilities`, removing the list.
+fn merge_capability_files(
+    manifest: &mut Value,
+    base: &Path,
+    options: LoadOptions,
+) -> Result<(), ManifestError> {
+    let Some(files) = manifest
+        .as_object_mut()
+        .and_then(|object| object.remove(CAPABILITY_FILES_KEY))
+    else {
+        return Ok(());
+    };
+    let Value::Array(files) = files else {
+        return Err(ManifestError::CapabilityFile {
+            file: files.to_string(),
+            reason: "capability_files must be an array of paths".into(),
+        });
+    };
+    let mut merged = match manifest.get_mut("capabilities").map(Value::take) {
+        Some(Value::Object(capabilities)) => capabilities,
+        _ => serde_json::Map::new(),
+    };
+    for file in files {
+        let file = file
+            .as_str()
+            .map_or_else(|| file.to_string(), ToOwned::to_owned);
+        let failed = |reason: String| ManifestError::CapabilityFile {
+            file: file.clone(),
+            reason,
+        };
+        let json = read_to_string(base.join(&file)).map_err(|err| failed(err.to_string()))?;
+        let fragment =
+            serde_json::from_str::<Value>(&json).map_err(|err| failed(err.to_string()))?;
+        if options.deny_unknown_fields {
+            check_capabilities(&fragment, &file)?;
+        }
+        let Value::Object(fragment) = fragment else {
+            return Err(failed("expected a capabilities object".into()));
+        };
+        for (class, grant) in fragment {
+            match (merged.get_mut(&class), grant) {
+                (Some(Value::Array(existing)), Value::Array(more)) if class == "custom" => {
+                    existing.extend(more);
+                }
+                (Some(existing), _) if !existing.is_null() => {
+                    return Err(failed(format!("`{class}` is already declared")));
+                }
+                (_, grant) => {
+                    merged.insert(class, grant);
+                }
+            }
+        }
+    }
+    manifest["capabilities"] = Value::Object(merged);
+    Ok(())
+}
+
+/// Reject keys the manifest structs do not declare, at any nesting level
+/// except free-form custom capability `params`.
+fn check_known_fields(manifest: &Value) -> Result<(), ManifestError> {
+    first_unknown(unknown_fields(manifest))
+}
+
+/// Problems in one capability set, see [`CapabilityManifest::validate_all`].
+fn capability_problems(capabilities: &Capabilities, problems: &mut Vec<ManifestError>) {
+    if let Some(fs_cap) = &capabilities.fs {
+        let patterns = [&fs_cap.read, &fs_cap.list].into_iter().flatten();
+        for (idx, pattern) in patterns.flat_map(|p| p.iter().enumerate()) {
+            match Matcher::parse(pattern) {
+                Ok(_) => {}
+                Err(MatcherError::Glob(err)) => problems.push(ManifestError::InvalidGlob {
+                    idx,
+                    pattern: pattern.clone(),
+                    err: err.to_string(),
+                }),
+                Err(err) => problems.push(ManifestError::InvalidMatcher {
+                    kind: MatcherKind::Regex.as_str(),
+                    idx,
+                    pattern: pattern.clone(),
+                    err: err.to_string(),
+                }),
+            }
+        }
+        for (idx, filter) in fs_cap.content_filters.iter().enumerate() {
+            let invalid = |err: String| ManifestError::InvalidContentFilter { idx, err };
+            if let Err(err) = Matcher::parse(&MatcherKind::Regex.spec(&filter.regex)) {
+                problems.push(invalid(err.to_string()));
+            }
+            for pattern in &filter.paths {
+                if let Err(err) = Pattern::new(pattern) {
+                    problems.push(invalid(format!("{pattern}: {err}")));
+                }
+            }
+        }
+    }
+    if capabilities
+        .time
+        .is_some_and(|time| time.granularity_ms == 0)
+    {
+        problems.push(ManifestError::InvalidTimeGranularity);
+    }
+    problems.extend(
+        capabilities
+            .custom
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.kind.is_empty())
+            .map(|(idx, _)| ManifestError::InvalidCustomKind(idx)),
+    );
+}
+
+/// Every undeclared key of `manifest`, see [`check_known_fields`].
+fn unknown_fields(manifest: &Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    collect_unknown::<CapabilityManifest>(manifest, "", &mut unknown);
+    collect_unknown_capabilities(&manifest["capabilities"], "capabilities", &mut unknown);
+    if let Some(profiles) = manifest["profiles"].as_object() {
+        for (name, capabilities) in profiles {
+            collect_unknown_capabilities(capabilities, &format!("profiles.{name}"), &mut unknown);
+        }
+    }
+    collect_unknown::<RunAs>(&manifest["run_as"], "run_as", &mut unknown);
+    collect_unknown::<Requirements>(&manifest["requires"], "requires", &mut unknown);
+    collect_unknown::<TracePolicy>(&manifest["trace_policy"], "trace_policy", &mut unknown);
+    for (idx, rule) in array_items(&manifest["trace_policy"]["rules"]) {
+        let path = format!("trace_policy.rules[{idx}]");
+        collect_unknown::<TraceRule>(rule, &path, &mut unknown);
+    }
+    for (idx, cert) in array_items(&manifest["delegation"]) {
+        collect_unknown::<IssuerCert>(cert, &format!("delegation[{idx}]"), &mut unknown);
+    }
+    unknown
+}
+
+fn check_capabilities(capabilities: &Value, path: &str) -> Result<(), ManifestError> {
+    let mut unknown = Vec::new();
+    collect_unknown_capabilities(capabilities, path, &mut unknown);
+    first_unknown(unknown)
+}
+
+fn first_unknown(unknown: Vec<String>) -> Result<(), ManifestError> {
+    unknown
+        .into_iter()
+        .next()
+        .map_or(Ok(()), |field| Err(ManifestError::UnknownField(field)))
+}
+
+fn collect_unknown_capabilities(capabilities: &Value, path: &str, unknown: &mut Vec<String>) {
+    collect_unknown::<Capabilities>(capabilities, path, unknown);
+    collect_unknown::<FsCapability>(&capabilities["fs"], &format!("{path}.fs"), unknown);
+    for field in ["read", "write", "list"] {
+        for (idx, entry) in array_items(&capabilities["fs"][field]) {
+            let entry_path = format!("{path}.fs.{field}[{idx}]");
+            collect_unknown::<TypedMatcher>(entry, &entry_path, unknown);
+        }
+    }
+    for (idx, filter) in array_items(&capabilities["fs"]["content_filters"]) {
+        let filter_path = format!("{path}.fs.content_filters[{idx}]");
+        collect_unknown::<ContentFilter>(filter, &filter_path, unknown);
+    }
+    collect_unknown::<TimeCapability>(&capabilities["time"], &format!("{path}.time"), unknown);
+    collect_unknown::<BlobCapability>(&capabilities["blob"], &format!("{path}.blob"), unknown);
+    collect_unknown::<ConfigCapability>(
+        &capabilities["config"],
+        &format!("{path}.config"),
+        unknown,
+    );
+    for (idx, custom) in array_items(&capabilities["custom"]) {
+        let custom_path = format!("{path}.custom[{idx}]");
+        collect_unknown::<CustomCapability>(custom, &custom_path, unknown);
+    }
+}
+
+fn collect_unknown<'de, T: Deserialize<'de>>(value: &Value, path: &str, unknown: &mut Vec<String>) {
+    let Some(object) = value.as_object() else {
+        return;
+    };
+    let fields = struct_fields::<T>();
+    for key in object.keys().filter(|key| !fields.contains(&key.as_str())) {
+        if path.is_empty() {
+            unknown.push(key.clone());
+        } else {
+            unknown.push(format!("{path}.{key}"));
+        }
+    }
+}
+
+fn array_items(value: &Value) -> impl Iterator<Item = (usize, &Value)> {
+    value.as_array().into_iter().flatten().enumerate()
+}
+
+/// Field names serde expects for struct `T`, captured from the
+/// `deserialize_struct` call of its derived `Deserialize` impl.
+fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct Probe<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for Probe<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("field probe"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("field probe"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Probe(&mut fields));
+    fields
+}
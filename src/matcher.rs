@@ -0,0 +1,246 @@
+use glob::{Pattern, PatternError};
+#[cfg(feature = "regex-matcher")]
+use regex_automata::meta::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+use thiserror::Error;
+
+/// Errors from parsing a [`Matcher`] spec.
+#[derive(Debug, Error)]
+pub enum MatcherError {
+    #[error("{0}")]
+    Glob(#[from] PatternError),
+
+    #[error("{0}")]
+    Regex(String),
+
+    #[error("regex matchers need the `regex-matcher` feature")]
+    RegexDisabled,
+}
+
+impl Clone for MatcherError {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Glob(err) => Self::Glob(PatternError {
+                pos: err.pos,
+                msg: err.msg,
+            }),
+            Self::Regex(err) => Self::Regex(err.clone()),
+            Self::RegexDisabled => Self::RegexDisabled,
+        }
+    }
+}
+
+/// Matcher type of an FS grant entry, the `type` of a
+/// `{"type": ..., "pattern": ...}` manifest entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatcherKind {
+    Glob,
+    Prefix,
+    Regex,
+}
+
+impl MatcherKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Glob => "glob",
+            Self::Prefix => "prefix",
+            Self::Regex => "regex",
+        }
+    }
+
+    /// Spec string storing `pattern` in the manifest: `type:pattern`, or the
+    /// bare pattern for globs.
+    #[must_use]
+    pub fn spec(self, pattern: &str) -> String {
+        match self {
+            Self::Glob => pattern.to_owned(),
+            _ => format!("{}:{pattern}", self.as_str()),
+        }
+    }
+}
+
+/// How an FS grant entry (`fs.read`, `fs.list`) matches paths.
+///
+/// Entries are stored as spec strings: a bare glob, or the pattern prefixed
+/// with its type (`prefix:./workspace/`, `regex:^\./workspace/.*\.toml$`).
+/// Per check:
+///
+/// - `glob` compiles the pattern and matches in time linear in the path,
+///   though `**` may backtrack.
+/// - `prefix` is a single string comparison, the cheapest. Paths climbing out
+///   of the prefix with a `..` component never match.
+/// - `regex` matches in linear time, but compiling costs far more than a
+///   glob; it needs the `regex-matcher` feature.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Glob(Pattern),
+    Prefix(String),
+    #[cfg(feature = "regex-matcher")]
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Parse a spec string, see [`Matcher`]. `glob:` may be spelled out.
+    ///
+    /// # Errors
+    ///
+    /// [`MatcherError`] if the pattern does not compile or regex support is
+    /// not enabled.
+    pub fn parse(spec: &str) -> Result<Self, MatcherError> {
+        let (kind, pattern) = split_spec(spec);
+        match kind {
+            MatcherKind::Glob => Ok(Self::Glob(Pattern::new(pattern)?)),
+            MatcherKind::Prefix => Ok(Self::Prefix(pattern.to_owned())),
+            #[cfg(feature = "regex-matcher")]
+            MatcherKind::Regex => Regex::new(pattern)
+                .map(Self::Regex)
+                .map_err(|err| MatcherError::Regex(err.to_string())),
+            #[cfg(not(feature = "regex-matcher"))]
+            MatcherKind::Regex => Err(MatcherError::RegexDisabled),
+        }
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> MatcherKind {
+        match self {
+            Self::Glob(_) => MatcherKind::Glob,
+            Self::Prefix(_) => MatcherKind::Prefix,
+            #[cfg(feature = "regex-matcher")]
+            Self::Regex(_) => MatcherKind::Regex,
+        }
+    }
+
+    /// Whether `path` matches
+    #[must_use]
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => pattern.matches(path),
+            Self::Prefix(prefix) => path.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                !Path::new(rest)
+                    .components()
+                    .any(|component| component == Component::ParentDir)
+            }),
+            #[cfg(feature = "regex-matcher")]
+            Self::Regex(regex) => regex.is_match(path),
+        }
+    }
+}
+
+/// FS grant specs compiled once, when a host loads or reloads its manifest.
+/// Specs that do not compile keep their error, in spec order.
+#[derive(Debug, Default)]
+pub struct CompiledSpecs(Vec<Result<Matcher, MatcherError>>);
+
+impl CompiledSpecs {
+    #[must_use]
+    pub fn new(specs: &[String]) -> Self {
+        Self(specs.iter().map(|spec| Matcher::parse(spec)).collect())
+    }
+
+    /// Compiled specs or their errors, in spec order
+    pub fn iter(&self) -> impl Iterator<Item = &Result<Matcher, MatcherError>> {
+        self.0.iter()
+    }
+
+    /// Index of the first spec matching `path` (invalid specs never match)
+    #[must_use]
+    pub fn position(&self, path: &str) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|matcher| matcher.as_ref().is_ok_and(|m| m.matches(path)))
+    }
+}
+
+/// Whether any spec in `specs` matches `path` (invalid specs never match).
+pub fn any_matches(specs: &[String], path: &str) -> bool {
+    specs
+        .iter()
+        .any(|spec| Matcher::parse(spec).is_ok_and(|m| m.matches(path)))
+}
+
+/// A glob matching at least every path `spec` does.
+///
+/// Its literal prefix bounds where those paths live (see
+/// [`crate::grant_root`]). Regexes not anchored with `^`, or with top-level
+/// alternation, only bound to `*`.
+#[must_use]
+pub fn bounding_glob(spec: &str) -> String {
+    let (kind, pattern) = split_spec(spec);
+    match kind {
+        MatcherKind::Glob => pattern.to_owned(),
+        MatcherKind::Prefix => format!("{}*", Pattern::escape(pattern)),
+        MatcherKind::Regex => format!("{}*", Pattern::escape(&regex_literal_prefix(pattern))),
+    }
+}
+
+fn split_spec(spec: &str) -> (MatcherKind, &str) {
+    [MatcherKind::Glob, MatcherKind::Prefix, MatcherKind::Regex]
+        .into_iter()
+        .find_map(|kind| {
+            spec.strip_prefix(kind.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+                .map(|pattern| (kind, pattern))
+        })
+        .unwrap_or((MatcherKind::Glob, spec))
+}
+
+/// Literal text every match of the anchored `regex` starts with.
+fn regex_literal_prefix(regex: &str) -> String {
+    let Some(rest) = regex.strip_prefix('^') else {
+        return String::new();
+    };
+    if has_top_level_alternation(rest) {
+        return String::new();
+    }
+    let mut prefix = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped.is_ascii_punctuation() => escaped,
+                _ => break,
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | '^' | '$' => break,
+            c => c,
+        };
+        // A quantified character may be absent from the match.
+        if chars
+            .peek()
+            .is_some_and(|next| matches!(next, '?' | '*' | '{'))
+        {
+            break;
+        }
+        prefix.push(literal);
+    }
+    prefix
+}
+
+fn has_top_level_alternation(regex: &str) -> bool {
+    let mut depth = 0_usize;
+    let mut chars = regex.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => {
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' if !escaped => break,
+                        '\\' => escaped = !escaped,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '|' if depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
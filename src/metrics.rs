@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Time spent evaluating one capability decision.
+///
+/// Kept beside the trace rather than in it, so signed traces stay
+/// reproducible across machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecisionTiming {
+    /// Seq of the last event recorded by the decision
+    pub seq: u64,
+    /// Monotonic evaluation time, including any policy hook
+    pub duration: Duration,
+}
+
+/// Aggregate decision evaluation stats, e.g. to spot pathological glob sets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecisionStats {
+    pub decisions: usize,
+    pub total: Duration,
+    pub max: Duration,
+    /// Seq of the slowest decision
+    pub slowest_seq: Option<u64>,
+}
+
+impl DecisionStats {
+    #[must_use]
+    pub fn from_timings(timings: &[DecisionTiming]) -> Self {
+        timings.iter().fold(Self::default(), |mut stats, timing| {
+            stats.decisions += 1;
+            stats.total += timing.duration;
+            if stats.slowest_seq.is_none() || timing.duration > stats.max {
+                stats.max = timing.duration;
+                stats.slowest_seq = Some(timing.seq);
+            }
+            stats
+        })
+    }
+
+    /// Mean evaluation time (zero without decisions)
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        u32::try_from(self.decisions)
+            .ok()
+            .and_then(|n| self.total.checked_div(n))
+            .unwrap_or_default()
+    }
+}
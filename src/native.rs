@@ -0,0 +1,319 @@
+use crate::{host::HostState, manifest::CapabilityManifest};
+use std::{collections::BTreeSet, io};
+use thiserror::Error;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// Child exit status reserved for "sandbox could not be installed".
+pub const SETUP_FAILED_EXIT: u8 = 125;
+
+/// Syscalls every native plugin needs to run and exit.
+const BASE_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_close,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    libc::SYS_brk,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mprotect,
+    libc::SYS_mremap,
+    libc::SYS_madvise,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_sigaltstack,
+    libc::SYS_futex,
+    libc::SYS_getrandom,
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_fstat,
+    libc::SYS_lseek,
+    libc::SYS_getpid,
+    libc::SYS_gettid,
+    libc::SYS_sched_yield,
+    libc::SYS_set_robust_list,
+    libc::SYS_rseq,
+    libc::SYS_ppoll,
+    libc::SYS_fcntl,
+];
+
+/// Index of the `tgkill` pid comparison in [`SeccompProfile::program`], patched
+/// with the child's pid after forking.
+const TGKILL_PID_INSN: usize = 6;
+
+/// Added when the manifest grants `fs.read`.
+const FS_READ_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_openat,
+    libc::SYS_newfstatat,
+    libc::SYS_statx,
+    libc::SYS_getdents64,
+    libc::SYS_pread64,
+    libc::SYS_readv,
+    libc::SYS_faccessat,
+];
+
+/// Added when the manifest grants `fs.write`.
+const FS_WRITE_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_openat,
+    libc::SYS_pwrite64,
+    libc::SYS_writev,
+    libc::SYS_mkdirat,
+    libc::SYS_unlinkat,
+    libc::SYS_renameat2,
+    libc::SYS_ftruncate,
+    libc::SYS_fsync,
+    libc::SYS_fdatasync,
+];
+
+/// Errors from running a native plugin.
+#[derive(Debug, Error)]
+pub enum NativeError {
+    #[error("Invalid syscall number {0}")]
+    InvalidSyscall(libc::c_long),
+
+    #[error("fork failed: {0}")]
+    Fork(#[source] io::Error),
+
+    #[error("waitpid failed: {0}")]
+    Wait(#[source] io::Error),
+
+    #[error("Child {pid} could not install the seccomp filter")]
+    Setup { pid: i32 },
+
+    #[error("Child {pid} was killed for a syscall outside its seccomp allowlist")]
+    SeccompViolation { pid: i32 },
+
+    #[error("Child {pid} exceeded its CPU time limit of {limit_secs}s")]
+    CpuQuotaExceeded { pid: i32, limit_secs: u64 },
+
+    #[error("Child {pid} aborted under its memory limit of {limit_bytes} bytes")]
+    MemoryQuotaExceeded { pid: i32, limit_bytes: u64 },
+
+    #[error("Child {pid} was killed by signal {signal}")]
+    Signaled { pid: i32, signal: i32 },
+}
+
+/// CPU time and memory limits installed in the native plugin child with
+/// `setrlimit` before the seccomp filter.
+///
+/// Native mode only runs on Linux, so there is no Windows Job Object
+/// counterpart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// CPU seconds (`RLIMIT_CPU`); the child gets `SIGXCPU` once used up,
+    /// and `SIGKILL` a second later.
+    pub cpu_secs: Option<u64>,
+    /// Address space in bytes (`RLIMIT_AS`); larger allocations fail.
+    pub memory_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Install the limits in the calling process. Async-signal-safe.
+    fn apply(self) -> bool {
+        let set = |resource, soft: u64, hard: u64| {
+            let limit = libc::rlimit {
+                rlim_cur: soft,
+                rlim_max: hard,
+            };
+            // SAFETY: `limit` is a valid rlimit for the duration of the call.
+            unsafe { libc::setrlimit(resource, &raw const limit) == 0 }
+        };
+        self.cpu_secs
+            .is_none_or(|secs| set(libc::RLIMIT_CPU, secs, secs.saturating_add(1)))
+            && self
+                .memory_bytes
+                .is_none_or(|bytes| set(libc::RLIMIT_AS, bytes, bytes))
+    }
+}
+
+/// A seccomp-bpf syscall allowlist derived from manifest capabilities.
+///
+/// Any syscall outside the list kills the child process with `SIGSYS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeccompProfile {
+    syscalls: BTreeSet<libc::c_long>,
+    limits: ResourceLimits,
+}
+
+impl SeccompProfile {
+    /// Base syscalls plus the FS syscalls the manifest's grants require.
+    #[must_use]
+    pub fn from_manifest(manifest: &CapabilityManifest) -> Self {
+        let mut syscalls = BASE_SYSCALLS.iter().copied().collect::<BTreeSet<_>>();
+        let fs = manifest.capabilities.fs.as_ref();
+        let granted = |patterns: Option<&Vec<String>>| patterns.is_some_and(|p| !p.is_empty());
+        if granted(fs.and_then(|fs| fs.read.as_ref())) {
+            syscalls.extend(FS_READ_SYSCALLS);
+        }
+        if granted(fs.and_then(|fs| fs.write.as_ref())) {
+            syscalls.extend(FS_WRITE_SYSCALLS);
+        }
+        Self {
+            syscalls,
+            limits: ResourceLimits::default(),
+        }
+    }
+
+    /// Run children under `limits`.
+    #[must_use]
+    pub const fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Also allow syscall `nr` (e.g. [`libc::SYS_socket`]).
+    #[must_use]
+    pub fn allow(mut self, nr: libc::c_long) -> Self {
+        self.syscalls.insert(nr);
+        self
+    }
+
+    /// Whether syscall `nr` is allowed
+    #[must_use]
+    pub fn allows(&self, nr: libc::c_long) -> bool {
+        self.syscalls.contains(&nr)
+    }
+
+    /// Fork, install the filter in the child and run `plugin` there; its
+    /// return value becomes the child's exit status.
+    ///
+    /// A child killed by the filter is recorded on `host` as a
+    /// `native.seccomp_violation` event, one stopped by its
+    /// [`ResourceLimits`] as `native.cpu_quota_exceeded` or
+    /// `native.memory_quota_exceeded`. A memory violation is inferred from
+    /// the child aborting (`SIGABRT`, as on a failed Rust allocation) while a
+    /// memory limit is set.
+    ///
+    /// # Errors
+    ///
+    /// [`NativeError`] if forking or waiting fails, or the child does not exit normally.
+    pub fn run<F: FnOnce() -> u8>(
+        &self,
+        host: &mut HostState,
+        plugin: F,
+    ) -> Result<u8, NativeError> {
+        // Built before forking: the child of a multithreaded parent must not allocate.
+        let mut filter = self.program()?;
+        let prog = libc::sock_fprog {
+            len: u16::try_from(filter.len()).unwrap_or(u16::MAX),
+            filter: filter.as_mut_ptr(),
+        };
+
+        // SAFETY: the child only calls async-signal-safe functions before `plugin`
+        // and leaves through `_exit`, never returning into the parent's stack.
+        let pid = unsafe { libc::fork() };
+        if pid < 0 {
+            return Err(NativeError::Fork(io::Error::last_os_error()));
+        }
+        if pid == 0 {
+            // SAFETY: see above; `prog` points at `filter`, alive until `_exit`.
+            unsafe {
+                if self.limits.memory_bytes.is_some() {
+                    filter[TGKILL_PID_INSN].k = libc::getpid().cast_unsigned();
+                }
+                if !self.limits.apply()
+                    || libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+                    || libc::syscall(
+                        libc::SYS_seccomp,
+                        libc::SECCOMP_SET_MODE_FILTER,
+                        0,
+                        &raw const prog,
+                    ) != 0
+                {
+                    libc::_exit(i32::from(SETUP_FAILED_EXIT));
+                }
+                libc::_exit(i32::from(plugin()));
+            }
+        }
+
+        let mut status = 0;
+        // SAFETY: all-zero is a valid `rusage`.
+        let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+        // SAFETY: `pid` is our child and both out pointers are valid.
+        if unsafe { libc::wait4(pid, &raw mut status, 0, &raw mut usage) } < 0 {
+            return Err(NativeError::Wait(io::Error::last_os_error()));
+        }
+
+        if libc::WIFEXITED(status) {
+            let code = u8::try_from(libc::WEXITSTATUS(status)).unwrap_or(u8::MAX);
+            if code == SETUP_FAILED_EXIT {
+                return Err(NativeError::Setup { pid });
+            }
+            return Ok(code);
+        }
+        let signal = libc::WTERMSIG(status);
+        if signal == libc::SIGSYS {
+            host.record_seccomp_violation(pid);
+            return Err(NativeError::SeccompViolation { pid });
+        }
+        let cpu_used = u64::try_from(usage.ru_utime.tv_sec + usage.ru_stime.tv_sec).unwrap_or(0);
+        if let Some(limit_secs) = self.limits.cpu_secs.filter(|limit| {
+            signal == libc::SIGXCPU || (signal == libc::SIGKILL && cpu_used >= *limit)
+        }) {
+            host.record_cpu_quota_exceeded(pid, limit_secs);
+            return Err(NativeError::CpuQuotaExceeded { pid, limit_secs });
+        }
+        if let Some(limit_bytes) = self.limits.memory_bytes.filter(|_| signal == libc::SIGABRT) {
+            host.record_memory_quota_exceeded(pid, limit_bytes);
+            return Err(NativeError::MemoryQuotaExceeded { pid, limit_bytes });
+        }
+        Err(NativeError::Signaled { pid, signal })
+    }
+
+    /// Classic BPF: kill on foreign arch, allow listed syscalls, kill otherwise.
+    /// Under a memory limit `tgkill` on the child itself is allowed too, so a
+    /// failed allocation can `abort`; the pid at [`TGKILL_PID_INSN`] is set
+    /// after forking.
+    fn program(&self) -> Result<Vec<libc::sock_filter>, NativeError> {
+        // BPF_LD | BPF_W | BPF_ABS, BPF_JMP | BPF_JEQ | BPF_K, BPF_RET | BPF_K
+        const LD_ABS: u16 = 0x20;
+        const JEQ: u16 = 0x15;
+        const RET: u16 = 0x06;
+        const NR_OFFSET: u32 = 0;
+        const ARCH_OFFSET: u32 = 4;
+        // Low word of `args[0]` on little-endian targets
+        const ARG0_OFFSET: u32 = 16;
+        let stmt = |code, k| libc::sock_filter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        let jump = |k, jt, jf| libc::sock_filter {
+            code: JEQ,
+            jt,
+            jf,
+            k,
+        };
+
+        let mut program = vec![
+            stmt(LD_ABS, ARCH_OFFSET),
+            jump(AUDIT_ARCH, 1, 0),
+            stmt(RET, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(LD_ABS, NR_OFFSET),
+        ];
+        if self.limits.memory_bytes.is_some() {
+            let tgkill = u32::try_from(libc::SYS_tgkill)
+                .map_err(|_| NativeError::InvalidSyscall(libc::SYS_tgkill))?;
+            program.extend([
+                jump(tgkill, 0, 4),
+                stmt(LD_ABS, ARG0_OFFSET),
+                jump(0, 0, 1),
+                stmt(RET, libc::SECCOMP_RET_ALLOW),
+                stmt(RET, libc::SECCOMP_RET_KILL_PROCESS),
+            ]);
+        }
+        for &nr in &self.syscalls {
+            let k = u32::try_from(nr).map_err(|_| NativeError::InvalidSyscall(nr))?;
+            program.push(jump(k, 0, 1));
+            program.push(stmt(RET, libc::SECCOMP_RET_ALLOW));
+        }
+        program.push(stmt(RET, libc::SECCOMP_RET_KILL_PROCESS));
+        Ok(program)
+    }
+}
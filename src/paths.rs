@@ -0,0 +1,81 @@
+use crate::config::CONFIG_FILE;
+use directories_next::ProjectDirs;
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
+
+/// Platform default locations for keys, manifests and traces.
+///
+/// On Linux these follow XDG: `~/.config/captra/{keys,manifests}` and
+/// `~/.local/share/captra/traces`.
+#[derive(Debug, Clone)]
+pub struct CaptraDirs {
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+}
+
+impl CaptraDirs {
+    /// Resolve the defaults for the current user (`None` if no home directory is known).
+    #[must_use]
+    pub fn new() -> Option<Self> {
+        ProjectDirs::from("", "", "captra").map(|dirs| Self {
+            config_dir: dirs.config_dir().to_path_buf(),
+            data_dir: dirs.data_dir().to_path_buf(),
+        })
+    }
+
+    /// Get `config_dir`
+    #[inline]
+    #[must_use]
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// Get `data_dir`
+    #[inline]
+    #[must_use]
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Host configuration file, see [`crate::HostConfig::load_default`]
+    #[inline]
+    #[must_use]
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join(CONFIG_FILE)
+    }
+
+    /// Directory holding signing keys
+    #[inline]
+    #[must_use]
+    pub fn keys_dir(&self) -> PathBuf {
+        self.config_dir.join("keys")
+    }
+
+    /// Directory holding capability manifests
+    #[inline]
+    #[must_use]
+    pub fn manifests_dir(&self) -> PathBuf {
+        self.config_dir.join("manifests")
+    }
+
+    /// Directory holding saved traces
+    #[inline]
+    #[must_use]
+    pub fn traces_dir(&self) -> PathBuf {
+        self.data_dir.join("traces")
+    }
+
+    /// Create all default directories if missing.
+    ///
+    /// # Errors
+    ///
+    /// If any directory cannot be created.
+    pub fn create_all(&self) -> std::io::Result<()> {
+        for dir in [self.keys_dir(), self.manifests_dir(), self.traces_dir()] {
+            create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,663 @@
+use crate::{
+    host::HostState,
+    trace::sha256_hex,
+    wasm::{
+        DEFAULT_MEMORY_EXPORT, GUEST_ALLOCATORS, HostNamespace, NONDETERMINISTIC_IMPORTS,
+        WasmError, add_wasm_linker_funcs_for, guest_memory_export, memory_export_is_64,
+    },
+    watchdog::{Watchdog, WatchdogConfig},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+use wasmtime::{
+    Config, Engine, Instance, Linker, Memory, Module, Precompiled, Store, Trap, UpdateDeadline,
+    WasmParams, WasmResults,
+};
+
+/// Fuel granted to a guest when none is configured (effectively unmetered,
+/// but still counted so consumption can be traced).
+pub const DEFAULT_FUEL: u64 = u64::MAX;
+
+/// Engine settings of a [`ModuleCache`]. The defaults favour determinism:
+/// NaN canonicalization and deterministic relaxed-SIMD are on, while
+/// threads and SIMD stay available unless disabled.
+#[allow(clippy::struct_excessive_bools)] // independent engine switches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WasmConfig {
+    pub nan_canonicalization: bool,
+    pub deterministic_relaxed_simd: bool,
+    pub threads: bool,
+    /// SIMD, including relaxed SIMD
+    pub simd: bool,
+    /// See [`ModuleCache::with_epoch_interruption`]
+    pub epoch_interruption: bool,
+    /// Reproducible execution: forces canonical NaNs, deterministic
+    /// relaxed-SIMD and no threads, and refuses modules importing
+    /// [`NONDETERMINISTIC_IMPORTS`], so a whole run can be replayed.
+    pub deterministic_guest: bool,
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        Self {
+            nan_canonicalization: true,
+            deterministic_relaxed_simd: true,
+            threads: true,
+            simd: true,
+            epoch_interruption: false,
+            deterministic_guest: false,
+        }
+    }
+}
+
+impl WasmConfig {
+    #[inline]
+    #[must_use]
+    pub const fn with_threads(mut self, enabled: bool) -> Self {
+        self.threads = enabled;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_simd(mut self, enabled: bool) -> Self {
+        self.simd = enabled;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_epoch_interruption(mut self) -> Self {
+        self.epoch_interruption = true;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_deterministic_guest(mut self) -> Self {
+        self.deterministic_guest = true;
+        self
+    }
+
+    /// The wasmtime configuration; fuel metering and memory64 are always on.
+    fn engine_config(self) -> Config {
+        let deterministic = self.deterministic_guest;
+        let mut config = Config::new();
+        config
+            .consume_fuel(true)
+            .wasm_memory64(true)
+            .epoch_interruption(self.epoch_interruption)
+            .cranelift_nan_canonicalization(self.nan_canonicalization || deterministic)
+            .relaxed_simd_deterministic(self.deterministic_relaxed_simd || deterministic)
+            .wasm_threads(self.threads && !deterministic)
+            .wasm_simd(self.simd)
+            .wasm_relaxed_simd(self.simd);
+        config
+    }
+}
+
+/// Compiled modules keyed by the SHA256 of their bytes, sharing one [`Engine`],
+/// so instantiating the same plugin repeatedly skips recompilation.
+#[derive(Debug, Clone)]
+pub struct ModuleCache {
+    engine: Engine,
+    config: WasmConfig,
+    modules: Arc<Mutex<HashMap<String, Module>>>,
+}
+
+impl ModuleCache {
+    /// Create an empty cache with captra's engine configuration (fuel metering
+    /// and memory64 on, [`WasmConfig::default`] otherwise).
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Engine`] if the engine cannot be created.
+    pub fn new() -> Result<Self, WasmError> {
+        Self::with_wasm_config(WasmConfig::default())
+    }
+
+    /// Like [`ModuleCache::new`], with epoch interruption compiled in so a
+    /// [`WatchdogConfig::with_interrupt`] watchdog can stop stalled guests.
+    /// Stores on this engine need an epoch deadline; [`PluginInstance`] sets one.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Engine`] if the engine cannot be created.
+    pub fn with_epoch_interruption() -> Result<Self, WasmError> {
+        Self::with_wasm_config(WasmConfig::default().with_epoch_interruption())
+    }
+
+    /// Create an empty cache whose engine uses `config`.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Engine`] if wasmtime rejects the configuration.
+    pub fn with_wasm_config(config: WasmConfig) -> Result<Self, WasmError> {
+        Ok(Self {
+            engine: Engine::new(&config.engine_config()).map_err(WasmError::Engine)?,
+            config,
+            modules: Arc::default(),
+        })
+    }
+
+    /// Process-wide cache used by [`PluginInstance::new`].
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Engine`] if the engine cannot be created on first use.
+    pub fn global() -> Result<&'static Self, WasmError> {
+        static GLOBAL: OnceLock<ModuleCache> = OnceLock::new();
+        if let Some(cache) = GLOBAL.get() {
+            return Ok(cache);
+        }
+        let cache = Self::new()?;
+        Ok(GLOBAL.get_or_init(|| cache))
+    }
+
+    /// Process-wide cache for `config`, created on first use and kept for
+    /// the rest of the process.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Engine`] if wasmtime rejects the configuration.
+    pub fn global_for(config: WasmConfig) -> Result<&'static Self, WasmError> {
+        static CACHES: OnceLock<Mutex<HashMap<WasmConfig, &'static ModuleCache>>> = OnceLock::new();
+        if config == WasmConfig::default() {
+            return Self::global();
+        }
+        let mut caches = CACHES
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(cache) = caches.get(&config) {
+            return Ok(cache);
+        }
+        let cache: &'static Self = Box::leak(Box::new(Self::with_wasm_config(config)?));
+        caches.insert(config, cache);
+        drop(caches);
+        Ok(cache)
+    }
+
+    /// Get `engine`
+    #[inline]
+    #[must_use]
+    pub const fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Get `config`
+    #[inline]
+    #[must_use]
+    pub const fn config(&self) -> &WasmConfig {
+        &self.config
+    }
+
+    /// Look up `module_hash` or compile `wasm` and insert it.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Compile`] if compilation fails.
+    pub fn get_or_compile(&self, module_hash: &str, wasm: &[u8]) -> Result<Module, WasmError> {
+        let mut modules = self.modules.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(module) = modules.get(module_hash) {
+            return Ok(module.clone());
+        }
+        let module = Module::new(&self.engine, wasm).map_err(WasmError::Compile)?;
+        modules.insert(module_hash.to_owned(), module.clone());
+        drop(modules);
+        Ok(module)
+    }
+
+    /// Compile `wasm` ahead of time into an artifact for
+    /// [`ModuleCache::load_precompiled`] on engines with the same configuration.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Compile`] if compilation fails.
+    pub fn precompile(&self, wasm: &[u8]) -> Result<Vec<u8>, WasmError> {
+        self.engine
+            .precompile_module(wasm)
+            .map_err(WasmError::Compile)
+    }
+
+    /// Load a precompiled (`.cwasm`) module, skipping compilation. The
+    /// artifact must hash to `expected_sha256`, since deserializing it runs
+    /// native code produced elsewhere.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError`] if the file cannot be read, its hash differs, it is not
+    /// a module artifact, or it was built for another engine configuration.
+    pub fn load_precompiled<P: AsRef<Path>>(
+        &self,
+        path: P,
+        expected_sha256: &str,
+    ) -> Result<Module, WasmError> {
+        let artifact = fs::read(path)?;
+        let artifact_hash = sha256_hex(&artifact);
+        if artifact_hash != expected_sha256 {
+            return Err(WasmError::ArtifactMismatch {
+                expected: expected_sha256.into(),
+                actual: artifact_hash,
+            });
+        }
+        let mut modules = self.modules.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(module) = modules.get(&artifact_hash) {
+            return Ok(module.clone());
+        }
+        if Engine::detect_precompiled(&artifact) != Some(Precompiled::Module) {
+            return Err(WasmError::NotPrecompiled);
+        }
+        // SAFETY: the artifact matches the hash the caller trusts, and
+        // `deserialize` rejects artifacts built for an incompatible engine.
+        let module =
+            unsafe { Module::deserialize(&self.engine, &artifact) }.map_err(WasmError::Compile)?;
+        modules.insert(artifact_hash, module.clone());
+        drop(modules);
+        Ok(module)
+    }
+
+    /// Number of cached modules
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.modules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn check_deterministic_imports(module: &Module, host_module: &str) -> Result<(), WasmError> {
+    module
+        .imports()
+        .find(|import| {
+            import.module() == host_module && NONDETERMINISTIC_IMPORTS.contains(&import.name())
+        })
+        .map_or(Ok(()), |import| {
+            Err(WasmError::NondeterministicImport {
+                module: import.module().into(),
+                name: import.name().into(),
+            })
+        })
+}
+
+/// A wasm plugin instantiated against a [`HostState`].
+///
+/// Instantiation and teardown are traced as `plugin.init` / `plugin.shutdown`
+/// events, so a run records resource usage and not only capability calls.
+#[derive(Debug)]
+pub struct PluginInstance {
+    store: Store<HostState>,
+    instance: Instance,
+    memory: String,
+    memory64: bool,
+}
+
+impl PluginInstance {
+    /// Compile `wasm` (binary or WAT) through the global [`ModuleCache`],
+    /// register the host functions and instantiate it.
+    ///
+    /// # Errors
+    ///
+    /// If compilation, linking or instantiation fails.
+    pub fn new(host: HostState, wasm: impl AsRef<[u8]>) -> Result<Self, WasmError> {
+        Self::with_cache(ModuleCache::global()?, host, wasm)
+    }
+
+    /// Like [`PluginInstance::new`], on an engine configured by `config`
+    /// (see [`ModuleCache::global_for`]).
+    ///
+    /// # Errors
+    ///
+    /// If the configuration is rejected, or compilation, linking or
+    /// instantiation fails.
+    pub fn with_wasm_config(
+        config: WasmConfig,
+        host: HostState,
+        wasm: impl AsRef<[u8]>,
+    ) -> Result<Self, WasmError> {
+        Self::with_cache(ModuleCache::global_for(config)?, host, wasm)
+    }
+
+    /// Like [`PluginInstance::new`], compiling through `cache`.
+    ///
+    /// # Errors
+    ///
+    /// If compilation, linking or instantiation fails.
+    pub fn with_cache(
+        cache: &ModuleCache,
+        host: HostState,
+        wasm: impl AsRef<[u8]>,
+    ) -> Result<Self, WasmError> {
+        let wasm = wasm.as_ref();
+        let module_hash = sha256_hex(wasm);
+        let module = cache.get_or_compile(&module_hash, wasm)?;
+        Self::instantiate(cache, host, &module, |host, error| {
+            host.record_plugin_init(&module_hash, error);
+        })
+    }
+
+    /// Instantiate a precompiled artifact through the global [`ModuleCache`],
+    /// see [`ModuleCache::load_precompiled`]. `plugin.init` records the
+    /// artifact hash instead of the wasm hash.
+    ///
+    /// # Errors
+    ///
+    /// If loading, linking or instantiation fails.
+    pub fn from_precompiled<P: AsRef<Path>>(
+        host: HostState,
+        path: P,
+        expected_sha256: &str,
+    ) -> Result<Self, WasmError> {
+        let cache = ModuleCache::global()?;
+        let module = cache.load_precompiled(path, expected_sha256)?;
+        Self::instantiate(cache, host, &module, |host, error| {
+            host.record_plugin_init_precompiled(expected_sha256, error);
+        })
+    }
+
+    fn instantiate(
+        cache: &ModuleCache,
+        mut host: HostState,
+        module: &Module,
+        record_init: impl FnOnce(&mut HostState, Option<&str>),
+    ) -> Result<Self, WasmError> {
+        host.check_run_as().map_err(WasmError::Denied)?;
+        host.check_requirements().map_err(WasmError::Denied)?;
+        let namespace = HostNamespace::default();
+        if cache.config.deterministic_guest {
+            check_deterministic_imports(module, &namespace.module())?;
+        }
+        let mut linker = Linker::new(cache.engine());
+        add_wasm_linker_funcs_for(&mut linker, &namespace, module)?;
+
+        let mut store = Store::new(cache.engine(), host);
+        store.set_fuel(DEFAULT_FUEL).map_err(WasmError::Engine)?;
+        if cache.config.epoch_interruption {
+            // Epoch ticks only matter to the store whose watchdog asked for them.
+            store.set_epoch_deadline(1);
+            store.epoch_deadline_callback(|store| {
+                if store
+                    .data()
+                    .watchdog()
+                    .is_some_and(Watchdog::interrupt_requested)
+                {
+                    return Err(Trap::Interrupt.into());
+                }
+                Ok(UpdateDeadline::Continue(1))
+            });
+        }
+        let instance = match linker.instantiate(&mut store, module) {
+            Ok(instance) => instance,
+            Err(source) => {
+                let err = WasmError::Instantiate(source);
+                record_init(store.data_mut(), Some(&err.to_string()));
+                return Err(err);
+            }
+        };
+        record_init(store.data_mut(), None);
+        let memory = guest_memory_export(module, DEFAULT_MEMORY_EXPORT);
+        let memory64 = memory_export_is_64(module, &memory);
+
+        Ok(Self {
+            store,
+            instance,
+            memory,
+            memory64,
+        })
+    }
+
+    /// Call an exported `() -> i32` function.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError::Call`] if the export is missing, has another signature, or traps.
+    pub fn call(&mut self, export: &str) -> Result<i32, WasmError> {
+        let call_err = |source| WasmError::Call {
+            export: export.into(),
+            source,
+        };
+        let func = self
+            .instance
+            .get_typed_func::<(), i32>(&mut self.store, export)
+            .map_err(call_err)?;
+        self.watched(|store| func.call(store, ()).map_err(call_err))
+    }
+
+    /// Watch the guest for stalls while it runs: a `guest.stalled` event is
+    /// recorded once a call goes `interval` without a host call, and with
+    /// [`WatchdogConfig::with_interrupt`] the guest is stopped with a trap.
+    #[must_use]
+    pub fn with_watchdog(mut self, config: WatchdogConfig) -> Self {
+        let watchdog = Watchdog::spawn(config, self.store.engine().clone());
+        self.store.data_mut().set_watchdog(Some(watchdog));
+        self
+    }
+
+    /// Run `call` with the watchdog (if any) marking the guest as running,
+    /// then record the stalls it saw.
+    fn watched<R>(
+        &mut self,
+        call: impl FnOnce(&mut Store<HostState>) -> Result<R, WasmError>,
+    ) -> Result<R, WasmError> {
+        let set_running = |host: &HostState, running| {
+            if let Some(watchdog) = host.watchdog() {
+                watchdog.set_running(running);
+            }
+        };
+        set_running(self.store.data(), true);
+        let result = call(&mut self.store);
+        set_running(self.store.data(), false);
+        self.store.data_mut().record_watchdog_stalls();
+        result
+    }
+
+    /// Write `input` to the guest memory and call an exported
+    /// `(ptr: i32, len: i32) -> i32` function with its location
+    /// (`(ptr: i64, len: i64) -> i32` for memory64 guests). The input goes
+    /// into a buffer from the guest allocator (see [`GUEST_ALLOCATORS`]),
+    /// released after the call.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError`] if the memory, allocator or export is missing, the input
+    /// does not fit, or the call traps.
+    pub fn call_with_input(&mut self, export: &str, input: &[u8]) -> Result<i32, WasmError> {
+        let (_, ptr) = self.write_input(input)?;
+        let result = self.call_typed::<i32>(export, ptr, input.len())?;
+        self.guest_dealloc(ptr, input.len())?;
+        Ok(result)
+    }
+
+    /// Serialize `input` as JSON, pass it like [`PluginInstance::call_with_input`]
+    /// to an exported `(ptr: i32, len: i32) -> i64` function and deserialize
+    /// the JSON it returns. The result packs the output location as
+    /// `(ptr << 32) | len`; a negative result is a guest error. Memory64
+    /// guests take `(ptr: i64, len: i64)` and instead return the address of
+    /// a little-endian `(ptr: u64, len: u64)` pair, so outputs are not
+    /// limited to 4 GiB. Guests with an allocator get the output released
+    /// with their `dealloc` once it is copied. Records a `plugin.call` event
+    /// with the input and output hashes.
+    ///
+    /// # Errors
+    ///
+    /// [`WasmError`] if (de)serialization fails, the memory, allocator or
+    /// export is missing, the call traps, or the result is out of bounds.
+    pub fn call_json<I: Serialize, O: DeserializeOwned>(
+        &mut self,
+        export: &str,
+        input: &I,
+    ) -> Result<O, WasmError> {
+        let input = serde_json::to_vec(input)?;
+        let (memory, input_ptr) = self.write_input(&input)?;
+        let result = self.call_typed::<i64>(export, input_ptr, input.len())?;
+        self.guest_dealloc(input_ptr, input.len())?;
+        let invalid = || WasmError::InvalidResult {
+            export: export.into(),
+            result,
+        };
+        let data = memory.data(&self.store);
+        let result = u64::try_from(result).map_err(|_| invalid())?;
+        let (ptr, len) = if self.memory64 {
+            let pair = byte_range(result, 16)
+                .and_then(|range| data.get(range))
+                .ok_or_else(invalid)?;
+            let (ptr, len) = pair.split_at(8);
+            let word = |bytes: &[u8]| bytes.try_into().map(u64::from_le_bytes);
+            (word(ptr), word(len))
+        } else {
+            (Ok(result >> 32), Ok(result & u64::from(u32::MAX)))
+        };
+        let (ptr, len) = ptr.ok().zip(len.ok()).ok_or_else(invalid)?;
+        let output = byte_range(ptr, len)
+            .and_then(|range| data.get(range))
+            .ok_or_else(invalid)?
+            .to_vec();
+        self.guest_dealloc(ptr, output.len())?;
+
+        self.store
+            .data_mut()
+            .record_plugin_call(export, &sha256_hex(&input), &sha256_hex(&output));
+        Ok(serde_json::from_slice(&output)?)
+    }
+
+    /// Write `input` to a buffer from the guest allocator and return its
+    /// location.
+    fn write_input(&mut self, input: &[u8]) -> Result<(Memory, u64), WasmError> {
+        let memory = self
+            .instance
+            .get_memory(&mut self.store, &self.memory)
+            .ok_or_else(|| WasmError::MissingMemory(self.memory.clone()))?;
+        let ptr = self.guest_alloc(input.len())?;
+        usize::try_from(ptr)
+            .ok()
+            .and_then(|offset| memory.write(&mut self.store, offset, input).ok())
+            .ok_or(WasmError::InputTooLarge(input.len()))?;
+        Ok((memory, ptr))
+    }
+
+    /// `(alloc, dealloc)` exports of the guest allocator, if any.
+    fn allocator(&mut self) -> Option<(&'static str, &'static str)> {
+        GUEST_ALLOCATORS
+            .iter()
+            .copied()
+            .find(|(alloc, _)| self.instance.get_func(&mut self.store, alloc).is_some())
+    }
+
+    /// Allocate `len` bytes with the guest allocator; guests without one
+    /// have no memory the host may safely write to.
+    fn guest_alloc(&mut self, len: usize) -> Result<u64, WasmError> {
+        let (alloc, _) = self.allocator().ok_or(WasmError::MissingAllocator)?;
+        let too_large = |_| WasmError::InputTooLarge(len);
+        let ptr = if self.memory64 {
+            self.call_export::<i64, i64>(alloc, i64::try_from(len).map_err(too_large)?)?
+        } else {
+            i64::from(self.call_export::<i32, i32>(alloc, i32::try_from(len).map_err(too_large)?)?)
+        };
+        u64::try_from(ptr).map_err(|_| WasmError::InvalidResult {
+            export: alloc.into(),
+            result: ptr,
+        })
+    }
+
+    /// Release `len` bytes at `ptr` with the guest's `dealloc`, if exported.
+    fn guest_dealloc(&mut self, ptr: u64, len: usize) -> Result<(), WasmError> {
+        let Some((_, dealloc)) = self.allocator() else {
+            return Ok(());
+        };
+        if self.instance.get_func(&mut self.store, dealloc).is_none() {
+            return Ok(());
+        }
+        self.call_typed::<()>(dealloc, ptr, len)
+    }
+
+    /// Call an exported `(ptr, len) -> R` function, with `i64` arguments for
+    /// memory64 guests.
+    fn call_typed<R: WasmResults>(
+        &mut self,
+        export: &str,
+        ptr: u64,
+        len: usize,
+    ) -> Result<R, WasmError> {
+        let too_large = |_| WasmError::InputTooLarge(len);
+        if self.memory64 {
+            let args = (
+                i64::try_from(ptr).map_err(too_large)?,
+                i64::try_from(len).map_err(too_large)?,
+            );
+            return self.call_export(export, args);
+        }
+        let args = (
+            i32::try_from(ptr).map_err(too_large)?,
+            i32::try_from(len).map_err(too_large)?,
+        );
+        self.call_export(export, args)
+    }
+
+    /// Call the exported function `export` under the watchdog.
+    fn call_export<P: WasmParams, R: WasmResults>(
+        &mut self,
+        export: &str,
+        params: P,
+    ) -> Result<R, WasmError> {
+        let call_err = |source| WasmError::Call {
+            export: export.into(),
+            source,
+        };
+        let func = self
+            .instance
+            .get_typed_func::<P, R>(&mut self.store, export)
+            .map_err(call_err)?;
+        self.watched(|store| func.call(store, params).map_err(call_err))
+    }
+
+    /// Get `host`
+    #[inline]
+    #[must_use]
+    pub fn host(&self) -> &HostState {
+        self.store.data()
+    }
+
+    /// Fuel consumed by the guest so far
+    #[must_use]
+    pub fn fuel_consumed(&self) -> u64 {
+        DEFAULT_FUEL - self.store.get_fuel().unwrap_or(DEFAULT_FUEL)
+    }
+
+    /// Linear memory size of the guest memory. Wasm memories never shrink,
+    /// so this is also the high-water mark.
+    #[must_use]
+    pub fn memory_high_water(&mut self) -> usize {
+        self.instance
+            .get_memory(&mut self.store, &self.memory)
+            .map_or(0, |memory| memory.data_size(&self.store))
+    }
+
+    /// Tear the instance down, recording `plugin.shutdown` with memory
+    /// high-water mark and fuel consumed, and hand back the host state.
+    #[must_use]
+    pub fn shutdown(mut self) -> HostState {
+        let memory_bytes = self.memory_high_water();
+        let fuel_consumed = self.fuel_consumed();
+        let mut host = self.store.into_data();
+        host.record_plugin_shutdown(memory_bytes, fuel_consumed);
+        host
+    }
+}
+
+/// `start..start + len` as indices, if it neither overflows nor exceeds `usize`.
+fn byte_range(start: u64, len: u64) -> Option<Range<usize>> {
+    let end = usize::try_from(start.checked_add(len)?).ok()?;
+    Some(usize::try_from(start).ok()?..end)
+}
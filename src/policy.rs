@@ -0,0 +1,112 @@
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Display},
+    sync::mpsc::{Sender, channel},
+    time::Duration,
+};
+
+/// Default time a [`PromptPolicy`] waits for a human before denying.
+pub const DEFAULT_PROMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A capability call the manifest allows, pending approval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalRequest {
+    pub plugin: String,
+    pub path: String,
+}
+
+/// Decision returned by a [`PolicyHook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Approve,
+    Deny,
+    /// Approve this path and every later request for it in this run.
+    AlwaysAllowSession,
+    /// No decision arrived in time; treated as a denial.
+    TimedOut,
+}
+
+/// Extra approval step consulted by [`crate::HostState`] after manifest enforcement.
+pub trait PolicyHook: Debug + Send {
+    fn decide(&mut self, request: &ApprovalRequest) -> Approval;
+}
+
+/// A pending [`ApprovalRequest`] forwarded to a UI, answered through `reply`.
+#[derive(Debug)]
+pub struct PromptMessage {
+    pub request: ApprovalRequest,
+    pub reply: Sender<Approval>,
+}
+
+/// [`PolicyHook`] that forwards each decision to a UI over a channel and waits
+/// up to a timeout, denying when no answer arrives.
+#[derive(Debug)]
+pub struct PromptPolicy {
+    ui: Sender<PromptMessage>,
+    timeout: Duration,
+    session_allowed: HashSet<String>,
+}
+
+impl PromptPolicy {
+    #[inline]
+    #[must_use]
+    pub fn new(ui: Sender<PromptMessage>) -> Self {
+        Self {
+            ui,
+            timeout: DEFAULT_PROMPT_TIMEOUT,
+            session_allowed: HashSet::new(),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl PolicyHook for PromptPolicy {
+    fn decide(&mut self, request: &ApprovalRequest) -> Approval {
+        if self.session_allowed.contains(&request.path) {
+            return Approval::AlwaysAllowSession;
+        }
+
+        let (reply, answer) = channel();
+        let message = PromptMessage {
+            request: request.clone(),
+            reply,
+        };
+        if self.ui.send(message).is_err() {
+            return Approval::Deny;
+        }
+
+        let approval = answer
+            .recv_timeout(self.timeout)
+            .unwrap_or(Approval::TimedOut);
+        if approval == Approval::AlwaysAllowSession {
+            self.session_allowed.insert(request.path.clone());
+        }
+        approval
+    }
+}
+
+impl Approval {
+    #[inline]
+    #[must_use]
+    pub const fn is_allowed(self) -> bool {
+        matches!(self, Self::Approve | Self::AlwaysAllowSession)
+    }
+}
+
+impl Display for Approval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Approve => "approve",
+            Self::Deny => "deny",
+            Self::AlwaysAllowSession => "always_allow_session",
+            Self::TimedOut => "timed_out",
+        };
+        f.write_str(s)
+    }
+}
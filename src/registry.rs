@@ -0,0 +1,138 @@
+use crate::manifest::{CapabilityManifest, ManifestError};
+use semver::{Version, VersionReq};
+use std::{
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Errors from registry loading/lookup.
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("IO error reading registry: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Index deserialization failed: {0}")]
+    Index(#[from] serde_json::Error),
+
+    #[error("Invalid manifest {path}: {source}")]
+    Manifest {
+        path: PathBuf,
+        source: ManifestError,
+    },
+
+    #[error("Invalid version for plugin {plugin}: {source}")]
+    InvalidVersion {
+        plugin: String,
+        source: ManifestError,
+    },
+
+    #[error("Invalid version requirement `{0}`")]
+    InvalidVersionReq(String),
+}
+
+/// A store of validated manifests, looked up by plugin name and semver requirement.
+#[derive(Debug, Default)]
+pub struct Registry {
+    entries: Vec<(Version, CapabilityManifest)>,
+}
+
+impl Registry {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every `*.json` manifest in `dir` (non-recursive).
+    ///
+    /// # Errors
+    ///
+    /// [`RegistryError`] if the directory cannot be read or any manifest is invalid.
+    pub fn open_dir<P: AsRef<Path>>(dir: P) -> Result<Self, RegistryError> {
+        let mut paths = read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        paths.sort();
+        Self::from_paths(paths)
+    }
+
+    /// Load the manifests listed in an index file: a JSON array of manifest
+    /// paths, relative to the index file's directory.
+    ///
+    /// # Errors
+    ///
+    /// [`RegistryError`] if the index or any listed manifest is invalid.
+    pub fn open_index<P: AsRef<Path>>(index: P) -> Result<Self, RegistryError> {
+        let index = index.as_ref();
+        let base = index.parent().unwrap_or_else(|| Path::new(""));
+        let listed = serde_json::from_str::<Vec<PathBuf>>(&read_to_string(index)?)?;
+        Self::from_paths(listed.into_iter().map(|path| base.join(path)))
+    }
+
+    fn from_paths<I: IntoIterator<Item = PathBuf>>(paths: I) -> Result<Self, RegistryError> {
+        let mut registry = Self::new();
+        for path in paths {
+            let manifest = CapabilityManifest::load(&path)
+                .map_err(|source| RegistryError::Manifest { path, source })?;
+            registry.insert(manifest)?;
+        }
+        Ok(registry)
+    }
+
+    /// Add a manifest to the registry.
+    ///
+    /// # Errors
+    ///
+    /// [`RegistryError::InvalidVersion`] if the manifest version is not semver.
+    pub fn insert(&mut self, manifest: CapabilityManifest) -> Result<(), RegistryError> {
+        let version =
+            manifest
+                .version_semver()
+                .map_err(|source| RegistryError::InvalidVersion {
+                    plugin: manifest.plugin.clone(),
+                    source,
+                })?;
+        self.entries.push((version, manifest));
+        Ok(())
+    }
+
+    /// Find the highest version of `plugin` matching `version_req` (e.g. `">=1.2"`).
+    ///
+    /// # Errors
+    ///
+    /// [`RegistryError::InvalidVersionReq`] if `version_req` cannot be parsed.
+    pub fn find(
+        &self,
+        plugin: &str,
+        version_req: &str,
+    ) -> Result<Option<&CapabilityManifest>, RegistryError> {
+        let req = VersionReq::parse(version_req)
+            .map_err(|_| RegistryError::InvalidVersionReq(version_req.into()))?;
+        Ok(self.find_req(plugin, &req))
+    }
+
+    /// Like [`Registry::find`], with an already parsed requirement.
+    #[must_use]
+    pub fn find_req(&self, plugin: &str, req: &VersionReq) -> Option<&CapabilityManifest> {
+        self.entries
+            .iter()
+            .filter(|(version, manifest)| manifest.plugin == plugin && req.matches(version))
+            .max_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs))
+            .map(|(_, manifest)| manifest)
+    }
+
+    /// Number of manifests in the registry
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
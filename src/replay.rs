@@ -0,0 +1,158 @@
+use crate::{
+    host::HostState,
+    trace::{TraceError, TraceEvent, differing_field, load_trace},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs,
+    path::Path,
+};
+
+/// Events of context kept before the first divergence by default.
+pub const DEFAULT_DIVERGENCE_CONTEXT: usize = 5;
+
+/// Where a replay first departed from its recorded trace, serializable as a
+/// CI artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivergenceReport {
+    /// Seq of the first differing event
+    pub seq: u64,
+    /// First differing field, or `missing`/`extra` when one side ran out
+    pub field: String,
+    /// Recorded event (`None` if the replay produced extra events)
+    pub expected: Option<TraceEvent>,
+    /// Replayed event (`None` if the replay stopped early)
+    pub actual: Option<TraceEvent>,
+    /// Events (identical on both sides) preceding the divergence
+    pub context: Vec<TraceEvent>,
+}
+
+impl DivergenceReport {
+    /// Write the report as pretty JSON.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] (JSON or IO).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), TraceError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl Display for DivergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replay diverged at seq {}: {}", self.seq, self.field)
+    }
+}
+
+impl Error for DivergenceReport {}
+
+/// Find the first event where `actual` departs from `expected`.
+///
+/// Only the deterministic fields are compared (type, input, outcome,
+/// `ts_seed`, vclock, details). With `prefix`, an `actual` that stops early
+/// is not a divergence.
+#[must_use]
+pub fn first_divergence(
+    expected: &[TraceEvent],
+    actual: &[TraceEvent],
+    context: usize,
+    prefix: bool,
+) -> Option<DivergenceReport> {
+    let len = expected.len().max(actual.len());
+    (0..len).find_map(|idx| {
+        let (want, got) = (expected.get(idx), actual.get(idx));
+        let field = match (want, got) {
+            (Some(want), Some(got)) => differing_field(want, got, |field| field == "run_id")?,
+            (Some(_), None) if prefix => return None,
+            (Some(_), None) => "missing",
+            (None, _) => "extra",
+        };
+        let seq = want.or(got).map_or(0, |ev| ev.seq);
+        Some(DivergenceReport {
+            seq,
+            field: field.into(),
+            expected: want.cloned(),
+            actual: got.cloned(),
+            context: actual[idx.saturating_sub(context)..idx].to_vec(),
+        })
+    })
+}
+
+/// A [`HostState`] re-running a recorded trace, checked event by event.
+#[derive(Debug)]
+pub struct ReplayHost {
+    host: HostState,
+    expected: Vec<TraceEvent>,
+    context: usize,
+}
+
+impl ReplayHost {
+    /// Replay `expected` on `host`, usually built with the recorded seed.
+    #[must_use]
+    pub const fn new(host: HostState, expected: Vec<TraceEvent>) -> Self {
+        Self {
+            host,
+            expected,
+            context: DEFAULT_DIVERGENCE_CONTEXT,
+        }
+    }
+
+    /// Replay the trace JSON at `path`, see [`load_trace`].
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] if the trace cannot be loaded.
+    pub fn from_trace_file<P: AsRef<Path>>(host: HostState, path: P) -> Result<Self, TraceError> {
+        Ok(Self::new(host, load_trace(path)?))
+    }
+
+    /// Keep `events` of context before a divergence (default
+    /// [`DEFAULT_DIVERGENCE_CONTEXT`]).
+    #[must_use]
+    pub const fn with_context(mut self, events: usize) -> Self {
+        self.context = events;
+        self
+    }
+
+    /// Get `host`
+    #[inline]
+    #[must_use]
+    pub const fn host(&self) -> &HostState {
+        &self.host
+    }
+
+    /// Host to drive the replayed calls through
+    #[inline]
+    pub const fn host_mut(&mut self) -> &mut HostState {
+        &mut self.host
+    }
+
+    /// Check the events replayed so far against the recording.
+    ///
+    /// # Errors
+    ///
+    /// The [`DivergenceReport`] of the first mismatch.
+    pub fn check(&self) -> Result<(), Box<DivergenceReport>> {
+        self.diverged(true)
+    }
+
+    /// Finish the replay, requiring it to have produced exactly the recorded
+    /// events, and hand back the host.
+    ///
+    /// # Errors
+    ///
+    /// The [`DivergenceReport`] of the first mismatch, including a replay
+    /// that stopped early.
+    pub fn finish(self) -> Result<HostState, Box<DivergenceReport>> {
+        self.diverged(false)?;
+        Ok(self.host)
+    }
+
+    fn diverged(&self, prefix: bool) -> Result<(), Box<DivergenceReport>> {
+        first_divergence(&self.expected, self.host.trace(), self.context, prefix)
+            .map_or(Ok(()), |report| Err(Box::new(report)))
+    }
+}
@@ -0,0 +1,93 @@
+use crate::trace::{CapEventSubtype, EventType, Severity, TraceEvent};
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+
+/// SARIF version written by [`to_sarif`].
+pub const SARIF_VERSION: &str = "2.1.0";
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Map the denials and non-info events of `trace` to a SARIF log with one
+/// run, so audit findings show up in code-review tools that ingest SARIF.
+///
+/// The rule is the [`CapEventSubtype`] for capability errors (the prefix of
+/// their `subtype: reason` input) and the event type otherwise; the location
+/// is the path the event is about, when its input names one.
+/// Levels follow the event [`Severity`], falling back to
+/// [`Severity::derived`] for traces recorded without one.
+#[must_use]
+pub fn to_sarif(trace: &[TraceEvent]) -> Value {
+    let mut rules = BTreeSet::new();
+    let results = trace
+        .iter()
+        .filter_map(|event| {
+            let severity = if event.severity.is_info() {
+                Severity::derived(&event.event_type, event.outcome)
+            } else {
+                event.severity
+            };
+            if event.outcome && severity.is_info() {
+                return None;
+            }
+            let subtype = event
+                .input
+                .split_once(": ")
+                .and_then(|(prefix, _)| prefix.parse::<CapEventSubtype>().ok());
+            let rule = subtype.map_or_else(|| event.event_type.to_string(), |s| s.to_string());
+            let mut result = json!({
+                "ruleId": rule,
+                "level": sarif_level(severity),
+                "message": { "text": event.input },
+                "properties": {
+                    "run_id": event.run_id,
+                    "seq": event.seq,
+                    "event_type": event.event_type.as_str(),
+                },
+            });
+            if let Some(path) = subtype.is_none().then(|| subject_path(event)).flatten() {
+                result["locations"] = json!([{
+                    "physicalLocation": { "artifactLocation": { "uri": path } }
+                }]);
+            }
+            rules.insert(rule);
+            Some(result)
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.into_iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// Path an event is about: the whole input of calls and FS events, the part
+/// before the decision of approvals.
+fn subject_path(event: &TraceEvent) -> Option<&str> {
+    match event.event_type {
+        EventType::CapCall
+        | EventType::FsRead
+        | EventType::FsList
+        | EventType::FsContentFiltered => Some(event.input.as_str()),
+        EventType::CapApproval => event.input.rsplit_once(": ").map(|(path, _)| path),
+        _ => None,
+    }
+    .filter(|path| !path.is_empty())
+}
+
+const fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "note",
+        Severity::Warn => "warning",
+        Severity::Critical => "error",
+    }
+}
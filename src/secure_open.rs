@@ -0,0 +1,234 @@
+use std::{
+    fs::File,
+    io,
+    path::{Component, Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Errors from [`secure_open`].
+#[derive(Debug, Error)]
+pub enum SecureOpenError {
+    #[error("Path `{path}` is not beneath its grant `{grant}`")]
+    OutsideGrant { path: PathBuf, grant: PathBuf },
+
+    #[error("Path `{0}` resolves through a symlink or outside its grant")]
+    Unsafe(PathBuf),
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Directory a read `pattern` grants access beneath.
+///
+/// These are its literal leading components up to the first glob
+/// metacharacter, never including the final (file) component; empty when the
+/// pattern has no literal directory prefix, e.g. `**/*.toml`.
+#[must_use]
+pub fn grant_root(pattern: &str) -> PathBuf {
+    let mut components = Path::new(pattern).components().collect::<Vec<_>>();
+    components.pop();
+    components
+        .into_iter()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Open `path` read-only without following symlinks or leaving `grant`.
+///
+/// The file read is then the one the capability check approved even if the
+/// tree changes in between; an empty grant only refuses symlinks.
+///
+/// On Linux this is a single `openat2` beneath `grant` with
+/// `RESOLVE_NO_SYMLINKS | RESOLVE_BENEATH`; kernels without `openat2` and
+/// other Unix systems fall back to `O_NOFOLLOW`, which only covers the final
+/// component. Elsewhere only the prefix check applies.
+///
+/// # Errors
+///
+/// [`SecureOpenError::OutsideGrant`] if `path` does not start with `grant`,
+/// [`SecureOpenError::Unsafe`] if resolving it crosses a symlink or leaves
+/// `grant`, or [`SecureOpenError::Io`] if the open fails otherwise.
+pub fn secure_open(path: &Path, grant: &Path) -> Result<File, SecureOpenError> {
+    let relative = beneath(path, grant)?;
+    open_beneath(grant, relative, false).map_err(|err| resolve_error(err, path))
+}
+
+/// Sorted entry names of directory `path`, opened like [`secure_open`] opens
+/// files.
+///
+/// Paths with a `..` component are refused outright, so listing stays beneath
+/// `grant` even where only the `O_NOFOLLOW` fallback is available.
+///
+/// # Errors
+///
+/// As [`secure_open`]; [`SecureOpenError::Unsafe`] for a `..` component.
+pub fn secure_read_dir(path: &Path, grant: &Path) -> Result<Vec<String>, SecureOpenError> {
+    let relative = beneath(path, grant)?;
+    if relative
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(SecureOpenError::Unsafe(path.to_path_buf()));
+    }
+    let mut entries = list_beneath(grant, relative).map_err(|err| resolve_error(err, path))?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// `path` relative to `grant` (all of it for an empty grant).
+fn beneath<'a>(path: &'a Path, grant: &Path) -> Result<&'a Path, SecureOpenError> {
+    if grant.as_os_str().is_empty() {
+        return Ok(path);
+    }
+    path.strip_prefix(grant)
+        .map_err(|_| SecureOpenError::OutsideGrant {
+            path: path.to_path_buf(),
+            grant: grant.to_path_buf(),
+        })
+}
+
+fn resolve_error(err: io::Error, path: &Path) -> SecureOpenError {
+    if is_resolve_error(&err) {
+        SecureOpenError::Unsafe(path.to_path_buf())
+    } else {
+        SecureOpenError::Io(err)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_beneath(grant: &Path, relative: &Path, directory: bool) -> io::Result<File> {
+    use std::{
+        ffi::CString,
+        fs::OpenOptions,
+        os::{
+            fd::{AsRawFd, FromRawFd},
+            unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+        },
+    };
+
+    let dir = if grant.as_os_str().is_empty() {
+        None
+    } else {
+        Some(
+            OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC)
+                .open(grant)?,
+        )
+    };
+    let (dirfd, resolve) =
+        dir.as_ref()
+            .map_or((libc::AT_FDCWD, libc::RESOLVE_NO_SYMLINKS), |dir| {
+                (
+                    dir.as_raw_fd(),
+                    libc::RESOLVE_NO_SYMLINKS | libc::RESOLVE_BENEATH,
+                )
+            });
+    let c_path = CString::new(relative.as_os_str().as_bytes())?;
+    // SAFETY: all-zero is a valid `open_how` (no flags, mode or resolve bits).
+    let mut how = unsafe { std::mem::zeroed::<libc::open_how>() };
+    let flags = if directory {
+        libc::O_RDONLY | libc::O_CLOEXEC | libc::O_DIRECTORY
+    } else {
+        libc::O_RDONLY | libc::O_CLOEXEC
+    };
+    how.flags = u64::try_from(flags).unwrap_or_default();
+    how.resolve = resolve;
+    // SAFETY: `c_path` is NUL-terminated, `how` is a valid `open_how` of the
+    // given size and `dirfd` is open (or `AT_FDCWD`).
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_openat2,
+            dirfd,
+            c_path.as_ptr(),
+            &raw const how,
+            size_of::<libc::open_how>(),
+        )
+    };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENOSYS) {
+            return open_nofollow(&grant.join(relative), directory);
+        }
+        return Err(err);
+    }
+    let fd = i32::try_from(fd).map_err(|_| io::Error::other("openat2 returned an invalid fd"))?;
+    // SAFETY: the kernel just returned this descriptor and nothing else owns it.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn open_beneath(grant: &Path, relative: &Path, directory: bool) -> io::Result<File> {
+    open_nofollow(&grant.join(relative), directory)
+}
+
+#[cfg(not(unix))]
+fn open_beneath(grant: &Path, relative: &Path, _directory: bool) -> io::Result<File> {
+    File::open(grant.join(relative))
+}
+
+#[cfg(unix)]
+fn open_nofollow(path: &Path, directory: bool) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+    let flags = if directory {
+        libc::O_NOFOLLOW | libc::O_CLOEXEC | libc::O_DIRECTORY
+    } else {
+        libc::O_NOFOLLOW | libc::O_CLOEXEC
+    };
+    OpenOptions::new().read(true).custom_flags(flags).open(path)
+}
+
+/// Entry names of the directory `relative` beneath `grant`, read through the
+/// descriptor [`open_beneath`] returns so the path is resolved only once.
+#[cfg(unix)]
+fn list_beneath(grant: &Path, relative: &Path) -> io::Result<Vec<String>> {
+    use std::{ffi::CStr, os::fd::IntoRawFd};
+
+    let fd = open_beneath(grant, relative, true)?.into_raw_fd();
+    // SAFETY: `fd` is an open directory descriptor owned by us; on success
+    // the stream takes it over and `closedir` below closes it.
+    let stream = unsafe { libc::fdopendir(fd) };
+    if stream.is_null() {
+        let err = io::Error::last_os_error();
+        // SAFETY: `fdopendir` failed, so `fd` is still ours to close.
+        unsafe { libc::close(fd) };
+        return Err(err);
+    }
+    let mut entries = Vec::new();
+    // SAFETY: `stream` is a valid open directory stream until `closedir`;
+    // each `dirent` is read before the next `readdir` call invalidates it.
+    // `readdir` only returns null at the end here: its sole documented
+    // failure, `EBADF`, cannot happen for a stream we just opened.
+    unsafe {
+        loop {
+            let entry = libc::readdir(stream);
+            if entry.is_null() {
+                break;
+            }
+            let name = CStr::from_ptr((*entry).d_name.as_ptr()).to_string_lossy();
+            if name != "." && name != ".." {
+                entries.push(name.into_owned());
+            }
+        }
+        libc::closedir(stream);
+    }
+    Ok(entries)
+}
+
+#[cfg(not(unix))]
+fn list_beneath(grant: &Path, relative: &Path) -> io::Result<Vec<String>> {
+    std::fs::read_dir(grant.join(relative))?
+        .map(|entry| entry.map(|e| e.file_name().to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// `ELOOP` for a symlink, `EXDEV` for an escape from `grant`.
+#[cfg(unix)]
+fn is_resolve_error(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::ELOOP | libc::EXDEV))
+}
+
+#[cfg(not(unix))]
+const fn is_resolve_error(_err: &io::Error) -> bool {
+    false
+}
@@ -0,0 +1,56 @@
+use crate::trace::{SeqCounter, VectorClock};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Shared logical clock for runs spanning several hosts.
+///
+/// Every [`crate::HostState`] joined to the same session ticks its own entry
+/// before recording an event and stamps the event with a snapshot of the
+/// clock, so traces from different hosts can be causally ordered during
+/// aggregation without synchronized wall clocks.
+#[derive(Debug, Clone, Default)]
+pub struct RunSession {
+    clock: Arc<Mutex<VectorClock>>,
+    /// Events recorded by all joined hosts
+    events: Arc<SeqCounter>,
+}
+
+impl RunSession {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Increment the counter of `host_id` and return the resulting clock.
+    pub fn tick(&self, host_id: &str) -> VectorClock {
+        let mut clock = self.clock.lock().unwrap_or_else(PoisonError::into_inner);
+        *clock.entry(host_id.to_owned()).or_default() += 1;
+        self.events.next();
+        clock.clone()
+    }
+
+    /// Merge a clock observed from another session (element-wise max).
+    pub fn observe(&self, other: &VectorClock) {
+        let mut clock = self.clock.lock().unwrap_or_else(PoisonError::into_inner);
+        for (host_id, counter) in other {
+            let entry = clock.entry(host_id.clone()).or_default();
+            *entry = (*entry).max(*counter);
+        }
+        drop(clock);
+    }
+
+    /// Get a snapshot of the current clock
+    #[must_use]
+    pub fn clock(&self) -> VectorClock {
+        self.clock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Number of events recorded across all joined hosts.
+    #[must_use]
+    pub fn event_count(&self) -> u64 {
+        self.events.last()
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{manifest::CapabilityManifest, trace::TraceEvent};
+use std::{collections::HashMap, sync::Arc};
+
+/// Export a spawned plugin is run through: `run() -> i32`.
+pub const SPAWN_EXPORT: &str = "run";
+
+/// Deepest chain of nested `host::spawn_plugin` calls; spawns below it are
+/// denied so plugins cannot recurse without bound.
+pub const MAX_SPAWN_DEPTH: u32 = 4;
+
+/// Plugins a guest may start with `host::spawn_plugin`, by manifest plugin name.
+///
+/// Each child runs under its own manifest, so a pipeline stage never gains
+/// the capabilities of the plugin that spawned it.
+#[derive(Debug, Clone, Default)]
+pub struct PluginCatalog {
+    plugins: HashMap<String, Arc<(CapabilityManifest, Vec<u8>)>>,
+}
+
+impl PluginCatalog {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `wasm` (binary or WAT) under `manifest.plugin`, replacing any
+    /// plugin of the same name.
+    #[must_use]
+    pub fn with_plugin(mut self, manifest: CapabilityManifest, wasm: impl Into<Vec<u8>>) -> Self {
+        self.plugins
+            .insert(manifest.plugin.clone(), Arc::new((manifest, wasm.into())));
+        self
+    }
+
+    /// Manifest and module of `name`, if registered
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<(&CapabilityManifest, &[u8])> {
+        self.plugins
+            .get(name)
+            .map(|entry| (&entry.0, entry.1.as_slice()))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.plugins.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+}
+
+/// A child run started by a guest through `host::spawn_plugin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildRun {
+    pub plugin: String,
+    pub run_id: String,
+    /// Run that spawned this one
+    pub parent_run_id: String,
+    /// Return value of [`SPAWN_EXPORT`] (`None` if the child failed)
+    pub result: Option<i32>,
+    /// Trace of the child run (empty if it could not be instantiated)
+    pub trace: Vec<TraceEvent>,
+}
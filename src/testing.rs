@@ -0,0 +1,332 @@
+//! Helpers for plugin authors unit-testing capability behavior (feature `test-util`).
+
+use crate::{
+    host::HostState,
+    manifest::{Capabilities, CapabilityManifest, FsCapability, Posture},
+    trace::{
+        EventType, RandomnessMode, TraceEvent, differing_field, load_trace, verify_chain,
+        verify_ts_seeds,
+    },
+};
+use ed25519_dalek::{SECRET_KEY_LENGTH, SigningKey};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+const GEN_DIRS: &[&str] = &["./workspace", "./workspace/sub", "./data", "/etc"];
+const GEN_FILES: &[&str] = &["a.toml", "b.md", "config.toml", "passwd", ""];
+const GEN_GLOBS: &[&str] = &["*", "*.toml", "**/*", "a.*", "[bc]*"];
+
+/// Deterministic signing key derived from a single byte. Never use outside tests.
+#[must_use]
+pub fn fixed_keypair(byte: u8) -> SigningKey {
+    SigningKey::from_bytes(&[byte; SECRET_KEY_LENGTH])
+}
+
+/// In-memory manifest granting FS reads for `read_patterns`.
+#[must_use]
+pub fn manifest_with_reads(plugin: &str, read_patterns: &[&str]) -> CapabilityManifest {
+    CapabilityManifest {
+        plugin: plugin.into(),
+        version: "0.1.0".into(),
+        capabilities: Capabilities {
+            fs: Some(FsCapability {
+                read: Some(read_patterns.iter().map(ToString::to_string).collect()),
+                write: None,
+                list: None,
+                max_file_bytes: None,
+                max_range_bytes: None,
+                extensions: None,
+                content_filters: Vec::new(),
+            }),
+            time: None,
+            blob: None,
+            config: None,
+            custom: Vec::new(),
+        },
+        profiles: BTreeMap::new(),
+        posture: Posture::default(),
+        issued_by: "captra-test".into(),
+        description: None,
+        data_classes: Vec::new(),
+        contact: None,
+        strict_metadata: false,
+        run_as: None,
+        requires: None,
+        trace_policy: None,
+        delegation: Vec::new(),
+        signature: None,
+    }
+}
+
+/// Host with a fixed seed and a fixed keypair, so traces and signatures are reproducible.
+#[must_use]
+pub fn deterministic_host(manifest: CapabilityManifest, seed: u64) -> HostState {
+    HostState::new(manifest, seed, fixed_keypair(7))
+}
+
+/// Property check of captra's determinism guarantees over `cases` random
+/// manifests and call sequences generated from `seed`.
+///
+/// For each case, two hosts with the same seed replay the same calls; their
+/// traces must be identical, have contiguous seqs and an intact hash chain,
+/// reproduce every `ts_seed`, and sign identically with a verifying signature.
+///
+/// # Errors
+///
+/// The failing case number (rerun with the same `seed`) and invariant.
+pub fn check_determinism(seed: u64, cases: u32) -> Result<(), String> {
+    for case in 0..cases {
+        let case_seed = seed.wrapping_add(u64::from(case));
+        check_determinism_case(case_seed).map_err(|msg| format!("case {case}: {msg}"))?;
+    }
+    Ok(())
+}
+
+fn check_determinism_case(seed: u64) -> Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut pick = |items: &[&'static str]| items.choose(&mut rng).copied().unwrap_or_default();
+    let patterns = (0..3)
+        .map(|_| format!("{}/{}", pick(GEN_DIRS), pick(GEN_GLOBS)))
+        .collect::<Vec<_>>();
+    let calls = (0..8)
+        .map(|_| format!("{}/{}", pick(GEN_DIRS), pick(GEN_FILES)))
+        .collect::<Vec<_>>();
+    let patterns = patterns.iter().map(String::as_str).collect::<Vec<_>>();
+    let manifest = manifest_with_reads("captra-prop", &patterns);
+    let host_seed = rng.r#gen();
+
+    let mut runs = [host_seed; 2].map(|s| deterministic_host(manifest.clone(), s));
+    for host in &mut runs {
+        for call in &calls {
+            let _ = host.execute_plugin(call);
+        }
+    }
+    let [first, second] = &mut runs;
+    if first.trace() != second.trace() {
+        return Err("replayed traces differ".into());
+    }
+    verify_chain(first.trace()).map_err(|err| err.to_string())?;
+    verify_ts_seeds(first.trace(), RandomnessMode::Deterministic(host_seed))
+        .map_err(|err| err.to_string())?;
+
+    let signed = first.sign_current_trace().map_err(|err| err.to_string())?;
+    let again = second.sign_current_trace().map_err(|err| err.to_string())?;
+    if signed.signature != again.signature {
+        return Err("signatures differ".into());
+    }
+    signed
+        .verify(&fixed_keypair(7).verifying_key())
+        .map_err(|err| err.to_string())
+}
+
+/// Compare event types and outcomes of `trace` against `expected`.
+///
+/// # Errors
+///
+/// A description of the first difference.
+pub fn check_trace(trace: &[TraceEvent], expected: &[(EventType, bool)]) -> Result<(), String> {
+    if trace.len() != expected.len() {
+        return Err(format!(
+            "trace has {} events, expected {}",
+            trace.len(),
+            expected.len()
+        ));
+    }
+    for (event, (event_type, outcome)) in trace.iter().zip(expected) {
+        if event.event_type != *event_type || event.outcome != *outcome {
+            return Err(format!(
+                "event seq {}: got ({}, {}), expected ({event_type}, {outcome})",
+                event.seq, event.event_type, event.outcome
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fields [`check_trace_equivalent`] skips besides `prev_hash`, which is
+/// always derived from the others.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceTolerance {
+    /// Ignore `run_id` (e.g. UUID run ids).
+    pub run_id: bool,
+    /// Ignore `ts_seed` (traces recorded with [`RandomnessMode::Os`]).
+    pub ts_seed: bool,
+    /// Event types whose `input` and `details` are not compared, e.g.
+    /// [`EventType::PluginShutdown`] when fuel use varies across wasmtime versions.
+    pub volatile_inputs: Vec<EventType>,
+}
+
+/// Compare the deterministic core of `actual` against a recorded golden trace.
+///
+/// # Errors
+///
+/// A description of the first difference.
+pub fn check_trace_equivalent(
+    expected: &[TraceEvent],
+    actual: &[TraceEvent],
+    tolerance: &TraceTolerance,
+) -> Result<(), String> {
+    if expected.len() != actual.len() {
+        return Err(format!(
+            "trace has {} events, expected {}",
+            actual.len(),
+            expected.len()
+        ));
+    }
+    for (want, got) in expected.iter().zip(actual) {
+        let volatile = tolerance.volatile_inputs.contains(&want.event_type);
+        let field = differing_field(want, got, |field| match field {
+            "run_id" => tolerance.run_id,
+            "ts_seed" => tolerance.ts_seed,
+            "input" | "details" => volatile,
+            _ => false,
+        });
+        if let Some(field) = field {
+            return Err(format!(
+                "event seq {}: {field} differs (expected {want:?}, got {got:?})",
+                want.seq
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Assert `actual` is equivalent (see [`check_trace_equivalent`]) to the
+/// golden trace JSON at `expected_path`.
+///
+/// # Panics
+///
+/// If the golden trace cannot be loaded or the traces differ.
+#[track_caller]
+pub fn assert_trace_equivalent<P: AsRef<Path>>(
+    expected_path: P,
+    actual: &[TraceEvent],
+    tolerance: &TraceTolerance,
+) {
+    let path = expected_path.as_ref();
+    let expected = load_trace(path)
+        .unwrap_or_else(|err| panic!("cannot load golden trace {}: {err}", path.display()));
+    if let Err(msg) = check_trace_equivalent(&expected, actual, tolerance) {
+        panic!("trace differs from {}: {msg}", path.display());
+    }
+}
+
+/// Failure a [`FaultInjector`] forces on a capability call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Deny the call as if the manifest refused it.
+    Deny,
+    /// Stall the call before enforcement.
+    Delay(Duration),
+    /// Flip one byte of the next data the host returns.
+    Corrupt,
+}
+
+impl Fault {
+    /// Short name used in `fault.injected` events.
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deny => "deny",
+            Self::Delay(_) => "delay",
+            Self::Corrupt => "corrupt",
+        }
+    }
+}
+
+/// Deterministic schedule of [`Fault`]s for [`HostState::with_fault_injector`],
+/// so plugin error paths can be exercised reproducibly.
+///
+/// Calls are numbered from 1 in decision order. Faults pinned with
+/// [`FaultInjector::at`] win; otherwise each [`FaultInjector::with_rate`] rule
+/// draws from an RNG seeded with `seed`, the first hit applying.
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    rng: StdRng,
+    calls: u64,
+    pinned: BTreeMap<u64, Fault>,
+    rates: Vec<(Fault, f64)>,
+    corrupt_pending: bool,
+}
+
+impl FaultInjector {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            calls: 0,
+            pinned: BTreeMap::new(),
+            rates: Vec::new(),
+            corrupt_pending: false,
+        }
+    }
+
+    /// Force `fault` on call number `call`.
+    #[must_use]
+    pub fn at(mut self, call: u64, fault: Fault) -> Self {
+        self.pinned.insert(call, fault);
+        self
+    }
+
+    /// Inject `fault` into each call with `probability` (0.0 to 1.0).
+    #[must_use]
+    pub fn with_rate(mut self, fault: Fault, probability: f64) -> Self {
+        self.rates.push((fault, probability));
+        self
+    }
+
+    /// Number of calls seen so far
+    #[must_use]
+    pub const fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Advance to the next call and return its fault, if any.
+    pub(crate) fn next_fault(&mut self) -> Option<Fault> {
+        self.calls += 1;
+        // Every rule draws on every call, so pinning a fault leaves the rest
+        // of the schedule unchanged.
+        let drawn = self
+            .rates
+            .iter()
+            .map(|(fault, probability)| (*fault, self.rng.r#gen::<f64>() < *probability))
+            .collect::<Vec<_>>();
+        let fault = self.pinned.get(&self.calls).copied().or_else(|| {
+            drawn
+                .into_iter()
+                .find_map(|(fault, hit)| hit.then_some(fault))
+        });
+        if fault == Some(Fault::Corrupt) {
+            self.corrupt_pending = true;
+        }
+        fault
+    }
+
+    /// Apply a pending [`Fault::Corrupt`] to `data`; returns whether it did.
+    /// Empty data stays pending until something can be corrupted.
+    pub(crate) fn corrupt(&mut self, data: &mut [u8]) -> bool {
+        if !self.corrupt_pending || data.is_empty() {
+            return false;
+        }
+        self.corrupt_pending = false;
+        let idx = self.rng.gen_range(0..data.len());
+        data[idx] ^= 0xff;
+        true
+    }
+}
+
+/// Assert the sequence of event types and outcomes of a trace.
+///
+/// ```ignore
+/// assert_trace_matches!(host.trace(), [CapCall => true, CapCall => false]);
+/// ```
+#[macro_export]
+macro_rules! assert_trace_matches {
+    ($trace:expr, [$($event_type:ident => $outcome:expr),* $(,)?]) => {{
+        let expected: &[($crate::EventType, bool)] =
+            &[$(($crate::EventType::$event_type, $outcome)),*];
+        if let Err(msg) = $crate::testing::check_trace(&$trace, expected) {
+            panic!("trace mismatch: {msg}");
+        }
+    }};
+}
@@ -1,6 +1,19 @@
+use crate::manifest::{AuditMetadata, CapabilityManifest, PRIME_MULTIPLIER};
 use base64::{Engine, engine::general_purpose};
-use serde::{Deserialize, Serialize};
-use std::{fmt::Display, fs, path::Path, str::FromStr};
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, Signature, Verifier, VerifyingKey};
+use rand::{Rng, SeedableRng, rngs::OsRng, rngs::StdRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt::{Display, Write as _},
+    fs,
+    io::Write,
+    path::Path,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+};
 use thiserror::Error;
 use tracing::info;
 
@@ -12,6 +25,69 @@ pub struct TraceEvent {
     pub input: String,
     pub outcome: bool,
     pub ts_seed: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vclock: Option<VectorClock>,
+    /// Manifest version in force, bumped by [`crate::HostState::reload_manifest`]
+    /// (0 for the initial manifest).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub policy_epoch: u64,
+    /// Alerting level, see [`SeverityRules`] (omitted when `info`).
+    #[serde(default, skip_serializing_if = "Severity::is_info")]
+    pub severity: Severity,
+    /// Structured context for errors (e.g. which manifest entry was malformed).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    /// Identical denials folded into this one by
+    /// [`crate::HostState::with_denial_dedup`]; they had seqs
+    /// `seq + 1..=seq + repeat_count`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub repeat_count: u64,
+    /// Hash of the preceding event (empty for the first event of a run).
+    #[serde(default)]
+    pub prev_hash: String,
+}
+
+impl TraceEvent {
+    /// Seq of the last occurrence folded into this event (`seq` unless
+    /// [`TraceEvent::repeat_count`] is set); the next event follows it.
+    #[inline]
+    #[must_use]
+    pub const fn last_seq(&self) -> u64 {
+        self.seq + self.repeat_count
+    }
+}
+
+/// Logical vector clock (`host_id` -> counter) stamped on events of multi-host runs.
+pub type VectorClock = BTreeMap<String, u64>;
+
+/// Monotonic allocator of event `seq` numbers, holding the last one handed
+/// out. Numbers come from the counter rather than the trace length, so events
+/// dropped before reaching a trace cannot cause duplicates.
+#[derive(Debug, Default)]
+pub struct SeqCounter(AtomicU64);
+
+impl SeqCounter {
+    /// Allocate the next number.
+    pub fn next(&self) -> u64 {
+        self.0.fetch_add(1, AtomicOrdering::Relaxed) + 1
+    }
+
+    /// Last number handed out (0 if none).
+    #[must_use]
+    pub fn last(&self) -> u64 {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Number the next [`SeqCounter::next`] will return.
+    #[must_use]
+    pub fn peek(&self) -> u64 {
+        self.last() + 1
+    }
+
+    /// Rewind (or advance) so the next number is `last + 1`.
+    pub fn reset(&self, last: u64) {
+        self.0.store(last, AtomicOrdering::Relaxed);
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +96,85 @@ pub struct SignedTrace {
     pub manifest_hash: String,
     pub trace_json: String,
     pub signature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AuditMetadata>,
+    /// Base64 signer public key (empty for traces signed before keys were embedded).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pubkey: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub randomness: Option<RandomnessMode>,
+    /// Granularity of `host::now_millis`, if the manifest granted time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_granularity_ms: Option<u64>,
+    /// Full manifest the run was pinned to, so the trace stays auditable
+    /// without the original manifest file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<CapabilityManifest>,
+    /// Business context set with [`crate::HostState::set_label`], for
+    /// filtering stored traces. Not covered by the signature.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: RunLabels,
+    /// Manifest profile the run was granted, see
+    /// [`crate::HostState::with_profile`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+/// Run labels (`key` -> `value`), e.g. ticket id, CI job or customer id.
+pub type RunLabels = BTreeMap<String, String>;
+
+/// Longest event input (in bytes) recorded verbatim by default.
+pub const DEFAULT_MAX_INPUT_LEN: usize = 4096;
+
+/// Signed trace body written by [`SignedTrace::write_detached`]
+pub const DETACHED_TRACE_FILE: &str = "trace.json";
+/// Base64 signature written by [`SignedTrace::write_detached`]
+pub const DETACHED_SIG_FILE: &str = "trace.sig";
+/// Header written by [`SignedTrace::write_detached`]
+pub const DETACHED_META_FILE: &str = "trace.meta.json";
+
+/// [`SignedTrace`] header without the trace body and signature.
+#[derive(Debug, Serialize, Deserialize)]
+struct DetachedMeta {
+    run_id: String,
+    manifest_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<AuditMetadata>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pubkey: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    randomness: Option<RandomnessMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time_granularity_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    manifest: Option<CapabilityManifest>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: RunLabels,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+}
+
+/// Source of event `ts_seed` values, recorded in the [`SignedTrace`] header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RandomnessMode {
+    /// Reproducible values derived from the seed and event seq (tests, repros).
+    Deterministic(u64),
+    /// Unpredictable values from the OS RNG (production).
+    Os,
+}
+
+impl RandomnessMode {
+    /// `ts_seed` for the event at `seq`.
+    #[must_use]
+    pub fn ts_seed(self, seq: u64) -> u64 {
+        match self {
+            Self::Deterministic(seed) => {
+                StdRng::seed_from_u64(seed.wrapping_mul(PRIME_MULTIPLIER + seq)).r#gen()
+            }
+            Self::Os => OsRng.r#gen(),
+        }
+    }
 }
 
 /// Errors from trace serialization/IO.
@@ -33,13 +188,68 @@ pub enum TraceError {
 
     #[error("Base64 encoding failed: {0}")]
     Base64(#[from] base64::DecodeError),
+
+    #[error("Signature verification failed")]
+    InvalidSignature,
+
+    #[error("Invalid or missing signer public key")]
+    InvalidPublicKey,
+
+    #[error("Signer fingerprint {actual} does not match pinned {expected}")]
+    FingerprintMismatch { expected: String, actual: String },
+
+    #[error("Trace integrity violation at seq {seq}: {reason}")]
+    IntegrityViolation { seq: u64, reason: String },
+
+    #[error("Unknown event type `{event_type}` at seq {seq}")]
+    UnknownEventType { seq: u64, event_type: String },
+
+    #[error("Embedded manifest hash {actual} does not match pinned {expected}")]
+    ManifestMismatch { expected: String, actual: String },
+
+    #[error("Unsupported trace file: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("Signer {fingerprint} is not in the keyring")]
+    UntrustedSigner { fingerprint: String },
+
+    #[error("Run label `{key}` set after the first event")]
+    LabelsSealed { key: String },
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
+/// Kind of a trace event.
+///
+/// Open-ended so traces from newer captra versions still load: a wire name
+/// this version does not know deserializes to [`EventType::Other`] and
+/// serializes back unchanged, keeping hash chains and signatures valid. Use
+/// [`TraceLoadOptions::strict`] to reject such traces instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EventType {
     CapCall,
     CapError,
+    CapApproval,
+    CapAudit,
+    ShadowMismatch,
+    PluginInit,
+    PluginShutdown,
+    PluginCall,
+    PluginSpawn,
+    GuestStalled,
+    HostBusy,
+    FsConstraintViolation,
+    FsRead,
+    FsList,
+    FsContentFiltered,
+    SeccompViolation,
+    CpuQuotaExceeded,
+    MemoryQuotaExceeded,
+    External,
+    Aggregate,
+    PolicyReload,
+    KeyRotate,
+    FaultInjected,
+    /// Event kind unknown to this version, by its serialized name
+    Other(String),
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -50,6 +260,17 @@ pub enum CapEventSubtype {
     NoReadPatterns,
     GlobMismatch,
     InvalidGlob,
+    ConstraintViolation,
+    NoCustomCapability,
+    CustomDenied,
+    BudgetExhausted,
+    RunAsMismatch,
+    UnmetRequirement,
+    NoTimeCapability,
+    NoBlobCapability,
+    BlobDenied,
+    NoConfigCapability,
+    ConfigDenied,
     // TODO: NetConnect, NetDeny, CpuQuotaExceeded
 }
 
@@ -67,32 +288,652 @@ impl SignedTrace {
             manifest_hash,
             trace_json,
             signature: general_purpose::STANDARD.encode(signature),
+            metadata: None,
+            pubkey: String::new(),
+            randomness: None,
+            time_granularity_ms: None,
+            manifest: None,
+            labels: RunLabels::new(),
+            profile: None,
+        }
+    }
+
+    /// Embed the signer public key so verifiers only need a fingerprint.
+    #[inline]
+    #[must_use]
+    pub fn with_pubkey(mut self, pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> Self {
+        self.pubkey = general_purpose::STANDARD.encode(pubkey);
+        self
+    }
+
+    /// Verify the signature over the SHA256 of `trace_json`, `run_id` and
+    /// `manifest_hash` (see [`trace_message`]) with `pubkey`.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::InvalidSignature`] if the signature does not match.
+    pub fn verify(&self, pubkey: &VerifyingKey) -> Result<(), TraceError> {
+        let sig_bytes = general_purpose::STANDARD.decode(&self.signature)?;
+        let signature =
+            Signature::from_slice(&sig_bytes).map_err(|_| TraceError::InvalidSignature)?;
+        let trace_hash = sha256_hex(self.trace_json.as_bytes());
+        let message = trace_message(&self.run_id, &self.manifest_hash, &trace_hash);
+        pubkey
+            .verify(message.as_bytes(), &signature)
+            .map_err(|_| TraceError::InvalidSignature)
+    }
+
+    /// Decode the embedded signer public key.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::InvalidPublicKey`] if absent or malformed.
+    pub fn embedded_pubkey(&self) -> Result<VerifyingKey, TraceError> {
+        decode_pubkey(&self.pubkey)
+    }
+
+    /// Verify a trace whose signing key may have been rotated mid-run: check
+    /// its hash chain (see [`verify_chain`]), walk the `key.rotate` events
+    /// from `initial` (see [`verify_key_rotations`]) and check the signature
+    /// with the last key. Returns that key.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] if the trace does not parse, its chain is broken, a
+    /// rotation is not signed by the key it replaces, or the final signature
+    /// does not match.
+    pub fn verify_with_rotations(
+        &self,
+        initial: &VerifyingKey,
+    ) -> Result<VerifyingKey, TraceError> {
+        let trace = serde_json::from_str::<Vec<TraceEvent>>(&self.trace_json)?;
+        verify_chain(&trace)?;
+        let current = verify_key_rotations(&trace, initial)?;
+        self.verify(&current)?;
+        Ok(current)
+    }
+
+    /// Verify with the embedded public key, after checking it against a pinned
+    /// fingerprint (see [`pubkey_fingerprint`]). Returns the verified key.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::FingerprintMismatch`] or [`TraceError::InvalidSignature`].
+    pub fn verify_with_pinned_fingerprint(&self, fpr: &str) -> Result<VerifyingKey, TraceError> {
+        let pubkey = self.embedded_pubkey()?;
+        let actual = pubkey_fingerprint(pubkey.as_bytes());
+        if actual != fpr {
+            return Err(TraceError::FingerprintMismatch {
+                expected: fpr.into(),
+                actual,
+            });
+        }
+        self.verify(&pubkey)?;
+        Ok(pubkey)
+    }
+
+    /// Attach manifest audit metadata to the header.
+    #[inline]
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: Option<AuditMetadata>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Record how the trace's `ts_seed` values were produced.
+    #[inline]
+    #[must_use]
+    pub const fn with_randomness(mut self, mode: RandomnessMode) -> Self {
+        self.randomness = Some(mode);
+        self
+    }
+
+    /// Record the granularity the guest clock was coarsened to.
+    #[inline]
+    #[must_use]
+    pub const fn with_time_granularity(mut self, granularity_ms: Option<u64>) -> Self {
+        self.time_granularity_ms = granularity_ms;
+        self
+    }
+
+    /// Embed the full manifest the run was pinned to, see [`SignedTrace::manifest`].
+    #[inline]
+    #[must_use]
+    pub fn with_manifest(mut self, manifest: CapabilityManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Attach run labels, see [`SignedTrace::labels`].
+    #[inline]
+    #[must_use]
+    pub fn with_labels(mut self, labels: RunLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Record the manifest profile, see [`SignedTrace::profile`].
+    #[inline]
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Value of run label `key`, if set.
+    #[must_use]
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels.get(key).map(String::as_str)
+    }
+
+    /// Verify the trace with `pubkey` and return the embedded manifest
+    /// (`None` if the trace only carries its hash). The signature covers
+    /// `manifest_hash`, which the embedded manifest must hash to.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::InvalidSignature`], or [`TraceError::ManifestMismatch`]
+    /// if the embedded manifest does not hash to `manifest_hash`.
+    pub fn manifest(
+        &self,
+        pubkey: &VerifyingKey,
+    ) -> Result<Option<&CapabilityManifest>, TraceError> {
+        self.verify(pubkey)?;
+        let Some(manifest) = &self.manifest else {
+            return Ok(None);
+        };
+        let actual = manifest.content_hash();
+        if actual != self.manifest_hash {
+            return Err(TraceError::ManifestMismatch {
+                expected: self.manifest_hash.clone(),
+                actual,
+            });
         }
+        Ok(Some(manifest))
+    }
+
+    /// Recompute `ts_seed`s of `trace_json` if the header records
+    /// deterministic randomness; a no-op otherwise.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::Serialize`] if `trace_json` is malformed, or
+    /// [`TraceError::IntegrityViolation`] at the first mismatching event.
+    pub fn verify_ts_seeds(&self) -> Result<(), TraceError> {
+        let Some(mode) = self.randomness else {
+            return Ok(());
+        };
+        let trace = serde_json::from_str::<Vec<TraceEvent>>(&self.trace_json)?;
+        verify_ts_seeds(&trace, mode)
+    }
+
+    /// Write the trace as detached artifacts in `dir`: the exact signed bytes
+    /// (`trace.json`), the base64 signature (`trace.sig`) and the remaining
+    /// header (`trace.meta.json`).
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] if `dir` cannot be created or a file cannot be written.
+    pub fn write_detached<P: AsRef<Path>>(&self, dir: P) -> Result<(), TraceError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let meta = DetachedMeta {
+            run_id: self.run_id.clone(),
+            manifest_hash: self.manifest_hash.clone(),
+            metadata: self.metadata.clone(),
+            pubkey: self.pubkey.clone(),
+            randomness: self.randomness,
+            time_granularity_ms: self.time_granularity_ms,
+            manifest: self.manifest.clone(),
+            labels: self.labels.clone(),
+            profile: self.profile.clone(),
+        };
+        fs::write(dir.join(DETACHED_TRACE_FILE), &self.trace_json)?;
+        fs::write(dir.join(DETACHED_SIG_FILE), format!("{}\n", self.signature))?;
+        fs::write(
+            dir.join(DETACHED_META_FILE),
+            serde_json::to_string_pretty(&meta)?,
+        )?;
+        Ok(())
+    }
+
+    /// Read artifacts written by [`SignedTrace::write_detached`] and verify
+    /// them with `pubkey`.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError`] if a file is missing or malformed, or the signature does not match.
+    pub fn verify_detached<P: AsRef<Path>>(
+        dir: P,
+        pubkey: &VerifyingKey,
+    ) -> Result<Self, TraceError> {
+        let dir = dir.as_ref();
+        let trace_json = fs::read_to_string(dir.join(DETACHED_TRACE_FILE))?;
+        let signature = fs::read_to_string(dir.join(DETACHED_SIG_FILE))?;
+        let meta = serde_json::from_str::<DetachedMeta>(&fs::read_to_string(
+            dir.join(DETACHED_META_FILE),
+        )?)?;
+        let signed = Self {
+            run_id: meta.run_id,
+            manifest_hash: meta.manifest_hash,
+            trace_json,
+            signature: signature.trim().to_owned(),
+            metadata: meta.metadata,
+            pubkey: meta.pubkey,
+            randomness: meta.randomness,
+            time_granularity_ms: meta.time_granularity_ms,
+            manifest: meta.manifest,
+            labels: meta.labels,
+            profile: meta.profile,
+        };
+        signed.verify(pubkey)?;
+        Ok(signed)
     }
 }
 
-/// Save the current trace to a file as pretty JSON.
+/// Magic string in the [`TraceFileHeader`] of saved trace files.
+pub const TRACE_MAGIC: &str = "CAPTRA";
+/// Envelope version written by [`save_trace`].
+pub const TRACE_FORMAT_VERSION: u32 = 1;
+
+/// Header object leading a saved trace file, so tools can recognize captra
+/// traces without parsing the events.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TraceFileHeader {
+    /// Always [`TRACE_MAGIC`]
+    pub magic: String,
+    pub format_version: u32,
+    /// `captra <version>` of the writer
+    pub producer: String,
+    pub run_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub manifest_hash: String,
+    /// Run labels, see [`SignedTrace::labels`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: RunLabels,
+    /// Manifest profile, see [`SignedTrace::profile`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+impl TraceFileHeader {
+    /// Header for a file written by this version of captra.
+    #[must_use]
+    pub fn new(run_id: impl Into<String>, manifest_hash: impl Into<String>) -> Self {
+        Self {
+            magic: TRACE_MAGIC.into(),
+            format_version: TRACE_FORMAT_VERSION,
+            producer: concat!("captra ", env!("CARGO_PKG_VERSION")).into(),
+            run_id: run_id.into(),
+            manifest_hash: manifest_hash.into(),
+            labels: RunLabels::new(),
+            profile: None,
+        }
+    }
+
+    /// Record run labels, see [`SignedTrace::labels`].
+    #[inline]
+    #[must_use]
+    pub fn with_labels(mut self, labels: RunLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Record the manifest profile, see [`SignedTrace::profile`].
+    #[inline]
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Header for `trace`, taking the run id from its first event.
+    #[must_use]
+    pub fn for_trace(trace: &[TraceEvent]) -> Self {
+        Self::new(
+            trace
+                .first()
+                .map(|event| event.run_id.clone())
+                .unwrap_or_default(),
+            String::new(),
+        )
+    }
+
+    fn check(&self) -> Result<(), TraceError> {
+        if self.magic != TRACE_MAGIC {
+            return Err(TraceError::UnsupportedFormat(format!(
+                "magic `{}` is not `{TRACE_MAGIC}`",
+                self.magic
+            )));
+        }
+        if self.format_version > TRACE_FORMAT_VERSION {
+            return Err(TraceError::UnsupportedFormat(format!(
+                "format version {} is newer than {TRACE_FORMAT_VERSION}",
+                self.format_version
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct TraceFileRef<'a> {
+    header: &'a TraceFileHeader,
+    events: &'a [TraceEvent],
+}
+
+#[derive(Deserialize)]
+struct TraceFile {
+    header: TraceFileHeader,
+    events: Vec<TraceEvent>,
+}
+
+/// Save the current trace to a file as pretty JSON, behind a
+/// [`TraceFileHeader`] derived from the events.
+///
+/// The trace is checked with [`verify_chain`] first, so corrupted in-memory
+/// state is never persisted.
 ///
 /// # Errors
 ///
-/// [`TraceError`] (JSON or IO).
+/// [`TraceError`] (JSON or IO), or [`TraceError::IntegrityViolation`] if the
+/// trace does not verify.
 pub fn save_trace<P: AsRef<Path>>(trace: &[TraceEvent], path: P) -> Result<(), TraceError> {
-    let json_str = serde_json::to_string_pretty(trace)?;
+    save_trace_with_header(trace, &TraceFileHeader::for_trace(trace), path)
+}
+
+/// Like [`save_trace`], with an explicit `header`.
+///
+/// # Errors
+///
+/// [`TraceError`] (JSON or IO), or [`TraceError::IntegrityViolation`] if the
+/// trace does not verify or belongs to another run than `header`.
+pub fn save_trace_with_header<P: AsRef<Path>>(
+    trace: &[TraceEvent],
+    header: &TraceFileHeader,
+    path: P,
+) -> Result<(), TraceError> {
+    verify_chain(trace)?;
+    if let Some(event) = trace
+        .first()
+        .filter(|event| !header.run_id.is_empty() && event.run_id != header.run_id)
+    {
+        return Err(TraceError::IntegrityViolation {
+            seq: event.seq,
+            reason: "run_id differs from file header".into(),
+        });
+    }
+    let file = TraceFileRef {
+        header,
+        events: trace,
+    };
+    let json_str = serde_json::to_string_pretty(&file)?;
     fs::write(path, json_str)?;
     Ok(())
 }
 
 /// Load a trace from a JSON file to [`Vec<TraceEvent>`].
 ///
+/// Both files with a [`TraceFileHeader`] and legacy bare event arrays are
+/// accepted. Events of unknown types are kept as [`EventType::Other`], see
+/// [`load_trace_with`].
+///
 /// # Errors
 ///
-/// [`TraceError`] (JSON or IO).
+/// [`TraceError`] (JSON or IO), or [`TraceError::UnsupportedFormat`] for a
+/// header with the wrong magic or a newer format version.
 pub fn load_trace<P: AsRef<Path>>(path: P) -> Result<Vec<TraceEvent>, TraceError> {
     let json_str = fs::read_to_string(path)?;
-    let trace = serde_json::from_str(&json_str)?;
+    parse_trace_file(&json_str).map(|(_, trace)| trace)
+}
+
+/// Header of a saved trace file (`None` for legacy bare arrays).
+///
+/// # Errors
+///
+/// [`TraceError`] (JSON or IO), or [`TraceError::UnsupportedFormat`] as for
+/// [`load_trace`].
+pub fn load_trace_header<P: AsRef<Path>>(path: P) -> Result<Option<TraceFileHeader>, TraceError> {
+    let json_str = fs::read_to_string(path)?;
+    parse_trace_file(&json_str).map(|(header, _)| header)
+}
+
+fn parse_trace_file(
+    json_str: &str,
+) -> Result<(Option<TraceFileHeader>, Vec<TraceEvent>), TraceError> {
+    if json_str.trim_start().starts_with('[') {
+        return Ok((None, serde_json::from_str(json_str)?));
+    }
+    let file = serde_json::from_str::<TraceFile>(json_str)?;
+    file.header.check()?;
+    Ok((Some(file.header), file.events))
+}
+
+/// How [`load_trace_with`] treats events it does not understand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceLoadOptions {
+    /// Reject events of a type unknown to this version
+    /// ([`EventType::Other`]) instead of keeping them verbatim.
+    pub deny_unknown_event_types: bool,
+}
+
+impl TraceLoadOptions {
+    /// Options rejecting unknown event types.
+    #[inline]
+    #[must_use]
+    pub const fn strict() -> Self {
+        Self {
+            deny_unknown_event_types: true,
+        }
+    }
+
+    /// Apply the options to an already parsed trace.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::UnknownEventType`] for the first event of an unknown
+    /// type, if those are denied.
+    pub fn check(&self, trace: &[TraceEvent]) -> Result<(), TraceError> {
+        if !self.deny_unknown_event_types {
+            return Ok(());
+        }
+        trace
+            .iter()
+            .find(|event| !event.event_type.is_known())
+            .map_or(Ok(()), |event| {
+                Err(TraceError::UnknownEventType {
+                    seq: event.seq,
+                    event_type: event.event_type.wire_name().to_owned(),
+                })
+            })
+    }
+}
+
+/// Like [`load_trace`], applying `options`.
+///
+/// # Errors
+///
+/// [`TraceError`] (JSON or IO), or [`TraceError::UnknownEventType`] under
+/// [`TraceLoadOptions::strict`].
+pub fn load_trace_with<P: AsRef<Path>>(
+    path: P,
+    options: TraceLoadOptions,
+) -> Result<Vec<TraceEvent>, TraceError> {
+    let trace = load_trace(path)?;
+    options.check(&trace)?;
     Ok(trace)
 }
 
+/// Column order of [`export_csv`].
+pub const CSV_HEADER: [&str; 12] = [
+    "run_id",
+    "seq",
+    "event_type",
+    "input",
+    "outcome",
+    "ts_seed",
+    "vclock",
+    "policy_epoch",
+    "severity",
+    "details",
+    "repeat_count",
+    "prev_hash",
+];
+
+/// Write the trace as RFC 4180 CSV (one row per event, header first) for
+/// analytics tooling. `vclock` and `details` are embedded as JSON (empty if
+/// absent).
+///
+/// # Errors
+///
+/// [`TraceError`] (JSON or IO), or [`TraceError::IntegrityViolation`] if the
+/// trace does not pass [`verify_chain`]; nothing is written then.
+pub fn export_csv<W: Write>(trace: &[TraceEvent], writer: W) -> Result<(), TraceError> {
+    verify_chain(trace)?;
+    write_csv(trace, writer)
+}
+
+/// Save the trace to a CSV file, see [`export_csv`]. Nothing is created if
+/// the chain is broken.
+///
+/// # Errors
+///
+/// [`TraceError`] (JSON or IO), or [`TraceError::IntegrityViolation`] as for
+/// [`export_csv`].
+pub fn save_trace_csv<P: AsRef<Path>>(trace: &[TraceEvent], path: P) -> Result<(), TraceError> {
+    verify_chain(trace)?;
+    let file = fs::File::create(path)?;
+    write_csv(trace, std::io::BufWriter::new(file))
+}
+
+/// [`export_csv`] of a trace whose chain was already verified.
+fn write_csv<W: Write>(trace: &[TraceEvent], mut writer: W) -> Result<(), TraceError> {
+    writeln!(writer, "{}", CSV_HEADER.join(","))?;
+    for event in trace {
+        let vclock = event
+            .vclock
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?
+            .unwrap_or_default();
+        let details = event
+            .details
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?
+            .unwrap_or_default();
+        let row = [
+            csv_field(&event.run_id),
+            event.seq.to_string(),
+            event.event_type.to_string(),
+            csv_field(&event.input),
+            event.outcome.to_string(),
+            event.ts_seed.to_string(),
+            csv_field(&vclock),
+            event.policy_epoch.to_string(),
+            event.severity.to_string(),
+            csv_field(&details),
+            event.repeat_count.to_string(),
+            event.prev_hash.clone(),
+        ];
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Render the trace as an aligned, ANSI-colored table of seq/type/outcome/input
+/// for quick human inspection; denials are highlighted in red.
+///
+/// Control and bidi characters in event types and inputs are escaped, so
+/// guest-chosen paths or crafted trace files cannot inject terminal sequences
+/// or break the layout.
+#[must_use]
+pub fn render_table(trace: &[TraceEvent]) -> String {
+    render_table_with(trace, true)
+}
+
+/// `text` with control and bidi override characters escaped as `\u{..}` (or
+/// `\n`, `\t`, ...).
+fn escape_controls(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut out, c| {
+            if c.is_control() || matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}') {
+                out.extend(c.escape_default());
+            } else {
+                out.push(c);
+            }
+            out
+        })
+}
+
+/// Like [`render_table`], optionally without ANSI colors (e.g. when not writing to a TTY).
+#[must_use]
+pub fn render_table_with(trace: &[TraceEvent], color: bool) -> String {
+    const RED: &str = "\x1b[31m";
+    const GREEN: &str = "\x1b[32m";
+    const BOLD: &str = "\x1b[1m";
+    const RESET: &str = "\x1b[0m";
+
+    let rows = trace
+        .iter()
+        .map(|event| {
+            let outcome = if event.outcome { "allow" } else { "deny" };
+            [
+                event.seq.to_string(),
+                escape_controls(&event.event_type.to_string()),
+                outcome.to_owned(),
+                escape_controls(&event.input),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["SEQ", "TYPE", "OUTCOME", "INPUT"].map(String::from);
+    let widths = [0, 1, 2].map(|col| {
+        rows.iter()
+            .chain(std::iter::once(&header))
+            .map(|row| row[col].chars().count())
+            .max()
+            .unwrap_or_default()
+    });
+
+    let paint = |text: String, code: &str| {
+        if color {
+            format!("{code}{text}{RESET}")
+        } else {
+            text
+        }
+    };
+    let line = |row: &[String; 4]| {
+        format!(
+            "{:>w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        )
+    };
+
+    let mut out = paint(line(&header), BOLD);
+    out.push('\n');
+    for (row, event) in rows.iter().zip(trace) {
+        let code = if event.outcome { GREEN } else { RED };
+        out.push_str(&paint(line(row), code));
+        out.push('\n');
+    }
+    out
+}
+
 /// Serialize trace to pretty JSON string (fallback to "[]").
 #[inline]
 #[must_use]
@@ -100,23 +941,754 @@ pub fn finalize_trace(trace: &[TraceEvent]) -> String {
     serde_json::to_string_pretty(trace).unwrap_or_else(|_| "[]".into())
 }
 
-/// Log a trace event
-pub fn log_trace_event(
-    seq: u64,
-    event_type: EventType,
-    input: &str,
-    outcome: bool,
-    ts_seed: u64,
-    plugin: &str,
-) {
-    info!(
-        seq = seq,
-        ts_seed = ts_seed,
-        event_type = %event_type,
-        input = %input,
-        outcome = outcome,
-        plugin = plugin,
-    );
+/// Running SHA256 of [`finalize_trace`] for a growing trace, updated as
+/// events are appended so its digest never needs the trace re-serialized.
+#[derive(Debug, Clone, Default)]
+pub struct TraceHasher {
+    hasher: Sha256,
+    /// State before the last event, see [`TraceHasher::replace_last`]
+    before_last: Sha256,
+    len: usize,
+}
+
+impl TraceHasher {
+    /// Hasher over every event of `trace`.
+    #[must_use]
+    pub fn from_trace(trace: &[TraceEvent]) -> Self {
+        let mut hasher = Self::default();
+        for event in trace {
+            hasher.push(event);
+        }
+        hasher
+    }
+
+    /// Append `event` as the pretty JSON array element [`finalize_trace`]
+    /// would write.
+    pub fn push(&mut self, event: &TraceEvent) {
+        self.before_last = self.hasher.clone();
+        self.hasher
+            .update(if self.len == 0 { "[\n  " } else { ",\n  " });
+        let json = serde_json::to_string_pretty(event).unwrap_or_default();
+        for (idx, line) in json.split('\n').enumerate() {
+            if idx > 0 {
+                self.hasher.update("\n  ");
+            }
+            self.hasher.update(line);
+        }
+        self.len += 1;
+    }
+
+    /// Swap the last pushed event for `event`, e.g. after it was updated in place.
+    pub fn replace_last(&mut self, event: &TraceEvent) {
+        if self.len > 0 {
+            self.hasher = self.before_last.clone();
+            self.len -= 1;
+        }
+        self.push(event);
+    }
+
+    /// Number of events hashed
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Hex digest, equal to `sha256_hex(finalize_trace(trace).as_bytes())`.
+    #[must_use]
+    pub fn digest(&self) -> String {
+        if self.len == 0 {
+            return sha256_hex(b"[]");
+        }
+        format!("{:x}", self.hasher.clone().chain_update("\n]").finalize())
+    }
+}
+
+/// Signature over the digest of a trace prefix, see
+/// [`crate::HostState::sign_checkpoint`]; cheap to take periodically during
+/// long runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceCheckpoint {
+    pub run_id: String,
+    /// Events covered: the first `len` of the trace
+    pub len: usize,
+    /// Last seq covered
+    pub seq: u64,
+    /// SHA256 hex of [`finalize_trace`] over the covered events
+    pub trace_hash: String,
+    /// Base64 signature of [`checkpoint_message`]
+    pub signature: String,
+}
+
+/// Message a [`SignedTrace`] signs: the SHA256 of its trace JSON, prefixed
+/// and bound to the run and the manifest hash in its header, so neither can
+/// be swapped without breaking the signature.
+#[must_use]
+pub fn trace_message(run_id: &str, manifest_hash: &str, trace_hash: &str) -> String {
+    format!("captra-trace\0{run_id}\0{manifest_hash}\0{trace_hash}")
+}
+
+/// Message a [`TraceCheckpoint`] signs. Prefixed and bound to the run and
+/// event count, so it can never pass for a [`SignedTrace`] signature over
+/// the truncated trace.
+#[must_use]
+pub fn checkpoint_message(run_id: &str, len: usize, trace_hash: &str) -> String {
+    format!("captra-checkpoint\0{run_id}\0{len}\0{trace_hash}")
+}
+
+impl TraceCheckpoint {
+    /// Check that the first [`TraceCheckpoint::len`] events of `trace` hash to
+    /// `trace_hash` and that `pubkey` signed it.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::IntegrityViolation`] if `trace` is shorter or its prefix
+    /// differs, [`TraceError::InvalidSignature`] if the signature does not match.
+    pub fn verify(&self, trace: &[TraceEvent], pubkey: &VerifyingKey) -> Result<(), TraceError> {
+        let violation = |reason: &str| TraceError::IntegrityViolation {
+            seq: self.seq,
+            reason: reason.into(),
+        };
+        let prefix = trace
+            .get(..self.len)
+            .ok_or_else(|| violation("trace is shorter than the checkpoint"))?;
+        if TraceHasher::from_trace(prefix).digest() != self.trace_hash {
+            return Err(violation("trace does not match the checkpoint hash"));
+        }
+        let sig_bytes = general_purpose::STANDARD.decode(&self.signature)?;
+        let signature =
+            Signature::from_slice(&sig_bytes).map_err(|_| TraceError::InvalidSignature)?;
+        let message = checkpoint_message(&self.run_id, self.len, &self.trace_hash);
+        pubkey
+            .verify(message.as_bytes(), &signature)
+            .map_err(|_| TraceError::InvalidSignature)
+    }
+}
+
+/// Cut `input` to at most `max_len` bytes (on a char boundary) and append
+/// `…(+N bytes, sha256=<hash of the full input>)`, so oversized guest input
+/// stays auditable without being stored.
+#[must_use]
+pub fn truncate_input(input: String, max_len: usize) -> String {
+    if input.len() <= max_len {
+        return input;
+    }
+    let cut = (0..=max_len)
+        .rev()
+        .find(|idx| input.is_char_boundary(*idx))
+        .unwrap_or_default();
+    format!(
+        "{}…(+{} bytes, sha256={})",
+        &input[..cut],
+        input.len() - cut,
+        sha256_hex(input.as_bytes())
+    )
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // serde passes fields by reference
+const fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// Hex-encoded SHA256 of `bytes`.
+#[must_use]
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+/// OpenSSH-style fingerprint of a public key: `SHA256:` + unpadded base64 digest.
+#[must_use]
+pub fn pubkey_fingerprint(pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(pubkey);
+    format!(
+        "SHA256:{}",
+        general_purpose::STANDARD_NO_PAD.encode(hasher.finalize())
+    )
+}
+
+/// Lowercase hex encoding of a public key.
+#[must_use]
+pub fn pubkey_hex(pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    pubkey.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// PEM `PUBLIC KEY` (X.509 `SubjectPublicKeyInfo`) encoding, as read by
+/// OpenSSL and most TLS tooling.
+#[must_use]
+pub fn pubkey_pem(pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    // SEQUENCE { SEQUENCE { OID 1.3.101.112 (Ed25519) }, BIT STRING { key } }
+    const SPKI_PREFIX: [u8; 12] = [
+        0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+    ];
+    let der = [SPKI_PREFIX.as_slice(), pubkey].concat();
+    format!(
+        "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+        general_purpose::STANDARD.encode(der)
+    )
+}
+
+/// `ssh-ed25519 <base64>` line as found in `authorized_keys`.
+#[must_use]
+pub fn pubkey_openssh(pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    format!(
+        "ssh-ed25519 {}",
+        general_purpose::STANDARD.encode(openssh_blob(pubkey))
+    )
+}
+
+/// Fingerprint exactly as printed by `ssh-keygen -l`: `SHA256:` + unpadded
+/// base64 digest of the SSH wire encoding. Unlike [`pubkey_fingerprint`],
+/// which digests the raw key bytes.
+#[must_use]
+pub fn pubkey_openssh_fingerprint(pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(openssh_blob(pubkey));
+    format!(
+        "SHA256:{}",
+        general_purpose::STANDARD_NO_PAD.encode(hasher.finalize())
+    )
+}
+
+/// SSH wire encoding: length-prefixed key type followed by the length-prefixed key.
+fn openssh_blob(pubkey: &[u8; PUBLIC_KEY_LENGTH]) -> Vec<u8> {
+    const KEY_TYPE: &[u8] = b"ssh-ed25519";
+    let mut blob = Vec::with_capacity(8 + KEY_TYPE.len() + PUBLIC_KEY_LENGTH);
+    for field in [KEY_TYPE, pubkey.as_slice()] {
+        blob.extend_from_slice(&u32::try_from(field.len()).unwrap_or(u32::MAX).to_be_bytes());
+        blob.extend_from_slice(field);
+    }
+    blob
+}
+
+/// Bytes the outgoing key signs in a `key.rotate` event: the event position
+/// (`run_id`, `seq`, `prev_hash`) and the incoming base64 public key.
+pub fn key_rotation_message(run_id: &str, seq: u64, prev_hash: &str, new_pubkey: &str) -> Vec<u8> {
+    format!("captra-key-rotate:{run_id}:{seq}:{prev_hash}:{new_pubkey}").into_bytes()
+}
+
+/// Follow the `key.rotate` events of `trace` starting from `initial`, checking
+/// each is signed by the key it replaces. Returns the key in force at the end.
+///
+/// # Errors
+///
+/// [`TraceError::IntegrityViolation`] at the first malformed or mis-signed rotation.
+pub fn verify_key_rotations(
+    trace: &[TraceEvent],
+    initial: &VerifyingKey,
+) -> Result<VerifyingKey, TraceError> {
+    let mut current = *initial;
+    for event in trace
+        .iter()
+        .filter(|ev| ev.event_type == EventType::KeyRotate)
+    {
+        let violation = |reason: &str| TraceError::IntegrityViolation {
+            seq: event.seq,
+            reason: reason.into(),
+        };
+        let field = |name| {
+            event
+                .details
+                .as_ref()
+                .and_then(|details| details[name].as_str())
+                .ok_or_else(|| violation(&format!("key.rotate without {name}")))
+        };
+        let new_pubkey = field("new_pubkey")?;
+        let signature = general_purpose::STANDARD
+            .decode(field("signature")?)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| violation("malformed rotation signature"))?;
+        let message = key_rotation_message(&event.run_id, event.seq, &event.prev_hash, new_pubkey);
+        current
+            .verify(&message, &signature)
+            .map_err(|_| violation("rotation not signed by the previous key"))?;
+        current = decode_pubkey(new_pubkey).map_err(|_| violation("malformed new public key"))?;
+    }
+    Ok(current)
+}
+
+fn decode_pubkey(encoded: &str) -> Result<VerifyingKey, TraceError> {
+    let bytes = general_purpose::STANDARD.decode(encoded)?;
+    let bytes = <[u8; PUBLIC_KEY_LENGTH]>::try_from(bytes.as_slice())
+        .map_err(|_| TraceError::InvalidPublicKey)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| TraceError::InvalidPublicKey)
+}
+
+/// Hash of a single event, including its `prev_hash` link.
+#[must_use]
+pub fn event_hash(event: &TraceEvent) -> String {
+    let json = serde_json::to_string(event).unwrap_or_default();
+    sha256_hex(json.as_bytes())
+}
+
+/// Verify seq contiguity (starting at 1), `run_id` consistency and the hash chain.
+/// Returns the hash of the last event (empty for an empty trace).
+///
+/// # Errors
+///
+/// [`TraceError::IntegrityViolation`] at the first offending event.
+pub fn verify_chain(trace: &[TraceEvent]) -> Result<String, TraceError> {
+    let mut chain = ChainVerifier::default();
+    for event in trace {
+        chain.push(event)?;
+    }
+    Ok(chain.head)
+}
+
+/// [`verify_chain`] fed one event at a time, for traces too large to load.
+#[derive(Debug, Clone, Default)]
+pub struct ChainVerifier {
+    run_id: Option<String>,
+    len: u64,
+    head: String,
+}
+
+impl ChainVerifier {
+    /// Check that `event` extends the chain verified so far.
+    ///
+    /// # Errors
+    ///
+    /// [`TraceError::IntegrityViolation`] if it does not; the verifier is
+    /// left unchanged.
+    pub fn push(&mut self, event: &TraceEvent) -> Result<(), TraceError> {
+        let violation = |reason: &str| TraceError::IntegrityViolation {
+            seq: event.seq,
+            reason: reason.into(),
+        };
+        let expected_seq = self.len + 1;
+        if event.seq != expected_seq {
+            return Err(violation(&format!("expected seq {expected_seq}")));
+        }
+        if self
+            .run_id
+            .as_ref()
+            .is_some_and(|run_id| *run_id != event.run_id)
+        {
+            return Err(violation("run_id differs from first event"));
+        }
+        if event.prev_hash != self.head {
+            return Err(violation("prev_hash does not link to previous event"));
+        }
+        self.run_id.get_or_insert_with(|| event.run_id.clone());
+        self.len = event.last_seq();
+        self.head = event_hash(event);
+        Ok(())
+    }
+
+    /// Number of events verified, counting folded repeats (the last seq)
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Hash of the last verified event (empty before the first)
+    #[inline]
+    #[must_use]
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Run id of the verified events
+    #[inline]
+    #[must_use]
+    pub fn run_id(&self) -> Option<&str> {
+        self.run_id.as_deref()
+    }
+}
+
+/// Controls which runs [`compact`] folds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionPolicy {
+    /// Shortest run of identical allow events folded into one aggregate (at least 2).
+    pub min_run: usize,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self { min_run: 2 }
+    }
+}
+
+/// Build a summary trace for long-running hosts, keeping every denial verbatim.
+///
+/// Each run of consecutive allow events with the same type, input and details
+/// becomes one `trace.aggregate` event whose details hold `event_type`,
+/// `count`, `first_seq` and `last_seq`. The summary is its own run,
+/// `{run_id}.summary` (see [`summary_run_id`]): it is renumbered from seq 1,
+/// draws `ts_seed`s for the new seqs from `randomness` and has its own hash
+/// chain, so it can be signed separately.
+#[must_use]
+pub fn compact(
+    trace: &[TraceEvent],
+    policy: CompactionPolicy,
+    randomness: RandomnessMode,
+) -> Vec<TraceEvent> {
+    let run_id = trace
+        .first()
+        .map(|ev| summary_run_id(&ev.run_id))
+        .unwrap_or_default();
+    let mut summary = Vec::<TraceEvent>::new();
+    let mut push = |mut event: TraceEvent| {
+        event.seq = summary.last().map_or(1, |prev| prev.last_seq() + 1);
+        event.ts_seed = randomness.ts_seed(event.seq);
+        event.run_id.clone_from(&run_id);
+        event.prev_hash = summary.last().map(event_hash).unwrap_or_default();
+        summary.push(event);
+    };
+
+    let mut rest = trace;
+    while let Some(first) = rest.first() {
+        let run = if first.outcome {
+            rest.iter()
+                .take_while(|ev| {
+                    ev.outcome
+                        && ev.event_type == first.event_type
+                        && ev.input == first.input
+                        && ev.details == first.details
+                })
+                .count()
+        } else {
+            1
+        };
+        let (group, tail) = rest.split_at(run);
+        rest = tail;
+
+        let Some(last) = group.last().filter(|_| run >= policy.min_run.max(2)) else {
+            group.iter().cloned().for_each(&mut push);
+            continue;
+        };
+        push(TraceEvent {
+            event_type: EventType::Aggregate,
+            vclock: last.vclock.clone(),
+            details: Some(serde_json::json!({
+                "event_type": first.event_type,
+                "count": run,
+                "first_seq": first.seq,
+                "last_seq": last.seq,
+            })),
+            ..first.clone()
+        });
+    }
+    summary
+}
+
+/// First field of the deterministic core of two events that differs, in the
+/// order `seq`, `event_type`, `run_id`, `input`, `outcome`, `ts_seed`,
+/// `vclock`, `details`, skipping fields `skip` returns true for.
+///
+/// Shared by replay divergence checks and golden-trace comparisons; `prev_hash`
+/// is derived from the others and never compared.
+pub fn differing_field(
+    want: &TraceEvent,
+    got: &TraceEvent,
+    skip: impl Fn(&str) -> bool,
+) -> Option<&'static str> {
+    [
+        ("seq", want.seq == got.seq),
+        ("event_type", want.event_type == got.event_type),
+        ("run_id", want.run_id == got.run_id),
+        ("input", want.input == got.input),
+        ("outcome", want.outcome == got.outcome),
+        ("ts_seed", want.ts_seed == got.ts_seed),
+        ("vclock", want.vclock == got.vclock),
+        ("details", want.details == got.details),
+    ]
+    .into_iter()
+    .find_map(|(name, equal)| (!equal && !skip(name)).then_some(name))
+}
+
+/// Run id of the [`compact`]ed summary of run `run_id`, so it cannot be
+/// mistaken for the full trace.
+#[must_use]
+pub fn summary_run_id(run_id: &str) -> String {
+    format!("{run_id}.summary")
+}
+
+/// Recompute every `ts_seed` under `mode`. Only [`RandomnessMode::Deterministic`]
+/// traces can be checked; [`RandomnessMode::Os`] always passes.
+///
+/// # Errors
+///
+/// [`TraceError::IntegrityViolation`] at the first mismatching event.
+pub fn verify_ts_seeds(trace: &[TraceEvent], mode: RandomnessMode) -> Result<(), TraceError> {
+    if mode == RandomnessMode::Os {
+        return Ok(());
+    }
+    if let Some(event) = trace.iter().find(|ev| ev.ts_seed != mode.ts_seed(ev.seq)) {
+        return Err(TraceError::IntegrityViolation {
+            seq: event.seq,
+            reason: "ts_seed does not match the recorded seed".into(),
+        });
+    }
+    Ok(())
+}
+
+/// Causal order of two vector clocks.
+///
+/// Returns `None` when the clocks are concurrent (neither happened before the other).
+#[must_use]
+pub fn causal_order(a: &VectorClock, b: &VectorClock) -> Option<Ordering> {
+    let mut order = Ordering::Equal;
+    for host_id in a.keys().chain(b.keys()) {
+        let lhs = a.get(host_id).copied().unwrap_or_default();
+        let rhs = b.get(host_id).copied().unwrap_or_default();
+        match (order, lhs.cmp(&rhs)) {
+            (_, Ordering::Equal) => {}
+            (Ordering::Equal, ord) => order = ord,
+            (prev, ord) if prev != ord => return None,
+            _ => {}
+        }
+    }
+    Some(order)
+}
+
+/// How much of an event reaches the `tracing` log. The trace itself always
+/// keeps every event, so verbosity never affects hashes or signatures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verbosity {
+    /// Log everything, including the input.
+    #[default]
+    Full,
+    /// Log the event without its input.
+    Summary,
+    /// Log only events whose seq is a multiple of `every`.
+    Sampled {
+        every: u64,
+    },
+    Off,
+}
+
+/// Verbosity for events of `event_type`, optionally only allowed
+/// (`outcome: true`) or denied (`outcome: false`) ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRule {
+    pub event_type: EventType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<bool>,
+    pub verbosity: Verbosity,
+}
+
+/// How urgently an event deserves attention downstream.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warn,
+    Critical,
+}
+
+impl Severity {
+    #[inline]
+    #[must_use]
+    pub fn is_info(&self) -> bool {
+        *self == Self::Info
+    }
+
+    /// Built-in level: seccomp violations are critical, denials, stalls and
+    /// policy mismatches warn, everything else is info.
+    #[must_use]
+    pub const fn derived(event_type: &EventType, outcome: bool) -> Self {
+        match event_type {
+            EventType::SeccompViolation => Self::Critical,
+            EventType::ShadowMismatch | EventType::GuestStalled => Self::Warn,
+            _ if outcome => Self::Info,
+            _ => Self::Warn,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Severity override for matching events. Every set field must match;
+/// `subject` is a glob over the path or request the event is about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeverityRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<EventType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub severity: Severity,
+}
+
+impl SeverityRule {
+    fn matches(&self, event_type: &EventType, outcome: bool, subject: &str) -> bool {
+        self.event_type.as_ref().is_none_or(|t| t == event_type)
+            && self.outcome.is_none_or(|o| o == outcome)
+            && self
+                .subject
+                .as_ref()
+                .is_none_or(|pattern| glob::Pattern::new(pattern).is_ok_and(|p| p.matches(subject)))
+    }
+}
+
+/// Rules table assigning [`Severity`]: the first matching rule wins,
+/// otherwise [`Severity::derived`] applies. E.g. a denied read of `/etc/**`
+/// can be made critical.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeverityRules {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<SeverityRule>,
+}
+
+impl SeverityRules {
+    /// Append a rule (matched after the existing ones).
+    #[must_use]
+    pub fn with_rule(mut self, rule: SeverityRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Severity of an event of `event_type` with `outcome` about `subject`
+    #[must_use]
+    pub fn severity_for(&self, event_type: &EventType, outcome: bool, subject: &str) -> Severity {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(event_type, outcome, subject))
+            .map_or_else(
+                || Severity::derived(event_type, outcome),
+                |rule| rule.severity,
+            )
+    }
+}
+
+/// Per-event-type logging verbosity, e.g. every FS denial but only sampled
+/// FS allows. The first matching rule wins, otherwise `default` applies.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TracePolicy {
+    #[serde(default)]
+    pub default: Verbosity,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<TraceRule>,
+}
+
+impl TracePolicy {
+    /// Append a rule (matched after the existing ones).
+    #[must_use]
+    pub fn with_rule(
+        mut self,
+        event_type: EventType,
+        outcome: Option<bool>,
+        verbosity: Verbosity,
+    ) -> Self {
+        self.rules.push(TraceRule {
+            event_type,
+            outcome,
+            verbosity,
+        });
+        self
+    }
+
+    /// Verbosity applied to an event of `event_type` with `outcome`
+    #[must_use]
+    pub fn verbosity_for(&self, event_type: &EventType, outcome: bool) -> Verbosity {
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.event_type == *event_type && rule.outcome.is_none_or(|o| o == outcome)
+            })
+            .map_or(self.default, |rule| rule.verbosity)
+    }
+
+    /// Whether an event is logged at all, and if so with its input.
+    #[must_use]
+    pub fn logs(&self, seq: u64, event_type: &EventType, outcome: bool) -> Option<bool> {
+        match self.verbosity_for(event_type, outcome) {
+            Verbosity::Full => Some(true),
+            Verbosity::Summary => Some(false),
+            Verbosity::Sampled { every } => {
+                (every != 0 && seq.is_multiple_of(every)).then_some(true)
+            }
+            Verbosity::Off => None,
+        }
+    }
+}
+
+/// `tracing` target every recorded trace event is emitted under.
+///
+/// Events are `INFO` level with these fields, all recorded as primitives
+/// (`record_str`/`record_u64`/`record_bool`) so a `Layer` can read them
+/// without parsing `Display` output:
+///
+/// | field          | type  | notes                                   |
+/// |----------------|-------|-----------------------------------------|
+/// | `run_id`       | str   |                                         |
+/// | `seq`          | u64   |                                         |
+/// | `event_type`   | str   | wire name, see [`EventType::as_str`]    |
+/// | `input`        | str   | omitted under [`Verbosity::Summary`]    |
+/// | `outcome`      | bool  |                                         |
+/// | `ts_seed`      | u64   |                                         |
+/// | `policy_epoch` | u64   |                                         |
+/// | `severity`     | str   | `info`, `warn` or `critical`            |
+/// | `plugin`       | str   | manifest plugin name                    |
+pub const TRACE_TARGET: &str = "captra::trace";
+
+/// Log `event` under [`TRACE_TARGET`], as far as `policy` allows;
+/// `input` replaces the recorded input (e.g. with a redacted one).
+pub fn log_trace_event(policy: &TracePolicy, event: &TraceEvent, input: &str, plugin: &str) {
+    match policy.logs(event.seq, &event.event_type, event.outcome) {
+        Some(true) => info!(
+            target: TRACE_TARGET,
+            run_id = event.run_id.as_str(),
+            seq = event.seq,
+            event_type = event.event_type.as_str(),
+            input = input,
+            outcome = event.outcome,
+            ts_seed = event.ts_seed,
+            policy_epoch = event.policy_epoch,
+            severity = event.severity.as_str(),
+            plugin = plugin,
+        ),
+        Some(false) => info!(
+            target: TRACE_TARGET,
+            run_id = event.run_id.as_str(),
+            seq = event.seq,
+            event_type = event.event_type.as_str(),
+            outcome = event.outcome,
+            ts_seed = event.ts_seed,
+            policy_epoch = event.policy_epoch,
+            severity = event.severity.as_str(),
+            plugin = plugin,
+        ),
+        None => {}
+    }
 }
 
 impl FromStr for EventType {
@@ -125,18 +1697,137 @@ impl FromStr for EventType {
         match s {
             "cap.call" => Ok(Self::CapCall),
             "cap.error" => Ok(Self::CapError),
+            "cap.approval" => Ok(Self::CapApproval),
+            "cap.audit" => Ok(Self::CapAudit),
+            "shadow.mismatch" => Ok(Self::ShadowMismatch),
+            "plugin.init" => Ok(Self::PluginInit),
+            "plugin.shutdown" => Ok(Self::PluginShutdown),
+            "plugin.call" => Ok(Self::PluginCall),
+            "plugin.spawn" => Ok(Self::PluginSpawn),
+            "guest.stalled" => Ok(Self::GuestStalled),
+            "host.busy" => Ok(Self::HostBusy),
+            "fs.constraint_violation" => Ok(Self::FsConstraintViolation),
+            "fs.read" => Ok(Self::FsRead),
+            "fs.list" => Ok(Self::FsList),
+            "fs.content_filtered" => Ok(Self::FsContentFiltered),
+            "native.seccomp_violation" => Ok(Self::SeccompViolation),
+            "native.cpu_quota_exceeded" => Ok(Self::CpuQuotaExceeded),
+            "native.memory_quota_exceeded" => Ok(Self::MemoryQuotaExceeded),
+            "external" => Ok(Self::External),
+            "trace.aggregate" => Ok(Self::Aggregate),
+            "policy.reload" => Ok(Self::PolicyReload),
+            "key.rotate" => Ok(Self::KeyRotate),
+            "fault.injected" => Ok(Self::FaultInjected),
             _ => Err("Unknown event type"),
         }
     }
 }
 
-impl Display for EventType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
+impl EventType {
+    /// Display name, e.g. `cap.call` (the serialized name for
+    /// [`EventType::Other`])
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
             Self::CapCall => "cap.call",
             Self::CapError => "cap.error",
-        };
-        f.write_str(s)
+            Self::CapApproval => "cap.approval",
+            Self::CapAudit => "cap.audit",
+            Self::ShadowMismatch => "shadow.mismatch",
+            Self::PluginInit => "plugin.init",
+            Self::PluginShutdown => "plugin.shutdown",
+            Self::PluginCall => "plugin.call",
+            Self::PluginSpawn => "plugin.spawn",
+            Self::GuestStalled => "guest.stalled",
+            Self::HostBusy => "host.busy",
+            Self::FsConstraintViolation => "fs.constraint_violation",
+            Self::FsRead => "fs.read",
+            Self::FsList => "fs.list",
+            Self::FsContentFiltered => "fs.content_filtered",
+            Self::SeccompViolation => "native.seccomp_violation",
+            Self::CpuQuotaExceeded => "native.cpu_quota_exceeded",
+            Self::MemoryQuotaExceeded => "native.memory_quota_exceeded",
+            Self::External => "external",
+            Self::Aggregate => "trace.aggregate",
+            Self::PolicyReload => "policy.reload",
+            Self::KeyRotate => "key.rotate",
+            Self::FaultInjected => "fault.injected",
+            Self::Other(name) => name,
+        }
+    }
+
+    /// Whether this version knows the event kind
+    #[inline]
+    #[must_use]
+    pub const fn is_known(&self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+
+    /// Serialized name, e.g. `cap_call`
+    #[must_use]
+    pub fn wire_name(&self) -> &str {
+        if let Self::Other(name) = self {
+            return name;
+        }
+        EVENT_TYPE_WIRE_NAMES
+            .iter()
+            .find_map(|(event_type, name)| (event_type == self).then_some(*name))
+            .unwrap_or_default()
+    }
+
+    /// Parse a serialized name, falling back to [`EventType::Other`].
+    #[must_use]
+    pub fn from_wire_name(name: &str) -> Self {
+        EVENT_TYPE_WIRE_NAMES
+            .iter()
+            .find_map(|(event_type, wire)| (*wire == name).then(|| event_type.clone()))
+            .unwrap_or_else(|| Self::Other(name.to_owned()))
+    }
+}
+
+/// Serialized name of every known [`EventType`].
+const EVENT_TYPE_WIRE_NAMES: &[(EventType, &str)] = &[
+    (EventType::CapCall, "cap_call"),
+    (EventType::CapError, "cap_error"),
+    (EventType::CapApproval, "cap_approval"),
+    (EventType::CapAudit, "cap_audit"),
+    (EventType::ShadowMismatch, "shadow_mismatch"),
+    (EventType::PluginInit, "plugin_init"),
+    (EventType::PluginShutdown, "plugin_shutdown"),
+    (EventType::PluginCall, "plugin_call"),
+    (EventType::PluginSpawn, "plugin_spawn"),
+    (EventType::GuestStalled, "guest_stalled"),
+    (EventType::HostBusy, "host_busy"),
+    (EventType::FsConstraintViolation, "fs_constraint_violation"),
+    (EventType::FsRead, "fs_read"),
+    (EventType::FsList, "fs_list"),
+    (EventType::FsContentFiltered, "fs_content_filtered"),
+    (EventType::SeccompViolation, "seccomp_violation"),
+    (EventType::CpuQuotaExceeded, "cpu_quota_exceeded"),
+    (EventType::MemoryQuotaExceeded, "memory_quota_exceeded"),
+    (EventType::External, "external"),
+    (EventType::Aggregate, "aggregate"),
+    (EventType::PolicyReload, "policy_reload"),
+    (EventType::KeyRotate, "key_rotate"),
+    (EventType::FaultInjected, "fault_injected"),
+];
+
+impl Serialize for EventType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.wire_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for EventType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::from_wire_name(&name))
+    }
+}
+
+impl Display for EventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -149,6 +1840,17 @@ impl FromStr for CapEventSubtype {
             "no_read_patterns" => Ok(Self::NoReadPatterns),
             "glob_mismatch" => Ok(Self::GlobMismatch),
             "invalid_glob" => Ok(Self::InvalidGlob),
+            "constraint_violation" => Ok(Self::ConstraintViolation),
+            "no_custom_capability" => Ok(Self::NoCustomCapability),
+            "custom_denied" => Ok(Self::CustomDenied),
+            "budget_exhausted" => Ok(Self::BudgetExhausted),
+            "run_as_mismatch" => Ok(Self::RunAsMismatch),
+            "unmet_requirement" => Ok(Self::UnmetRequirement),
+            "no_time_capability" => Ok(Self::NoTimeCapability),
+            "no_blob_capability" => Ok(Self::NoBlobCapability),
+            "blob_denied" => Ok(Self::BlobDenied),
+            "no_config_capability" => Ok(Self::NoConfigCapability),
+            "config_denied" => Ok(Self::ConfigDenied),
             _ => Err("Unknown cap event subtype"),
         }
     }
@@ -162,6 +1864,17 @@ impl Display for CapEventSubtype {
             Self::NoReadPatterns => "no_read_patterns",
             Self::GlobMismatch => "glob_mismatch",
             Self::InvalidGlob => "invalid_glob",
+            Self::ConstraintViolation => "constraint_violation",
+            Self::NoCustomCapability => "no_custom_capability",
+            Self::CustomDenied => "custom_denied",
+            Self::BudgetExhausted => "budget_exhausted",
+            Self::RunAsMismatch => "run_as_mismatch",
+            Self::UnmetRequirement => "unmet_requirement",
+            Self::NoTimeCapability => "no_time_capability",
+            Self::NoBlobCapability => "no_blob_capability",
+            Self::BlobDenied => "blob_denied",
+            Self::NoConfigCapability => "no_config_capability",
+            Self::ConfigDenied => "config_denied",
         };
         f.write_str(s)
     }
@@ -170,7 +1883,11 @@ impl Display for CapEventSubtype {
 impl From<CapEventSubtype> for EventType {
     fn from(subtype: CapEventSubtype) -> Self {
         match subtype {
-            CapEventSubtype::GlobMismatch => Self::CapCall,
+            CapEventSubtype::GlobMismatch
+            | CapEventSubtype::CustomDenied
+            | CapEventSubtype::BlobDenied
+            | CapEventSubtype::ConfigDenied => Self::CapCall,
+            CapEventSubtype::ConstraintViolation => Self::FsConstraintViolation,
             _ => Self::CapError,
         }
     }
@@ -0,0 +1,756 @@
+use crate::host::{CapError, HostState, HostStatus};
+use std::{
+    cell::UnsafeCell,
+    io,
+    ops::Range,
+    sync::atomic::{AtomicU8, Ordering},
+};
+use thiserror::Error;
+use wasmtime::{Caller, Extern, Linker, Memory, MemoryType, Module, SharedMemory, Trap, WasmTy};
+
+/// Default guest memory export the host functions read from and write to.
+pub const DEFAULT_MEMORY_EXPORT: &str = "memory";
+
+/// Host functions whose results differ between runs of the same trace,
+/// refused under [`crate::WasmConfig::deterministic_guest`].
+pub const NONDETERMINISTIC_IMPORTS: &[&str] = &["now_millis"];
+
+/// Guest allocator exports as `(alloc, dealloc)` pairs, in lookup order.
+///
+/// `alloc(len) -> ptr` and `dealloc(ptr, len)` take `i64` for memory64. The
+/// host places inputs and `read_alloc` results in buffers from the first
+/// exported `alloc`, and releases inputs and outputs it is done with through
+/// the matching `dealloc`, if exported.
+pub const GUEST_ALLOCATORS: &[(&str, &str)] =
+    &[("captra_alloc", "captra_dealloc"), ("alloc", "dealloc")];
+
+/// Errors from compiling, linking, instantiating and calling wasm plugins.
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("Wasm engine setup failed: {0}")]
+    Engine(#[source] wasmtime::Error),
+
+    #[error("Wasm compilation failed: {0}")]
+    Compile(#[source] wasmtime::Error),
+
+    #[error("Host function registration failed: {0}")]
+    Link(#[source] wasmtime::Error),
+
+    #[error("Instantiation failed: {0}")]
+    Instantiate(#[source] wasmtime::Error),
+
+    #[error("Guest export `{export}` failed: {source}")]
+    Call {
+        export: String,
+        source: wasmtime::Error,
+    },
+
+    #[error("Guest exports no `{0}` memory")]
+    MissingMemory(String),
+
+    #[error("Guest input of {0} bytes does not fit in guest memory")]
+    InputTooLarge(usize),
+
+    #[error("Guest exports no allocator to place input in")]
+    MissingAllocator,
+
+    #[error("Precompiled artifact sha256={actual} does not match expected {expected}")]
+    ArtifactMismatch { expected: String, actual: String },
+
+    #[error("Import `{module}::{name}` is nondeterministic")]
+    NondeterministicImport { module: String, name: String },
+
+    #[error("Plugin start denied: {0}")]
+    Denied(#[source] CapError),
+
+    #[error("Guest export `{export}` returned invalid result {result:#x}")]
+    InvalidResult { export: String, result: i64 },
+
+    #[error("Guest JSON could not be (de)serialized: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Not a precompiled wasm module")]
+    NotPrecompiled,
+
+    #[error("IO error reading wasm artifact: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Wasm import module under which the host functions are registered.
+///
+/// Defaults to `host`; embedders exposing their own host APIs can pick a
+/// distinct name and version suffix (e.g. `captra_v1`) to avoid collisions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostNamespace {
+    name: String,
+    version: Option<String>,
+    memory: String,
+}
+
+impl HostNamespace {
+    #[inline]
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: None,
+            memory: DEFAULT_MEMORY_EXPORT.into(),
+        }
+    }
+
+    /// Append a version suffix: `captra` + `v1` -> `captra_v1`.
+    #[inline]
+    #[must_use]
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Guest memory export host functions access (default `memory`), for
+    /// multi-memory guests whose data lives elsewhere.
+    #[inline]
+    #[must_use]
+    pub fn with_memory(mut self, export: impl Into<String>) -> Self {
+        self.memory = export.into();
+        self
+    }
+
+    /// Get `memory`
+    #[inline]
+    #[must_use]
+    pub fn memory(&self) -> &str {
+        &self.memory
+    }
+
+    /// Full wasm import module name
+    #[must_use]
+    pub fn module(&self) -> String {
+        self.version.as_ref().map_or_else(
+            || self.name.clone(),
+            |version| format!("{}_{version}", self.name),
+        )
+    }
+}
+
+impl Default for HostNamespace {
+    fn default() -> Self {
+        Self::new("host")
+    }
+}
+
+/// Register host functions for Wasmtime on the provided linker under the
+/// default `host` namespace.
+///
+/// # Errors
+///
+/// See [`add_wasm_linker_funcs_in`].
+pub fn add_wasm_linker_funcs(linker: &mut Linker<HostState>) -> Result<(), WasmError> {
+    add_wasm_linker_funcs_in(linker, &HostNamespace::default())
+}
+
+/// Register host functions for Wasmtime on the provided linker, with 32-bit
+/// pointers. See [`add_wasm_linker_funcs_for`] for memory64 guests.
+///
+/// Exposes (module name given by `namespace`):
+///  - `host::read_file(ptr: i32, len: i32) -> Result<i32, Trap>`
+///  - `host::capabilities_json(buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::get_config(key_ptr: i32, key_len: i32, buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::list_dir(ptr: i32, len: i32, buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::read_range(ptr: i32, len: i32, offset: i64, length: i64, buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::read_alloc(ptr: i32, len: i32, out_ptr: i32) -> Result<i32, Trap>`
+///  - `host::read_open(ptr: i32, len: i32) -> Result<i32, Trap>`
+///  - `host::read_chunk(handle: i32, buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::read_close(handle: i32) -> Result<i64, Trap>`
+///  - `host::get_blob(name_ptr: i32, name_len: i32) -> Result<i32, Trap>`
+///  - `host::blob_len(handle: i32) -> Result<i64, Trap>`
+///  - `host::blob_read(handle: i32, offset: i64, buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::last_error(buf_ptr: i32, buf_len: i32) -> Result<i32, Trap>`
+///  - `host::now_millis() -> i64`
+///  - `host::spawn_plugin(name_ptr: i32, name_len: i32) -> Result<i32, Trap>`
+///  - `host::status_allowed() -> i32`
+///  - `host::status_denied() -> i32`
+///  - `host::status_error() -> i32`
+///  - `host::status_busy() -> i32`
+///
+/// # Errors
+///
+/// Results follow the [`HostStatus`] table: `read_file` and `spawn_plugin`
+/// return a status code, the other imports return values with a denial as
+/// `-1` and an elapsed latency cap as `-2` ([`HostStatus::length_code`]).
+/// Exceptional errors (OOB, invalid pointer, invalid UTF-8) trap.
+///
+/// `capabilities_json` returns the length of the plugin's redacted grants as
+/// JSON and writes them to the buffer only if they fit, so guests can retry
+/// with a larger buffer. `get_config` does the same with the JSON value of a
+/// config key (`null` if unset, see [`HostState::get_config`]), or returns
+/// `-1` if the key is denied. `list_dir` does the same with the
+/// newline-separated entry names, or returns `-1` if listing the directory is
+/// denied.
+/// `read_range` does the same with up to `length` bytes of the file from
+/// `offset` (see [`HostState::read_file_range`]), or returns `-1` if denied.
+/// `read_alloc` reads the whole file (see [`HostState::read_file`]) into a
+/// buffer from the guest's allocator (see [`GUEST_ALLOCATORS`]), stores its
+/// pointer at `out_ptr` and returns its length, or `-1` if denied; guests
+/// without an allocator export trap.
+/// `read_open` returns a handle for streaming a file (`-1` if denied),
+/// `read_chunk` fills the buffer from it and returns the bytes written (0 at
+/// end of file, `-1` past `max_file_bytes`), and `read_close` releases it and
+/// returns the bytes read; unknown handles trap.
+/// `get_blob` returns a handle for a host blob (`-1` if denied, see
+/// [`HostState::get_blob`]), `blob_len` its size, and `blob_read` copies from
+/// `offset` into the buffer and returns the bytes written (0 past the end).
+/// `last_error` does the same with `"<reason_code>: <message>"` of the most
+/// recent denial (length 0 if there was none). `now_millis` returns the
+/// coarsened clock (see [`HostState::now_millis`]) or `-1` if time is denied.
+/// `spawn_plugin` runs a catalog plugin as a child run (see
+/// [`HostState::spawn_plugin`]) and returns its [`HostStatus`].
+/// Once a host call's latency cap elapses (see
+/// [`HostState::with_latency_cap`]), `read_file` returns [`HostStatus::Busy`]
+/// (`2`) and `list_dir`, `read_range`, `read_alloc`, `read_open`,
+/// `read_chunk` and `get_blob` return it negated (`-2`).
+///
+/// Registration fails with [`WasmError::Link`] if a function is already defined.
+pub fn add_wasm_linker_funcs_in(
+    linker: &mut Linker<HostState>,
+    namespace: &HostNamespace,
+) -> Result<(), WasmError> {
+    register_funcs::<i32>(linker, &namespace.module(), namespace.memory()).map_err(WasmError::Link)
+}
+
+/// Like [`add_wasm_linker_funcs_in`], adapted to `module`.
+///
+/// Host functions use the memory picked by [`guest_memory_export`], and
+/// memory64 guests get `i64` pointers, lengths and `capabilities_json` result;
+/// status codes stay `i32`.
+///
+/// # Errors
+///
+/// [`WasmError::Link`] if a host function cannot be registered.
+pub fn add_wasm_linker_funcs_for(
+    linker: &mut Linker<HostState>,
+    namespace: &HostNamespace,
+    module: &Module,
+) -> Result<(), WasmError> {
+    let memory = guest_memory_export(module, namespace.memory());
+    if memory_export_is_64(module, &memory) {
+        register_funcs::<i64>(linker, &namespace.module(), &memory)
+    } else {
+        register_funcs::<i32>(linker, &namespace.module(), &memory)
+    }
+    .map_err(WasmError::Link)
+}
+
+/// Memory export of `module` host functions should use: `preferred` if
+/// exported, otherwise the only exported memory, otherwise `preferred`.
+#[must_use]
+pub fn guest_memory_export(module: &Module, preferred: &str) -> String {
+    if module
+        .get_export(preferred)
+        .is_some_and(|ty| ty.memory().is_some())
+    {
+        return preferred.to_owned();
+    }
+    let mut memories = module
+        .exports()
+        .filter(|export| export.ty().memory().is_some());
+    match (memories.next(), memories.next()) {
+        (Some(only), None) => only.name().to_owned(),
+        _ => preferred.to_owned(),
+    }
+}
+
+/// Whether the memory exported as `export` is 64-bit, falling back to
+/// [`is_memory64`] if `module` exports no such memory.
+#[must_use]
+pub fn memory_export_is_64(module: &Module, export: &str) -> bool {
+    module
+        .get_export(export)
+        .and_then(|ty| ty.memory().map(MemoryType::is_64))
+        .unwrap_or_else(|| is_memory64(module))
+}
+
+/// Whether `module` defines or imports a 64-bit memory.
+#[must_use]
+pub fn is_memory64(module: &Module) -> bool {
+    module
+        .exports()
+        .filter_map(|export| export.ty().memory().map(MemoryType::is_64))
+        .chain(
+            module
+                .imports()
+                .filter_map(|import| import.ty().memory().map(MemoryType::is_64)),
+        )
+        .any(|is_64| is_64)
+}
+
+/// Guest pointer/length type: `i32` for 32-bit memories, `i64` for memory64.
+trait GuestPtr: WasmTy + Copy + TryInto<u64> + TryFrom<usize> {
+    /// Length result signalling a policy denial, see [`HostStatus::length_code`]
+    const DENIED: Self;
+    /// Length result signalling an elapsed latency cap, see [`HostStatus::length_code`]
+    const BUSY: Self;
+
+    /// Little-endian bytes, as a guest load of the pointer type expects
+    fn to_le_vec(self) -> Vec<u8>;
+}
+
+impl GuestPtr for i32 {
+    const DENIED: Self = -(HostStatus::Denied as Self);
+    const BUSY: Self = -(HostStatus::Busy as Self);
+
+    fn to_le_vec(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl GuestPtr for i64 {
+    const DENIED: Self = -(HostStatus::Denied as Self);
+    const BUSY: Self = -(HostStatus::Busy as Self);
+
+    fn to_le_vec(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+fn register_funcs<P: GuestPtr>(
+    linker: &mut Linker<HostState>,
+    module: &str,
+    memory_export: &str,
+) -> anyhow::Result<()> {
+    register_fs_funcs::<P>(linker, module, memory_export)?;
+    register_stream_funcs::<P>(linker, module, memory_export)?;
+    register_blob_funcs::<P>(linker, module, memory_export)?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "capabilities_json",
+        move |mut caller: Caller<'_, HostState>, buf_ptr: P, buf_len: P| -> anyhow::Result<P> {
+            let json = caller.data().capabilities_json();
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let buf = guest_range(buf_ptr, buf_len, memory.data_size(&caller))?;
+            write_if_fits(&memory, &mut caller, buf, json.as_bytes())
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "get_config",
+        move |mut caller: Caller<'_, HostState>,
+              ptr: P,
+              len: P,
+              buf_ptr: P,
+              buf_len: P|
+              -> anyhow::Result<P> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let mem_len = memory.data_size(&caller);
+            let range = guest_range(ptr, len, mem_len)?;
+            let buf = guest_range(buf_ptr, buf_len, mem_len)?;
+            let key = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+
+            let json = match caller.data_mut().get_config(&key) {
+                Ok(value) => value.unwrap_or_default().to_string(),
+                Err(err) => return denied_or_trap(&err),
+            };
+            write_if_fits(&memory, &mut caller, buf, json.as_bytes())
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "last_error",
+        move |mut caller: Caller<'_, HostState>, buf_ptr: P, buf_len: P| -> anyhow::Result<P> {
+            let message = caller.data().last_denial().unwrap_or_default().to_owned();
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let buf = guest_range(buf_ptr, buf_len, memory.data_size(&caller))?;
+            write_if_fits(&memory, &mut caller, buf, message.as_bytes())
+        },
+    )?;
+    linker.func_wrap(
+        module,
+        "now_millis",
+        |mut caller: Caller<'_, HostState>| -> i64 {
+            caller
+                .data_mut()
+                .now_millis()
+                .map_or(i64::DENIED, |now| i64::try_from(now).unwrap_or(i64::MAX))
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "spawn_plugin",
+        move |mut caller: Caller<'_, HostState>, ptr: P, len: P| -> anyhow::Result<i32> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let range = guest_range(ptr, len, memory.data_size(&caller))?;
+            let name = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+            Ok(caller.data_mut().spawn_plugin(&name).into())
+        },
+    )?;
+    linker.func_wrap(module, "status_allowed", || -> i32 {
+        HostStatus::Allowed.into()
+    })?;
+    linker.func_wrap(module, "status_denied", || -> i32 {
+        HostStatus::Denied.into()
+    })?;
+    linker.func_wrap(module, "status_error", || -> i32 {
+        HostStatus::Error.into()
+    })?;
+    linker.func_wrap(module, "status_busy", || -> i32 { HostStatus::Busy.into() })?;
+    Ok(())
+}
+
+/// File system host functions: `read_file`, `list_dir`, `read_range` and
+/// `read_alloc`.
+fn register_fs_funcs<P: GuestPtr>(
+    linker: &mut Linker<HostState>,
+    module: &str,
+    memory_export: &str,
+) -> anyhow::Result<()> {
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "read_file",
+        move |mut caller: Caller<'_, HostState>, ptr: P, len: P| -> anyhow::Result<i32> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let range = guest_range(ptr, len, memory.data_size(&caller))?;
+            let path_str = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+
+            match caller.data_mut().check_read(path_str) {
+                Ok(true) => Ok(HostStatus::Allowed.into()),
+                Ok(false) => Ok(HostStatus::Denied.into()),
+                Err(err) => match HostStatus::from(&err) {
+                    HostStatus::Error => Err(Trap::MemoryOutOfBounds.into()),
+                    status => Ok(status.into()),
+                },
+            }
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "list_dir",
+        move |mut caller: Caller<'_, HostState>,
+              ptr: P,
+              len: P,
+              buf_ptr: P,
+              buf_len: P|
+              -> anyhow::Result<P> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let mem_len = memory.data_size(&caller);
+            let range = guest_range(ptr, len, mem_len)?;
+            let buf = guest_range(buf_ptr, buf_len, mem_len)?;
+            let path_str = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+
+            let listing = match caller.data_mut().list_dir(path_str) {
+                Ok(entries) => entries.join("\n"),
+                Err(err) => return denied_or_trap(&err),
+            };
+            write_if_fits(&memory, &mut caller, buf, listing.as_bytes())
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "read_range",
+        move |mut caller: Caller<'_, HostState>,
+              ptr: P,
+              len: P,
+              offset: i64,
+              length: i64,
+              buf_ptr: P,
+              buf_len: P|
+              -> anyhow::Result<P> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let mem_len = memory.data_size(&caller);
+            let range = guest_range(ptr, len, mem_len)?;
+            let buf = guest_range(buf_ptr, buf_len, mem_len)?;
+            let path_str = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+            let offset = u64::try_from(offset).map_err(|_| Trap::BadConversionToInteger)?;
+            let length = u64::try_from(length).map_err(|_| Trap::BadConversionToInteger)?;
+
+            let contents = match caller.data_mut().read_file_range(path_str, offset, length) {
+                Ok(contents) => contents,
+                Err(err) => return denied_or_trap(&err),
+            };
+            write_if_fits(&memory, &mut caller, buf, &contents)
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "read_alloc",
+        move |mut caller: Caller<'_, HostState>, ptr: P, len: P, out_ptr: P| -> anyhow::Result<P> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let mem_len = memory.data_size(&caller);
+            let range = guest_range(ptr, len, mem_len)?;
+            let out = guest_range(
+                out_ptr,
+                P::try_from(size_of::<P>()).map_err(|_| Trap::BadConversionToInteger)?,
+                mem_len,
+            )?;
+            let path_str = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+
+            let contents = match caller.data_mut().read_file(path_str) {
+                Ok(contents) => contents,
+                Err(err) => return denied_or_trap(&err),
+            };
+            let buf_ptr = alloc_in_guest::<P>(&mut caller, &memory, &contents)?;
+            memory.write(&mut caller, out.start, &buf_ptr.to_le_vec())?;
+            Ok(P::try_from(contents.len()).map_err(|_| Trap::BadConversionToInteger)?)
+        },
+    )?;
+    Ok(())
+}
+
+/// Streaming read protocol: `read_open`, `read_chunk` and `read_close`.
+fn register_stream_funcs<P: GuestPtr>(
+    linker: &mut Linker<HostState>,
+    module: &str,
+    memory_export: &str,
+) -> anyhow::Result<()> {
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "read_open",
+        move |mut caller: Caller<'_, HostState>, ptr: P, len: P| -> anyhow::Result<i32> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let range = guest_range(ptr, len, memory.data_size(&caller))?;
+            let path_str = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+
+            match caller.data_mut().open_read(path_str) {
+                Ok(handle) => Ok(i32::try_from(handle).map_err(|_| Trap::BadConversionToInteger)?),
+                Err(err) => denied_or_trap(&err),
+            }
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "read_chunk",
+        move |mut caller: Caller<'_, HostState>,
+              handle: i32,
+              buf_ptr: P,
+              buf_len: P|
+              -> anyhow::Result<P> {
+            let handle = u32::try_from(handle).map_err(|_| Trap::BadConversionToInteger)?;
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let buf = guest_range(buf_ptr, buf_len, memory.data_size(&caller))?;
+            let max_len = u64::try_from(buf.len()).map_err(|_| Trap::BadConversionToInteger)?;
+
+            let chunk = match caller.data_mut().read_chunk(handle, max_len) {
+                Ok(chunk) => chunk,
+                Err(err) => return denied_or_trap(&err),
+            };
+            write_if_fits(&memory, &mut caller, buf, &chunk)
+        },
+    )?;
+    linker.func_wrap(
+        module,
+        "read_close",
+        |mut caller: Caller<'_, HostState>, handle: i32| -> anyhow::Result<i64> {
+            let handle = u32::try_from(handle).map_err(|_| Trap::BadConversionToInteger)?;
+            let bytes = caller
+                .data_mut()
+                .close_read(handle)
+                .map_err(|_| Trap::MemoryOutOfBounds)?;
+            Ok(i64::try_from(bytes).unwrap_or(i64::MAX))
+        },
+    )?;
+    Ok(())
+}
+
+/// Host blob access: `get_blob`, `blob_len` and `blob_read`.
+fn register_blob_funcs<P: GuestPtr>(
+    linker: &mut Linker<HostState>,
+    module: &str,
+    memory_export: &str,
+) -> anyhow::Result<()> {
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "get_blob",
+        move |mut caller: Caller<'_, HostState>, ptr: P, len: P| -> anyhow::Result<i32> {
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let range = guest_range(ptr, len, memory.data_size(&caller))?;
+            let name = String::from_utf8(memory.read(&caller, range))
+                .map_err(|_| Trap::BadConversionToInteger)?;
+
+            match caller.data_mut().get_blob(&name) {
+                Ok(handle) => Ok(i32::try_from(handle).map_err(|_| Trap::BadConversionToInteger)?),
+                Err(err) => denied_or_trap(&err),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        module,
+        "blob_len",
+        |caller: Caller<'_, HostState>, handle: i32| -> anyhow::Result<i64> {
+            let handle = u32::try_from(handle).map_err(|_| Trap::BadConversionToInteger)?;
+            let blob = caller
+                .data()
+                .blob(handle)
+                .map_err(|_| Trap::MemoryOutOfBounds)?;
+            Ok(i64::try_from(blob.len()).unwrap_or(i64::MAX))
+        },
+    )?;
+    let export = memory_export.to_owned();
+    linker.func_wrap(
+        module,
+        "blob_read",
+        move |mut caller: Caller<'_, HostState>,
+              handle: i32,
+              offset: i64,
+              buf_ptr: P,
+              buf_len: P|
+              -> anyhow::Result<P> {
+            let handle = u32::try_from(handle).map_err(|_| Trap::BadConversionToInteger)?;
+            let offset = usize::try_from(offset).map_err(|_| Trap::BadConversionToInteger)?;
+            let memory = GuestMemory::get(&mut caller, &export)?;
+            let buf = guest_range(buf_ptr, buf_len, memory.data_size(&caller))?;
+
+            let blob = caller
+                .data()
+                .blob(handle)
+                .map_err(|_| Trap::MemoryOutOfBounds)?;
+            let rest = blob.get(offset..).unwrap_or_default();
+            let chunk = rest[..rest.len().min(buf.len())].to_vec();
+            write_if_fits(&memory, &mut caller, buf, &chunk)
+        },
+    )?;
+    Ok(())
+}
+
+/// A guest memory export, either instance-local or shared between threads.
+#[derive(Debug, Clone)]
+enum GuestMemory {
+    Local(Memory),
+    Shared(SharedMemory),
+}
+
+impl GuestMemory {
+    fn get(caller: &mut Caller<'_, HostState>, export: &str) -> Result<Self, Trap> {
+        match caller.get_export(export) {
+            Some(Extern::Memory(memory)) => Ok(Self::Local(memory)),
+            Some(Extern::SharedMemory(memory)) => Ok(Self::Shared(memory)),
+            _ => Err(Trap::MemoryOutOfBounds),
+        }
+    }
+
+    fn data_size(&self, caller: &Caller<'_, HostState>) -> usize {
+        match self {
+            Self::Local(memory) => memory.data_size(caller),
+            Self::Shared(memory) => memory.data_size(),
+        }
+    }
+
+    /// Copy out `range` (already bounds-checked against `data_size`).
+    fn read(&self, caller: &Caller<'_, HostState>, range: Range<usize>) -> Vec<u8> {
+        match self {
+            Self::Local(memory) => memory.data(caller)[range].to_vec(),
+            Self::Shared(memory) => memory.data()[range]
+                .iter()
+                .map(|cell| shared_byte(cell).load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+
+    fn write(
+        &self,
+        caller: &mut Caller<'_, HostState>,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<(), Trap> {
+        match self {
+            Self::Local(memory) => memory
+                .write(caller, offset, bytes)
+                .map_err(|_| Trap::MemoryOutOfBounds),
+            Self::Shared(memory) => {
+                let cells = offset
+                    .checked_add(bytes.len())
+                    .and_then(|end| memory.data().get(offset..end))
+                    .ok_or(Trap::MemoryOutOfBounds)?;
+                for (cell, byte) in cells.iter().zip(bytes) {
+                    shared_byte(cell).store(*byte, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Byte of a shared memory, accessed atomically: guest threads may load and
+/// store it concurrently, which plain host reads and writes would race with.
+const fn shared_byte(cell: &UnsafeCell<u8>) -> &AtomicU8 {
+    // SAFETY: `AtomicU8` has the size and alignment of `u8`, and the cell
+    // stays valid while borrowed from `SharedMemory::data`. The host only
+    // touches shared memories through these atomics, so its own accesses
+    // never race non-atomically; concurrent guest accesses are the atomic
+    // access `SharedMemory::data` documents as required.
+    unsafe { AtomicU8::from_ptr(cell.get()) }
+}
+
+/// Length or handle result for a failed host call: [`GuestPtr::DENIED`] or
+/// [`GuestPtr::BUSY`], trapping on other errors.
+fn denied_or_trap<P: GuestPtr>(err: &CapError) -> anyhow::Result<P> {
+    match HostStatus::from(err) {
+        HostStatus::Denied => Ok(P::DENIED),
+        HostStatus::Busy => Ok(P::BUSY),
+        HostStatus::Allowed | HostStatus::Error => Err(Trap::MemoryOutOfBounds.into()),
+    }
+}
+
+/// Write `bytes` to `buf` if they fit and return their length either way, so
+/// guests can retry with a larger buffer.
+fn write_if_fits<P: GuestPtr>(
+    memory: &GuestMemory,
+    caller: &mut Caller<'_, HostState>,
+    buf: Range<usize>,
+    bytes: &[u8],
+) -> anyhow::Result<P> {
+    let len = P::try_from(bytes.len()).map_err(|_| Trap::BadConversionToInteger)?;
+    if bytes.len() <= buf.len() {
+        memory.write(caller, buf.start, bytes)?;
+    }
+    Ok(len)
+}
+
+/// Copy `bytes` into a buffer from the guest's allocator (see
+/// [`GUEST_ALLOCATORS`]) and return its pointer.
+fn alloc_in_guest<P: GuestPtr>(
+    caller: &mut Caller<'_, HostState>,
+    memory: &GuestMemory,
+    bytes: &[u8],
+) -> anyhow::Result<P> {
+    let alloc = GUEST_ALLOCATORS
+        .iter()
+        .find_map(|(alloc, _)| caller.get_export(alloc).and_then(Extern::into_func))
+        .ok_or_else(|| anyhow::anyhow!("guest exports no allocator"))?;
+    let len = P::try_from(bytes.len()).map_err(|_| Trap::BadConversionToInteger)?;
+    let ptr = alloc.typed::<P, P>(&*caller)?.call(&mut *caller, len)?;
+    let buf = guest_range(ptr, len, memory.data_size(caller))?;
+    memory.write(caller, buf.start, bytes)?;
+    Ok(ptr)
+}
+
+/// Bounds-checked byte range `ptr..ptr + len` within a memory of `mem_len`
+/// bytes. The check runs in `u64`, so memory64 offsets are never truncated,
+/// and negative pointers or lengths are rejected.
+fn guest_range<P: GuestPtr>(ptr: P, len: P, mem_len: usize) -> Result<Range<usize>, Trap> {
+    let start: u64 = ptr.try_into().map_err(|_| Trap::BadConversionToInteger)?;
+    let len: u64 = len.try_into().map_err(|_| Trap::BadConversionToInteger)?;
+    let mem_len = u64::try_from(mem_len).map_err(|_| Trap::BadConversionToInteger)?;
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= mem_len)
+        .ok_or(Trap::MemoryOutOfBounds)?;
+    // Both bounds are at most `mem_len`, which came from a `usize`.
+    let to_usize = |offset| usize::try_from(offset).map_err(|_| Trap::MemoryOutOfBounds);
+    Ok(to_usize(start)?..to_usize(end)?)
+}
@@ -0,0 +1,150 @@
+use std::{
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use wasmtime::Engine;
+
+/// Settings for [`crate::PluginInstance::with_watchdog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// How long a running guest may go without a host call before it counts as stalled
+    pub interval: Duration,
+    /// Also interrupt the stalled guest (needs an engine from
+    /// [`crate::ModuleCache::with_epoch_interruption`]).
+    pub interrupt: bool,
+}
+
+impl WatchdogConfig {
+    #[inline]
+    #[must_use]
+    pub const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            interrupt: false,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_interrupt(mut self) -> Self {
+        self.interrupt = true;
+        self
+    }
+}
+
+/// One stalled stretch observed by a [`Watchdog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stall {
+    /// Time without progress when the stall was detected
+    pub idle_ms: u64,
+    /// Whether the guest was interrupted
+    pub interrupted: bool,
+}
+
+#[derive(Debug, Default)]
+struct Liveness {
+    beats: AtomicU64,
+    running: AtomicBool,
+    interrupt: AtomicBool,
+    stop: AtomicBool,
+    stalls: Mutex<Vec<Stall>>,
+}
+
+/// Background thread watching a guest for progress. Stalls are queued and
+/// recorded as `guest.stalled` events once the host regains control; the
+/// thread stops when the watchdog is dropped.
+#[derive(Debug)]
+pub struct Watchdog {
+    liveness: Arc<Liveness>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start watching; `engine` is the one whose epoch is bumped on interrupt.
+    #[must_use]
+    pub fn spawn(config: WatchdogConfig, engine: Engine) -> Self {
+        let liveness = Arc::new(Liveness::default());
+        let watched = Arc::clone(&liveness);
+        let thread = thread::spawn(move || watch(&config, &engine, &watched));
+        Self {
+            liveness,
+            thread: Some(thread),
+        }
+    }
+
+    /// Note progress (a host call).
+    pub fn beat(&self) {
+        self.liveness.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark a guest call as started or finished; only running guests can stall.
+    pub fn set_running(&self, running: bool) {
+        self.beat();
+        self.liveness.running.store(running, Ordering::Relaxed);
+        if !running {
+            self.liveness.interrupt.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether the current guest call should be interrupted
+    #[must_use]
+    pub fn interrupt_requested(&self) -> bool {
+        self.liveness.interrupt.load(Ordering::Relaxed)
+    }
+
+    /// Take the stalls observed since the last call.
+    pub fn take_stalls(&self) -> Vec<Stall> {
+        let mut stalls = self
+            .liveness
+            .stalls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::take(&mut *stalls)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.liveness.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Report a stall once per stretch without progress.
+fn watch(config: &WatchdogConfig, engine: &Engine, liveness: &Liveness) {
+    let mut last_beats = liveness.beats.load(Ordering::Relaxed);
+    let mut since = Instant::now();
+    let mut reported = false;
+    while !liveness.stop.load(Ordering::Relaxed) {
+        thread::park_timeout(config.interval);
+        let beats = liveness.beats.load(Ordering::Relaxed);
+        if beats != last_beats || !liveness.running.load(Ordering::Relaxed) {
+            (last_beats, since, reported) = (beats, Instant::now(), false);
+            continue;
+        }
+        let idle = since.elapsed();
+        if reported || idle < config.interval {
+            continue;
+        }
+        reported = true;
+        if config.interrupt {
+            liveness.interrupt.store(true, Ordering::Relaxed);
+            engine.increment_epoch();
+        }
+        liveness
+            .stalls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Stall {
+                idle_ms: u64::try_from(idle.as_millis()).unwrap_or(u64::MAX),
+                interrupted: config.interrupt,
+            });
+    }
+}
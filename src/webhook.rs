@@ -0,0 +1,293 @@
+use crate::trace::{Severity, TraceEvent};
+use sha2::{Digest, Sha256, digest::Output};
+use std::{
+    fmt::Debug,
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tracing::warn;
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>`, see [`sign_webhook_payload`].
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Captra-Signature";
+
+/// Settings for [`WebhookSink::spawn`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// HMAC key shared with the receiver
+    pub secret: Vec<u8>,
+    /// Events per request at most
+    pub batch_size: usize,
+    /// How long a partial batch waits for more events before it is sent
+    pub flush_interval: Duration,
+    /// Further attempts after a failed request
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each further one
+    pub retry_backoff: Duration,
+}
+
+impl WebhookConfig {
+    #[must_use]
+    pub fn new(url: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            url: url.into(),
+            secret: secret.into(),
+            batch_size: 20,
+            flush_interval: Duration::from_secs(1),
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+}
+
+/// Delivers webhook requests; swap in a custom transport for TLS or tests.
+pub trait WebhookTransport: Debug + Send + 'static {
+    /// POST `body` (JSON) to `url` with the [`WEBHOOK_SIGNATURE_HEADER`] value
+    /// `signature`.
+    ///
+    /// # Errors
+    ///
+    /// Any failure, including non-2xx responses; the request is retried.
+    fn post(&mut self, url: &str, signature: &str, body: &[u8]) -> io::Result<()>;
+}
+
+/// Plain `http://` transport over a TCP socket (front `https` receivers with
+/// a TLS-terminating proxy, or bring a [`WebhookTransport`]).
+#[derive(Debug, Clone, Copy)]
+pub struct HttpTransport {
+    pub timeout: Duration,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl WebhookTransport for HttpTransport {
+    fn post(&mut self, url: &str, signature: &str, body: &[u8]) -> io::Result<()> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("not an http URL: {url}"),
+            )
+        })?;
+        let (authority, path) = rest
+            .find('/')
+            .map_or((rest, "/"), |idx| (&rest[..idx], &rest[idx..]));
+        let addr = if authority.contains(':') {
+            authority.to_owned()
+        } else {
+            format!("{authority}:80")
+        };
+
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(
+            stream,
+            "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n{WEBHOOK_SIGNATURE_HEADER}: {signature}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )?;
+        stream.write_all(body)?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "webhook responded `{}`",
+                status.trim()
+            ))),
+        }
+    }
+}
+
+/// Delivery counters returned by [`WebhookSink::close`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WebhookStats {
+    pub delivered_batches: u64,
+    pub delivered_events: u64,
+    /// Batches dropped after exhausting their retries
+    pub failed_batches: u64,
+    pub retries: u64,
+}
+
+/// Background sender of alert webhooks.
+///
+/// Denials and critical events handed to [`WebhookSink::notify`] are batched,
+/// signed with [`sign_webhook_payload`] and `POST`ed as `{"events": [...]}`,
+/// retrying failed requests. Dropping the sink flushes the last batch.
+#[derive(Debug)]
+pub struct WebhookSink {
+    events: Option<Sender<TraceEvent>>,
+    thread: Option<JoinHandle<WebhookStats>>,
+}
+
+impl WebhookSink {
+    /// Start the delivery thread.
+    #[must_use]
+    pub fn spawn(config: WebhookConfig, transport: impl WebhookTransport) -> Self {
+        let (events, received) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut delivery = Delivery {
+                config,
+                transport,
+                stats: WebhookStats::default(),
+            };
+            delivery.run(&received);
+            delivery.stats
+        });
+        Self {
+            events: Some(events),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queue `event` if it is an alert (see [`is_alert`]); never blocks.
+    pub fn notify(&self, event: &TraceEvent) {
+        if let Some(events) = self.events.as_ref().filter(|_| is_alert(event)) {
+            let _ = events.send(event.clone());
+        }
+    }
+
+    /// Flush pending events and stop the thread.
+    #[must_use]
+    pub fn close(mut self) -> WebhookStats {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> WebhookStats {
+        self.events.take();
+        self.thread
+            .take()
+            .and_then(|thread| thread.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for WebhookSink {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Whether a webhook fires for `event`: denials and critical events.
+#[must_use]
+pub fn is_alert(event: &TraceEvent) -> bool {
+    !event.outcome || event.severity == Severity::Critical
+}
+
+/// [`WEBHOOK_SIGNATURE_HEADER`] value for `body`: `sha256=` and the hex
+/// HMAC-SHA256 under `secret`, for receivers to authenticate requests.
+#[must_use]
+pub fn sign_webhook_payload(secret: &[u8], body: &[u8]) -> String {
+    format!("sha256={:x}", hmac_sha256(secret, body))
+}
+
+/// HMAC (RFC 2104) over SHA256.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Output<Sha256> {
+    const BLOCK_LEN: usize = 64;
+    let mut block = [0_u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+}
+
+struct Delivery<T> {
+    config: WebhookConfig,
+    transport: T,
+    stats: WebhookStats,
+}
+
+impl<T: WebhookTransport> Delivery<T> {
+    /// Batch events until the batch is full, the flush interval passes
+    /// without new events, or the sink is closed.
+    fn run(&mut self, received: &mpsc::Receiver<TraceEvent>) {
+        let mut batch = Vec::new();
+        loop {
+            let closed = match received.recv_timeout(self.config.flush_interval) {
+                Ok(event) => {
+                    batch.push(event);
+                    if batch.len() < self.config.batch_size.max(1) {
+                        continue;
+                    }
+                    false
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+            if !batch.is_empty() {
+                self.send(&batch);
+                batch.clear();
+            }
+            if closed {
+                return;
+            }
+        }
+    }
+
+    fn send(&mut self, batch: &[TraceEvent]) {
+        let body = serde_json::json!({ "events": batch }).to_string();
+        let signature = sign_webhook_payload(&self.config.secret, body.as_bytes());
+        let mut backoff = self.config.retry_backoff;
+        for attempt in 0..=self.config.max_retries {
+            if attempt > 0 {
+                self.stats.retries += 1;
+                thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+            }
+            match self
+                .transport
+                .post(&self.config.url, &signature, body.as_bytes())
+            {
+                Ok(()) => {
+                    self.stats.delivered_batches += 1;
+                    self.stats.delivered_events += u64::try_from(batch.len()).unwrap_or(u64::MAX);
+                    return;
+                }
+                Err(err) => warn!(url = %self.config.url, attempt, %err, "webhook delivery failed"),
+            }
+        }
+        self.stats.failed_batches += 1;
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use crate::common::{host::make_host_with_seed, manifest::load_example_manifest};
+use captra::{
+    ARCHIVE_MANIFEST_FILE, ARCHIVE_TRACE_FILE, ArchiveError, read_run_bundle, to_sarif,
+    write_run_bundle,
+};
+use claims::{assert_err, assert_matches, assert_ok, assert_some};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn archive_run_bundle_round_trips_as_dir_and_zip() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let signed = assert_ok!(host.sign_current_trace());
+    let pubkey = assert_ok!(signed.embedded_pubkey());
+    let manifest = load_example_manifest();
+    let report = to_sarif(host.trace());
+
+    let dir = assert_ok!(tempdir());
+    for name in ["run", "run.zip"] {
+        let path = dir.path().join(name);
+        assert_ok!(write_run_bundle(
+            &path,
+            &signed,
+            &manifest,
+            &pubkey,
+            Some(&report)
+        ));
+        let bundle = assert_ok!(read_run_bundle(&path));
+        assert_eq!(bundle.trace.trace_json, signed.trace_json);
+        assert_eq!(bundle.manifest.content_hash(), signed.manifest_hash);
+        assert_eq!(bundle.pubkey, pubkey);
+        assert_eq!(assert_some!(bundle.report), report);
+    }
+
+    let zip = assert_ok!(fs::read(dir.path().join("run.zip")));
+    assert_eq!(&zip[..4], b"PK\x03\x04");
+}
+
+#[test]
+fn archive_run_bundle_detects_tampering() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let signed = assert_ok!(host.sign_current_trace());
+    let pubkey = assert_ok!(signed.embedded_pubkey());
+    let mut manifest = load_example_manifest();
+    let dir = assert_ok!(tempdir());
+    let path = dir.path().join("run");
+    assert_ok!(write_run_bundle(&path, &signed, &manifest, &pubkey, None));
+
+    let trace_path = path.join(ARCHIVE_TRACE_FILE);
+    let original = assert_ok!(fs::read_to_string(&trace_path));
+    assert_ok!(fs::write(
+        &trace_path,
+        original.replace("config.toml", "other.toml")
+    ));
+    let err = assert_err!(read_run_bundle(&path));
+    assert_matches!(err, ArchiveError::HashMismatch { ref file } if file == ARCHIVE_TRACE_FILE);
+    assert_ok!(fs::write(&trace_path, original));
+
+    assert_ok!(fs::remove_file(path.join(ARCHIVE_MANIFEST_FILE)));
+    let err = assert_err!(read_run_bundle(&path));
+    assert_matches!(err, ArchiveError::MissingFile(_));
+
+    manifest.plugin = "other".into();
+    let err = assert_err!(write_run_bundle(
+        dir.path().join("mismatch"),
+        &signed,
+        &manifest,
+        &pubkey,
+        None
+    ));
+    assert_matches!(err, ArchiveError::ManifestMismatch { .. });
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use crate::common::manifest::load_example_manifest;
+use captra::{
+    BatchProgress, HostState, Keyring, TraceError, pubkey_fingerprint, verify_batch,
+    verify_signed_trace_file,
+};
+use claims::{assert_matches, assert_ok};
+use ed25519_dalek::SigningKey;
+use std::{
+    fs,
+    sync::{Mutex, PoisonError},
+};
+use tempfile::tempdir;
+
+#[test]
+fn audit_verify_batch_reports_each_trace() {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let dir = assert_ok!(tempdir());
+    let mut paths = Vec::new();
+    for seed in 1..=3 {
+        let mut host = HostState::new(load_example_manifest(), seed, key.clone());
+        let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+        let signed = assert_ok!(host.sign_current_trace());
+        let path = dir.path().join(format!("{seed}.json"));
+        assert_ok!(fs::write(&path, assert_ok!(serde_json::to_string(&signed))));
+        paths.push(path);
+    }
+    let keyring = Keyring::new().with_key(key.verifying_key());
+
+    let tampered = assert_ok!(fs::read_to_string(&paths[1])).replace("config.toml", "other.toml");
+    assert_ok!(fs::write(&paths[1], tampered));
+    paths.push(dir.path().join("missing.json"));
+
+    let seen = Mutex::new(Vec::new());
+    let results = verify_batch(&paths, &keyring, |progress| {
+        seen.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(progress);
+    });
+    assert_eq!(
+        results.iter().map(|res| &res.path).collect::<Vec<_>>(),
+        paths.iter().collect::<Vec<_>>()
+    );
+    let verified = assert_ok!(&results[0].result);
+    assert_eq!(
+        (verified.run_id.as_str(), verified.events),
+        ("captra-run-1", 1)
+    );
+    assert_matches!(&results[1].result, Err(TraceError::InvalidSignature));
+    assert_ok!(&results[2].result);
+    assert_matches!(&results[3].result, Err(TraceError::Io(_)));
+
+    let seen = seen.into_inner().unwrap_or_else(PoisonError::into_inner);
+    assert_eq!(seen.len(), 4);
+    assert!(seen.contains(&BatchProgress {
+        done: 4,
+        failed: 2,
+        total: 4
+    }));
+
+    let other = SigningKey::from_bytes(&[9; 32]).verifying_key();
+    let results = verify_batch(&paths[..1], &Keyring::new().with_key(other), |_| {});
+    assert_matches!(&results[0].result, Err(TraceError::UntrustedSigner { .. }));
+}
+
+#[test]
+fn audit_verify_follows_rotations_and_rejects_renamed_runs() {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let keyring = Keyring::new().with_key(key.verifying_key());
+    let dir = assert_ok!(tempdir());
+    let mut host = HostState::new(load_example_manifest(), 1, key.clone());
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    host.rotate_key(SigningKey::from_bytes(&[9; 32]));
+    let mut signed = assert_ok!(host.sign_current_trace());
+    let path = dir.path().join("rotated.json");
+    assert_ok!(fs::write(&path, assert_ok!(serde_json::to_string(&signed))));
+
+    let verified = assert_ok!(verify_signed_trace_file(&path, &keyring));
+    assert_eq!(
+        verified.fingerprint,
+        pubkey_fingerprint(key.verifying_key().as_bytes())
+    );
+    assert_eq!(
+        (verified.run_id.as_str(), verified.events),
+        ("captra-run-1", 2)
+    );
+
+    signed.run_id = "captra-run-renamed".into();
+    assert_ok!(fs::write(&path, assert_ok!(serde_json::to_string(&signed))));
+    assert_matches!(
+        verify_signed_trace_file(&path, &keyring),
+        Err(TraceError::InvalidSignature)
+    );
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use crate::common::manifest::load_example_manifest;
+use captra::{BatchRunner, EventType, HostStatus, RandomnessMode, TraceEvent, verify_chain};
+use claims::assert_ok;
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+
+const READ_INPUT_WAT: &str = r#"
+    (module
+      (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (func (export "alloc") (param i32) (result i32) i32.const 1024)
+      (func (export "run") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            call $read_file)
+      )
+"#;
+
+#[test]
+fn batch_runner_aggregates_in_input_order() {
+    let keypair = SigningKey::generate(&mut OsRng);
+    let runner = assert_ok!(BatchRunner::new(
+        load_example_manifest(),
+        READ_INPUT_WAT,
+        keypair.clone()
+    ))
+    .with_seed(100)
+    .with_workers(3);
+
+    let inputs = (0..8)
+        .map(|i| {
+            if i % 2 == 0 {
+                format!("./workspace/{i}.txt")
+            } else {
+                format!("/etc/{i}")
+            }
+        })
+        .collect::<Vec<_>>();
+    let report = assert_ok!(runner.run_all(&inputs));
+
+    assert_eq!(report.results.len(), 8);
+    for (i, result) in report.results.iter().enumerate() {
+        let expected = if i % 2 == 0 {
+            HostStatus::Allowed
+        } else {
+            HostStatus::Denied
+        };
+        assert_eq!(*result.as_ref().expect("call succeeds"), expected as i32);
+    }
+
+    // init, cap call, shutdown per input; runs are seeded by index.
+    assert_eq!(report.traces.len(), 8);
+    for (i, run) in report.traces.iter().enumerate() {
+        assert_eq!(run.len(), 3);
+        assert_eq!(run[0].run_id, format!("captra-run-{}", 100 + i));
+        assert_eq!(run[0].event_type, EventType::PluginInit);
+        assert_eq!(run[1].outcome, i % 2 == 0);
+        assert!(run[1].vclock.is_some());
+        assert_ok!(verify_chain(run));
+    }
+
+    // One signed trace chains every input's events in input order.
+    let signed = &report.signed;
+    assert_ok!(signed.verify(&keypair.verifying_key()));
+    assert_eq!(signed.run_id, "captra-batch-100");
+    assert_eq!(signed.randomness, Some(RandomnessMode::Deterministic(100)));
+    assert_ok!(signed.verify_ts_seeds());
+    let events = assert_ok!(serde_json::from_str::<Vec<TraceEvent>>(&signed.trace_json));
+    assert_ok!(verify_chain(&events));
+    assert_eq!(events.len(), 24);
+    for (event, original) in events.iter().zip(report.traces.iter().flatten()) {
+        assert_eq!(event.run_id, signed.run_id);
+        assert_eq!(
+            (&event.event_type, &event.input, event.outcome),
+            (&original.event_type, &original.input, original.outcome)
+        );
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use crate::common::manifest::load_example_manifest;
+use captra::{
+    Bundle, BundleError, HostState, MANIFEST_SECTION, ManifestError, PluginInstance, TrustRoots,
+    custom_sections, embed_manifest,
+};
+use claims::{assert_err, assert_matches, assert_ok};
+use ed25519_dalek::SigningKey;
+
+/// `(module (func (export "run") (result i32) i32.const 7))`
+const RUN_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type: () -> i32
+    0x03, 0x02, 0x01, 0x00, // func 0: type 0
+    0x07, 0x07, 0x01, 0x03, b'r', b'u', b'n', 0x00, 0x00, // export "run"
+    0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x07, 0x0b, // body: i32.const 7
+];
+
+#[test]
+fn bundle_embedded_manifest_round_trip() {
+    let root = SigningKey::from_bytes(&[5; 32]);
+    let mut manifest = load_example_manifest();
+    manifest.issued_by = "root".into();
+    assert_ok!(manifest.sign(&root));
+    let roots = TrustRoots::from([("root".to_owned(), root.verifying_key())]);
+
+    let wasm = assert_ok!(embed_manifest(RUN_MODULE, &manifest));
+    assert_eq!(
+        assert_ok!(custom_sections(&wasm, MANIFEST_SECTION)).len(),
+        1
+    );
+    let bundle = assert_ok!(Bundle::from_wasm_with_embedded_manifest(wasm, &roots));
+    assert_eq!(bundle.manifest().content_hash(), manifest.content_hash());
+    assert_eq!(bundle.signer(), &root.verifying_key());
+
+    let (embedded, wasm) = bundle.into_parts();
+    let host = HostState::new(embedded, 12_345, SigningKey::from_bytes(&[7; 32]));
+    let mut plugin = assert_ok!(PluginInstance::new(host, wasm.as_slice()));
+    assert_eq!(assert_ok!(plugin.call("run")), 7);
+
+    assert_matches!(
+        assert_err!(embed_manifest(&wasm, &manifest)),
+        BundleError::DuplicateManifest
+    );
+}
+
+#[test]
+fn bundle_rejects_unsigned_or_missing_manifest() {
+    let root = SigningKey::from_bytes(&[5; 32]);
+    let roots = TrustRoots::from([("root".to_owned(), root.verifying_key())]);
+    let err = assert_err!(Bundle::from_wasm_with_embedded_manifest(RUN_MODULE, &roots));
+    assert_matches!(err, BundleError::MissingManifest);
+
+    let mut manifest = load_example_manifest();
+    manifest.issued_by = "root".into();
+    let wasm = assert_ok!(embed_manifest(RUN_MODULE, &manifest));
+    let err = assert_err!(Bundle::from_wasm_with_embedded_manifest(wasm, &roots));
+    assert_matches!(err, BundleError::Manifest(ManifestError::InvalidSignature));
+
+    let err = assert_err!(Bundle::from_wasm_with_embedded_manifest(
+        b"(module)".to_vec(),
+        &roots
+    ));
+    assert_matches!(err, BundleError::NotWasm);
+    let mut truncated = RUN_MODULE.to_vec();
+    truncated.truncate(RUN_MODULE.len() - 2);
+    let err = assert_err!(custom_sections(&truncated, MANIFEST_SECTION));
+    assert_matches!(err, BundleError::Malformed(_));
+}
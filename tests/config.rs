@@ -0,0 +1,108 @@
+mod common;
+
+use crate::common::host::make_host_with_seed;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use captra::{
+    ConfigError, DenialMode, HostConfig, TraceFormat, Verbosity, load_trace, verify_jsonl,
+};
+use claims::{assert_matches, assert_none, assert_ok, assert_some};
+use ed25519_dalek::SigningKey;
+use std::{fs, io::BufReader, time::Duration};
+use tempfile::tempdir;
+
+#[test]
+fn config_loads_and_resolves_relative_paths() {
+    let dir = assert_ok!(tempdir());
+    let key = SigningKey::from_bytes(&[9; 32]);
+    assert_ok!(fs::create_dir(dir.path().join("keys")));
+    assert_ok!(fs::write(
+        dir.path().join("keys/host.key"),
+        STANDARD.encode(key.to_bytes()) + "\n"
+    ));
+    let root = STANDARD.encode(key.verifying_key().as_bytes());
+    let path = dir.path().join("captra.toml");
+    assert_ok!(fs::write(
+        &path,
+        format!(
+            r#"
+            denial_mode = "deny"
+            signing_key = "keys/host.key"
+
+            [trace]
+            dir = "traces"
+            format = "jsonl"
+
+            [trace_policy]
+            default = {{ sampled = {{ every = 10 }} }}
+
+            [latency_caps_ms]
+            read_file = 50
+
+            [trusted_issuers]
+            dev-team = "{root}"
+            "#
+        )
+    ));
+
+    let config = assert_ok!(HostConfig::load(&path));
+    assert_eq!(config.denial_mode, DenialMode::Deny);
+    assert_eq!(config.trace.format, TraceFormat::Jsonl);
+    assert_eq!(
+        assert_some!(config.trace_policy.as_ref()).default,
+        Verbosity::Sampled { every: 10 }
+    );
+    assert_eq!(assert_some!(config.trace_dir()), dir.path().join("traces"));
+    let loaded_key = assert_some!(assert_ok!(config.signing_key()));
+    assert_eq!(loaded_key.to_bytes(), key.to_bytes());
+    let roots = assert_ok!(config.trust_roots());
+    assert_eq!(roots.get("dev-team"), Some(&key.verifying_key()));
+
+    let mut host = config.configure(make_host_with_seed(12_345));
+    assert_eq!(
+        host.latency_cap("read_file"),
+        Some(Duration::from_millis(50))
+    );
+    assert!(!assert_ok!(host.execute_plugin("/etc/passwd")));
+    assert_ok!(fs::create_dir(dir.path().join("traces")));
+    let saved = assert_ok!(config.save_trace(&mut host));
+    assert_eq!(
+        saved.extension().and_then(|ext| ext.to_str()),
+        Some("jsonl")
+    );
+    let file = BufReader::new(assert_ok!(fs::File::open(&saved)));
+    let pubkey = ed25519_dalek::VerifyingKey::from_bytes(host.pubkey());
+    assert_eq!(
+        assert_ok!(verify_jsonl(file, &assert_ok!(pubkey))).events,
+        1
+    );
+}
+
+#[test]
+fn config_defaults_and_rejects_unknown_keys() {
+    let config = assert_ok!(HostConfig::from_toml(""));
+    assert_eq!(config, HostConfig::default());
+    assert_none!(assert_ok!(config.signing_key()));
+
+    assert_matches!(
+        HostConfig::from_toml("denial_mdoe = \"deny\""),
+        Err(ConfigError::Parse(_))
+    );
+    let bad_issuer = assert_ok!(HostConfig::from_toml(
+        "[trusted_issuers]\ndev-team = \"not a key\""
+    ));
+    assert_matches!(
+        bad_issuer.trust_roots(),
+        Err(ConfigError::InvalidIssuer { name }) if name == "dev-team"
+    );
+
+    let dir = assert_ok!(tempdir());
+    let config = assert_ok!(HostConfig::from_toml(&format!(
+        "[trace]\ndir = {:?}",
+        dir.path().display().to_string()
+    )));
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let saved = assert_ok!(config.save_trace(&mut host));
+    assert_eq!(saved, dir.path().join(format!("{}.json", host.run_id())));
+    assert_eq!(assert_ok!(load_trace(&saved)), host.trace());
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use crate::common::manifest::load_example_manifest;
+use captra::{IssuerCert, ManifestError, TrustRoots};
+use claims::{assert_err, assert_matches, assert_ok};
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+
+#[test]
+fn delegation_chain_from_root_to_team() {
+    let root = SigningKey::generate(&mut OsRng);
+    let org = SigningKey::generate(&mut OsRng);
+    let team = SigningKey::generate(&mut OsRng);
+    let roots = TrustRoots::from([("root".to_owned(), root.verifying_key())]);
+
+    let mut manifest = load_example_manifest();
+    manifest.delegation = vec![
+        IssuerCert::issue("root", &root, "org", &org.verifying_key()),
+        IssuerCert::issue("org", &org, "dev-team", &team.verifying_key()),
+    ];
+    assert_ok!(manifest.sign(&team));
+
+    let signer = assert_ok!(manifest.validate_chain(&roots));
+    assert_eq!(signer, team.verifying_key());
+
+    let json = assert_ok!(serde_json::to_string(&manifest));
+    let mut reloaded = assert_ok!(serde_json::from_str::<captra::CapabilityManifest>(&json));
+    assert_ok!(reloaded.validate_chain(&roots));
+
+    reloaded.plugin = "evil".into();
+    let err = assert_err!(reloaded.validate_chain(&roots));
+    assert_matches!(err, ManifestError::InvalidSignature);
+}
+
+#[test]
+fn delegation_chain_rejects_forged_link() {
+    let root = SigningKey::generate(&mut OsRng);
+    let mallory = SigningKey::generate(&mut OsRng);
+    let roots = TrustRoots::from([("root".to_owned(), root.verifying_key())]);
+
+    let mut manifest = load_example_manifest();
+    manifest.delegation = vec![IssuerCert::issue(
+        "root",
+        &mallory,
+        "dev-team",
+        &mallory.verifying_key(),
+    )];
+    assert_ok!(manifest.sign(&mallory));
+    let err = assert_err!(manifest.validate_chain(&roots));
+    assert_matches!(err, ManifestError::BrokenDelegation { idx: 0, .. });
+
+    manifest.delegation.clear();
+    let err = assert_err!(manifest.validate_chain(&roots));
+    assert_matches!(err, ManifestError::UntrustedIssuer(_));
+}
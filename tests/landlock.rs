@@ -0,0 +1,52 @@
+#![cfg(all(feature = "landlock", target_os = "linux"))]
+
+mod common;
+
+use crate::common::manifest::load_example_manifest;
+use captra::{LandlockError, LandlockRules};
+use claims::{assert_err, assert_ok};
+use std::{fs, path::PathBuf, thread};
+use tempfile::tempdir;
+
+#[test]
+fn landlock_rules_from_manifest_globs() {
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![
+            "./workspace/*".into(),
+            "./workspace/**/*.toml".into(),
+            "docs/[a-z]*.md".into(),
+            "*.txt".into(),
+        ]);
+    }
+    let rules = LandlockRules::from_manifest(&manifest);
+    assert_eq!(
+        rules.read_paths(),
+        [".", "./workspace", "docs"].map(PathBuf::from)
+    );
+}
+
+#[test]
+fn landlock_restrict_self_denies_outside_reads() {
+    if LandlockRules::abi_version().is_none() {
+        return;
+    }
+    let granted = assert_ok!(tempdir());
+    let other = assert_ok!(tempdir());
+    assert_ok!(fs::write(granted.path().join("a.txt"), "a"));
+    assert_ok!(fs::write(other.path().join("b.txt"), "b"));
+
+    let rules = LandlockRules::default().allow_read(granted.path());
+    let (granted, other) = (granted.path().to_owned(), other.path().to_owned());
+    // Landlock restricts the calling thread only, keep it off the test harness thread.
+    let handle = thread::spawn(move || {
+        rules.restrict_self()?;
+        Ok::<_, LandlockError>((
+            fs::read(granted.join("a.txt")),
+            fs::read(other.join("b.txt")),
+        ))
+    });
+    let (inside, outside) = assert_ok!(assert_ok!(handle.join()));
+    assert_ok!(inside);
+    assert_err!(outside);
+}
@@ -1,15 +1,29 @@
 mod common;
 
-use crate::common::host::make_host_with_seed;
+use crate::common::{host::make_host_with_seed, manifest::load_example_manifest};
 use base64::{Engine, engine::general_purpose::STANDARD};
 use captra::{
-    CapError, EventType, HostState, ManifestError, TraceEvent, init_tracing, load_manifest,
-    load_trace,
+    BlobCapability, Budget, BudgetThreshold, CapError, CapabilityManifest, CaptraError,
+    ConfigCapability, CustomCapability, Decision, DenialMode, EventType, HostState, HostStatus,
+    LoadOptions, MAX_CAPPED_WORKERS, ManifestError, Matcher, MatcherError, MatcherKind, Posture,
+    ProcessIdentity, Requirements, RunAs, SecureOpenError, SimCall, TraceError, TraceEvent,
+    bounding_glob, grant_root, init_tracing, load_manifest, load_trace, secure_open,
+    secure_read_dir,
+    semver::{Version, VersionReq},
+    validate_report, verify_chain,
 };
 use claims::{assert_err, assert_matches, assert_ok, assert_some};
 use ed25519_dalek::SigningKey;
 use rand::rngs::OsRng;
-use std::{fs::File, io::Write};
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
 use tempfile::tempdir;
 
 #[test]
@@ -98,7 +112,7 @@ fn host_enforcement_allowed() {
 
     assert_eq!(ev.run_id, "captra-run-12345");
     assert_eq!(ev.seq, 1);
-    assert_matches!(ev.event_type, EventType::CapCall);
+    assert_matches!(&ev.event_type, EventType::CapCall);
     assert_eq!(ev.input, "./workspace/config.toml");
     assert!(ev.outcome);
     assert_eq!(ev.ts_seed, 8_166_419_713_379_829_776);
@@ -110,19 +124,73 @@ fn host_enforcement_denied() {
     let mut host = make_host_with_seed(12_345);
 
     let err = assert_err!(host.execute_plugin("/etc/passwd"));
-    assert_matches!(err, CapError::GlobMismatch);
+    assert_eq!(
+        err,
+        CapError::GlobMismatch {
+            path: "/etc/passwd".into(),
+            patterns_tried: vec!["./workspace/*".into()],
+        }
+    );
 
     assert_eq!(host.trace().len(), 1);
     let ev = assert_some!(host.trace().first());
 
     assert_eq!(ev.run_id, "captra-run-12345");
     assert_eq!(ev.seq, 1);
-    assert_matches!(ev.event_type, EventType::CapCall);
+    assert_matches!(&ev.event_type, EventType::CapCall);
     assert!(!ev.outcome);
     assert!(ev.input.starts_with("glob_mismatch: "));
     assert_eq!(ev.ts_seed, 8_166_419_713_379_829_776);
 }
 
+#[test]
+fn host_enforcement_runtime_invalid_glob() {
+    init_tracing();
+    let manifest = assert_ok!(serde_json::from_str::<CapabilityManifest>(
+        r#"
+        {
+          "plugin": "test",
+          "version": "0.1",
+          "capabilities": { "fs": { "read": ["["] } },
+          "issued_by": "dev"
+        }
+    "#
+    ));
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+
+    let err = assert_err!(host.execute_plugin("./workspace/config.toml"));
+    assert_matches!(
+        err,
+        CapError::RuntimeInvalidGlob { capability: "fs.read", idx: 0, ref pattern, .. } if pattern == "["
+    );
+    assert!(err.to_string().contains("`[`"));
+
+    let ev = assert_some!(host.trace().first());
+    assert_eq!(ev.event_type, EventType::CapError);
+    assert!(!ev.outcome);
+    let details = assert_some!(ev.details.as_ref());
+    assert_eq!(details["capability"], "fs.read");
+    assert_eq!(details["index"], 0);
+    assert_eq!(details["pattern"], "[");
+    let CapError::RuntimeInvalidGlob { source, .. } = err else {
+        unreachable!()
+    };
+    assert_eq!(details["error"], source.to_string());
+
+    // A later valid pattern still grants, after tracing the invalid one.
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec!["[".into(), "./workspace/*".into()]);
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+    let events = host.trace();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].event_type, EventType::CapError);
+    assert_eq!(assert_some!(events[0].details.as_ref())["pattern"], "[");
+    assert!(events[1].outcome);
+}
+
 #[test]
 fn host_enforcement_signed() {
     init_tracing();
@@ -175,14 +243,14 @@ fn trace_reproducibility() {
 
     let mut host1 = HostState::new(manifest.clone(), fixed_seed, keypair1);
     let out1 = assert_err!(host1.execute_plugin("./allowed.txt"));
-    assert_eq!(out1, CapError::GlobMismatch);
+    assert_matches!(out1, CapError::GlobMismatch { .. });
     let trace1 = host1.get_trace_json();
 
     let mut csprng2 = OsRng;
     let keypair2 = SigningKey::generate(&mut csprng2);
     let mut host2 = HostState::new(manifest, fixed_seed, keypair2);
     let out2 = assert_err!(host2.execute_plugin("./allowed.txt"));
-    assert_eq!(out2, CapError::GlobMismatch);
+    assert_matches!(out2, CapError::GlobMismatch { .. });
     let trace2 = host2.get_trace_json();
 
     let parsed1 = assert_ok!(serde_json::from_str::<Vec<TraceEvent>>(&trace1));
@@ -190,3 +258,1529 @@ fn trace_reproducibility() {
     assert_eq!(parsed1, parsed2);
     assert_eq!(parsed1[0].ts_seed, parsed2[0].ts_seed);
 }
+
+#[test]
+fn host_resume_continues_run() {
+    init_tracing();
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let partial = host.trace().to_vec();
+    assert_ok!(verify_chain(&partial));
+
+    let manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    let keypair = SigningKey::generate(&mut OsRng);
+    let mut resumed = assert_ok!(HostState::resume(manifest, 12_345, keypair, partial));
+    let _ = assert_ok!(resumed.execute_plugin("./workspace/other.toml"));
+
+    assert_eq!(resumed.trace().len(), 3);
+    let last = assert_some!(resumed.trace().last());
+    assert_eq!(last.seq, 3);
+    assert_eq!(last.run_id, "captra-run-12345");
+    assert_ok!(verify_chain(resumed.trace()));
+}
+
+#[test]
+fn host_resume_applies_profile_of_the_run() {
+    let manifest = assert_ok!(CapabilityManifest::from_json_with(
+        r#"{
+            "plugin": "profiled",
+            "version": "0.1",
+            "capabilities": { "fs": { "read": ["./workspace/*"] } },
+            "profiles": { "ci": { "fs": { "read": ["./fixtures/*"] } } },
+            "issued_by": "dev-team"
+        }"#,
+        LoadOptions::default()
+    ));
+    let key = || SigningKey::from_bytes(&[7; 32]);
+    let mut host = assert_ok!(HostState::new(manifest.clone(), 12_345, key()).with_profile("ci"));
+    assert_ok!(host.execute_plugin("./fixtures/a.json"));
+
+    let mut resumed = assert_ok!(
+        assert_ok!(HostState::new(manifest, 12_345, key()).with_profile("ci"))
+            .with_resumed_trace(host.trace().to_vec())
+    );
+    assert_eq!(resumed.profile(), Some("ci"));
+    assert_eq!(resumed.manifest_hash(), host.manifest_hash());
+    assert_ok!(resumed.execute_plugin("./fixtures/b.json"));
+    let signed = assert_ok!(resumed.sign_current_trace());
+    assert_eq!(signed.profile.as_deref(), Some("ci"));
+    assert_eq!(signed.run_id, "captra-run-12345");
+    assert_ok!(verify_chain(resumed.trace()));
+}
+
+#[test]
+fn host_resume_rejects_foreign_or_broken_trace() {
+    init_tracing();
+    let mut host = make_host_with_seed(1);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_ok!(host.execute_plugin("./workspace/other.toml"));
+
+    let manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    let err = assert_err!(HostState::resume(
+        manifest.clone(),
+        2,
+        SigningKey::generate(&mut OsRng),
+        host.trace().to_vec(),
+    ));
+    assert_matches!(err, TraceError::IntegrityViolation { seq: 1, .. });
+
+    let mut tampered = host.trace().to_vec();
+    tampered[0].input = "./workspace/secret".into();
+    let err = assert_err!(HostState::resume(
+        manifest,
+        1,
+        SigningKey::generate(&mut OsRng),
+        tampered,
+    ));
+    assert_matches!(err, TraceError::IntegrityViolation { seq: 2, .. });
+}
+
+#[test]
+fn manifest_version_semver() {
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    let version = assert_ok!(manifest.version_semver());
+    assert_eq!(version, Version::new(0, 1, 0));
+    assert_err!(manifest.version_semver_strict());
+
+    assert_ok!(manifest.require_version(&assert_ok!(VersionReq::parse(">=0.1"))));
+    let err = assert_err!(manifest.require_version(&assert_ok!(VersionReq::parse(">=1.2"))));
+    assert_matches!(err, ManifestError::VersionRequirement { .. });
+
+    manifest.version = "1.2.3-beta.1".into();
+    assert_ok!(manifest.version_semver_strict());
+
+    manifest.version = "one".into();
+    let err = assert_err!(manifest.validate());
+    assert_matches!(err, ManifestError::InvalidSemver { .. });
+}
+
+#[test]
+fn host_snapshot_restore() {
+    init_tracing();
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let snapshot = host.snapshot();
+    let expected = host.trace().to_vec();
+
+    let _ = assert_ok!(host.execute_plugin("./workspace/preview.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_eq!(host.trace().len(), 3);
+
+    assert_ok!(host.restore(&snapshot));
+    assert_eq!(host.trace(), expected.as_slice());
+
+    // Continuing after a rollback reuses the discarded seq and keeps the chain valid.
+    let _ = assert_ok!(host.execute_plugin("./workspace/final.toml"));
+    let last = assert_some!(host.trace().last());
+    assert_eq!(last.seq, 2);
+    assert_ok!(verify_chain(host.trace()));
+
+    let stale = host.snapshot();
+    assert_ok!(host.restore(&snapshot));
+    assert_err!(host.restore(&stale));
+}
+
+#[test]
+fn host_restore_undoes_manifest_reload() {
+    let mut host = make_host_with_seed(12_345).with_decision_cache();
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+    let snapshot = host.snapshot();
+    let initial_hash = host.manifest_hash().to_owned();
+
+    let mut narrowed = load_example_manifest();
+    narrowed.capabilities.fs = None;
+    assert_ok!(host.reload_manifest(narrowed));
+    assert_err!(host.execute_plugin("./workspace/config.toml"));
+
+    assert_ok!(host.restore(&snapshot));
+    assert_eq!(host.policy_epoch(), 0);
+    assert_eq!(host.manifest_hash(), initial_hash);
+    assert!(
+        host.trace()
+            .iter()
+            .all(|ev| ev.event_type != EventType::PolicyReload)
+    );
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+    let last = assert_some!(host.trace().last());
+    assert_eq!(last.policy_epoch, 0);
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn manifest_strict_metadata() {
+    init_tracing();
+    let json = r#"
+        {
+          "plugin": "test",
+          "version": "0.1",
+          "capabilities": { "fs": { "read": ["./workspace/*"] } },
+          "issued_by": "dev",
+          "description": "Formats workspace files",
+          "data_classes": ["source-code"],
+          "strict_metadata": true
+        }
+    "#;
+    let mut manifest = assert_ok!(serde_json::from_str::<CapabilityManifest>(json));
+    let err = assert_err!(manifest.validate());
+    assert_matches!(err, ManifestError::InvalidMetadata("contact"));
+
+    manifest.contact = Some("security@example.com".into());
+    assert_ok!(manifest.validate());
+
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let signed = assert_ok!(host.sign_current_trace());
+    let metadata = assert_some!(signed.metadata);
+    assert_eq!(metadata.data_classes, vec!["source-code"]);
+    assert_eq!(metadata.contact.as_deref(), Some("security@example.com"));
+}
+
+#[test]
+fn host_shadow_manifest_mismatch() {
+    init_tracing();
+    let mut candidate = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = candidate.capabilities.fs.as_mut() {
+        fs.read = Some(vec!["./workspace/*.toml".into()]);
+    }
+    let mut host = assert_ok!(make_host_with_seed(12_345).with_shadow_manifest(candidate));
+
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    assert_eq!(host.trace().len(), 1);
+
+    let _ = assert_ok!(host.execute_plugin("./workspace/notes.md"));
+    assert_eq!(host.trace().len(), 3);
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::ShadowMismatch);
+    assert_eq!(ev.input, "./workspace/notes.md: enforced=allow shadow=deny");
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn host_shadow_manifest_is_validated() {
+    init_tracing();
+    let mut candidate = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = candidate.capabilities.fs.as_mut() {
+        fs.read = Some(vec!["./workspace/[".into()]);
+    }
+    let err = assert_err!(make_host_with_seed(12_345).with_shadow_manifest(candidate));
+    assert_matches!(err, ManifestError::InvalidGlob { .. });
+}
+
+#[test]
+fn host_denial_mode_uniform_deny() {
+    init_tracing();
+    let mut typed = make_host_with_seed(12_345);
+    let mut uniform = make_host_with_seed(12_345).with_denial_mode(DenialMode::Deny);
+
+    assert_err!(typed.execute_plugin("/etc/passwd"));
+    assert!(!assert_ok!(uniform.execute_plugin("/etc/passwd")));
+    assert!(assert_ok!(
+        uniform.execute_plugin("./workspace/config.toml")
+    ));
+    assert_ok!(typed.execute_plugin("./workspace/config.toml"));
+    assert_eq!(typed.trace(), uniform.trace());
+
+    let err = assert_err!(uniform.execute_plugin(""));
+    assert_matches!(err, CapError::InvalidPath);
+}
+
+#[test]
+fn host_read_file_constraints() {
+    init_tracing();
+    let dir = assert_ok!(tempdir());
+    let small = dir.path().join("small.toml");
+    let large = dir.path().join("large.toml");
+    let notes = dir.path().join("notes.md");
+    assert_ok!(std::fs::write(&small, b"a=1"));
+    assert_ok!(std::fs::write(&large, b"a = 12345"));
+    assert_ok!(std::fs::write(&notes, b"# hi"));
+
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+        fs.max_file_bytes = Some(4);
+        fs.extensions = Some(vec!["toml".into()]);
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+
+    assert_eq!(assert_ok!(host.read_file(&small)), b"a=1");
+
+    let err = assert_err!(host.read_file(&large));
+    assert_matches!(err, CapError::ConstraintViolation { .. });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsConstraintViolation);
+    assert_eq!(
+        ev.input,
+        "constraint_violation: file exceeds max_file_bytes=4"
+    );
+
+    let err = assert_err!(host.read_file(&notes));
+    assert_matches!(err, CapError::ConstraintViolation { .. });
+    assert_eq!(host.trace().len(), 4);
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn host_read_file_range_limits_and_traces_range() {
+    let dir = assert_ok!(tempdir());
+    let data = dir.path().join("data.bin");
+    assert_ok!(std::fs::write(&data, b"HEADERbody-of-the-file"));
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+        fs.max_range_bytes = Some(8);
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+
+    assert_eq!(assert_ok!(host.read_file_range(&data, 0, 6)), b"HEADER");
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsRead);
+    assert_eq!(ev.input, format!("{}: range=0+6", data.display()));
+    assert_eq!(assert_ok!(host.read_file_range(&data, 18, 8)), b"file");
+
+    let err = assert_err!(host.read_file_range(&data, 0, 9));
+    assert_matches!(err, CapError::ConstraintViolation { .. });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsConstraintViolation);
+    assert_eq!(
+        ev.input,
+        "constraint_violation: range=0+9 exceeds max_range_bytes=8"
+    );
+    assert_err!(host.read_file_range("/etc/passwd", 0, 4));
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn host_streaming_read_accounts_bytes_per_handle() {
+    let dir = assert_ok!(tempdir());
+    let data = dir.path().join("data.bin");
+    assert_ok!(std::fs::write(&data, b"0123456789"));
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+        fs.max_file_bytes = Some(10);
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+
+    let handle = assert_ok!(host.open_read(&data));
+    let mut contents = Vec::new();
+    loop {
+        let chunk = assert_ok!(host.read_chunk(handle, 4));
+        if chunk.is_empty() {
+            break;
+        }
+        contents.extend(chunk);
+    }
+    assert_eq!(contents, b"0123456789");
+    assert_eq!(assert_ok!(host.close_read(handle)), 10);
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsRead);
+    assert_eq!(
+        ev.input,
+        format!("{}: close handle={handle} bytes=10", data.display())
+    );
+    assert_matches!(host.read_chunk(handle, 4), Err(CapError::Io(_)));
+
+    assert_ok!(std::fs::write(&data, b"0123456789+"));
+    let handle = assert_ok!(host.open_read(&data));
+    assert_ok!(host.read_chunk(handle, 8));
+    assert_matches!(
+        host.read_chunk(handle, 8),
+        Err(CapError::ConstraintViolation { .. })
+    );
+    assert_matches!(host.close_read(handle), Err(CapError::Io(_)));
+    assert_err!(host.open_read("/etc/passwd"));
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[cfg(unix)]
+#[test]
+fn host_read_file_is_busy_past_latency_cap() {
+    let root = assert_ok!(tempdir());
+    let fast = root.path().join("fast.md");
+    assert_ok!(std::fs::write(&fast, b"# hi"));
+    // Opening a FIFO blocks until a writer shows up, like a stalled mount.
+    let slow = root.path().join("slow.md");
+    assert!(assert_ok!(Command::new("mkfifo").arg(&slow).status()).success());
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", root.path().display())]);
+    }
+    let cap = Duration::from_millis(50);
+    let mut host = HostState::new(manifest, 12_345, SigningKey::from_bytes(&[7; 32]))
+        .with_latency_cap("read_file", cap)
+        .with_latency_cap("read_open", cap);
+    assert_eq!(assert_ok!(host.read_file(&fast)), b"# hi");
+    assert!(assert_ok!(host.check_read(&fast)));
+
+    let err = assert_err!(host.read_file(&slow));
+    assert_eq!(
+        err,
+        CapError::Busy {
+            import: "read_file".into(),
+            cap_ms: 50,
+        }
+    );
+    assert_eq!(HostStatus::from(&err), HostStatus::Busy);
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::HostBusy);
+    assert!(!ev.outcome);
+    assert!(ev.input.starts_with("read_file "));
+
+    // The guest `read_file` import opens granted paths within the cap too.
+    assert_matches!(host.check_read(&slow), Err(CapError::Busy { .. }));
+    assert_matches!(
+        host.open_read(&slow),
+        Err(CapError::Busy { import, .. }) if import == "read_open"
+    );
+    for _ in 3..MAX_CAPPED_WORKERS {
+        assert_matches!(host.read_file(&slow), Err(CapError::Busy { .. }));
+    }
+    // Every helper is stuck, so even fast storage is refused at once.
+    assert_matches!(host.read_file(&fast), Err(CapError::Busy { .. }));
+    let ev = assert_some!(host.trace().last());
+    assert!(ev.input.ends_with("no free worker"));
+    assert_ok!(verify_chain(host.trace()));
+
+    // Release the helpers still waiting on the FIFO.
+    drop(assert_ok!(OpenOptions::new().write(true).open(&slow)));
+    let released = (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(20));
+        host.read_file(&fast).is_ok()
+    });
+    assert!(released);
+}
+
+#[cfg(unix)]
+#[test]
+fn host_read_file_refuses_symlinks_and_escapes() {
+    let root = assert_ok!(tempdir());
+    let granted = root.path().join("granted");
+    assert_ok!(std::fs::create_dir(&granted));
+    let secret = root.path().join("secret.md");
+    assert_ok!(std::fs::write(&secret, b"top secret"));
+    assert_ok!(std::fs::write(granted.join("notes.md"), b"# hi"));
+    let link = granted.join("link.md");
+    assert_ok!(std::os::unix::fs::symlink(&secret, &link));
+
+    let pattern = format!("{}/*.md", granted.display());
+    assert_eq!(grant_root(&pattern), granted);
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![pattern]);
+        fs.extensions = None;
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+
+    assert_eq!(
+        assert_ok!(host.read_file(granted.join("notes.md"))),
+        b"# hi"
+    );
+    for path in [link, granted.join("../secret.md")] {
+        let err = assert_err!(host.read_file(&path));
+        assert_matches!(err, CapError::ConstraintViolation { .. });
+        let ev = assert_some!(host.trace().last());
+        assert_eq!(ev.event_type, EventType::FsConstraintViolation);
+    }
+    assert_matches!(
+        assert_err!(secure_open(&secret, &granted)),
+        SecureOpenError::OutsideGrant { .. }
+    );
+    let open = || -> captra::Result<File> { Ok(secure_open(&secret, &granted)?) };
+    assert_matches!(
+        assert_err!(open()),
+        CaptraError::SecureOpen(SecureOpenError::OutsideGrant { .. })
+    );
+}
+
+#[cfg(feature = "regex-matcher")]
+#[test]
+fn host_read_file_content_filters() {
+    init_tracing();
+    let dir = assert_ok!(tempdir());
+    let env = dir.path().join("app.toml");
+    let notes = dir.path().join("notes.txt");
+    assert_ok!(std::fs::write(
+        &env,
+        b"name = \"app\"\nAPI_KEY = \"hunter2\"\nsecret=1\nport = 80"
+    ));
+    assert_ok!(std::fs::write(&notes, b"secret plans\n"));
+
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+        fs.content_filters = vec![captra::ContentFilter {
+            regex: "(?i)api_key|secret".into(),
+            paths: vec!["**/*.toml".into()],
+        }];
+    }
+    assert_ok!(manifest.validate());
+    let mut host = HostState::new(manifest.clone(), 12_345, SigningKey::generate(&mut OsRng));
+
+    let contents = assert_ok!(host.read_file(&env));
+    assert_eq!(contents, b"name = \"app\"\nport = 80");
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsContentFiltered);
+    assert!(!ev.outcome);
+    assert_eq!(ev.input, format!("{}: removed 2 lines", env.display()));
+    assert_eq!(
+        ev.details,
+        Some(serde_json::json!({ "filters": [{ "index": 0, "lines": 2 }] }))
+    );
+
+    assert_eq!(assert_ok!(host.read_file(&notes)), b"secret plans\n");
+    assert_eq!(host.trace().len(), 3);
+    assert_ok!(verify_chain(host.trace()));
+
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.content_filters[0].regex = "(unclosed".into();
+    }
+    assert_matches!(
+        manifest.validate(),
+        Err(ManifestError::InvalidContentFilter { idx: 0, .. })
+    );
+}
+
+#[test]
+fn host_read_file_content_hash() {
+    init_tracing();
+    let dir = assert_ok!(tempdir());
+    let file = dir.path().join("config.toml");
+    assert_ok!(std::fs::write(&file, b"a=1"));
+
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+    }
+    let mut host =
+        HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng)).with_read_hashing(true);
+
+    assert_ok!(host.read_file(&file));
+    assert_eq!(host.trace().len(), 2);
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsRead);
+    assert_eq!(
+        ev.input,
+        format!(
+            "{}: sha256=c22fea5d7428e5cf47ef6354c97c9223c95d6dcdc3e0d2300ff79056b1ff3d85 bytes=3",
+            file.display()
+        )
+    );
+}
+
+#[cfg(feature = "ulid")]
+#[test]
+fn host_uuid_run_id() {
+    let mut first = make_host_with_seed(12_345).with_uuid_run_id();
+    let second = make_host_with_seed(12_345).with_uuid_run_id();
+    assert_ne!(first.run_id(), second.run_id());
+    assert!(first.run_id() < second.run_id());
+
+    let _ = assert_ok!(first.execute_plugin("./workspace/config.toml"));
+    let run_id = first.run_id().to_owned();
+    let mut first = first.with_uuid_run_id();
+    assert_eq!(first.run_id(), run_id);
+    let signed = assert_ok!(first.sign_current_trace());
+    assert_eq!(signed.run_id, run_id);
+    assert_eq!(
+        signed.randomness,
+        Some(captra::RandomnessMode::Deterministic(12_345))
+    );
+
+    let mut resumed = assert_ok!(HostState::resume(
+        load_example_manifest(),
+        12_345,
+        SigningKey::generate(&mut OsRng),
+        first.trace().to_vec(),
+    ));
+    assert_eq!(resumed.run_id(), run_id);
+    let _ = assert_ok!(resumed.execute_plugin("./workspace/other.toml"));
+    assert_ok!(verify_chain(resumed.trace()));
+}
+
+#[test]
+fn host_list_dir_separate_from_reads() {
+    init_tracing();
+    let dir = assert_ok!(tempdir());
+    assert_ok!(std::fs::write(dir.path().join("b.toml"), ""));
+    assert_ok!(std::fs::write(dir.path().join("a.md"), ""));
+    let dir_str = dir.path().display().to_string();
+
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{dir_str}/*")]);
+    }
+    let mut read_only = HostState::new(manifest.clone(), 12_345, SigningKey::generate(&mut OsRng));
+    let err = assert_err!(read_only.list_dir(dir.path()));
+    assert_matches!(err, CapError::GlobMismatch { .. });
+    let ev = assert_some!(read_only.trace().last());
+    assert_eq!(ev.event_type, EventType::FsList);
+    assert!(!ev.outcome);
+
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = None;
+        fs.list = Some(vec![dir_str]);
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+    assert_eq!(assert_ok!(host.list_dir(dir.path())), ["a.md", "b.toml"]);
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::FsList);
+    assert!(ev.input.ends_with(": 2 entries"));
+    assert_err!(host.execute_plugin(dir.path().join("a.md")));
+}
+
+#[cfg(unix)]
+#[test]
+fn host_list_dir_refuses_traversal_and_symlinks() {
+    init_tracing();
+    let root = assert_ok!(tempdir());
+    let granted = root.path().join("granted");
+    let secret = root.path().join("secret");
+    assert_ok!(std::fs::create_dir(&granted));
+    assert_ok!(std::fs::create_dir(&secret));
+    assert_ok!(std::fs::write(granted.join("a.md"), ""));
+    assert_ok!(std::fs::write(secret.join("key.pem"), ""));
+    assert_ok!(std::os::unix::fs::symlink(&secret, granted.join("link")));
+
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.list = Some(vec![
+            "./workspace/*".into(),
+            granted.display().to_string(),
+            format!("{}/*", granted.display()),
+        ]);
+    }
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+    assert_eq!(assert_ok!(host.list_dir(&granted)), ["a.md", "link"]);
+
+    for path in [
+        PathBuf::from("./workspace/../../etc"),
+        granted.join("../secret"),
+        granted.join("link"),
+    ] {
+        let err = assert_err!(host.list_dir(&path));
+        assert_matches!(err, CapError::ConstraintViolation { .. });
+        let ev = assert_some!(host.trace().last());
+        assert_eq!(ev.event_type, EventType::FsConstraintViolation);
+    }
+    assert_matches!(
+        assert_err!(secure_read_dir(&secret, &granted)),
+        SecureOpenError::OutsideGrant { .. }
+    );
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn manifest_posture_for_undeclared_fs() {
+    init_tracing();
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    manifest.capabilities.fs = None;
+    assert_eq!(manifest.posture, Posture::DenyAll);
+
+    let mut deny = HostState::new(manifest.clone(), 12_345, SigningKey::generate(&mut OsRng));
+    let err = assert_err!(deny.execute_plugin("./workspace/config.toml"));
+    assert_matches!(err, CapError::NoFsCapability);
+
+    manifest.posture = Posture::Audit;
+    let mut audit = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+    assert!(assert_ok!(audit.execute_plugin("./workspace/config.toml")));
+    let types = audit
+        .trace()
+        .iter()
+        .map(|ev| ev.event_type.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(types, [EventType::CapAudit]);
+    assert!(audit.capabilities_json().contains(r#""posture":"audit""#));
+}
+
+#[test]
+fn cap_error_host_status_mapping_is_stable() {
+    let pattern = assert_err!(glob::Pattern::new("["));
+    let cases = [
+        (CapError::NoFsCapability, HostStatus::Denied),
+        (CapError::NoReadPatterns, HostStatus::Denied),
+        (
+            CapError::GlobMismatch {
+                path: "/etc/passwd".into(),
+                patterns_tried: vec!["./workspace/*".into()],
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::RuntimeInvalidGlob {
+                capability: "fs.read",
+                idx: 0,
+                pattern: "[".into(),
+                source: pattern,
+            },
+            HostStatus::Denied,
+        ),
+        (CapError::InvalidPath, HostStatus::Error),
+        (
+            CapError::ApprovalDenied {
+                path: "./workspace/a".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::ConstraintViolation {
+                path: "./workspace/a".into(),
+                reason: "too large".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::NoCustomCapability {
+                kind: "clipboard".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::CustomDenied {
+                kind: "clipboard".into(),
+                request: "paste".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::BudgetExhausted { resource: "calls" },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::RunAsMismatch {
+                identity: "uid=0(root) gid=0".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::UnmetRequirement {
+                requirement: "features `net`".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (CapError::NoTimeCapability, HostStatus::Denied),
+        (CapError::NoBlobCapability, HostStatus::Denied),
+        (
+            CapError::BlobDenied {
+                name: "secret".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (CapError::NoConfigCapability, HostStatus::Denied),
+        (
+            CapError::ConfigDenied {
+                key: "db_password".into(),
+            },
+            HostStatus::Denied,
+        ),
+        (
+            CapError::Busy {
+                import: "read_file".into(),
+                cap_ms: 50,
+            },
+            HostStatus::Busy,
+        ),
+        (
+            CapError::Io(std::io::ErrorKind::NotFound.into()),
+            HostStatus::Error,
+        ),
+    ];
+    for (err, status) in &cases {
+        assert_eq!(HostStatus::from(err), *status, "{}", err.reason_code());
+    }
+
+    for (status, code, name) in [
+        (HostStatus::Allowed, 0, "allowed"),
+        (HostStatus::Denied, 1, "denied"),
+        (HostStatus::Error, -1, "error"),
+        (HostStatus::Busy, 2, "busy"),
+    ] {
+        assert_eq!(i32::from(status), code);
+        assert_eq!(HostStatus::try_from(code), Ok(status));
+        assert_eq!(status.to_string(), name);
+        let json = assert_ok!(serde_json::to_string(&status));
+        assert_eq!(json, format!("\"{name}\""));
+        assert_eq!(
+            assert_ok!(serde_json::from_str::<HostStatus>(&json)),
+            status
+        );
+    }
+    assert_eq!(HostStatus::try_from(3), Err(3));
+}
+
+#[test]
+fn manifest_custom_capability_enforcer() {
+    init_tracing();
+    let mut manifest = assert_ok!(load_manifest("examples/manifest.json"));
+    manifest.capabilities.custom = vec![CustomCapability {
+        kind: "clipboard".into(),
+        params: serde_json::json!({ "ops": ["copy"] }),
+    }];
+    assert_ok!(manifest.validate());
+
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng));
+    let err = assert_err!(host.check_custom("clipboard", "copy"));
+    assert_matches!(err, CapError::CustomDenied { .. });
+
+    host.register_custom_enforcer("clipboard", |params, request| {
+        params["ops"]
+            .as_array()
+            .is_some_and(|ops| ops.iter().any(|op| op == request))
+    });
+    assert!(assert_ok!(host.check_custom("clipboard", "copy")));
+    let err = assert_err!(host.check_custom("clipboard", "paste"));
+    assert_eq!(
+        err,
+        CapError::CustomDenied {
+            kind: "clipboard".into(),
+            request: "paste".into(),
+        }
+    );
+    let err = assert_err!(host.check_custom("dialog", "open"));
+    assert_eq!(err.reason_code(), "no_custom_capability");
+
+    let trace = host.trace();
+    assert_eq!(trace.len(), 4);
+    assert_eq!(trace[1].event_type, EventType::CapCall);
+    assert_eq!(trace[1].input, "clipboard: copy");
+    assert!(trace[1].outcome);
+    assert_eq!(trace[2].event_type, EventType::CapCall);
+    assert!(!trace[2].outcome);
+    assert_eq!(trace[3].event_type, EventType::CapError);
+    assert_ok!(verify_chain(trace));
+}
+
+#[test]
+fn manifest_hot_reload_tags_events_with_epoch() {
+    init_tracing();
+    let mut host = make_host_with_seed(12_345);
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+    let initial_hash = host.manifest_hash().to_owned();
+
+    let mut invalid = load_example_manifest();
+    invalid.plugin.clear();
+    assert_err!(host.reload_manifest(invalid));
+    assert_eq!(host.policy_epoch(), 0);
+
+    let mut narrowed = load_example_manifest();
+    narrowed.capabilities.fs = None;
+    assert_ok!(host.reload_manifest(narrowed));
+    assert_eq!(host.policy_epoch(), 1);
+    assert_ne!(host.manifest_hash(), initial_hash);
+    assert_err!(host.execute_plugin("./workspace/config.toml"));
+
+    let epochs = host
+        .trace()
+        .iter()
+        .map(|ev| (ev.event_type.clone(), ev.policy_epoch))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        epochs,
+        [
+            (EventType::CapCall, 0),
+            (EventType::PolicyReload, 1),
+            (EventType::CapError, 1),
+        ]
+    );
+    assert!(host.trace()[1].input.contains(host.manifest_hash()));
+    assert!(!assert_ok!(serde_json::to_string(&host.trace()[0])).contains("policy_epoch"));
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn manifest_reload_rechecks_host_and_closes_handles() {
+    let dir = assert_ok!(tempdir());
+    let data = dir.path().join("data.bin");
+    assert_ok!(std::fs::write(&data, b"0123456789"));
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+    }
+    let mut host = HostState::new(manifest.clone(), 12_345, SigningKey::from_bytes(&[7; 32]));
+    let handle = assert_ok!(host.open_read(&data));
+    assert_ok!(host.read_chunk(handle, 4));
+
+    let mut unmet = manifest.clone();
+    unmet.requires = Some(Requirements {
+        captra: None,
+        features: vec!["net".into()],
+    });
+    let err = assert_err!(host.reload_manifest(unmet));
+    assert_matches!(err, CaptraError::Cap(CapError::UnmetRequirement { .. }));
+    let mut foreign = manifest.clone();
+    foreign.run_as = Some(RunAs {
+        users: vec!["captra-nobody-user".into()],
+        ..RunAs::default()
+    });
+    let err = assert_err!(host.reload_manifest(foreign));
+    assert_matches!(err, CaptraError::Cap(CapError::RunAsMismatch { .. }));
+    assert_eq!(host.policy_epoch(), 0);
+    assert_ok!(host.read_chunk(handle, 4));
+
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec!["./other/*".into()]);
+    }
+    assert_ok!(host.reload_manifest(manifest));
+    assert_matches!(host.read_chunk(handle, 4), Err(CapError::Io(_)));
+    assert_err!(host.open_read(&data));
+
+    let trace = host.trace();
+    let close = trace.iter().position(|ev| {
+        ev.input
+            .ends_with(&format!("close handle={handle} bytes=8"))
+    });
+    let reload = trace
+        .iter()
+        .position(|ev| ev.event_type == EventType::PolicyReload);
+    assert_eq!(close.map(|idx| idx + 1), reload);
+    assert_ok!(verify_chain(trace));
+}
+
+#[test]
+fn decision_cache_is_flushed_on_reload() {
+    init_tracing();
+    let mut host = make_host_with_seed(12_345).with_decision_cache();
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+
+    let mut revoked = load_example_manifest();
+    if let Some(fs) = revoked.capabilities.fs.as_mut() {
+        fs.read = Some(vec!["./other/*".into()]);
+    }
+    assert_ok!(host.reload_manifest(revoked));
+    let err = assert_err!(host.execute_plugin("./workspace/config.toml"));
+    assert_eq!(err.reason_code(), "glob_mismatch");
+
+    let types = host
+        .trace()
+        .iter()
+        .map(|ev| (ev.event_type.clone(), ev.outcome))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            (EventType::CapCall, true),
+            (EventType::CapCall, true),
+            (EventType::PolicyReload, true),
+            (EventType::CapCall, false),
+        ]
+    );
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn manifest_budget_denies_calls_once_used_up() {
+    init_tracing();
+    let mut host = make_host_with_seed(12_345).with_budget(Budget {
+        wall_ms: None,
+        calls: Some(2),
+    });
+    assert!(assert_ok!(host.execute_plugin("./workspace/config.toml")));
+    assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_eq!(
+        host.remaining_budget(),
+        Some(Budget {
+            wall_ms: None,
+            calls: Some(0),
+        })
+    );
+    let err = assert_err!(host.execute_plugin("./workspace/config.toml"));
+    assert_eq!(err, CapError::BudgetExhausted { resource: "calls" });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CapError);
+    assert_eq!(ev.input, "budget_exhausted: calls budget used up");
+
+    let mut timed = make_host_with_seed(12_345).with_budget(Budget {
+        wall_ms: Some(0),
+        calls: None,
+    });
+    let err = assert_err!(timed.execute_plugin("./workspace/config.toml"));
+    assert_eq!(
+        err,
+        CapError::BudgetExhausted {
+            resource: "wall_ms"
+        }
+    );
+}
+
+#[test]
+fn manifest_budget_threshold_fires_once() {
+    let crossed = Arc::new(Mutex::new(Vec::new()));
+    let mut host = make_host_with_seed(12_345).with_budget(Budget {
+        wall_ms: None,
+        calls: Some(5),
+    });
+    let sink = Arc::clone(&crossed);
+    host.on_threshold("calls", 80, move |threshold| {
+        sink.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(*threshold);
+    });
+    host.on_threshold("wall_ms", 50, |_| panic!("no wall_ms limit set"));
+    for _ in 0..5 {
+        assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    }
+    assert_eq!(
+        *crossed.lock().unwrap_or_else(PoisonError::into_inner),
+        [BudgetThreshold {
+            resource: "calls",
+            percent: 80,
+            used: 4,
+            limit: 5,
+        }]
+    );
+}
+
+#[test]
+fn manifest_strict_load_rejects_unknown_fields() {
+    assert_ok!(CapabilityManifest::load_with(
+        "examples/manifest.json",
+        LoadOptions::strict()
+    ));
+
+    let typo = r#"{
+        "plugin": "formatter-v1",
+        "version": "0.1",
+        "issued_by": "dev-team",
+        "capabilities": { "fs": { "raed": ["./workspace/*"], "write": null } }
+    }"#;
+    let lenient = assert_ok!(CapabilityManifest::from_json_with(
+        typo,
+        LoadOptions::default()
+    ));
+    assert!(!lenient.allows_read("./workspace/a.toml"));
+    let err = assert_err!(CapabilityManifest::from_json_with(
+        typo,
+        LoadOptions::strict()
+    ));
+    assert_matches!(err, ManifestError::UnknownField(ref field) if field == "capabilities.fs.raed");
+
+    let top_level = typo.replace(
+        "\"issued_by\"",
+        "\"posture\": \"audit\", \"isued_by\": \"x\", \"issued_by\"",
+    );
+    let err = assert_err!(CapabilityManifest::from_json_with(
+        &top_level,
+        LoadOptions::strict()
+    ));
+    assert_matches!(err, ManifestError::UnknownField(ref field) if field == "isued_by");
+}
+
+#[test]
+fn host_blobs_follow_blob_capability() {
+    let mut host = make_host_with_seed(12_345).with_blob("config", b"mode=fast".as_slice());
+    assert_eq!(
+        assert_err!(host.get_blob("config")),
+        CapError::NoBlobCapability
+    );
+
+    let mut manifest = load_example_manifest();
+    manifest.capabilities.blob = Some(BlobCapability {
+        allow: vec!["config".into(), "assets".into()],
+    });
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng))
+        .with_blob("config", b"mode=fast".as_slice())
+        .with_blob("secret", b"hunter2".as_slice());
+    let handle = assert_ok!(host.get_blob("config"));
+    assert_eq!(assert_ok!(host.blob(handle)), b"mode=fast");
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CapCall);
+    assert_eq!(ev.input, "blob config: handle=0 bytes=9");
+
+    assert_eq!(
+        assert_err!(host.get_blob("secret")),
+        CapError::BlobDenied {
+            name: "secret".into()
+        }
+    );
+    assert!(!assert_some!(host.trace().last()).outcome);
+    assert_matches!(assert_err!(host.get_blob("assets")), CapError::Io(_));
+    assert_matches!(assert_err!(host.blob(7)), CapError::Io(_));
+}
+
+#[test]
+fn host_config_follows_config_capability() {
+    let values = BTreeMap::from([
+        ("mode".to_owned(), serde_json::json!("fast")),
+        ("db_password".to_owned(), serde_json::json!("hunter2")),
+    ]);
+    let mut host = make_host_with_seed(12_345).with_config_provider(values.clone());
+    assert_eq!(
+        assert_err!(host.get_config("mode")),
+        CapError::NoConfigCapability
+    );
+
+    let mut manifest = load_example_manifest();
+    manifest.capabilities.config = Some(ConfigCapability {
+        keys: vec!["mode".into(), "retries".into()],
+    });
+    assert_eq!(
+        captra::simulate(
+            &manifest,
+            &[
+                SimCall::Config("mode".into()),
+                SimCall::Config("db_password".into())
+            ]
+        ),
+        [Decision::Allow, Decision::Deny("config_denied")]
+    );
+    let mut host = HostState::new(manifest, 12_345, SigningKey::generate(&mut OsRng))
+        .with_config_provider(values);
+    assert_eq!(
+        assert_ok!(host.get_config("mode")),
+        Some(serde_json::json!("fast"))
+    );
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CapCall);
+    assert_eq!(ev.input, "config mode: bytes=6");
+    assert_eq!(assert_ok!(host.get_config("retries")), None);
+
+    assert_eq!(
+        assert_err!(host.get_config("db_password")),
+        CapError::ConfigDenied {
+            key: "db_password".into()
+        }
+    );
+    assert!(!assert_some!(host.trace().last()).outcome);
+    assert!(host.trace().iter().all(|ev| !ev.input.contains("hunter2")));
+}
+
+#[test]
+fn manifest_validate_report_lists_every_problem() {
+    assert!(validate_report("examples/manifest.json", LoadOptions::strict()).is_valid());
+
+    let dir = assert_ok!(tempdir());
+    let path = dir.path().join("manifest.json");
+    let json = r#"{
+        "plugin": "",
+        "version": "0.1",
+        "issued_by": "",
+        "capabilities": {
+            "fs": { "read": ["./workspace/[", "./ok/*"], "raed": [], "write": null },
+            "time": { "granularity_ms": 0 },
+            "custom": [{ "kind": "" }]
+        }
+    }"#;
+    assert_ok!(std::fs::write(&path, json));
+    assert_err!(load_manifest(&path));
+
+    let report = validate_report(&path, LoadOptions::strict());
+    let codes = report.problems.iter().map(|p| p.code).collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            "unknown_field",
+            "invalid_plugin",
+            "invalid_issuer",
+            "invalid_glob",
+            "invalid_time_granularity",
+            "invalid_custom_kind",
+        ]
+    );
+    let parsed = assert_ok!(serde_json::from_str::<serde_json::Value>(&report.to_json()));
+    assert_eq!(
+        parsed["problems"][0]["message"],
+        "Unknown manifest field `capabilities.fs.raed`"
+    );
+
+    let report = validate_report(dir.path().join("missing.json"), LoadOptions::default());
+    assert_eq!(report.problems.len(), 1);
+    assert_eq!(report.problems[0].code, "io");
+}
+
+#[test]
+fn manifest_run_as_checks_process_identity() {
+    let identity = ProcessIdentity {
+        uid: Some(1000),
+        user: Some("builder".into()),
+        gids: vec![1000, 27],
+        groups: vec!["builder".into(), "sudo".into()],
+        sid: None,
+    };
+    let run_as = |users: &[&str], groups: &[&str], sids: &[&str]| RunAs {
+        users: users.iter().map(ToString::to_string).collect(),
+        groups: groups.iter().map(ToString::to_string).collect(),
+        sids: sids.iter().map(ToString::to_string).collect(),
+    };
+    assert!(run_as(&[], &[], &[]).permits(&identity));
+    assert!(run_as(&["builder"], &["27"], &[]).permits(&identity));
+    assert!(run_as(&["1000"], &[], &[]).permits(&identity));
+    assert!(!run_as(&["root", "0"], &[], &[]).permits(&identity));
+    assert!(!run_as(&[], &[], &["S-1-5-32-545"]).permits(&identity));
+
+    let mut manifest = load_example_manifest();
+    manifest.run_as = Some(run_as(&["captra-nobody-user"], &[], &[]));
+    let mut host = HostState::new(manifest.clone(), 12_345, SigningKey::from_bytes(&[7; 32]));
+    let err = assert_err!(host.check_run_as());
+    assert_matches!(err, CapError::RunAsMismatch { .. });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CapError);
+    assert!(ev.input.starts_with("run_as_mismatch: uid="));
+
+    let current = ProcessIdentity::current();
+    let uid = assert_some!(current.uid).to_string();
+    manifest.run_as = Some(run_as(&[&uid], &[], &[]));
+    let mut host = HostState::new(manifest, 12_345, SigningKey::from_bytes(&[7; 32]));
+    assert_ok!(host.check_run_as());
+    assert!(host.trace().is_empty());
+}
+
+#[test]
+fn manifest_requires_checks_host_features() {
+    let key = || SigningKey::from_bytes(&[7; 32]);
+    let requires = |captra: &str, features: &[&str]| Requirements {
+        captra: Some(captra.into()),
+        features: features.iter().map(ToString::to_string).collect(),
+    };
+    let mut manifest = load_example_manifest();
+    manifest.requires = Some(requires(">=0.1", &["fs", "blob"]));
+    assert_ok!(HostState::try_new(manifest.clone(), 12_345, key()));
+
+    manifest.requires = Some(requires(">=99.0", &[]));
+    let err = assert_err!(HostState::try_new(manifest.clone(), 12_345, key()));
+    assert_matches!(err, CapError::UnmetRequirement { requirement } if requirement.starts_with("captra >=99.0"));
+
+    manifest.requires = Some(requires("*", &["net", "http-client"]));
+    let mut host = HostState::new(manifest.clone(), 12_345, key());
+    let err = assert_err!(host.check_requirements());
+    assert_eq!(
+        err,
+        CapError::UnmetRequirement {
+            requirement: "features `net`, `http-client`".into()
+        }
+    );
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CapError);
+    assert!(ev.input.starts_with("unmet_requirement: features"));
+
+    host.register_custom_enforcer("net", |_, _| true);
+    host.register_custom_enforcer("http-client", |_, _| true);
+    assert_ok!(host.check_requirements());
+
+    manifest.requires = Some(requires("not a version", &[]));
+    let err = assert_err!(manifest.validate());
+    assert_eq!(err.code(), "invalid_requirement");
+}
+
+#[test]
+fn manifest_typed_matchers_select_per_entry() {
+    let manifest = assert_ok!(CapabilityManifest::from_json_with(
+        r#"{
+            "plugin": "matchers",
+            "version": "0.1",
+            "capabilities": { "fs": { "read": [
+                "./docs/*.md",
+                { "type": "prefix", "pattern": "./workspace/" }
+            ] } },
+            "issued_by": "dev-team"
+        }"#,
+        LoadOptions::strict()
+    ));
+    let fs = assert_some!(manifest.capabilities.fs.as_ref());
+    assert_eq!(
+        fs.read.as_deref(),
+        Some(["./docs/*.md".to_owned(), "prefix:./workspace/".to_owned()].as_slice())
+    );
+    assert!(manifest.allows_read("./docs/a.md"));
+    assert!(manifest.allows_read("./workspace/deep/nested/file.bin"));
+    assert!(manifest.allows_read("./workspace/a..b"));
+    assert!(!manifest.allows_read("./other/file.md"));
+    assert!(!manifest.allows_read("./workspace/../etc/passwd"));
+    assert!(!manifest.allows_read("./workspace/deep/../../etc/passwd"));
+    let mut host = HostState::new(manifest.clone(), 12_345, SigningKey::from_bytes(&[7; 32]));
+    assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    assert_err!(host.execute_plugin("./workspace/../etc/passwd"));
+
+    let parse = |spec| -> captra::Result<Matcher> { Ok(Matcher::parse(spec)?) };
+    assert_matches!(
+        assert_err!(parse("[unclosed")),
+        CaptraError::Matcher(MatcherError::Glob(_))
+    );
+    let prefix = assert_ok!(Matcher::parse("prefix:./workspace/"));
+    assert_eq!(prefix.kind(), MatcherKind::Prefix);
+    assert!(prefix.matches("./workspace/a/b"));
+    // A prefix match must not climb out through `..`, even back into the prefix.
+    assert!(!prefix.matches("./workspace/../secret"));
+    assert!(!prefix.matches("./workspace/a/../b"));
+    assert_eq!(bounding_glob("prefix:./workspace/"), "./workspace/*");
+    assert_eq!(
+        bounding_glob(r"regex:^\./workspace/.*\.toml$"),
+        "./workspace/*"
+    );
+    assert_eq!(bounding_glob(r"regex:^\./a|b"), "*");
+
+    let typo = r#"{
+        "plugin": "matchers",
+        "version": "0.1",
+        "capabilities": { "fs": { "read": [{ "type": "prefix", "pattern": "./", "case": 1 }] } },
+        "issued_by": "dev-team"
+    }"#;
+    let err = assert_err!(CapabilityManifest::from_json_with(
+        typo,
+        LoadOptions::strict()
+    ));
+    assert_matches!(err, ManifestError::UnknownField(field) if field == "capabilities.fs.read[0].case");
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![r"regex:^\./workspace/[a-z]+\.toml$".into()]);
+    }
+    if cfg!(feature = "regex-matcher") {
+        assert!(manifest.allows_read("./workspace/config.toml"));
+        assert!(!manifest.allows_read("./workspace/config.toml.bak"));
+        let mut host = HostState::new(manifest, 12_345, SigningKey::from_bytes(&[7; 32]));
+        assert_ok!(host.execute_plugin("./workspace/config.toml"));
+        assert_err!(host.execute_plugin("./workspace/Config.toml"));
+    } else {
+        let err = assert_err!(manifest.validate());
+        assert_eq!(err.code(), "invalid_matcher");
+        let mut host = HostState::new(manifest, 12_345, SigningKey::from_bytes(&[7; 32]));
+        let err = assert_err!(host.execute_plugin("./workspace/config.toml"));
+        assert_matches!(err, CapError::RuntimeInvalidMatcher { idx: 0, .. });
+
+        let mut manifest = load_example_manifest();
+        if let Some(fs) = manifest.capabilities.fs.as_mut() {
+            fs.content_filters = vec![captra::ContentFilter {
+                regex: "secret".into(),
+                paths: Vec::new(),
+            }];
+        }
+        assert_matches!(
+            manifest.validate(),
+            Err(ManifestError::InvalidContentFilter { idx: 0, .. })
+        );
+    }
+}
+
+#[test]
+fn manifest_time_capability_coarsens_clock() {
+    let mut manifest = assert_ok!(CapabilityManifest::from_json_with(
+        r#"{
+            "plugin": "clock",
+            "version": "0.1",
+            "capabilities": { "fs": null, "time": { "granularity_ms": 60000 } },
+            "issued_by": "dev-team"
+        }"#,
+        LoadOptions::strict()
+    ));
+    let mut host = HostState::new(manifest.clone(), 12_345, SigningKey::from_bytes(&[7; 32]));
+    let now = assert_ok!(host.now_millis());
+    assert_eq!(now % 60_000, 0);
+    assert!(now > 0);
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CapCall);
+    assert_eq!(ev.input, "time: now_millis granularity_ms=60000");
+    let signed = assert_ok!(host.sign_current_trace());
+    assert_eq!(signed.time_granularity_ms, Some(60_000));
+
+    manifest.capabilities.time = None;
+    let mut host = HostState::new(manifest.clone(), 12_345, SigningKey::from_bytes(&[7; 32]));
+    assert_eq!(assert_err!(host.now_millis()), CapError::NoTimeCapability);
+    assert_eq!(
+        assert_ok!(host.sign_current_trace()).time_granularity_ms,
+        None
+    );
+
+    let json = assert_ok!(serde_json::to_string(&manifest))
+        .replace(r#""fs":null"#, r#""fs":null,"time":{"granularity_ms":0}"#);
+    assert_matches!(
+        CapabilityManifest::from_json_with(&json, LoadOptions::strict()),
+        Err(ManifestError::InvalidTimeGranularity)
+    );
+}
+
+#[test]
+fn manifest_merges_capability_files() {
+    let dir = assert_ok!(tempdir());
+    let write = |name: &str, json: &str| assert_ok!(std::fs::write(dir.path().join(name), json));
+    write(
+        "manifest.json",
+        r#"{
+            "plugin": "composed",
+            "version": "0.1",
+            "capabilities": { "custom": [{ "kind": "clipboard" }] },
+            "capability_files": ["fs.json", "extra.json"],
+            "issued_by": "dev-team"
+        }"#,
+    );
+    write(
+        "fs.json",
+        r#"{ "fs": { "read": ["./workspace/*"], "write": [] } }"#,
+    );
+    write(
+        "extra.json",
+        r#"{ "time": { "granularity_ms": 1000 }, "custom": [{ "kind": "dialog" }] }"#,
+    );
+
+    let path = dir.path().join("manifest.json");
+    let mut manifest = assert_ok!(CapabilityManifest::load_with(&path, LoadOptions::strict()));
+    assert!(manifest.allows_read("./workspace/config.toml"));
+    assert_eq!(
+        assert_some!(manifest.capabilities.time).granularity_ms,
+        1000
+    );
+    let kinds = manifest
+        .capabilities
+        .custom
+        .iter()
+        .map(|cap| cap.kind.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["clipboard", "dialog"]);
+
+    // The signature covers the merged manifest, which no longer lists the files.
+    let key = SigningKey::from_bytes(&[3; 32]);
+    assert_ok!(manifest.sign(&key));
+    let merged = assert_ok!(serde_json::to_string(&manifest));
+    assert!(!merged.contains("capability_files"));
+    let roots = [("dev-team".to_owned(), key.verifying_key())].into();
+    let reloaded = assert_ok!(CapabilityManifest::from_json_with(
+        &merged,
+        LoadOptions::strict()
+    ));
+    assert_ok!(reloaded.validate_chain(&roots));
+
+    write("extra.json", r#"{ "fs": { "read": ["/etc/*"] } }"#);
+    let err = assert_err!(CapabilityManifest::load(&path));
+    assert_matches!(err, ManifestError::CapabilityFile { ref file, .. } if file == "extra.json");
+    assert!(err.to_string().contains("`fs` is already declared"));
+
+    write("extra.json", r#"{ "tiem": { "granularity_ms": 1000 } }"#);
+    assert_ok!(CapabilityManifest::load(&path));
+    assert_matches!(
+        CapabilityManifest::load_with(&path, LoadOptions::strict()),
+        Err(ManifestError::UnknownField(field)) if field == "extra.json.tiem"
+    );
+
+    let json = assert_ok!(std::fs::read_to_string(&path));
+    assert_matches!(
+        CapabilityManifest::from_json_with(&json, LoadOptions::default()),
+        Err(ManifestError::CapabilityFile { .. })
+    );
+}
+
+#[test]
+fn manifest_summary_lists_grants() {
+    let summary = load_example_manifest().summary();
+    assert_eq!(
+        summary,
+        "plugin: formatter-v1 0.1\n\
+         issued by: dev-team\n\
+         expires: never\n\
+         fs: 1 read, 0 write, 0 list, 0 content filters\n\
+         time: none\n\
+         custom: 0\n\
+         widest glob: ./workspace/*"
+    );
+
+    let mut manifest = load_example_manifest();
+    let fs = assert_some!(manifest.capabilities.fs.as_mut());
+    fs.list = Some(vec!["./workspace/docs/**".into(), "./**".into()]);
+    manifest.capabilities.custom = vec![CustomCapability {
+        kind: "clipboard".into(),
+        params: serde_json::Value::Null,
+    }];
+    manifest.posture = Posture::Audit;
+    assert_eq!(manifest.widest_glob(), Some("./**"));
+    let summary = manifest.summary();
+    assert!(summary.contains("fs: 1 read, 0 write, 2 list"));
+    assert!(summary.contains("custom: 1 (clipboard)"));
+    assert!(summary.contains("posture: audit"));
+}
+
+#[test]
+fn manifest_simulate_decides_without_host() {
+    let mut manifest = load_example_manifest();
+    let calls = [
+        SimCall::Read("./workspace/config.toml".into()),
+        SimCall::Read("/etc/passwd".into()),
+        SimCall::List("./workspace".into()),
+        SimCall::Time,
+        SimCall::Custom("clipboard".into()),
+        SimCall::Read(String::new()),
+    ];
+    assert_eq!(
+        captra::simulate(&manifest, &calls),
+        [
+            Decision::Allow,
+            Decision::Deny("glob_mismatch"),
+            Decision::Deny("glob_mismatch"),
+            Decision::Deny("no_time_capability"),
+            Decision::Deny("no_custom_capability"),
+            Decision::Deny("invalid_path"),
+        ]
+    );
+
+    assert_some!(manifest.capabilities.fs.as_mut()).extensions = Some(vec!["md".into()]);
+    manifest.posture = Posture::Audit;
+    let decisions = captra::simulate(&manifest, &calls[..4]);
+    assert_eq!(decisions[0], Decision::Deny("constraint_violation"));
+    assert_eq!(decisions[3], Decision::Audit);
+    assert!(decisions[3].is_allowed());
+}
+
+#[test]
+fn manifest_profiles_overlay_capabilities() {
+    let json = r#"{
+        "plugin": "profiles",
+        "version": "0.1",
+        "capabilities": {
+            "fs": { "read": ["./workspace/*"] },
+            "time": { "granularity_ms": 1000 }
+        },
+        "profiles": {
+            "ci": { "fs": { "read": ["./workspace/*", "./fixtures/*"] } },
+            "local": { "time": { "granularity_ms": 10 } }
+        },
+        "issued_by": "dev-team"
+    }"#;
+    let manifest = assert_ok!(CapabilityManifest::from_json_with(
+        json,
+        LoadOptions::strict()
+    ));
+    assert_eq!(
+        manifest.profile_names().collect::<Vec<_>>(),
+        ["ci", "local"]
+    );
+    assert!(manifest.summary().contains("profiles: ci, local"));
+
+    let ci = assert_ok!(manifest.resolve_profile("ci"));
+    assert!(ci.allows_read("./fixtures/a.json"));
+    assert_eq!(ci.capabilities.time, manifest.capabilities.time);
+    assert!(ci.profiles.is_empty());
+    let local = assert_ok!(manifest.resolve_profile("local"));
+    assert!(!local.allows_read("./fixtures/a.json"));
+    assert_eq!(assert_some!(local.capabilities.time).granularity_ms, 10);
+    assert_matches!(
+        manifest.resolve_profile("prod"),
+        Err(ManifestError::UnknownProfile(name)) if name == "prod"
+    );
+
+    let key = || SigningKey::from_bytes(&[7; 32]);
+    let mut host = assert_ok!(HostState::new(manifest.clone(), 12_345, key()).with_profile("ci"));
+    assert_eq!(host.profile(), Some("ci"));
+    assert_eq!(host.manifest_hash(), ci.content_hash());
+    assert_ok!(host.execute_plugin("./fixtures/a.json"));
+    let signed = assert_ok!(host.with_embedded_manifest(true).sign_current_trace());
+    assert_eq!(signed.profile.as_deref(), Some("ci"));
+    assert_ok!(signed.manifest(&assert_ok!(signed.embedded_pubkey())));
+
+    let mut host = HostState::new(manifest, 12_345, key());
+    assert_err!(host.execute_plugin("./fixtures/a.json"));
+    assert!(host.profile().is_none());
+
+    let invalid = json.replace("\"granularity_ms\": 10 }", "\"granularity_ms\": 0 }");
+    assert_matches!(
+        CapabilityManifest::from_json_with(&invalid, LoadOptions::strict()),
+        Err(ManifestError::InvalidTimeGranularity)
+    );
+    let typo = json.replace("\"local\": { \"time\"", "\"local\": { \"tmie\"");
+    assert_matches!(
+        CapabilityManifest::from_json_with(&typo, LoadOptions::strict()),
+        Err(ManifestError::UnknownField(field)) if field == "profiles.local.tmie"
+    );
+}
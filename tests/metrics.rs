@@ -0,0 +1,50 @@
+mod common;
+
+use crate::common::host::make_host_with_seed;
+use captra::{DecisionStats, DecisionTiming};
+use claims::{assert_err, assert_ok};
+use std::time::Duration;
+
+#[test]
+fn metrics_decision_timings_beside_trace() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let snapshot = host.snapshot();
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+
+    let seqs = host
+        .decision_timings()
+        .iter()
+        .map(|timing| timing.seq)
+        .collect::<Vec<_>>();
+    assert_eq!(seqs, [1, 2]);
+    assert_eq!(host.decision_stats().decisions, 2);
+    assert!(!host.get_trace_json().contains("duration"));
+
+    assert_ok!(host.restore(&snapshot));
+    assert_eq!(host.decision_timings().len(), 1);
+}
+
+#[test]
+fn metrics_decision_stats_aggregate() {
+    let timings = [
+        DecisionTiming {
+            seq: 1,
+            duration: Duration::from_micros(10),
+        },
+        DecisionTiming {
+            seq: 3,
+            duration: Duration::from_micros(50),
+        },
+        DecisionTiming {
+            seq: 4,
+            duration: Duration::from_micros(30),
+        },
+    ];
+    let stats = DecisionStats::from_timings(&timings);
+    assert_eq!(stats.decisions, 3);
+    assert_eq!(stats.max, Duration::from_micros(50));
+    assert_eq!(stats.slowest_seq, Some(3));
+    assert_eq!(stats.mean(), Duration::from_micros(30));
+    assert_eq!(DecisionStats::default().mean(), Duration::ZERO);
+}
@@ -0,0 +1,86 @@
+#![cfg(all(
+    feature = "native",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+
+mod common;
+
+use crate::common::{host::make_host_with_seed, manifest::load_example_manifest};
+use captra::{EventType, NativeError, ResourceLimits, SeccompProfile};
+use claims::{assert_err, assert_matches, assert_ok, assert_some};
+
+#[test]
+fn seccomp_profile_follows_capabilities() {
+    let mut manifest = load_example_manifest();
+    let profile = SeccompProfile::from_manifest(&manifest);
+    assert!(profile.allows(libc::SYS_openat));
+    assert!(!profile.allows(libc::SYS_unlinkat));
+    assert!(!profile.allows(libc::SYS_socket));
+
+    manifest.capabilities.fs = None;
+    let profile = SeccompProfile::from_manifest(&manifest).allow(libc::SYS_socket);
+    assert!(!profile.allows(libc::SYS_openat));
+    assert!(profile.allows(libc::SYS_socket));
+}
+
+#[test]
+fn seccomp_child_violation_is_traced() {
+    let profile = SeccompProfile::from_manifest(&load_example_manifest());
+    let mut host = make_host_with_seed(12_345);
+
+    assert_eq!(assert_ok!(profile.run(&mut host, || 7)), 7);
+    assert!(host.trace().is_empty());
+
+    // SAFETY: plain syscall, the child is killed before it returns.
+    let err = assert_err!(profile.run(&mut host, || unsafe {
+        libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        0
+    }));
+    assert_matches!(err, NativeError::SeccompViolation { .. });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::SeccompViolation);
+    assert!(!ev.outcome);
+}
+
+#[test]
+fn native_resource_limits_are_traced() {
+    let mut host = make_host_with_seed(12_345);
+    let limits = ResourceLimits {
+        cpu_secs: Some(1),
+        memory_bytes: Some(256 << 20),
+    };
+    let profile = SeccompProfile::from_manifest(&load_example_manifest()).with_limits(limits);
+    assert_eq!(assert_ok!(profile.run(&mut host, || 3)), 3);
+
+    let err = assert_err!(profile.run(&mut host, || {
+        let mut counter = 0_u64;
+        loop {
+            counter = std::hint::black_box(counter.wrapping_add(1));
+        }
+    }));
+    assert_matches!(err, NativeError::CpuQuotaExceeded { limit_secs: 1, .. });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::CpuQuotaExceeded);
+
+    // Maps directly instead of allocating: the forked child must not touch malloc.
+    let err = assert_err!(profile.run(&mut host, || unsafe {
+        let len = 1 << 30;
+        let mapped = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if mapped == libc::MAP_FAILED {
+            libc::abort();
+        }
+        0
+    }));
+    assert_matches!(err, NativeError::MemoryQuotaExceeded { .. });
+    let ev = assert_some!(host.trace().last());
+    assert_eq!(ev.event_type, EventType::MemoryQuotaExceeded);
+    assert!(!ev.outcome);
+}
@@ -0,0 +1,12 @@
+use captra::CaptraDirs;
+use claims::assert_some;
+
+#[test]
+fn default_dirs_layout() {
+    let dirs = assert_some!(CaptraDirs::new());
+    assert!(dirs.config_dir().ends_with("captra"));
+    assert!(dirs.keys_dir().starts_with(dirs.config_dir()));
+    assert!(dirs.manifests_dir().ends_with("captra/manifests"));
+    assert!(dirs.traces_dir().starts_with(dirs.data_dir()));
+    assert!(dirs.traces_dir().ends_with("captra/traces"));
+}
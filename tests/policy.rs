@@ -0,0 +1,66 @@
+mod common;
+
+use crate::common::host::make_host_with_seed;
+use captra::{Approval, CapError, EventType, PromptMessage, PromptPolicy};
+use claims::{assert_err, assert_matches, assert_ok};
+use std::{sync::mpsc::channel, thread, time::Duration};
+
+#[test]
+fn prompt_policy_records_human_decisions() {
+    let (ui, prompts) = channel::<PromptMessage>();
+    let ui_thread = thread::spawn(move || {
+        for message in prompts {
+            let answer = if message.request.path.ends_with("secret.toml") {
+                Approval::Deny
+            } else {
+                Approval::AlwaysAllowSession
+            };
+            message.reply.send(answer).expect("reply");
+        }
+    });
+
+    let mut host = make_host_with_seed(12_345).with_policy_hook(PromptPolicy::new(ui));
+    assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let err = assert_err!(host.execute_plugin("./workspace/secret.toml"));
+    assert_matches!(err, CapError::ApprovalDenied { .. });
+    // Manifest denials never reach the prompt.
+    assert_err!(host.execute_plugin("/etc/passwd"));
+
+    let types = host
+        .trace()
+        .iter()
+        .map(|ev| ev.event_type.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            EventType::CapApproval,
+            EventType::CapCall,
+            EventType::CapApproval,
+            EventType::CapCall,
+            EventType::CapApproval,
+            EventType::CapCall,
+        ]
+    );
+    assert_eq!(
+        host.trace()[0].input,
+        "./workspace/config.toml: always_allow_session"
+    );
+    assert!(!host.trace()[4].outcome);
+
+    drop(host);
+    ui_thread.join().expect("ui thread");
+}
+
+#[test]
+fn prompt_policy_times_out_to_deny() {
+    let (ui, _prompts) = channel::<PromptMessage>();
+    let policy = PromptPolicy::new(ui).with_timeout(Duration::from_millis(10));
+    let mut host = make_host_with_seed(12_345).with_policy_hook(policy);
+
+    let err = assert_err!(host.execute_plugin("./workspace/config.toml"));
+    assert_matches!(err, CapError::ApprovalDenied { .. });
+    assert_eq!(host.trace().len(), 1);
+    assert_eq!(host.trace()[0].input, "./workspace/config.toml: timed_out");
+}
@@ -0,0 +1,71 @@
+use captra::{ManifestError, Registry, RegistryError};
+use claims::{assert_err, assert_matches, assert_none, assert_ok, assert_some};
+use std::{fs, path::Path};
+use tempfile::tempdir;
+
+fn write_manifest(dir: &Path, file: &str, plugin: &str, version: &str) {
+    let json = format!(
+        r#"{{
+          "plugin": "{plugin}",
+          "version": "{version}",
+          "capabilities": {{ "fs": {{ "read": ["./workspace/*"] }} }},
+          "issued_by": "dev-team"
+        }}"#
+    );
+    fs::write(dir.join(file), json).expect("Write failed");
+}
+
+#[test]
+fn registry_find_highest_matching() {
+    let tmp_dir = tempdir().expect("tempdir");
+    write_manifest(tmp_dir.as_ref(), "a.json", "formatter", "1.1.0");
+    write_manifest(tmp_dir.as_ref(), "b.json", "formatter", "1.3.2");
+    write_manifest(tmp_dir.as_ref(), "c.json", "formatter", "2.0.0");
+    write_manifest(tmp_dir.as_ref(), "d.json", "linter", "1.5");
+    fs::write(tmp_dir.as_ref().join("notes.txt"), "ignored").expect("Write failed");
+
+    let registry = assert_ok!(Registry::open_dir(&tmp_dir));
+    assert_eq!(registry.len(), 4);
+
+    let found = assert_some!(assert_ok!(registry.find("formatter", ">=1.2, <2")));
+    assert_eq!(found.version, "1.3.2");
+    let found = assert_some!(assert_ok!(registry.find("linter", "^1.2")));
+    assert_eq!(found.version, "1.5");
+    assert_none!(assert_ok!(registry.find("formatter", ">=3")));
+    assert_none!(assert_ok!(registry.find("unknown", "*")));
+
+    let err = assert_err!(registry.find("formatter", "not a req"));
+    assert_matches!(err, RegistryError::InvalidVersionReq(_));
+}
+
+#[test]
+fn registry_open_index() {
+    let tmp_dir = tempdir().expect("tempdir");
+    fs::create_dir(tmp_dir.as_ref().join("manifests")).expect("mkdir");
+    write_manifest(
+        &tmp_dir.as_ref().join("manifests"),
+        "f.json",
+        "formatter",
+        "0.1",
+    );
+    let index = tmp_dir.as_ref().join("index.json");
+    fs::write(&index, r#"["manifests/f.json"]"#).expect("Write failed");
+
+    let registry = assert_ok!(Registry::open_index(index));
+    let found = assert_some!(assert_ok!(registry.find("formatter", "0.1")));
+    assert_eq!(found.plugin, "formatter");
+}
+
+#[test]
+fn registry_rejects_non_semver() {
+    let tmp_dir = tempdir().expect("tempdir");
+    write_manifest(tmp_dir.as_ref(), "a.json", "formatter", "latest");
+    let err = assert_err!(Registry::open_dir(&tmp_dir));
+    assert_matches!(
+        err,
+        RegistryError::Manifest {
+            source: ManifestError::InvalidSemver { .. },
+            ..
+        }
+    );
+}
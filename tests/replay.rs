@@ -0,0 +1,74 @@
+mod common;
+
+use crate::common::host::make_host_with_seed;
+use captra::{DivergenceReport, EventType, ReplayHost};
+use claims::{assert_err, assert_ok};
+use tempfile::tempdir;
+
+#[test]
+fn replay_matching_run_finishes_cleanly() {
+    let mut recorded = make_host_with_seed(12_345);
+    let _ = assert_ok!(recorded.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(recorded.execute_plugin("/etc/passwd"));
+
+    let dir = assert_ok!(tempdir());
+    let path = dir.path().join("trace.json");
+    assert_ok!(recorded.save_current_trace(&path));
+
+    let mut replay = assert_ok!(ReplayHost::from_trace_file(
+        make_host_with_seed(12_345),
+        &path
+    ));
+    let _ = assert_ok!(replay.host_mut().execute_plugin("./workspace/config.toml"));
+    assert_ok!(replay.check());
+    let _ = assert_err!(replay.host_mut().execute_plugin("/etc/passwd"));
+    let host = assert_ok!(replay.finish());
+    assert_eq!(host.trace().len(), 2);
+}
+
+#[test]
+fn replay_reports_first_divergence_with_context() {
+    let mut recorded = make_host_with_seed(12_345);
+    for path in [
+        "./workspace/a.toml",
+        "./workspace/b.toml",
+        "./workspace/c.toml",
+    ] {
+        let _ = assert_ok!(recorded.execute_plugin(path));
+    }
+
+    let mut replay =
+        ReplayHost::new(make_host_with_seed(12_345), recorded.trace().to_vec()).with_context(1);
+    let host = replay.host_mut();
+    let _ = assert_ok!(host.execute_plugin("./workspace/a.toml"));
+    let _ = assert_ok!(host.execute_plugin("./workspace/b.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+
+    let report = assert_err!(replay.check());
+    assert_eq!(report.seq, 3);
+    assert_eq!(report.field, "input");
+    assert_eq!(report.context.len(), 1);
+    assert_eq!(report.context[0].input, "./workspace/b.toml");
+    assert_eq!(report.to_string(), "replay diverged at seq 3: input");
+
+    let dir = assert_ok!(tempdir());
+    let path = dir.path().join("divergence.json");
+    assert_ok!(report.save(&path));
+    let json = assert_ok!(std::fs::read_to_string(&path));
+    let parsed = assert_ok!(serde_json::from_str::<DivergenceReport>(&json));
+    assert_eq!(parsed, *report);
+    assert_eq!(
+        parsed.expected.map(|ev| ev.input),
+        Some("./workspace/c.toml".into())
+    );
+    assert_eq!(
+        parsed.actual.map(|ev| ev.event_type),
+        Some(EventType::CapCall)
+    );
+
+    let mut short = ReplayHost::new(make_host_with_seed(12_345), recorded.trace().to_vec());
+    let _ = assert_ok!(short.host_mut().execute_plugin("./workspace/a.toml"));
+    assert_ok!(short.check());
+    let report = assert_err!(short.finish());
+    assert_eq!((report.seq, report.field.as_str()), (2, "missing"));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use crate::common::host::make_host_with_seed;
+use captra::{RunSession, causal_order};
+use claims::{assert_none, assert_ok, assert_some};
+use std::cmp::Ordering;
+
+#[test]
+fn session_vector_clock_orders_hosts() {
+    let session = RunSession::new();
+    let mut host_a = make_host_with_seed(1).with_session(&session, "a");
+    let mut host_b = make_host_with_seed(2).with_session(&session, "b");
+
+    assert_ok!(host_a.execute_plugin("./workspace/a.txt"));
+    assert_ok!(host_b.execute_plugin("./workspace/b.txt"));
+
+    let ev_a = assert_some!(host_a.trace().first());
+    let ev_b = assert_some!(host_b.trace().first());
+    let clock_a = assert_some!(ev_a.vclock.as_ref());
+    let clock_b = assert_some!(ev_b.vclock.as_ref());
+
+    assert_eq!(clock_a.get("a"), Some(&1));
+    assert_eq!(clock_b.get("b"), Some(&1));
+    assert_eq!(causal_order(clock_a, clock_b), Some(Ordering::Less));
+}
+
+#[test]
+fn session_vector_clock_concurrent() {
+    let a = [("a".to_owned(), 2), ("b".to_owned(), 0)].into();
+    let b = [("a".to_owned(), 1), ("b".to_owned(), 1)].into();
+    assert_none!(causal_order(&a, &b));
+}
+
+#[test]
+fn session_absent_leaves_vclock_empty() {
+    let mut host = make_host_with_seed(1);
+    assert_ok!(host.execute_plugin("./workspace/a.txt"));
+    let ev = assert_some!(host.trace().first());
+    assert_none!(&ev.vclock);
+}
+
+#[test]
+fn session_counts_events_across_hosts() {
+    let session = RunSession::new();
+    let mut host_a = make_host_with_seed(1).with_session(&session, "a");
+    let mut host_b = make_host_with_seed(2).with_session(&session, "b");
+
+    assert_ok!(host_a.execute_plugin("./workspace/a.txt"));
+    assert_ok!(host_b.execute_plugin("./workspace/b.txt"));
+    assert_ok!(host_a.execute_plugin("./workspace/c.txt"));
+
+    assert_eq!(session.event_count(), 3);
+    let seqs = host_a.trace().iter().map(|ev| ev.seq).collect::<Vec<_>>();
+    assert_eq!(seqs, [1, 2]);
+}
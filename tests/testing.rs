@@ -0,0 +1,118 @@
+#![cfg(feature = "test-util")]
+
+use captra::{
+    EventType, assert_trace_matches,
+    testing::{
+        Fault, FaultInjector, TraceTolerance, assert_trace_equivalent, check_determinism,
+        check_trace, check_trace_equivalent, deterministic_host, fixed_keypair,
+        manifest_with_reads,
+    },
+};
+use claims::{assert_err, assert_ok};
+use std::{fs, time::Duration};
+use tempfile::tempdir;
+
+#[test]
+fn testing_deterministic_hosts_sign_identically() {
+    let manifest = manifest_with_reads("formatter", &["./workspace/*"]);
+    let mut host1 = deterministic_host(manifest.clone(), 42);
+    let mut host2 = deterministic_host(manifest, 42);
+
+    for host in [&mut host1, &mut host2] {
+        assert_ok!(host.execute_plugin("./workspace/a.toml"));
+        assert_err!(host.execute_plugin("/etc/passwd"));
+    }
+    assert_trace_matches!(host1.trace(), [CapCall => true, CapCall => false]);
+
+    let signed1 = assert_ok!(host1.sign_current_trace());
+    let signed2 = assert_ok!(host2.sign_current_trace());
+    assert_eq!(signed1.signature, signed2.signature);
+    assert_ok!(signed1.verify(&fixed_keypair(7).verifying_key()));
+}
+
+#[test]
+fn testing_check_trace_reports_mismatch() {
+    let mut host = deterministic_host(manifest_with_reads("formatter", &["./workspace/*"]), 1);
+    assert_ok!(host.execute_plugin("./workspace/a.toml"));
+    let err = assert_err!(check_trace(
+        host.trace(),
+        &[(captra::EventType::CapError, false)]
+    ));
+    assert!(err.contains("event seq 1"));
+}
+
+#[test]
+fn testing_determinism_properties_hold() {
+    assert_ok!(check_determinism(12_345, 64));
+}
+
+#[test]
+fn testing_golden_trace_equivalence() {
+    let manifest = manifest_with_reads("formatter", &["./workspace/*"]);
+    let mut golden = deterministic_host(manifest.clone(), 42);
+    assert_ok!(golden.execute_plugin("./workspace/a.toml"));
+    golden.record_plugin_shutdown(65_536, 100);
+    let dir = assert_ok!(tempdir());
+    let path = dir.path().join("golden.json");
+    assert_ok!(golden.save_current_trace(&path));
+
+    let mut actual = deterministic_host(manifest, 7);
+    assert_ok!(actual.execute_plugin("./workspace/a.toml"));
+    actual.record_plugin_shutdown(65_536, 250);
+
+    let strict = TraceTolerance::default();
+    let err = assert_err!(check_trace_equivalent(
+        golden.trace(),
+        actual.trace(),
+        &strict
+    ));
+    assert!(err.contains("run_id differs"));
+
+    let tolerance = TraceTolerance {
+        run_id: true,
+        ts_seed: true,
+        volatile_inputs: vec![EventType::PluginShutdown],
+    };
+    assert_trace_equivalent(&path, actual.trace(), &tolerance);
+}
+
+#[test]
+fn testing_fault_injector_is_reproducible() {
+    let dir = assert_ok!(tempdir());
+    let file = dir.path().join("a.txt");
+    assert_ok!(fs::write(&file, "hello"));
+    let pattern = format!("{}/*", dir.path().display());
+    let manifest = manifest_with_reads("formatter", &[&pattern]);
+    let injector = FaultInjector::new(9)
+        .at(1, Fault::Deny)
+        .at(2, Fault::Corrupt)
+        .at(3, Fault::Delay(Duration::from_millis(1)));
+
+    let mut host = deterministic_host(manifest.clone(), 1).with_fault_injector(injector.clone());
+    assert_err!(host.read_file(&file));
+    let corrupted = assert_ok!(host.read_file(&file));
+    assert_ne!(corrupted, b"hello");
+    assert_eq!(assert_ok!(host.read_file(&file)), b"hello");
+    assert_trace_matches!(host.trace(), [
+        FaultInjected => false,
+        FaultInjected => true,
+        CapCall => true,
+        FaultInjected => true,
+        CapCall => true,
+    ]);
+
+    let mut again = deterministic_host(manifest.clone(), 1).with_fault_injector(injector);
+    let _ = again.read_file(&file);
+    assert_eq!(assert_ok!(again.read_file(&file)), corrupted);
+
+    let outcomes = || {
+        let mut host = deterministic_host(manifest.clone(), 1)
+            .with_fault_injector(FaultInjector::new(3).with_rate(Fault::Deny, 0.5));
+        (0..16)
+            .map(|_| host.read_file(&file).is_ok())
+            .collect::<Vec<_>>()
+    };
+    let first = outcomes();
+    assert!(first.contains(&true) && first.contains(&false));
+    assert_eq!(first, outcomes());
+}
@@ -0,0 +1,935 @@
+mod common;
+
+use crate::common::{host::make_host_with_seed, manifest::load_example_manifest};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use captra::{
+    Approval, ApprovalRequest, ChainVerifier, CompactionPolicy, DETACHED_TRACE_FILE, EventType,
+    HostState, JsonlWriter, PolicyHook, RandomnessMode, Severity, SeverityRule, SeverityRules,
+    SignedTrace, TRACE_FORMAT_VERSION, TRACE_MAGIC, TRACE_TARGET, TraceError, TraceEvent,
+    TraceHasher, TraceLoadOptions, TracePolicy, VectorClock, Verbosity, compact, event_hash,
+    export_csv, load_trace, load_trace_header, load_trace_with, pubkey_fingerprint, render_table,
+    render_table_with, save_trace, save_trace_csv, summary_run_id, to_sarif, trace_message,
+    verify_chain, verify_jsonl, verify_ts_seeds,
+};
+use claims::{assert_err, assert_matches, assert_ok, assert_some};
+use ed25519_dalek::{Signer, SigningKey};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+use tempfile::tempdir;
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{Layer, layer::Context, prelude::*};
+
+#[test]
+fn trace_export_csv() {
+    let mut host = make_host_with_seed(12_345).with_denial_dedup(true);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_ok!(host.execute_plugin("./workspace/a,\"quoted\".txt"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_ok!(host.reload_manifest(load_example_manifest()));
+    host.rotate_key(SigningKey::from_bytes(&[9; 32]));
+
+    let mut out = Vec::new();
+    assert_ok!(export_csv(host.trace(), &mut out));
+    let csv = assert_ok!(String::from_utf8(out));
+    let lines = csv.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), host.trace().len() + 1);
+    assert_eq!(
+        lines[0],
+        "run_id,seq,event_type,input,outcome,ts_seed,vclock,policy_epoch,severity,details,repeat_count,prev_hash"
+    );
+    assert!(lines[1].starts_with("captra-run-12345,1,cap.call,./workspace/config.toml,true,"));
+    assert!(
+        lines[2].starts_with(r#"captra-run-12345,2,cap.call,"./workspace/a,""quoted"".txt",true,"#)
+    );
+    let denied = assert_some!(host.trace().get(2));
+    assert_eq!(
+        lines[3],
+        format!(
+            "captra-run-12345,3,cap.call,glob_mismatch: no matching pattern,false,{},,0,warn,,1,{}",
+            denied.ts_seed, denied.prev_hash
+        )
+    );
+    assert!(lines[4].contains(",policy.reload,"));
+    assert!(lines[4].contains(",,1,info,,0,"));
+    assert!(lines[5].contains(r#",1,info,"{""new_pubkey"":""#));
+
+    let tmp_dir = tempdir().expect("tempdir");
+    let path = tmp_dir.as_ref().join("trace.csv");
+    assert_ok!(save_trace_csv(host.trace(), &path));
+    assert_eq!(assert_ok!(fs::read_to_string(path)), csv);
+}
+
+#[test]
+fn trace_sarif_maps_denials_to_results() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    host.record_external_event("ui", serde_json::json!({ "action": "deny" }));
+
+    let sarif = to_sarif(host.trace());
+    assert_eq!(sarif["version"], "2.1.0");
+    let run = &sarif["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "captra");
+    assert_eq!(
+        run["tool"]["driver"]["rules"],
+        serde_json::json!([{ "id": "glob_mismatch" }])
+    );
+    let results = assert_some!(run["results"].as_array());
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["ruleId"], "glob_mismatch");
+    assert_eq!(results[0]["level"], "warning");
+    assert_eq!(results[0]["properties"]["seq"], 2);
+    assert!(results[0].get("locations").is_none());
+
+    let mut denied = make_host_with_seed(12_345).with_policy_hook(DenyAll);
+    let _ = assert_err!(denied.execute_plugin("./workspace/config.toml"));
+    let sarif = to_sarif(denied.trace());
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "cap.approval");
+    assert_eq!(
+        result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+        "./workspace/config.toml"
+    );
+}
+
+#[derive(Debug)]
+struct DenyAll;
+
+impl PolicyHook for DenyAll {
+    fn decide(&mut self, _request: &ApprovalRequest) -> Approval {
+        Approval::Deny
+    }
+}
+
+#[test]
+fn trace_event_types_round_trip_and_unknown_types_load() {
+    let known = [
+        EventType::CapCall,
+        EventType::CapError,
+        EventType::CapApproval,
+        EventType::CapAudit,
+        EventType::ShadowMismatch,
+        EventType::PluginInit,
+        EventType::PluginShutdown,
+        EventType::PluginCall,
+        EventType::PluginSpawn,
+        EventType::GuestStalled,
+        EventType::HostBusy,
+        EventType::FsConstraintViolation,
+        EventType::FsRead,
+        EventType::FsList,
+        EventType::FsContentFiltered,
+        EventType::SeccompViolation,
+        EventType::CpuQuotaExceeded,
+        EventType::MemoryQuotaExceeded,
+        EventType::External,
+        EventType::Aggregate,
+        EventType::PolicyReload,
+        EventType::KeyRotate,
+        EventType::FaultInjected,
+    ];
+    for event_type in known {
+        assert!(event_type.is_known());
+        let json = assert_ok!(serde_json::to_string(&event_type));
+        assert_eq!(json, format!("\"{}\"", event_type.wire_name()));
+        assert_eq!(
+            assert_ok!(serde_json::from_str::<EventType>(&json)),
+            event_type
+        );
+        assert_eq!(event_type.as_str().parse::<EventType>(), Ok(event_type));
+    }
+
+    // A trace from a newer version with an event kind this one lacks.
+    let mut host = make_host_with_seed(12_345);
+    for _ in 0..3 {
+        let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    }
+    let mut trace = host.trace().to_vec();
+    trace[1].event_type = EventType::Other("net_connect".into());
+    trace[2].prev_hash = event_hash(&trace[1]);
+    let tmp_dir = tempdir().expect("tempdir");
+    let path = tmp_dir.path().join("future.json");
+    assert_ok!(fs::write(&path, assert_ok!(serde_json::to_string(&trace))));
+    assert!(assert_ok!(fs::read_to_string(&path)).contains("\"net_connect\""));
+
+    let loaded = assert_ok!(load_trace(&path));
+    assert_eq!(loaded, trace);
+    assert!(!loaded[1].event_type.is_known());
+    assert_ok!(verify_chain(&loaded));
+    let err = assert_err!(load_trace_with(&path, TraceLoadOptions::strict()));
+    assert_matches!(
+        err,
+        TraceError::UnknownEventType { seq: 2, ref event_type } if event_type == "net_connect"
+    );
+}
+
+#[test]
+fn trace_file_header_and_legacy_arrays_load() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let tmp_dir = tempdir().expect("tempdir");
+
+    let path = tmp_dir.path().join("trace.json");
+    assert_ok!(host.save_current_trace(&path));
+    let json = assert_ok!(fs::read_to_string(&path));
+    assert!(
+        json.trim_start()
+            .starts_with("{\n  \"header\": {\n    \"magic\": \"CAPTRA\"")
+    );
+    let header = assert_some!(assert_ok!(load_trace_header(&path)));
+    assert_eq!(header.magic, TRACE_MAGIC);
+    assert_eq!(header.format_version, TRACE_FORMAT_VERSION);
+    assert_eq!(
+        header.producer,
+        concat!("captra ", env!("CARGO_PKG_VERSION"))
+    );
+    assert_eq!(header.run_id, host.run_id());
+    assert_eq!(header.manifest_hash, host.manifest_hash());
+    assert_eq!(assert_ok!(load_trace(&path)), host.trace());
+
+    let legacy = tmp_dir.path().join("legacy.json");
+    assert_ok!(fs::write(
+        &legacy,
+        assert_ok!(serde_json::to_string(host.trace()))
+    ));
+    assert_eq!(assert_ok!(load_trace_header(&legacy)), None);
+    assert_eq!(assert_ok!(load_trace(&legacy)), host.trace());
+
+    let newer = json.replace(
+        &format!("\"format_version\": {TRACE_FORMAT_VERSION}"),
+        &format!("\"format_version\": {}", TRACE_FORMAT_VERSION + 1),
+    );
+    assert_ok!(fs::write(&path, newer));
+    assert_matches!(load_trace(&path), Err(TraceError::UnsupportedFormat(_)));
+    assert_ok!(fs::write(&path, json.replace(TRACE_MAGIC, "NOTME")));
+    assert_matches!(load_trace(&path), Err(TraceError::UnsupportedFormat(_)));
+}
+
+#[test]
+fn trace_sinks_refuse_broken_chains() {
+    let mut host = make_host_with_seed(12_345);
+    for _ in 0..3 {
+        let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    }
+    let tmp_dir = tempdir().expect("tempdir");
+    let path = tmp_dir.path().join("trace.json");
+    let csv = tmp_dir.path().join("trace.csv");
+
+    let mut gap = host.trace().to_vec();
+    gap.remove(1);
+    let mut foreign = host.trace().to_vec();
+    foreign[2].run_id = "captra-run-other".into();
+    let mut tampered = host.trace().to_vec();
+    tampered[0].outcome = false;
+
+    for (trace, bad_seq) in [(gap, 3), (foreign, 3), (tampered, 2)] {
+        let err = assert_err!(save_trace(&trace, &path));
+        assert_matches!(err, TraceError::IntegrityViolation { seq, .. } if seq == bad_seq);
+        assert_err!(save_trace_csv(&trace, &csv));
+
+        let mut writer = JsonlWriter::new(Vec::new());
+        let pushed = trace.iter().map(|event| writer.push(event));
+        assert_err!(pushed.collect::<Result<Vec<_>, _>>());
+    }
+    assert!(!path.exists());
+    assert!(!csv.exists());
+    assert_ok!(save_trace(host.trace(), &path));
+}
+
+#[test]
+fn trace_verify_with_pinned_fingerprint() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let fpr = pubkey_fingerprint(host.pubkey());
+    assert!(fpr.starts_with("SHA256:"));
+
+    let signed = assert_ok!(host.sign_current_trace());
+    let key = assert_ok!(signed.verify_with_pinned_fingerprint(&fpr));
+    assert_eq!(key.as_bytes(), host.pubkey());
+
+    let other = make_host_with_seed(12_345);
+    let err =
+        assert_err!(signed.verify_with_pinned_fingerprint(&pubkey_fingerprint(other.pubkey())));
+    assert_matches!(err, TraceError::FingerprintMismatch { .. });
+
+    let mut tampered = signed;
+    tampered.trace_json = tampered.trace_json.replace("config", "secret");
+    let err = assert_err!(tampered.verify_with_pinned_fingerprint(&fpr));
+    assert_matches!(err, TraceError::InvalidSignature);
+}
+
+#[test]
+fn trace_render_table() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+
+    let plain = render_table_with(host.trace(), false);
+    let lines = plain.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "SEQ  TYPE      OUTCOME  INPUT");
+    assert_eq!(lines[1], "  1  cap.call  allow    ./workspace/config.toml");
+    assert_eq!(
+        lines[2],
+        "  2  cap.call  deny     glob_mismatch: no matching pattern"
+    );
+
+    let colored = render_table(host.trace());
+    assert!(colored.contains("\x1b[31m  2  cap.call  deny"));
+
+    let _ = assert_ok!(host.execute_plugin("./workspace/\x1b[2Jx\ny\u{202e}.toml"));
+    let plain = render_table_with(host.trace(), false);
+    let lines = plain.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[3],
+        r"  3  cap.call  allow    ./workspace/\u{1b}[2Jx\ny\u{202e}.toml"
+    );
+}
+
+#[test]
+fn trace_render_table_escapes_unknown_event_types() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let mut event = host.trace()[0].clone();
+    event.event_type = EventType::Other("x\x1b[2J\u{202e}".into());
+    event.input = "./in\x1b[31m\u{202e}put".into();
+
+    let plain = render_table_with(&[event.clone()], false);
+    let row = plain.lines().nth(1).unwrap_or_default();
+    assert!(!row.contains(['\x1b', '\u{202e}']), "{row:?}");
+    assert_eq!(
+        row,
+        r"  1  x\u{1b}[2J\u{202e}  allow    ./in\u{1b}[31m\u{202e}put"
+    );
+
+    // Colored output only carries its own color codes around the row.
+    let colored = render_table(&[event]);
+    let row = colored.lines().nth(1).unwrap_or_default();
+    assert_eq!(
+        row,
+        format!(
+            "\x1b[32m{}\x1b[0m",
+            plain.lines().nth(1).unwrap_or_default()
+        )
+    );
+}
+
+#[test]
+fn trace_randomness_mode_verification() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let signed = assert_ok!(host.sign_current_trace());
+    assert_eq!(
+        signed.randomness,
+        Some(RandomnessMode::Deterministic(12_345))
+    );
+    assert_ok!(signed.verify_ts_seeds());
+
+    let mut tampered = host.trace().to_vec();
+    tampered[1].ts_seed ^= 1;
+    let err = assert_err!(verify_ts_seeds(
+        &tampered,
+        RandomnessMode::Deterministic(12_345)
+    ));
+    assert_matches!(err, TraceError::IntegrityViolation { seq: 2, .. });
+
+    let mut prod = make_host_with_seed(12_345).with_randomness(RandomnessMode::Os);
+    let _ = assert_ok!(prod.execute_plugin("./workspace/config.toml"));
+    assert_ne!(prod.trace()[0].ts_seed, host.trace()[0].ts_seed);
+    assert_ok!(verify_ts_seeds(prod.trace(), RandomnessMode::Os));
+    let signed = assert_ok!(prod.sign_current_trace());
+    assert_eq!(signed.randomness, Some(RandomnessMode::Os));
+}
+
+#[test]
+fn trace_detached_signature_round_trip() {
+    let mut host = make_host_with_seed(12_345);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let signed = assert_ok!(host.sign_current_trace());
+    let pubkey = assert_ok!(signed.embedded_pubkey());
+
+    let dir = assert_ok!(tempdir());
+    assert_ok!(signed.write_detached(dir.path()));
+    assert_eq!(
+        assert_ok!(fs::read_to_string(dir.path().join(DETACHED_TRACE_FILE))),
+        signed.trace_json
+    );
+    let loaded = assert_ok!(SignedTrace::verify_detached(dir.path(), &pubkey));
+    assert_eq!(loaded.run_id, signed.run_id);
+    assert_eq!(loaded.randomness, signed.randomness);
+
+    assert_ok!(fs::write(dir.path().join(DETACHED_TRACE_FILE), "[]"));
+    let err = assert_err!(SignedTrace::verify_detached(dir.path(), &pubkey));
+    assert_matches!(err, TraceError::InvalidSignature);
+}
+
+#[test]
+fn trace_embedded_manifest_survives_round_trip() {
+    let mut host = make_host_with_seed(12_345).with_embedded_manifest(true);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let signed = assert_ok!(host.sign_current_trace());
+    let pubkey = assert_ok!(signed.embedded_pubkey());
+
+    let json = assert_ok!(serde_json::to_string(&signed));
+    let loaded = assert_ok!(serde_json::from_str::<SignedTrace>(&json));
+    let manifest = assert_some!(assert_ok!(loaded.manifest(&pubkey)));
+    assert_eq!(manifest.plugin, "formatter-v1");
+    assert_eq!(manifest.content_hash(), signed.manifest_hash);
+
+    let mut tampered = loaded;
+    if let Some(manifest) = tampered.manifest.as_mut() {
+        manifest.plugin = "other".into();
+    }
+    let err = assert_err!(tampered.manifest(&pubkey));
+    assert_matches!(err, TraceError::ManifestMismatch { .. });
+
+    // Swapping the manifest together with its hash breaks the signature.
+    if let Some(manifest) = tampered.manifest.as_ref() {
+        tampered.manifest_hash = manifest.content_hash();
+    }
+    let err = assert_err!(tampered.manifest(&pubkey));
+    assert_matches!(err, TraceError::InvalidSignature);
+    let mut renamed = signed;
+    renamed.run_id = "captra-run-other".into();
+    assert_matches!(renamed.verify(&pubkey), Err(TraceError::InvalidSignature));
+
+    let mut plain = make_host_with_seed(12_345);
+    let signed = assert_ok!(plain.sign_current_trace());
+    let pubkey = assert_ok!(signed.embedded_pubkey());
+    assert!(signed.manifest.is_none());
+    assert!(assert_ok!(signed.manifest(&pubkey)).is_none());
+}
+
+#[test]
+fn trace_external_events_are_chained_and_signed() {
+    let mut host = make_host_with_seed(12_345);
+    host.record_external_event("ui", serde_json::json!({ "action": "approve", "job": 7 }));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+
+    let ev = &host.trace()[0];
+    assert_eq!(ev.event_type, EventType::External);
+    assert_eq!(ev.input, "ui");
+    assert_eq!(
+        ev.details,
+        Some(serde_json::json!({ "action": "approve", "job": 7 }))
+    );
+    assert_eq!(host.trace()[1].prev_hash, event_hash(ev));
+    assert_ok!(verify_chain(host.trace()));
+
+    let signed = assert_ok!(host.sign_current_trace());
+    assert!(signed.trace_json.contains("\"approve\""));
+    assert_ok!(signed.verify(&assert_ok!(signed.embedded_pubkey())));
+}
+
+#[test]
+fn trace_compact_folds_allow_runs_and_keeps_denials() {
+    let mut host = make_host_with_seed(12_345);
+    for _ in 0..3 {
+        let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    }
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+
+    let randomness = RandomnessMode::Deterministic(12_345);
+    let summary = compact(host.trace(), CompactionPolicy::default(), randomness);
+    assert_eq!(summary.len(), 4);
+    assert_ok!(verify_chain(&summary));
+    assert_ok!(verify_ts_seeds(&summary, randomness));
+    let run_id = summary_run_id(&host.trace()[0].run_id);
+    assert!(summary.iter().all(|ev| ev.run_id == run_id));
+
+    assert_eq!(summary[0].event_type, EventType::Aggregate);
+    assert_eq!(
+        summary[0].details,
+        Some(serde_json::json!({
+            "event_type": "cap_call",
+            "count": 3,
+            "first_seq": 1,
+            "last_seq": 3,
+        }))
+    );
+    for (i, original) in [(1, &host.trace()[3]), (2, &host.trace()[4])] {
+        assert_eq!(summary[i].input, original.input);
+        assert_eq!(summary[i].event_type, original.event_type);
+        assert!(!summary[i].outcome);
+    }
+    assert_eq!(summary[3].event_type, EventType::CapCall);
+
+    let signed = assert_ok!(host.sign_summary_trace(CompactionPolicy::default()));
+    assert_eq!(signed.run_id, run_id);
+    assert_eq!(signed.randomness, Some(randomness));
+    assert_ok!(signed.verify(&assert_ok!(signed.embedded_pubkey())));
+    let signed_events = assert_ok!(serde_json::from_str::<Vec<TraceEvent>>(&signed.trace_json));
+    assert_eq!(signed_events, summary);
+}
+
+#[test]
+fn trace_truncates_oversized_inputs() {
+    let mut host = make_host_with_seed(12_345).with_max_input_len(32);
+    let long_path = format!("./workspace/{}", "é".repeat(100));
+    let _ = assert_ok!(host.execute_plugin(&long_path));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+
+    let input = &host.trace()[0].input;
+    let (kept, suffix) = assert_some!(input.split_once('…'));
+    assert!(kept.len() <= 32);
+    assert!(long_path.starts_with(kept));
+    let hash = format!("{:x}", Sha256::digest(long_path.as_bytes()));
+    assert_eq!(
+        suffix,
+        format!("(+{} bytes, sha256={hash})", long_path.len() - kept.len())
+    );
+    assert_eq!(host.trace()[1].input, "./workspace/config.toml");
+}
+
+#[test]
+fn trace_pubkey_export_formats() {
+    let host = HostState::new(
+        load_example_manifest(),
+        12_345,
+        SigningKey::from_bytes(&[7; 32]),
+    );
+    assert_eq!(
+        host.pubkey_hex(),
+        "ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c"
+    );
+    assert_eq!(
+        host.pubkey_pem(),
+        "-----BEGIN PUBLIC KEY-----\n\
+         MCowBQYDK2VwAyEA6kpsY+KcUgq+9VB7Ey7F+ZVHdq6+vnuSQh7qaRRG0iw=\n\
+         -----END PUBLIC KEY-----\n"
+    );
+    assert_eq!(
+        host.pubkey_openssh(),
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOpKbGPinFIKvvVQexMuxfmVR3auvr57kkIe6mkURtIs"
+    );
+    // Matches `ssh-keygen -lf` for the key above
+    assert_eq!(
+        host.pubkey_openssh_fingerprint(),
+        "SHA256:z/fSv0Z0RZS+Lccbc6ZoOWwt/fbj1VFJGSBKQbo4icE"
+    );
+    assert_eq!(host.pubkey_fingerprint(), pubkey_fingerprint(host.pubkey()));
+}
+
+#[test]
+fn trace_key_rotation_keeps_chain_verifiable() {
+    let mut host = make_host_with_seed(12_345);
+    let initial = assert_ok!(ed25519_dalek::VerifyingKey::from_bytes(host.pubkey()));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let rotated = SigningKey::from_bytes(&[9; 32]);
+    host.rotate_key(rotated.clone());
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+
+    assert_eq!(host.pubkey(), rotated.verifying_key().as_bytes());
+    assert_eq!(host.trace()[1].event_type, EventType::KeyRotate);
+    assert_ok!(verify_chain(host.trace()));
+
+    let signed = assert_ok!(host.sign_current_trace());
+    assert_matches!(signed.verify(&initial), Err(TraceError::InvalidSignature));
+    let current = assert_ok!(signed.verify_with_rotations(&initial));
+    assert_eq!(current, rotated.verifying_key());
+
+    let forged = SigningKey::from_bytes(&[3; 32]);
+    let err = assert_err!(signed.verify_with_rotations(&forged.verifying_key()));
+    assert_matches!(err, TraceError::IntegrityViolation { seq: 2, .. });
+
+    // A broken chain fails even when the final key signed it.
+    let mut events = host.trace().to_vec();
+    events[2].prev_hash = "00".repeat(32);
+    let trace_json = assert_ok!(serde_json::to_string_pretty(&events));
+    let hash = format!("{:x}", Sha256::digest(&trace_json));
+    let message = trace_message(&signed.run_id, &signed.manifest_hash, &hash);
+    let resigned = SignedTrace::new(
+        signed.run_id.clone(),
+        signed.manifest_hash,
+        trace_json,
+        rotated.sign(message.as_bytes()).to_bytes().to_vec(),
+    );
+    assert_ok!(resigned.verify(&rotated.verifying_key()));
+    assert_matches!(
+        resigned.verify_with_rotations(&initial),
+        Err(TraceError::IntegrityViolation { seq: 3, .. })
+    );
+}
+
+#[test]
+fn trace_restore_undoes_key_rotation() {
+    let mut host = make_host_with_seed(12_345);
+    let initial = assert_ok!(ed25519_dalek::VerifyingKey::from_bytes(host.pubkey()));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let snapshot = host.snapshot();
+    host.rotate_key(SigningKey::from_bytes(&[9; 32]));
+    assert_ok!(host.restore(&snapshot));
+
+    assert_eq!(host.pubkey(), initial.as_bytes());
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let signed = assert_ok!(host.sign_current_trace());
+    assert_ok!(signed.verify(&initial));
+    assert_eq!(assert_ok!(signed.verify_with_rotations(&initial)), initial);
+}
+
+#[test]
+fn trace_policy_selects_verbosity_per_event_type() {
+    let policy = TracePolicy::default()
+        .with_rule(EventType::CapCall, Some(false), Verbosity::Full)
+        .with_rule(EventType::CapCall, None, Verbosity::Sampled { every: 10 })
+        .with_rule(EventType::FsRead, None, Verbosity::Off);
+    assert_eq!(policy.logs(3, &EventType::CapCall, false), Some(true));
+    assert_eq!(policy.logs(3, &EventType::CapCall, true), None);
+    assert_eq!(policy.logs(20, &EventType::CapCall, true), Some(true));
+    assert_eq!(policy.logs(20, &EventType::FsRead, true), None);
+    assert_eq!(policy.logs(1, &EventType::PluginInit, true), Some(true));
+
+    let json = r#"{"default":"summary","rules":[{"event_type":"cap_call","outcome":true,"verbosity":{"sampled":{"every":5}}}]}"#;
+    let parsed = assert_ok!(serde_json::from_str::<TracePolicy>(json));
+    assert_eq!(
+        parsed.verbosity_for(&EventType::CapError, false),
+        Verbosity::Summary
+    );
+    assert_eq!(
+        parsed.verbosity_for(&EventType::CapCall, true),
+        Verbosity::Sampled { every: 5 }
+    );
+
+    // Verbosity only shapes the log output; the trace keeps every event.
+    let mut host = make_host_with_seed(12_345).with_trace_policy(TracePolicy {
+        default: Verbosity::Off,
+        rules: Vec::new(),
+    });
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_eq!(host.trace().len(), 2);
+}
+
+/// Records `(target, field, kind)` for every field of every event.
+#[derive(Clone, Default)]
+struct FieldKinds(Arc<Mutex<Vec<FieldKind>>>);
+
+type FieldKind = (String, String, &'static str);
+
+struct Visitor<'a>(&'a FieldKinds, &'a str);
+
+impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &Field, _: &str) {
+        self.push(field, "str");
+    }
+    fn record_u64(&mut self, field: &Field, _: u64) {
+        self.push(field, "u64");
+    }
+    fn record_bool(&mut self, field: &Field, _: bool) {
+        self.push(field, "bool");
+    }
+    fn record_debug(&mut self, field: &Field, _: &dyn std::fmt::Debug) {
+        self.push(field, "debug");
+    }
+}
+
+impl Visitor<'_> {
+    fn push(&self, field: &Field, kind: &'static str) {
+        let mut fields = self
+            .0
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        fields.push((self.1.to_owned(), field.name().to_owned(), kind));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for FieldKinds {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        event.record(&mut Visitor(self, event.metadata().target()));
+    }
+}
+
+#[test]
+fn trace_events_are_emitted_with_typed_fields() {
+    let layer = FieldKinds::default();
+    let subscriber = tracing_subscriber::registry().with(layer.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        let mut host = make_host_with_seed(12_345);
+        let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    });
+
+    let fields = std::mem::take(
+        &mut *layer
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    );
+    let kinds = fields
+        .iter()
+        .map(|(target, name, kind)| {
+            assert_eq!(target, TRACE_TARGET);
+            (name.as_str(), *kind)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ("run_id", "str"),
+            ("seq", "u64"),
+            ("event_type", "str"),
+            ("input", "str"),
+            ("outcome", "bool"),
+            ("ts_seed", "u64"),
+            ("policy_epoch", "u64"),
+            ("severity", "str"),
+            ("plugin", "str"),
+        ]
+    );
+}
+
+#[test]
+fn trace_jsonl_streaming_verification() {
+    let key = SigningKey::from_bytes(&[9; 32]);
+    let mut host = HostState::new(load_example_manifest(), 12_345, key.clone());
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let _ = assert_ok!(host.execute_plugin("./workspace/b.toml"));
+
+    let jsonl = assert_ok!(host.write_signed_jsonl(Vec::new()));
+    let text = assert_ok!(String::from_utf8(jsonl.clone()));
+    assert_eq!(text.lines().count(), 4);
+    let verified = assert_ok!(verify_jsonl(jsonl.as_slice(), &key.verifying_key()));
+    assert_eq!(verified.events, 3);
+    assert_eq!(verified.run_id.as_deref(), Some(host.run_id()));
+    assert_eq!(verified.head, assert_ok!(verify_chain(host.trace())));
+
+    let mut chain = ChainVerifier::default();
+    for event in host.trace() {
+        assert_ok!(chain.push(event));
+    }
+    assert_eq!(chain.head(), verified.head);
+
+    let other = SigningKey::from_bytes(&[1; 32]).verifying_key();
+    assert_matches!(
+        verify_jsonl(jsonl.as_slice(), &other),
+        Err(TraceError::InvalidSignature)
+    );
+    let tampered = text.replacen("config.toml", "secret.toml", 1);
+    assert_matches!(
+        verify_jsonl(tampered.as_bytes(), &key.verifying_key()),
+        Err(TraceError::IntegrityViolation { seq: 2, .. })
+    );
+    let mut lines = text.lines().collect::<Vec<_>>();
+    lines.remove(1);
+    assert_matches!(
+        verify_jsonl(lines.join("\n").as_bytes(), &key.verifying_key()),
+        Err(TraceError::IntegrityViolation { seq: 3, .. })
+    );
+    let truncated = text.lines().take(3).collect::<Vec<_>>().join("\n");
+    assert_err!(verify_jsonl(truncated.as_bytes(), &key.verifying_key()));
+}
+
+#[test]
+fn trace_events_carry_severity() {
+    let rules = SeverityRules::default().with_rule(SeverityRule {
+        event_type: None,
+        outcome: Some(false),
+        subject: Some("/etc/**".into()),
+        severity: Severity::Critical,
+    });
+    let mut host = make_host_with_seed(12_345).with_severity_rules(rules);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("./other/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+
+    let severities = host
+        .trace()
+        .iter()
+        .map(|ev| ev.severity)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        severities,
+        [Severity::Info, Severity::Warn, Severity::Critical]
+    );
+    assert!(Severity::Critical > Severity::Warn);
+
+    let json = assert_ok!(serde_json::to_string(host.trace()));
+    assert_eq!(json.matches(r#""severity":"warn""#).count(), 1);
+    assert_eq!(json.matches(r#""severity":"critical""#).count(), 1);
+    assert!(!json.contains(r#""severity":"info""#));
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn trace_dedup_folds_repeated_denials() {
+    let mut host = make_host_with_seed(12_345).with_denial_dedup(true);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    for _ in 0..3 {
+        let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    }
+    let snapshot = host.snapshot();
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_ok!(host.restore(&snapshot));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+
+    let trace = host.trace();
+    let seqs = trace
+        .iter()
+        .map(|ev| (ev.seq, ev.repeat_count))
+        .collect::<Vec<_>>();
+    assert_eq!(seqs, [(1, 0), (2, 2), (5, 0), (6, 0)]);
+    assert_eq!(trace[1].last_seq(), 4);
+    assert_ok!(verify_chain(trace));
+    assert_ok!(verify_chain(&compact(
+        trace,
+        CompactionPolicy::default(),
+        RandomnessMode::Deterministic(12_345)
+    )));
+
+    let signed = assert_ok!(host.sign_current_trace());
+    assert!(signed.trace_json.contains(r#""repeat_count": 2"#));
+    assert_ok!(signed.verify(&assert_ok!(signed.embedded_pubkey())));
+}
+
+#[test]
+fn trace_run_labels_reach_headers() {
+    let mut host = make_host_with_seed(12_345);
+    assert_ok!(host.set_label("ticket", "OPS-42"));
+    assert_ok!(host.set_label("ci_job", "1234"));
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    assert_matches!(
+        host.set_label("customer", "acme"),
+        Err(TraceError::LabelsSealed { key }) if key == "customer"
+    );
+    assert_eq!(host.labels().len(), 2);
+
+    let signed = assert_ok!(host.sign_current_trace());
+    assert_eq!(signed.label("ticket"), Some("OPS-42"));
+    let dir = assert_ok!(tempdir());
+    assert_ok!(signed.write_detached(dir.path()));
+    let loaded = assert_ok!(SignedTrace::verify_detached(
+        dir.path(),
+        &assert_ok!(signed.embedded_pubkey())
+    ));
+    assert_eq!(loaded.labels, signed.labels);
+
+    let path = dir.path().join("trace.captra.json");
+    assert_ok!(host.save_current_trace(&path));
+    let header = assert_some!(assert_ok!(load_trace_header(&path)));
+    assert_eq!(&header.labels, host.labels());
+}
+
+#[test]
+fn trace_hasher_matches_finalized_json() {
+    let digest = |json: &str| format!("{:x}", Sha256::digest(json));
+    assert_eq!(TraceHasher::default().digest(), digest("[]"));
+
+    let mut host = make_host_with_seed(12_345).with_denial_dedup(true);
+    let _ = assert_ok!(host.execute_plugin("./workspace/config.toml"));
+    let checkpoint = host.sign_checkpoint();
+    let snapshot = host.snapshot();
+    for _ in 0..2 {
+        let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    }
+    host.record_external_event(
+        "deploy",
+        serde_json::json!({ "env": ["prod"], "empty": {} }),
+    );
+    let mut hasher = TraceHasher::from_trace(&host.trace()[..1]);
+    for event in &host.trace()[1..] {
+        hasher.push(event);
+    }
+    assert_eq!(hasher.digest(), digest(&host.get_trace_json()));
+
+    let signed = assert_ok!(host.sign_current_trace());
+    let pubkey = assert_ok!(signed.embedded_pubkey());
+    assert_ok!(signed.verify(&pubkey));
+    assert_ok!(checkpoint.verify(host.trace(), &pubkey));
+    assert_eq!((checkpoint.len, checkpoint.seq), (1, 1));
+
+    // A checkpoint over a prefix must not pass as a signature of the truncated trace.
+    let truncated = SignedTrace::new(
+        checkpoint.run_id.clone(),
+        signed.manifest_hash,
+        assert_ok!(serde_json::to_string_pretty(&host.trace()[..1])),
+        assert_ok!(STANDARD.decode(&checkpoint.signature)),
+    )
+    .with_pubkey(host.pubkey());
+    assert_matches!(truncated.verify(&pubkey), Err(TraceError::InvalidSignature));
+
+    assert_ok!(host.restore(&snapshot));
+    let _ = assert_ok!(host.execute_plugin("./workspace/final.toml"));
+    let later = host.sign_checkpoint();
+    assert_ok!(later.verify(host.trace(), &pubkey));
+    assert_matches!(
+        later.verify(&host.trace()[..1], &pubkey),
+        Err(TraceError::IntegrityViolation { seq: 2, .. })
+    );
+    let mut tampered = host.trace().to_vec();
+    tampered[1].input = "./workspace/other.toml".into();
+    assert_matches!(
+        later.verify(&tampered, &pubkey),
+        Err(TraceError::IntegrityViolation { .. })
+    );
+}
+
+#[test]
+fn trace_checkpoint_seals_events_against_dedup() {
+    let mut host = make_host_with_seed(12_345).with_denial_dedup(true);
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    let checkpoint = host.sign_checkpoint();
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_eq!(host.trace().len(), 2, "sealed denial is not folded into");
+    assert_eq!(host.trace()[0].repeat_count, 0);
+    let pubkey = assert_ok!(ed25519_dalek::VerifyingKey::from_bytes(host.pubkey()));
+    assert_ok!(checkpoint.verify(host.trace(), &pubkey));
+
+    let _ = assert_err!(host.execute_plugin("/etc/passwd"));
+    assert_eq!(host.trace().len(), 2, "unsealed denials still fold");
+    assert_eq!(host.trace()[1].repeat_count, 1);
+    assert_ok!(verify_chain(host.trace()));
+}
+
+#[test]
+fn trace_hasher_matches_serde_json_for_every_field() {
+    let mut vclock = VectorClock::new();
+    vclock.insert("host-é".into(), 3);
+    let event = TraceEvent {
+        run_id: "run-\"quoted\"".into(),
+        seq: 7,
+        event_type: EventType::Other("vendor.custom".into()),
+        input: "naïve 日本語 🦀 \"q\" back\\slash\nline\ttab\u{1b}[31m\u{0}</script>\u{2028}"
+            .into(),
+        outcome: false,
+        ts_seed: u64::MAX,
+        vclock: Some(vclock),
+        policy_epoch: 2,
+        severity: Severity::Critical,
+        details: Some(serde_json::json!({
+            "nested": { "list": [1, -2.5, null, true, "ü\n"], "empty": [] },
+            "empty": {},
+            "ключ": "значение",
+        })),
+        repeat_count: 4,
+        prev_hash: "ab".repeat(32),
+    };
+    let plain = TraceEvent {
+        details: None,
+        vclock: None,
+        severity: Severity::Info,
+        repeat_count: 0,
+        policy_epoch: 0,
+        input: String::new(),
+        ..event.clone()
+    };
+    for trace in [vec![event.clone()], vec![plain.clone(), event, plain]] {
+        let json = assert_ok!(serde_json::to_string_pretty(&trace));
+        assert_eq!(
+            TraceHasher::from_trace(&trace).digest(),
+            format!("{:x}", Sha256::digest(&json))
+        );
+    }
+}
@@ -1,9 +1,20 @@
 mod common;
 
-use crate::common::{host::make_host_with_seed, wasm::wasm_store_with_hosts};
-use captra::HostStatus;
-use claims::{assert_ok, assert_some};
-use wasmtime::Module;
+use crate::common::{
+    host::make_host_with_seed, manifest::load_example_manifest, wasm::wasm_store_with_hosts,
+};
+use captra::{
+    BlobCapability, CaptraError, ConfigCapability, EventType, HostNamespace, HostState, HostStatus,
+    ModuleCache, PluginCatalog, PluginInstance, WasmConfig, WasmError, WatchdogConfig,
+    add_wasm_linker_funcs_in, guest_memory_export, is_memory64, load_manifest,
+};
+use claims::{assert_err, assert_matches, assert_ok, assert_some};
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, time::Duration};
+use tempfile::tempdir;
+use wasmtime::{Engine, Linker, Module, Store};
 
 #[test]
 fn wasm_integration_allowed() {
@@ -43,3 +54,1002 @@ fn wasm_integration_allowed() {
     assert_eq!(ev.input, "./workspace/test.txt");
     assert!(ev.outcome);
 }
+
+#[test]
+fn wasm_integration_custom_namespace() {
+    let host = make_host_with_seed(12345);
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    let namespace = HostNamespace::new("captra").with_version("v1");
+    assert_eq!(namespace.module(), "captra_v1");
+    assert_ok!(add_wasm_linker_funcs_in(&mut linker, &namespace));
+    let mut store = Store::new(&engine, host);
+
+    let path = "./workspace/test.txt";
+    let wat = format!(
+        r#"
+        (module
+          (import "captra_v1" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{path}")
+          (func (export "run") (result i32)
+                i32.const 0
+                i32.const {len}
+                call $read_file)
+          )
+    "#,
+        len = path.len()
+    );
+
+    let module = assert_ok!(Module::new(&engine, &wat));
+    let instance = assert_ok!(linker.instantiate(&mut store, &module));
+    let run = assert_ok!(instance.get_typed_func::<(), i32>(&mut store, "run"));
+    let ret = assert_ok!(run.call(&mut store, ()));
+    assert_eq!(ret, HostStatus::Allowed as i32);
+
+    let legacy = r#"(module (import "host" "status_allowed" (func (result i32))))"#;
+    let module = assert_ok!(Module::new(&engine, legacy));
+    assert_err!(linker.instantiate(&mut store, &module));
+}
+
+#[test]
+fn plugin_instance_lifecycle_events() {
+    let host = make_host_with_seed(12345);
+    let path = "./workspace/test.txt";
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (memory (export "memory") 2)
+          (data (i32.const 0) "{path}")
+          (func (export "run") (result i32)
+                i32.const 0
+                i32.const {len}
+                call $read_file)
+          )
+    "#,
+        len = path.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, &wat));
+    let ret = assert_ok!(plugin.call("run"));
+    assert_eq!(ret, HostStatus::Allowed as i32);
+    assert!(plugin.fuel_consumed() > 0);
+
+    let host = plugin.shutdown();
+    let types = host
+        .trace()
+        .iter()
+        .map(|ev| ev.event_type.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            EventType::PluginInit,
+            EventType::CapCall,
+            EventType::PluginShutdown
+        ]
+    );
+    assert!(host.trace()[0].input.starts_with("module_sha256="));
+    let shutdown = assert_some!(host.trace().last());
+    assert!(
+        shutdown
+            .input
+            .starts_with("memory_high_water=131072 fuel_consumed=")
+    );
+}
+
+#[test]
+fn wasm_capabilities_json_introspection() {
+    let host = make_host_with_seed(12345);
+    let (engine, linker, mut store) = wasm_store_with_hosts(host);
+    let wat = r#"
+        (module
+          (import "host" "capabilities_json" (func $caps (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "probe") (param i32) (result i32)
+                i32.const 1024
+                local.get 0
+                call $caps)
+          )
+    "#;
+
+    let module = assert_ok!(Module::new(&engine, wat));
+    let instance = assert_ok!(linker.instantiate(&mut store, &module));
+    let probe = assert_ok!(instance.get_typed_func::<i32, i32>(&mut store, "probe"));
+    let memory = assert_some!(instance.get_memory(&mut store, "memory"));
+
+    let needed = assert_ok!(probe.call(&mut store, 4));
+    assert!(needed > 4);
+    assert!(memory.data(&store)[1024..1028].iter().all(|b| *b == 0));
+
+    let written = assert_ok!(probe.call(&mut store, 4096));
+    assert_eq!(written, needed);
+    let len = usize::try_from(written).expect("positive length");
+    let json = assert_ok!(std::str::from_utf8(&memory.data(&store)[1024..1024 + len]));
+    let value = assert_ok!(serde_json::from_str::<serde_json::Value>(json));
+    assert_eq!(value["plugin"], "formatter-v1");
+    assert_eq!(value["capabilities"]["fs"]["read"][0], "./workspace/*");
+    assert!(value.get("issued_by").is_none());
+
+    assert_err!(probe.call(&mut store, 65_536));
+}
+
+#[test]
+fn plugin_module_cache_reuses_compilation() {
+    let cache = assert_ok!(ModuleCache::new());
+    let wat = r#"(module (func (export "run") (result i32) i32.const 7))"#;
+
+    for seed in 0..3 {
+        let mut plugin = assert_ok!(PluginInstance::with_cache(
+            &cache,
+            make_host_with_seed(seed),
+            wat
+        ));
+        assert_eq!(assert_ok!(plugin.call("run")), 7);
+    }
+    assert_eq!(cache.len(), 1);
+
+    let other = r#"(module (func (export "run") (result i32) i32.const 8))"#;
+    let _ = assert_ok!(PluginInstance::with_cache(
+        &cache,
+        make_host_with_seed(0),
+        other
+    ));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn wasm_memory64_guest_uses_i64_pointers() {
+    let path = "./workspace/test.txt";
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i64 i64) (result i32)))
+          (memory (export "memory") i64 1)
+          (data (i64.const 0) "{path}")
+          (func (export "run") (result i32)
+                i64.const 0
+                i64.const {len}
+                call $read_file)
+          )
+    "#,
+        len = path.len()
+    );
+
+    let cache = assert_ok!(ModuleCache::new());
+    let module = assert_ok!(cache.get_or_compile("memory64", wat.as_bytes()));
+    assert!(is_memory64(&module));
+
+    let mut plugin = assert_ok!(PluginInstance::with_cache(
+        &cache,
+        make_host_with_seed(12345),
+        &wat
+    ));
+    assert_eq!(assert_ok!(plugin.call("run")), HostStatus::Allowed as i32);
+}
+
+#[test]
+fn wasm_import_signature_mismatch_fails_instantiation() {
+    let wat = r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32) (result i32)))
+          (memory (export "memory") 1)
+          )
+    "#;
+    let err = assert_err!(PluginInstance::new(make_host_with_seed(12345), wat));
+    assert!(format!("{err:#}").contains("read_file"));
+
+    let wat = r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i64 i64) (result i32)))
+          (memory (export "memory") 1)
+          )
+    "#;
+    assert_err!(PluginInstance::new(make_host_with_seed(12345), wat));
+}
+
+#[test]
+fn wasm_multi_memory_non_default_export() {
+    let path = "./workspace/test.txt";
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (memory $scratch 1)
+          (memory $data (export "data") 1)
+          (data (memory $data) (i32.const 0) "{path}")
+          (func (export "run") (result i32)
+                i32.const 0
+                i32.const {len}
+                call $read_file)
+          )
+    "#,
+        len = path.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), &wat));
+    assert_eq!(assert_ok!(plugin.call("run")), HostStatus::Allowed as i32);
+
+    let engine = Engine::default();
+    let module = assert_ok!(Module::new(&engine, &wat));
+    assert_eq!(guest_memory_export(&module, "memory"), "data");
+
+    let mut linker = Linker::new(&engine);
+    let namespace = HostNamespace::default().with_memory("missing");
+    assert_ok!(add_wasm_linker_funcs_in(&mut linker, &namespace));
+    let mut store = Store::new(&engine, make_host_with_seed(12345));
+    let instance = assert_ok!(linker.instantiate(&mut store, &module));
+    let run = assert_ok!(instance.get_typed_func::<(), i32>(&mut store, "run"));
+    assert_err!(run.call(&mut store, ()));
+}
+
+#[test]
+fn wasm_shared_memory_guest() {
+    let path = "./workspace/test.txt";
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (memory (export "memory") 1 1 shared)
+          (data (i32.const 0) "{path}")
+          (func (export "run") (result i32)
+                i32.const 0
+                i32.const {len}
+                call $read_file)
+          )
+    "#,
+        len = path.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), &wat));
+    assert_eq!(assert_ok!(plugin.call("run")), HostStatus::Allowed as i32);
+}
+
+#[test]
+fn wasm_list_dir_writes_entries() {
+    let dir = assert_ok!(tempfile::tempdir());
+    assert_ok!(std::fs::write(dir.path().join("a.txt"), ""));
+    assert_ok!(std::fs::write(dir.path().join("b.txt"), ""));
+    let dir_str = dir.path().display().to_string();
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.list = Some(vec![dir_str.clone()]);
+    }
+    let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng));
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "list_dir" (func $list_dir (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{dir_str}")
+          (data (i32.const 512) "/etc")
+          (func (export "allowed") (result i32)
+                i32.const 0
+                i32.const {len}
+                i32.const 1024
+                i32.const 64
+                call $list_dir)
+          (func (export "denied") (result i32)
+                i32.const 512
+                i32.const 4
+                i32.const 1024
+                i32.const 64
+                call $list_dir)
+          )
+    "#,
+        len = dir_str.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, &wat));
+    assert_eq!(assert_ok!(plugin.call("allowed")), 11);
+    assert_eq!(assert_ok!(plugin.call("denied")), -1);
+    let host = plugin.shutdown();
+    let listed = host
+        .trace()
+        .iter()
+        .filter(|ev| ev.event_type == EventType::FsList)
+        .map(|ev| ev.outcome)
+        .collect::<Vec<_>>();
+    assert_eq!(listed, [true, false]);
+}
+
+#[test]
+fn wasm_read_range_writes_bytes() {
+    let dir = assert_ok!(tempfile::tempdir());
+    let file = dir.path().join("data.bin");
+    assert_ok!(std::fs::write(&file, b"MAGIC-and-the-rest"));
+    let file_str = file.display().to_string();
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+        fs.max_range_bytes = Some(16);
+    }
+    let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng));
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_range"
+            (func $read_range (param i32 i32 i64 i64 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{file_str}")
+          (func (export "header") (result i32)
+                i32.const 0
+                i32.const {len}
+                i64.const 0
+                i64.const 5
+                i32.const 1024
+                i32.const 64
+                call $read_range)
+          (func (export "too_long") (result i32)
+                i32.const 0
+                i32.const {len}
+                i64.const 0
+                i64.const 17
+                i32.const 1024
+                i32.const 64
+                call $read_range)
+          (func (export "first_byte") (result i32)
+                i32.const 1024
+                i32.load8_u)
+          )
+    "#,
+        len = file_str.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, &wat));
+    assert_eq!(assert_ok!(plugin.call("header")), 5);
+    assert_eq!(assert_ok!(plugin.call("first_byte")), i32::from(b'M'));
+    assert_eq!(assert_ok!(plugin.call("too_long")), -1);
+}
+
+#[test]
+fn wasm_guest_allocator_places_inputs_and_reads() {
+    let dir = assert_ok!(tempfile::tempdir());
+    let file = dir.path().join("data.bin");
+    assert_ok!(std::fs::write(&file, b"MAGIC"));
+    let file_str = file.display().to_string();
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+    }
+    let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng));
+    // Bump allocator from 4096; `dealloc` only counts the released bytes.
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_alloc" (func $read_alloc (param i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 4096))
+          (global $freed (mut i32) (i32.const 0))
+          (data (i32.const 0) "{file_str}")
+          (data (i32.const 1024) "/etc/passwd")
+          (func $alloc (export "captra_alloc") (param $len i32) (result i32)
+                global.get $next
+                (global.set $next (i32.add (global.get $next) (local.get $len))))
+          (func (export "captra_dealloc") (param i32 i32)
+                (global.set $freed (i32.add (global.get $freed) (local.get 1))))
+          (func (export "freed") (result i32) global.get $freed)
+          (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+                (local $out i32)
+                (local.set $out (call $alloc (local.get $len)))
+                (memory.copy (local.get $out) (local.get $ptr) (local.get $len))
+                (i64.or
+                    (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+                    (i64.extend_i32_u (local.get $len))))
+          (func (export "read") (result i32)
+                i32.const 0
+                i32.const {len}
+                i32.const 2048
+                call $read_alloc)
+          (func (export "denied") (result i32)
+                i32.const 1024
+                i32.const 11
+                i32.const 2048
+                call $read_alloc)
+          (func (export "first_byte") (result i32)
+                (i32.load8_u (i32.load (i32.const 2048))))
+          )
+    "#,
+        len = file_str.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, &wat));
+    let input = serde_json::json!({ "lines": [1, 2] });
+    let output = assert_ok!(plugin.call_json::<_, serde_json::Value>("echo", &input));
+    assert_eq!(output, input);
+    let input_len = assert_ok!(serde_json::to_vec(&input)).len();
+    assert_eq!(
+        assert_ok!(plugin.call("freed")),
+        2 * i32::try_from(input_len).unwrap_or_default()
+    );
+
+    assert_eq!(assert_ok!(plugin.call("read")), 5);
+    assert_eq!(assert_ok!(plugin.call("first_byte")), i32::from(b'M'));
+    assert_eq!(assert_ok!(plugin.call("denied")), -1);
+}
+
+#[test]
+fn wasm_streaming_read_sums_chunks() {
+    let dir = assert_ok!(tempfile::tempdir());
+    let file = dir.path().join("large.bin");
+    assert_ok!(std::fs::write(&file, vec![7_u8; 1000]));
+    let file_str = file.display().to_string();
+
+    let mut manifest = load_example_manifest();
+    if let Some(fs) = manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec![format!("{}/*", dir.path().display())]);
+    }
+    let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng));
+    // Reads the file through a 64-byte buffer and returns the closed byte count.
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_open" (func $read_open (param i32 i32) (result i32)))
+          (import "host" "read_chunk" (func $read_chunk (param i32 i32 i32) (result i32)))
+          (import "host" "read_close" (func $read_close (param i32) (result i64)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{file_str}")
+          (func (export "run") (result i32)
+                (local $handle i32)
+                i32.const 0
+                i32.const {len}
+                call $read_open
+                local.set $handle
+                (block $done
+                  (loop $next
+                    local.get $handle
+                    i32.const 1024
+                    i32.const 64
+                    call $read_chunk
+                    i32.eqz
+                    br_if $done
+                    br $next))
+                local.get $handle
+                call $read_close
+                i32.wrap_i64)
+          )
+    "#,
+        len = file_str.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, &wat));
+    assert_eq!(assert_ok!(plugin.call("run")), 1000);
+    let host = plugin.shutdown();
+    let closed = assert_some!(
+        host.trace()
+            .iter()
+            .rfind(|ev| ev.event_type == EventType::FsRead)
+    );
+    assert!(closed.input.ends_with("bytes=1000"));
+}
+
+#[test]
+fn wasm_get_blob_reads_host_buffer() {
+    let mut manifest = load_example_manifest();
+    manifest.capabilities.blob = Some(BlobCapability {
+        allow: vec!["config".into()],
+    });
+    let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng))
+        .with_blob("config", b"mode=fast".as_slice())
+        .with_blob("secret", b"hunter2".as_slice());
+    let wat = r#"
+        (module
+          (import "host" "get_blob" (func $get_blob (param i32 i32) (result i32)))
+          (import "host" "blob_len" (func $blob_len (param i32) (result i64)))
+          (import "host" "blob_read" (func $blob_read (param i32 i64 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "config")
+          (data (i32.const 16) "secret")
+          (func (export "len") (result i32)
+                i32.const 0
+                i32.const 6
+                call $get_blob
+                call $blob_len
+                i32.wrap_i64)
+          (func (export "tail_byte") (result i32)
+                i32.const 0
+                i32.const 6
+                call $get_blob
+                i64.const 5
+                i32.const 1024
+                i32.const 64
+                call $blob_read
+                drop
+                i32.const 1024
+                i32.load8_u)
+          (func (export "denied") (result i32)
+                i32.const 16
+                i32.const 6
+                call $get_blob)
+          )
+    "#;
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, wat));
+    assert_eq!(assert_ok!(plugin.call("len")), 9);
+    assert_eq!(assert_ok!(plugin.call("tail_byte")), i32::from(b'f'));
+    assert_eq!(assert_ok!(plugin.call("denied")), -1);
+}
+
+#[test]
+fn wasm_get_config_writes_json_value() {
+    let mut manifest = load_example_manifest();
+    manifest.capabilities.config = Some(ConfigCapability {
+        keys: vec!["limits".into(), "unset".into()],
+    });
+    let values = BTreeMap::from([("limits".to_owned(), serde_json::json!({ "max": 3 }))]);
+    let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng))
+        .with_config_provider(values);
+    let wat = r#"
+        (module
+          (import "host" "get_config" (func $get_config (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "limits")
+          (data (i32.const 16) "unset")
+          (data (i32.const 32) "secret")
+          (func (export "limits") (result i32)
+                i32.const 0
+                i32.const 6
+                i32.const 1024
+                i32.const 64
+                call $get_config
+                drop
+                i32.const 1031
+                i32.load8_u)
+          (func (export "unset") (result i32)
+                i32.const 16
+                i32.const 5
+                i32.const 1024
+                i32.const 64
+                call $get_config)
+          (func (export "denied") (result i32)
+                i32.const 32
+                i32.const 6
+                i32.const 1024
+                i32.const 64
+                call $get_config)
+          )
+    "#;
+
+    let mut plugin = assert_ok!(PluginInstance::new(host, wat));
+    // `{"max":3}`
+    assert_eq!(assert_ok!(plugin.call("limits")), i32::from(b'3'));
+    assert_eq!(assert_ok!(plugin.call("unset")), 4);
+    assert_eq!(assert_ok!(plugin.call("denied")), -1);
+}
+
+#[test]
+fn wasm_import_status_codes_are_pinned() {
+    let wat = r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (import "host" "spawn_plugin" (func $spawn_plugin (param i32 i32) (result i32)))
+          (import "host" "list_dir" (func $list_dir (param i32 i32 i32 i32) (result i32)))
+          (import "host" "read_range"
+            (func $read_range (param i32 i32 i64 i64 i32 i32) (result i32)))
+          (import "host" "read_alloc" (func $read_alloc (param i32 i32 i32) (result i32)))
+          (import "host" "read_open" (func $read_open (param i32 i32) (result i32)))
+          (import "host" "get_blob" (func $get_blob (param i32 i32) (result i32)))
+          (import "host" "get_config" (func $get_config (param i32 i32 i32 i32) (result i32)))
+          (import "host" "now_millis" (func $now_millis (result i64)))
+          (import "host" "status_allowed" (func $status_allowed (result i32)))
+          (import "host" "status_denied" (func $status_denied (result i32)))
+          (import "host" "status_error" (func $status_error (result i32)))
+          (import "host" "status_busy" (func $status_busy (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "/etc/passwd")
+          (func (export "read_file") (result i32)
+                (call $read_file (i32.const 0) (i32.const 11)))
+          (func (export "spawn_plugin") (result i32)
+                (call $spawn_plugin (i32.const 0) (i32.const 11)))
+          (func (export "list_dir") (result i32)
+                (call $list_dir (i32.const 0) (i32.const 4) (i32.const 1024) (i32.const 64)))
+          (func (export "read_range") (result i32)
+                (call $read_range (i32.const 0) (i32.const 11) (i64.const 0) (i64.const 8)
+                  (i32.const 1024) (i32.const 64)))
+          (func (export "read_alloc") (result i32)
+                (call $read_alloc (i32.const 0) (i32.const 11) (i32.const 1024)))
+          (func (export "read_open") (result i32)
+                (call $read_open (i32.const 0) (i32.const 11)))
+          (func (export "get_blob") (result i32)
+                (call $get_blob (i32.const 0) (i32.const 11)))
+          (func (export "get_config") (result i32)
+                (call $get_config (i32.const 0) (i32.const 11) (i32.const 1024) (i32.const 64)))
+          (func (export "now_millis") (result i32)
+                (i32.wrap_i64 (call $now_millis)))
+          (func (export "status_allowed") (result i32) (call $status_allowed))
+          (func (export "status_denied") (result i32) (call $status_denied))
+          (func (export "status_error") (result i32) (call $status_error))
+          (func (export "status_busy") (result i32) (call $status_busy))
+          )
+    "#;
+
+    let denied = HostStatus::Denied.length_code();
+    let cases = [
+        ("read_file", i32::from(HostStatus::Denied)),
+        ("spawn_plugin", i32::from(HostStatus::Denied)),
+        ("list_dir", denied),
+        ("read_range", denied),
+        ("read_alloc", denied),
+        ("read_open", denied),
+        ("get_blob", denied),
+        ("get_config", denied),
+        ("now_millis", denied),
+        ("status_allowed", 0),
+        ("status_denied", 1),
+        ("status_error", -1),
+        ("status_busy", 2),
+    ];
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12_345), wat));
+    for (export, code) in cases {
+        assert_eq!(assert_ok!(plugin.call(export)), code, "{export}");
+    }
+    assert_eq!(denied, -1);
+    assert_eq!(HostStatus::Busy.length_code(), -2);
+}
+
+#[test]
+fn wasm_last_error_reports_denial_reason() {
+    let path = "/etc/passwd";
+    let wat = format!(
+        r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (import "host" "last_error" (func $last_error (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{path}")
+          (func (export "last_error_len") (result i32)
+                i32.const 1024
+                i32.const 0
+                call $last_error)
+          (func (export "run") (result i32)
+                i32.const 0
+                i32.const {len}
+                call $read_file
+                drop
+                i32.const 1024
+                i32.const 512
+                call $last_error)
+          )
+    "#,
+        len = path.len()
+    );
+
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), &wat));
+    assert_eq!(assert_ok!(plugin.call("last_error_len")), 0);
+    let len = assert_ok!(plugin.call("run"));
+    let expected = assert_some!(plugin.host().last_denial()).to_owned();
+    assert!(expected.starts_with("glob_mismatch: Path `/etc/passwd`"));
+    assert_eq!(usize::try_from(len).ok(), Some(expected.len()));
+}
+
+#[test]
+fn plugin_from_precompiled_artifact() {
+    let wat = r#"(module (func (export "run") (result i32) i32.const 7))"#;
+    let artifact = assert_ok!(assert_ok!(ModuleCache::global()).precompile(wat.as_bytes()));
+    let artifact_hash = format!("{:x}", Sha256::digest(&artifact));
+    let dir = assert_ok!(tempdir());
+    let path = dir.path().join("plugin.cwasm");
+    assert_ok!(std::fs::write(&path, &artifact));
+
+    let mut plugin = assert_ok!(PluginInstance::from_precompiled(
+        make_host_with_seed(12345),
+        &path,
+        &artifact_hash
+    ));
+    assert_eq!(assert_ok!(plugin.call("run")), 7);
+    let host = plugin.shutdown();
+    assert_eq!(
+        host.trace()[0].input,
+        format!("precompiled_sha256={artifact_hash}")
+    );
+
+    let err = assert_err!(PluginInstance::from_precompiled(
+        make_host_with_seed(12345),
+        &path,
+        "0000"
+    ));
+    assert_matches!(err, WasmError::ArtifactMismatch { .. });
+
+    let foreign = assert_ok!(Engine::default().precompile_module(wat.as_bytes()));
+    let foreign_hash = format!("{:x}", Sha256::digest(&foreign));
+    assert_ok!(std::fs::write(&path, &foreign));
+    assert_err!(PluginInstance::from_precompiled(
+        make_host_with_seed(12345),
+        &path,
+        &foreign_hash
+    ));
+}
+
+#[test]
+fn wasm_errors_are_typed_and_unify_into_captra_error() {
+    let wat = r#"(module (func (export "run") (result i32) unreachable))"#;
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), wat));
+    let err = assert_err!(plugin.call("missing"));
+    assert_matches!(&err, WasmError::Call { export, .. } if export == "missing");
+    assert_matches!(assert_err!(plugin.call("run")), WasmError::Call { .. });
+    let err = assert_err!(plugin.call_with_input("run", b"x"));
+    assert_matches!(err, WasmError::MissingMemory(ref name) if name == "memory");
+
+    let run = || -> captra::Result<i32> {
+        let manifest = load_manifest("examples/manifest.json")?;
+        let host = HostState::new(manifest, 12345, SigningKey::generate(&mut OsRng));
+        let mut plugin = PluginInstance::new(host, "(module")?;
+        Ok(plugin.call("run")?)
+    };
+    assert_matches!(assert_err!(run()), CaptraError::Wasm(WasmError::Compile(_)));
+}
+
+#[test]
+fn wasm_input_without_guest_allocator_is_rejected() {
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "run") (param i32 i32) (result i32) (i32.const 0))
+            (func (export "echo") (param i32 i32) (result i64) (i64.const 0)))
+    "#;
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), wat));
+    let err = assert_err!(plugin.call_with_input("run", b"./workspace/test.txt"));
+    assert_matches!(err, WasmError::MissingAllocator);
+    let err = assert_err!(plugin.call_json::<_, serde_json::Value>("echo", &"input"));
+    assert_matches!(err, WasmError::MissingAllocator);
+
+    // Neither call reached the guest.
+    let ev = assert_some!(plugin.host().trace().last());
+    assert_eq!(ev.event_type, EventType::PluginInit);
+}
+
+#[test]
+fn wasm_call_json_round_trips_through_guest_memory() {
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+                (i64.or
+                    (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                    (i64.extend_i32_u (local.get $len))))
+            (func (export "fail") (param i32 i32) (result i64) (i64.const -1))
+            (func (export "oob") (param i32 i32) (result i64) (i64.const 0xffff00000010)))
+    "#;
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), wat));
+    let input = serde_json::json!({ "path": "./workspace/config.toml", "lines": [1, 2] });
+    let output = assert_ok!(plugin.call_json::<_, serde_json::Value>("echo", &input));
+    assert_eq!(output, input);
+
+    let ev = assert_some!(plugin.host().trace().last());
+    assert_eq!(ev.event_type, EventType::PluginCall);
+    let hash = format!(
+        "{:x}",
+        Sha256::digest(assert_ok!(serde_json::to_vec(&input)))
+    );
+    assert_eq!(
+        ev.input,
+        format!("echo input_sha256={hash} output_sha256={hash}")
+    );
+
+    let err = assert_err!(plugin.call_json::<_, serde_json::Value>("fail", &input));
+    assert_matches!(err, WasmError::InvalidResult { result: -1, .. });
+    let err = assert_err!(plugin.call_json::<_, serde_json::Value>("oob", &input));
+    assert_matches!(err, WasmError::InvalidResult { .. });
+    let err = assert_err!(plugin.call_json::<_, Vec<u8>>("echo", &input));
+    assert_matches!(err, WasmError::Json(_));
+}
+
+#[test]
+fn wasm_watchdog_records_and_interrupts_stalled_guest() {
+    let wat = r#"
+        (module
+            (func (export "ok") (result i32) i32.const 0)
+            (func (export "hang") (result i32) (loop $spin (br $spin)) i32.const 0))
+    "#;
+    let cache = assert_ok!(ModuleCache::with_epoch_interruption());
+    let config = WatchdogConfig::new(Duration::from_millis(20)).with_interrupt();
+    let mut plugin = assert_ok!(PluginInstance::with_cache(
+        &cache,
+        make_host_with_seed(12345),
+        wat
+    ))
+    .with_watchdog(config);
+
+    assert_eq!(assert_ok!(plugin.call("ok")), 0);
+    assert!(
+        plugin
+            .host()
+            .trace()
+            .iter()
+            .all(|ev| ev.event_type != EventType::GuestStalled)
+    );
+
+    let err = assert_err!(plugin.call("hang"));
+    assert_matches!(err, WasmError::Call { ref export, .. } if export == "hang");
+    let ev = assert_some!(plugin.host().trace().last());
+    assert_eq!(ev.event_type, EventType::GuestStalled);
+    assert!(!ev.outcome);
+    assert!(ev.input.ends_with("interrupted=true"), "{}", ev.input);
+
+    assert_eq!(assert_ok!(plugin.call("ok")), 0);
+}
+
+#[test]
+fn wasm_memory64_abi_checks_bounds_in_u64() {
+    let wat = r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i64 i64) (result i32)))
+          (memory (export "memory") i64 1)
+          (func (export "alloc") (param i64) (result i64) i64.const 2048)
+          (func (export "read") (param $ptr i64) (param $len i64) (result i32)
+                (call $read_file (local.get $ptr) (local.get $len)))
+          (func (export "past_4gib") (param i64 i64) (result i32)
+                (call $read_file (i64.const 0x100000000) (i64.const 4)))
+          (func (export "negative") (param i64 i64) (result i32)
+                (call $read_file (i64.const -1) (i64.const 4)))
+          (func (export "echo") (param $ptr i64) (param $len i64) (result i64)
+                (i64.store (i64.const 1024) (local.get $ptr))
+                (i64.store (i64.const 1032) (local.get $len))
+                (i64.const 1024)))
+    "#;
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), wat));
+    let status = assert_ok!(plugin.call_with_input("read", b"./workspace/test.txt"));
+    assert_eq!(status, HostStatus::Allowed as i32);
+    assert_err!(plugin.call_with_input("past_4gib", b""));
+    assert_err!(plugin.call_with_input("negative", b""));
+
+    let input = serde_json::json!({ "rows": 3 });
+    let output = assert_ok!(plugin.call_json::<_, serde_json::Value>("echo", &input));
+    assert_eq!(output, input);
+}
+
+#[test]
+fn wasm_config_hardens_engine() {
+    // Propagating a NaN with a payload yields the canonical NaN once canonicalized.
+    let nan = r#"
+        (module
+          (func (export "run") (result i32)
+                (i32.reinterpret_f32 (f32.add (f32.const nan:0x200000) (f32.const 0)))))
+    "#;
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), nan));
+    assert_eq!(assert_ok!(plugin.call("run")), 0x7fc0_0000);
+
+    let shared = r#"(module (memory (export "memory") 1 1 shared))"#;
+    let no_threads = WasmConfig::default().with_threads(false);
+    let err = assert_err!(PluginInstance::with_wasm_config(
+        no_threads,
+        make_host_with_seed(12345),
+        shared
+    ));
+    assert_matches!(err, WasmError::Compile(_));
+
+    let simd = r#"
+        (module
+          (func (export "run") (result i32)
+                (i32x4.extract_lane 0 (v128.const i32x4 7 0 0 0))))
+    "#;
+    let no_simd = assert_ok!(ModuleCache::with_wasm_config(
+        WasmConfig::default().with_simd(false)
+    ));
+    assert!(!no_simd.config().simd);
+    let err = assert_err!(PluginInstance::with_cache(
+        &no_simd,
+        make_host_with_seed(12345),
+        simd
+    ));
+    assert_matches!(err, WasmError::Compile(_));
+    let mut plugin = assert_ok!(PluginInstance::new(make_host_with_seed(12345), simd));
+    assert_eq!(assert_ok!(plugin.call("run")), 7);
+}
+
+#[test]
+fn wasm_deterministic_guest_mode() {
+    let config = WasmConfig::default().with_deterministic_guest();
+    let clock = r#"
+        (module
+          (import "host" "now_millis" (func $now (result i64)))
+          (func (export "run") (result i32) (i32.wrap_i64 (call $now))))
+    "#;
+    let err = assert_err!(PluginInstance::with_wasm_config(
+        config,
+        make_host_with_seed(12345),
+        clock
+    ));
+    assert_matches!(
+        err,
+        WasmError::NondeterministicImport { ref name, .. } if name == "now_millis"
+    );
+
+    let shared = r#"(module (memory (export "memory") 1 1 shared))"#;
+    let err = assert_err!(PluginInstance::with_wasm_config(
+        config.with_threads(true),
+        make_host_with_seed(12345),
+        shared
+    ));
+    assert_matches!(err, WasmError::Compile(_));
+
+    let wat = r#"
+        (module
+          (func (export "run") (result i32)
+                (local $i i32)
+                (loop $l
+                  (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                  (br_if $l (i32.lt_u (local.get $i) (i32.const 100))))
+                (local.get $i)))
+    "#;
+    let run = || {
+        let mut plugin = assert_ok!(PluginInstance::with_wasm_config(
+            config,
+            make_host_with_seed(12345),
+            wat
+        ));
+        assert_eq!(assert_ok!(plugin.call("run")), 100);
+        (plugin.fuel_consumed(), plugin.shutdown().trace().to_vec())
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn wasm_spawn_plugin_runs_child_under_own_manifest() {
+    let mut child_manifest = load_example_manifest();
+    child_manifest.plugin = "reader".into();
+    if let Some(fs) = child_manifest.capabilities.fs.as_mut() {
+        fs.read = Some(vec!["./other/*".into()]);
+    }
+    let path = "./workspace/config.toml";
+    let child = format!(
+        r#"
+        (module
+          (import "host" "read_file" (func $read_file (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{path}")
+          (func (export "run") (result i32)
+                (call $read_file (i32.const 0) (i32.const {len}))))
+    "#,
+        len = path.len()
+    );
+    let catalog = PluginCatalog::new().with_plugin(child_manifest, child);
+    let host = make_host_with_seed(12345).with_plugin_catalog(catalog);
+
+    let parent = r#"
+        (module
+          (import "host" "spawn_plugin" (func $spawn (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "reader")
+          (data (i32.const 16) "missing")
+          (func (export "run") (result i32)
+                (i32.add
+                  (i32.mul (call $spawn (i32.const 0) (i32.const 6)) (i32.const 10))
+                  (call $spawn (i32.const 16) (i32.const 7)))))
+    "#;
+    let mut plugin = assert_ok!(PluginInstance::new(host, parent));
+    assert_eq!(
+        assert_ok!(plugin.call("run")),
+        HostStatus::Allowed as i32 * 10 + HostStatus::Denied as i32
+    );
+    let host = plugin.shutdown();
+
+    let spawns = host
+        .trace()
+        .iter()
+        .filter(|ev| ev.event_type == EventType::PluginSpawn)
+        .collect::<Vec<_>>();
+    assert_eq!(spawns.len(), 2);
+    assert!(spawns[0].outcome);
+    assert_eq!(spawns[1].input, "missing: denied");
+    assert!(!spawns[1].outcome);
+
+    let [run] = host.child_runs() else {
+        panic!("expected one child run, got {:?}", host.child_runs());
+    };
+    assert_eq!(run.plugin, "reader");
+    assert_eq!(run.parent_run_id, host.run_id());
+    assert_eq!(run.result, Some(HostStatus::Denied as i32));
+    let details = assert_some!(spawns[0].details.as_ref());
+    assert_eq!(details["child_run_id"], run.run_id.as_str());
+    assert!(
+        run.trace
+            .iter()
+            .any(|ev| ev.event_type == EventType::CapCall && !ev.outcome)
+    );
+}
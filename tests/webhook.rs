@@ -0,0 +1,163 @@
+mod common;
+
+use crate::common::host::make_host_with_seed;
+use captra::{
+    HttpTransport, PluginInstance, WEBHOOK_SIGNATURE_HEADER, WasmError, WebhookConfig, WebhookSink,
+    WebhookStats, WebhookTransport, sign_webhook_payload,
+};
+use claims::{assert_err, assert_matches, assert_ok, assert_some};
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+type Requests = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+#[derive(Debug, Clone, Default)]
+struct MockTransport {
+    failures: u32,
+    requests: Requests,
+}
+
+impl WebhookTransport for MockTransport {
+    fn post(&mut self, _url: &str, signature: &str, body: &[u8]) -> io::Result<()> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(io::Error::other("unavailable"));
+        }
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((signature.to_owned(), body.to_vec()));
+        Ok(())
+    }
+}
+
+fn config() -> WebhookConfig {
+    WebhookConfig::new("http://alerts.invalid/hook", "secret")
+        .with_batch_size(2)
+        .with_retries(2, Duration::from_millis(1))
+}
+
+#[test]
+fn webhook_signature_is_hmac_sha256() {
+    // RFC 4231 test case 2
+    assert_eq!(
+        sign_webhook_payload(b"Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn webhook_batches_signed_denials() {
+    let transport = MockTransport::default();
+    let requests = transport.requests.clone();
+    let mut host = make_host_with_seed(1).with_webhook(WebhookSink::spawn(config(), transport));
+
+    assert_ok!(host.execute_plugin("./workspace/a.txt"));
+    for path in ["/etc/passwd", "/etc/shadow", "/etc/hosts"] {
+        assert_err!(host.execute_plugin(path));
+    }
+    let stats = assert_some!(host.close_webhook());
+    assert_eq!(
+        stats,
+        WebhookStats {
+            delivered_batches: 2,
+            delivered_events: 3,
+            ..WebhookStats::default()
+        }
+    );
+
+    let (signature, body) = requests.lock().unwrap_or_else(PoisonError::into_inner)[0].clone();
+    assert_eq!(signature, sign_webhook_payload(b"secret", &body));
+    let payload: Value = assert_ok!(serde_json::from_slice(&body));
+    let events = assert_some!(payload["events"].as_array());
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|ev| ev["outcome"] == false));
+}
+
+#[test]
+fn webhook_reports_failed_plugin_init() {
+    let transport = MockTransport::default();
+    let requests = transport.requests.clone();
+    let host = make_host_with_seed(1)
+        .with_webhook(WebhookSink::spawn(config().with_batch_size(1), transport));
+    let wat = "(module (func $boom unreachable) (start $boom))";
+    assert_matches!(
+        assert_err!(PluginInstance::new(host, wat)),
+        WasmError::Instantiate(_)
+    );
+
+    let body = {
+        let requests = requests.lock().unwrap_or_else(PoisonError::into_inner);
+        assert_some!(requests.first()).1.clone()
+    };
+    let payload: Value = assert_ok!(serde_json::from_slice(&body));
+    let event = &payload["events"][0];
+    assert_eq!(event["event_type"], "plugin_init");
+    assert_eq!(event["outcome"], false);
+    let input = assert_some!(event["input"].as_str());
+    assert!(input.starts_with("module_sha256="), "{input}");
+    assert!(input.contains(" error=Instantiation failed"), "{input}");
+}
+
+#[test]
+fn webhook_retries_then_gives_up() {
+    let transport = MockTransport {
+        failures: 1,
+        ..MockTransport::default()
+    };
+    let sink = WebhookSink::spawn(config().with_batch_size(1), transport);
+    let mut host = make_host_with_seed(1).with_webhook(sink);
+    assert_err!(host.execute_plugin("/etc/passwd"));
+    let stats = assert_some!(host.close_webhook());
+    assert_eq!((stats.delivered_batches, stats.retries), (1, 1));
+
+    let transport = MockTransport {
+        failures: 5,
+        ..MockTransport::default()
+    };
+    let mut host = make_host_with_seed(1).with_webhook(WebhookSink::spawn(config(), transport));
+    assert_err!(host.execute_plugin("/etc/passwd"));
+    let stats = assert_some!(host.close_webhook());
+    assert_eq!((stats.failed_batches, stats.retries), (1, 2));
+}
+
+#[test]
+fn webhook_http_transport_posts_signature_header() {
+    let listener = assert_ok!(TcpListener::bind("127.0.0.1:0"));
+    let url = format!("http://{}/hook", assert_ok!(listener.local_addr()));
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept()?;
+        let mut reader = BufReader::new(stream);
+        let mut head = Vec::new();
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            head.push(line.trim_end().to_owned());
+            line.clear();
+        }
+        let len = head
+            .iter()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .and_then(|len| len.parse().ok())
+            .unwrap_or_default();
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")?;
+        Ok::<_, io::Error>((head, body))
+    });
+
+    assert_ok!(HttpTransport::default().post(&url, "sha256=00", b"{}"));
+    let (head, body) = assert_ok!(assert_ok!(server.join()));
+    assert_eq!(head[0], "POST /hook HTTP/1.1");
+    assert!(head.contains(&format!("{WEBHOOK_SIGNATURE_HEADER}: sha256=00")));
+    assert_eq!(body, b"{}");
+
+    assert_err!(HttpTransport::default().post("https://example.com/", "", b"{}"));
+}